@@ -0,0 +1,68 @@
+use std::{
+    fs,
+    io::{self, Read},
+    str::{self, FromStr},
+};
+
+use bitcoin::{consensus::deserialize, hashes::hex::FromHex, Psbt, Transaction};
+
+/// Binary PSBTs start with the magic bytes `psbt` followed by 0xff.
+const PSBT_MAGIC: &[u8] = b"psbt\xff";
+
+/// The bytes behind an argument: `-` reads stdin, `@path` reads a file and
+/// anything else is taken literally.
+fn read_arg(arg: &str) -> Result<Vec<u8>, String> {
+    let bytes = if arg == "-" {
+        let mut bytes = Vec::new();
+        io::stdin()
+            .read_to_end(&mut bytes)
+            .map_err(|e| format!("cannot read stdin: {}", e))?;
+        bytes
+    } else if let Some(path) = arg.strip_prefix('@') {
+        fs::read(path).map_err(|e| format!("cannot read {}: {}", path, e))?
+    } else {
+        arg.as_bytes().to_vec()
+    };
+
+    if bytes.is_empty() {
+        return Err("empty input".to_string());
+    }
+    Ok(bytes)
+}
+
+/// Hex text, ignoring surrounding whitespace, decoded to bytes.
+fn decode_hex_text(bytes: &[u8]) -> Option<Vec<u8>> {
+    let text = str::from_utf8(bytes).ok()?.trim();
+    Vec::from_hex(text).ok()
+}
+
+/// A PSBT given in BIP174 binary, base64 or hex.
+pub fn parse_psbt(arg: &str) -> Result<Psbt, String> {
+    let bytes = read_arg(arg)?;
+    if bytes.starts_with(PSBT_MAGIC) {
+        return Psbt::deserialize(&bytes).map_err(|e| format!("invalid PSBT: {}", e));
+    }
+    if let Some(raw) = decode_hex_text(&bytes) {
+        return Psbt::deserialize(&raw).map_err(|e| format!("invalid PSBT: {}", e));
+    }
+
+    let text = str::from_utf8(&bytes).map_err(|_| "PSBT is neither binary nor text")?;
+    Psbt::from_str(text.trim()).map_err(|e| format!("invalid PSBT: {}", e))
+}
+
+/// A transaction given in hex or as raw consensus-encoded bytes.
+pub fn parse_tx(arg: &str) -> Result<Transaction, String> {
+    let bytes = read_arg(arg)?;
+    let raw = match decode_hex_text(&bytes) {
+        Some(raw) => raw,
+        // Serialized transactions always contain control bytes, so plain text was meant as hex
+        None if bytes
+            .iter()
+            .all(|b| b.is_ascii_graphic() || b.is_ascii_whitespace()) =>
+        {
+            return Err("transaction is not valid hex".to_string());
+        }
+        None => bytes,
+    };
+    deserialize(&raw).map_err(|e| format!("invalid transaction: {}", e))
+}
@@ -1,12 +1,20 @@
 use std::path::PathBuf;
 
 use bitcoin::amount::Denomination::Bitcoin;
-use bitcoin::{Amount, Network};
+use bitcoin::{absolute, relative, Amount, FeeRate, OutPoint, Psbt, ScriptBuf, Transaction, Txid};
 use bitcoincore_rpc::json::AddressType;
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
 
-use satoshi_suite_config::Config;
-use satoshi_suite_utxo_selection::UTXOStrategy;
+use satoshi_suite_client::FeeTarget;
+
+use satoshi_suite_config::{resolve_profile, BackendKind, Chain, Config, ConfigError, Profile};
+use satoshi_suite_utxo_selection::CoinSelectorRegistry;
+use satoshi_suite_wallet::{ChangePolicy, PolicyKey, TimelockedLeaf};
+
+use crate::output::OutputFormat;
+
+mod input;
+pub use input::*;
 
 #[derive(Parser, Debug)]
 #[command(name = "satoshi-suite")]
@@ -20,11 +28,23 @@ pub struct Cli {
 
 #[derive(Parser, Debug)]
 pub struct Options {
-    /// Network to use (mainnet, testnet, regtest)
-    #[arg(long, value_parser = parse_network_type, default_value = "regtest")]
-    pub network: Network,
+    /// Path to a satoshi-suite.toml config file
+    #[arg(long)]
+    pub config: Option<PathBuf>,
+
+    /// Named profile from the config file to use
+    #[arg(long)]
+    pub profile: Option<String>,
+
+    /// Network to use (mainnet, testnet, testnet4, signet, regtest) [default: regtest]
+    #[arg(long, value_parser = parse_network_type)]
+    pub network: Option<Chain>,
 
-    /// RPC URL for Bitcoin Core
+    /// Hex-encoded challenge script of a custom signet
+    #[arg(long, value_parser = parse_script_hex)]
+    pub signet_challenge: Option<ScriptBuf>,
+
+    /// RPC URL for Bitcoin Core; the network's default port is used unless one is given
     #[arg(long)]
     pub rpc_url: Option<String>,
 
@@ -44,39 +64,57 @@ pub struct Options {
     #[arg(long)]
     pub bitcoin_data_dir: Option<PathBuf>,
 
-    /// Whether to create wallets if they don't exist
-    #[arg(long, default_value = "true")]
-    pub create_wallets: bool,
+    /// Whether to create wallets if they don't exist [default: true]
+    #[arg(long)]
+    pub create_wallets: Option<bool>,
+
+    /// Backend for chain queries and broadcasts (bitcoin-core, esplora, electrum) [default: bitcoin-core]
+    #[arg(long, value_parser = parse_backend)]
+    pub backend: Option<BackendKind>,
+
+    /// Esplora API URL; defaults to a public instance for the network (a local electrs on regtest)
+    #[arg(long)]
+    pub esplora_url: Option<String>,
+
+    /// Electrum server URL (tcp://host:port or ssl://host:port); defaults to a local electrs
+    #[arg(long)]
+    pub electrum_url: Option<String>,
+
+    /// ord server URL; wallet UTXOs holding inscriptions or runes are then never selected
+    #[arg(long)]
+    pub ord_url: Option<String>,
+
+    /// Format of command results on stdout (text, json); logs go to stderr
+    #[arg(long, global = true, value_parser = parse_output_format, default_value_t = OutputFormat::Text)]
+    pub output: OutputFormat,
 }
 
 impl Options {
-    pub fn make_config(&self) -> Config {
-        // If rpc_url is provided, treat as external Bitcoin Core
-        if self.rpc_url.is_some() {
-            Config::new_external(
-                self.network,
-                self.rpc_url.clone().unwrap(),
-                self.rpc_username.clone(),
-                self.rpc_password.clone(),
-                self.cookie_file.clone(),
-                self.create_wallets,
-            )
-        } else {
-            // Otherwise use internal Bitcoin Core with default or specified settings
-            Config::new_internal(
-                self.network,
-                "http://127.0.0.1".to_string(),
-                self.rpc_username
-                    .clone()
-                    .unwrap_or_else(|| "user".to_string()),
-                self.rpc_password
-                    .clone()
-                    .unwrap_or_else(|| "password".to_string()),
-                self.bitcoin_data_dir
-                    .clone()
-                    .unwrap_or_else(|| PathBuf::from("./data/bitcoin")),
-                self.create_wallets,
-            )
+    /// Build the config from CLI flags, falling back to `SATOSHI_SUITE_*`
+    /// environment variables and then to the selected config file profile.
+    pub fn make_config(&self) -> Result<Config, ConfigError> {
+        resolve_profile(
+            self.as_profile(),
+            self.config.as_deref(),
+            self.profile.as_deref(),
+        )?
+        .into_config()
+    }
+
+    fn as_profile(&self) -> Profile {
+        Profile {
+            network: self.network,
+            signet_challenge: self.signet_challenge.clone(),
+            rpc_url: self.rpc_url.clone(),
+            rpc_username: self.rpc_username.clone(),
+            rpc_password: self.rpc_password.clone(),
+            cookie_file: self.cookie_file.clone(),
+            bitcoin_data_dir: self.bitcoin_data_dir.clone(),
+            create_wallets: self.create_wallets,
+            backend: self.backend,
+            esplora_url: self.esplora_url.clone(),
+            electrum_url: self.electrum_url.clone(),
+            ord_url: self.ord_url.clone(),
         }
     }
 }
@@ -102,15 +140,48 @@ pub enum Action {
 
     /// Create a new multisig wallet
     NewMultisig {
-        /// List of wallet names to use in multisig
-        #[arg(short = 'v', long, value_delimiter = ',')]
+        /// List of wallet names on the node to use as cosigners
+        #[arg(
+            short = 'v',
+            long,
+            value_delimiter = ',',
+            required_unless_present = "xpubs"
+        )]
         wallet_names: Vec<String>,
+        /// Account key of a cosigner held elsewhere, as [fingerprint/path]xpub; repeat for each
+        #[arg(long = "xpub")]
+        xpubs: Vec<String>,
         /// Number of required signatures
         #[arg(short = 'n', long)]
         nrequired: u32,
         /// Name for the multisig wallet
         #[arg(short = 'm', long)]
         multisig_name: String,
+        /// Lock coins to tr(sortedmulti_a) instead of wsh(sortedmulti), allowing more than 20 keys
+        #[arg(long)]
+        taproot: bool,
+        /// Cosigner, by wallet name or key, who can also spend alone by the taproot key path
+        #[arg(long, requires = "taproot")]
+        key_path_signer: Option<String>,
+        /// Taproot leaf needing fewer signatures once coins are BLOCKS old, as NREQUIRED@BLOCKS
+        #[arg(long, requires = "taproot")]
+        timelocked: Vec<TimelockedLeaf>,
+    },
+
+    /// Create a watch-only wallet whose coins spend under a miniscript policy
+    Policy {
+        /// Spending policy naming its keys, such as or(pk(A),and(pk(B),older(1000)))
+        #[arg(short = 'p', long)]
+        policy: String,
+        /// Signer of a key the policy names, as NAME=WALLET or NAME=[fingerprint/path]xpub; repeat for each
+        #[arg(short = 'k', long = "key", required = true)]
+        keys: Vec<PolicyKey>,
+        /// Name for the policy wallet
+        #[arg(short = 'm', long)]
+        wallet_name: String,
+        /// Compile to tr() instead of wsh(), with the likeliest lone key spending by the key path
+        #[arg(long)]
+        taproot: bool,
     },
 
     /// Get wallet information
@@ -172,6 +243,9 @@ pub enum Action {
         /// Name of the wallet
         #[arg(short = 'w', long, default_value = "default_wallet")]
         wallet_name: String,
+        /// Watch-only descriptor to query through the Electrum backend instead of a node wallet; repeat for several
+        #[arg(short = 'd', long = "descriptor")]
+        descriptors: Vec<String>,
     },
 
     /// List unspent transactions
@@ -179,6 +253,19 @@ pub enum Action {
         /// Name of the wallet
         #[arg(short = 'w', long, default_value = "default_wallet")]
         wallet_name: String,
+        /// Watch-only descriptor to query through the Electrum backend instead of a node wallet; repeat for several
+        #[arg(short = 'd', long = "descriptor")]
+        descriptors: Vec<String>,
+    },
+
+    /// List the transactions of watch-only descriptors through the Electrum backend
+    GetHistory {
+        /// Name of the watch-only wallet
+        #[arg(short = 'w', long, default_value = "watch_only")]
+        wallet_name: String,
+        /// Descriptor to track; repeat for several
+        #[arg(short = 'd', long = "descriptor", required = true)]
+        descriptors: Vec<String>,
     },
 
     /// Get transaction information
@@ -201,6 +288,21 @@ pub enum Action {
         vout: u32,
     },
 
+    /// List confirmed UTXOs paying to an address or script, without a wallet
+    ScanUtxos {
+        /// Address to look up
+        #[arg(
+            short = 'a',
+            long,
+            conflicts_with = "script_pubkey",
+            required_unless_present = "script_pubkey"
+        )]
+        address: Option<String>,
+        /// Hex-encoded scriptPubKey to look up
+        #[arg(short = 's', long, value_parser = parse_script_hex)]
+        script_pubkey: Option<ScriptBuf>,
+    },
+
     /// Send BTC to an address
     SendBtc {
         /// Name of the wallet
@@ -225,33 +327,173 @@ pub enum Action {
         /// Amount to send
         #[arg(short='x', long, value_parser = parse_amount)]
         amount: Amount,
-        /// Fee amount
-        #[arg(short='f', long, value_parser = parse_amount)]
-        fee_amount: Amount,
-        /// UTXO selection strategy
-        #[arg(short='y', long, value_parser = parse_utxo_strategy, default_value = "fifo")]
-        utxo_strat: UTXOStrategy,
+        #[command(flatten)]
+        fee: FeeArgs,
+        #[command(flatten)]
+        selection: SelectionArgs,
+    },
+
+    /// Pay many recipients in one transaction
+    SendMany {
+        /// Name of the wallet
+        #[arg(short = 'w', long, default_value = "default_wallet")]
+        wallet_name: String,
+        #[command(flatten)]
+        batch: BatchArgs,
+        #[command(flatten)]
+        fee: FeeArgs,
+        #[command(flatten)]
+        selection: SelectionArgs,
+    },
+
+    /// Replace an unconfirmed wallet transaction with one paying a higher fee
+    BumpFee {
+        /// Name of the wallet
+        #[arg(short = 'w', long, default_value = "default_wallet")]
+        wallet_name: String,
+        /// Transaction to replace
+        #[arg(short = 'i', long)]
+        txid: Txid,
+        #[command(flatten)]
+        fee: FeeArgs,
+        /// Cancel the transaction by spending its inputs back to the wallet
+        #[arg(long)]
+        cancel: bool,
+        #[command(flatten)]
+        selection: SelectionArgs,
+        /// Broadcast the signed replacement
+        #[arg(long)]
+        broadcast: bool,
+        /// Also write a replacement PSBT from a multisig wallet to this file in BIP174 binary
+        #[arg(short = 'o', long)]
+        out: Option<PathBuf>,
+    },
+
+    /// Speed up an unconfirmed transaction by spending its output to the wallet at a higher fee
+    Cpfp {
+        /// Name of the wallet
+        #[arg(short = 'w', long, default_value = "default_wallet")]
+        wallet_name: String,
+        /// Parent transaction, looked up by ID
+        #[arg(
+            short = 'i',
+            long,
+            conflicts_with = "tx",
+            required_unless_present = "tx"
+        )]
+        txid: Option<Txid>,
+        /// Parent transaction in hex or raw bytes, for one not yet in the mempool; `-` reads stdin and `@path` a file
+        #[arg(short = 't', long, value_parser = parse_tx)]
+        tx: Option<Transaction>,
+        /// Output of the parent to spend [default: the wallet's largest]
+        #[arg(long)]
+        vout: Option<u32>,
+        #[command(flatten)]
+        fee: FeeArgs,
+        /// Broadcast the child, as a package with the parent when the parent is not in the mempool
+        #[arg(long)]
+        broadcast: bool,
+    },
+
+    /// Merge small UTXOs into fewer outputs of the wallet while fees are low
+    Consolidate {
+        /// Name of the wallet
+        #[arg(short = 'w', long, default_value = "default_wallet")]
+        wallet_name: String,
+        /// Merge UTXOs worth less than this
+        #[arg(short = 'x', long, value_parser = parse_amount)]
+        below: Amount,
+        /// Fee rate in sat/vB
+        #[arg(long, value_parser = parse_fee_rate, conflicts_with = "conf_target")]
+        fee_rate: Option<FeeRate>,
+        /// Estimate the fee rate to confirm within this many blocks
+        #[arg(long, default_value = "144")]
+        conf_target: u16,
+        /// Spend at most this many UTXOs per transaction [default: as many as standardness allows]
+        #[arg(long, value_parser = clap::value_parser!(u16).range(2..))]
+        max_inputs: Option<u16>,
+        /// Broadcast the signed transactions
+        #[arg(long)]
+        broadcast: bool,
+    },
+
+    /// Send the whole balance of a wallet, less the fee
+    Sweep {
+        /// Name of the wallet
+        #[arg(short = 'w', long, default_value = "default_wallet")]
+        wallet_name: String,
+        /// Recipient address; repeatable, to split the balance evenly
+        #[arg(short = 'r', long = "recipient", required = true)]
+        recipients: Vec<String>,
+        #[command(flatten)]
+        fee: FeeArgs,
+        /// Broadcast the signed transaction
+        #[arg(long)]
+        broadcast: bool,
+    },
+
+    /// Keep UTXOs from being spent, until the node restarts or for good with --freeze
+    LockUnspent {
+        /// Name of the wallet
+        #[arg(short = 'w', long, default_value = "default_wallet")]
+        wallet_name: String,
+        /// Outpoint (txid:vout) to lock; repeatable
+        #[arg(
+            short = 'u',
+            long = "outpoint",
+            value_name = "OUTPOINT",
+            required = true
+        )]
+        outpoints: Vec<OutPoint>,
+        /// Freeze the outpoints in a set saved on disk instead of locking them in the node
+        #[arg(long)]
+        freeze: bool,
+    },
+
+    /// Let locked or frozen UTXOs be spent again
+    UnlockUnspent {
+        /// Name of the wallet
+        #[arg(short = 'w', long, default_value = "default_wallet")]
+        wallet_name: String,
+        /// Outpoint (txid:vout) to unlock or thaw; repeatable
+        #[arg(
+            short = 'u',
+            long = "outpoint",
+            value_name = "OUTPOINT",
+            required_unless_present = "all"
+        )]
+        outpoints: Vec<OutPoint>,
+        /// Unlock and thaw every UTXO
+        #[arg(long, conflicts_with = "outpoints")]
+        all: bool,
+    },
+
+    /// List the wallet's locked and frozen UTXOs
+    ListLocked {
+        /// Name of the wallet
+        #[arg(short = 'w', long, default_value = "default_wallet")]
+        wallet_name: String,
     },
 
     /// Decode a raw transaction
     DecodeTx {
-        /// Raw transaction hex
-        #[arg(short = 't', long)]
-        tx_hex: String,
+        /// Transaction in hex or raw bytes; `-` reads stdin and `@path` a file
+        #[arg(short = 't', long, alias = "tx-hex", value_parser = parse_tx)]
+        tx: Transaction,
     },
 
     /// Verify a signed transaction
     VerifySignedTx {
-        /// Signed transaction hex
-        #[arg(short = 't', long)]
-        tx_hex: String,
+        /// Transaction in hex or raw bytes; `-` reads stdin and `@path` a file
+        #[arg(short = 't', long, alias = "tx-hex", value_parser = parse_tx)]
+        tx: Transaction,
     },
 
     /// Broadcast a transaction
     BroadcastTx {
-        /// Transaction hex to broadcast
-        #[arg(short = 't', long)]
-        tx_hex: String,
+        /// Transaction in hex or raw bytes; `-` reads stdin and `@path` a file
+        #[arg(short = 't', long, alias = "tx-hex", value_parser = parse_tx)]
+        tx: Transaction,
     },
 
     /// Create a PSBT
@@ -265,12 +507,15 @@ pub enum Action {
         /// Amount to send
         #[arg(short='x', long, value_parser = parse_amount)]
         amount: Amount,
-        /// Fee amount
-        #[arg(short='f', long, value_parser = parse_amount)]
-        fee_amount: Amount,
-        /// UTXO selection strategy
-        #[arg(short='y', long, value_parser = parse_utxo_strategy, default_value = "fifo")]
-        utxo_strat: UTXOStrategy,
+        #[command(flatten)]
+        fee: FeeArgs,
+        #[command(flatten)]
+        selection: SelectionArgs,
+        #[command(flatten)]
+        timelock: TimelockArgs,
+        /// Also write the resulting PSBT to this file in BIP174 binary
+        #[arg(short = 'o', long)]
+        out: Option<PathBuf>,
     },
 
     /// Process a PSBT
@@ -278,44 +523,47 @@ pub enum Action {
         /// Name of the wallet
         #[arg(short = 'w', long, default_value = "default_wallet")]
         wallet_name: String,
-        /// PSBT hex
-        #[arg(short = 'p', long)]
-        psbt_hex: String,
+        /// PSBT in base64, hex or BIP174 binary; `-` reads stdin and `@path` a file
+        #[arg(short = 'p', long, alias = "psbt-hex", value_parser = parse_psbt)]
+        psbt: Psbt,
+        /// Also write the resulting PSBT to this file in BIP174 binary
+        #[arg(short = 'o', long)]
+        out: Option<PathBuf>,
     },
 
     /// Decode a PSBT
     DecodePsbt {
-        /// PSBT hex
-        #[arg(short = 'p', long)]
-        psbt_hex: String,
+        /// PSBT in base64, hex or BIP174 binary; `-` reads stdin and `@path` a file
+        #[arg(short = 'p', long, alias = "psbt-hex", value_parser = parse_psbt)]
+        psbt: Psbt,
     },
 
     /// Analyze a PSBT
     AnalyzePsbt {
-        /// PSBT hex
-        #[arg(short = 'p', long)]
-        psbt_hex: String,
+        /// PSBT in base64, hex or BIP174 binary; `-` reads stdin and `@path` a file
+        #[arg(short = 'p', long, alias = "psbt-hex", value_parser = parse_psbt)]
+        psbt: Psbt,
     },
 
     /// Combine multiple PSBTs
     CombinePsbts {
-        /// List of PSBT hexes
-        #[arg(short = 'l', long, value_delimiter = ',')]
-        psbts: Vec<String>,
+        /// Comma separated PSBTs, each in base64, hex, `-` for stdin or `@path`
+        #[arg(short = 'l', long, value_delimiter = ',', value_parser = parse_psbt)]
+        psbts: Vec<Psbt>,
     },
 
     /// Finalize a PSBT
     FinalizePsbt {
-        /// PSBT hex
-        #[arg(short = 'p', long)]
-        psbt_hex: String,
+        /// PSBT in base64, hex or BIP174 binary; `-` reads stdin and `@path` a file
+        #[arg(short = 'p', long, alias = "psbt-hex", value_parser = parse_psbt)]
+        psbt: Psbt,
     },
 
     /// Finalize and broadcast a PSBT
     FinalizePsbtAndBroadcast {
-        /// PSBT hex
-        #[arg(short = 'p', long)]
-        psbt_hex: String,
+        /// PSBT in base64, hex or BIP174 binary; `-` reads stdin and `@path` a file
+        #[arg(short = 'p', long, alias = "psbt-hex", value_parser = parse_psbt)]
+        psbt: Psbt,
     },
 
     /// Inscribe an ordinal
@@ -329,6 +577,8 @@ pub enum Action {
         /// File path for inscription
         #[arg(short = 'f', long)]
         file_path: String,
+        #[command(flatten)]
+        fee: FeeArgs,
     },
 
     /// Etch a rune
@@ -342,6 +592,8 @@ pub enum Action {
         /// File path for etching data
         #[arg(short = 'f', long)]
         file_path: String,
+        #[command(flatten)]
+        fee: FeeArgs,
     },
 
     /// Mine blocks
@@ -358,19 +610,138 @@ pub enum Action {
     },
 }
 
+/// How the fee of a new transaction is chosen.
+#[derive(Args, Debug)]
+pub struct FeeArgs {
+    /// Fee rate in sat/vB
+    #[arg(long, value_parser = parse_fee_rate, conflicts_with = "conf_target")]
+    pub fee_rate: Option<FeeRate>,
+    /// Estimate the fee rate to confirm within this many blocks
+    #[arg(long, default_value = "6")]
+    pub conf_target: u16,
+}
+
+/// How to choose the coins a transaction spends and pay back its change.
+#[derive(Args, Debug)]
+pub struct SelectionArgs {
+    /// UTXO selection strategy, by the name of a registered coin selector
+    #[arg(short='y', long, value_parser = parse_utxo_strategy, default_value = "fifo")]
+    pub utxo_strat: String,
+    /// Fee rate in sat/vB spending coins is expected to cost later on
+    #[arg(long, value_parser = parse_fee_rate, default_value = "10")]
+    pub long_term_fee_rate: FeeRate,
+    /// Seed for strategies that pick coins at random, to make their choice reproducible
+    #[arg(long, conflicts_with = "inputs")]
+    pub seed: Option<u64>,
+    /// Spend exactly this outpoint (txid:vout) instead of selecting coins; repeatable
+    #[arg(long = "input", value_name = "OUTPOINT", conflicts_with = "utxo_strat")]
+    pub inputs: Vec<OutPoint>,
+    /// Spend this outpoint (txid:vout) even if it holds inscriptions or runes; repeatable
+    #[arg(long, value_name = "OUTPOINT")]
+    pub allow_protected: Vec<OutPoint>,
+    /// Pay change to the same type of address as the recipient
+    #[arg(long)]
+    pub match_change_type: bool,
+    /// Put change at a random position among the outputs instead of last
+    #[arg(long)]
+    pub random_change_position: bool,
+    /// Split change into up to this many outputs, as far as none is dust
+    #[arg(long, value_parser = clap::value_parser!(u16).range(1..=100), default_value = "1")]
+    pub change_outputs: u16,
+}
+
+impl SelectionArgs {
+    pub fn change_policy(&self) -> ChangePolicy {
+        ChangePolicy {
+            match_recipient_type: self.match_change_type,
+            random_position: self.random_change_position,
+            max_outputs: self.change_outputs.into(),
+        }
+    }
+}
+
+/// Timelocks a transaction satisfies, to spend through timelocked branches.
+#[derive(Args, Debug)]
+pub struct TimelockArgs {
+    /// Lock inputs for this many blocks, to spend through a timelocked leaf or older() branch
+    #[arg(long, value_parser = clap::value_parser!(u16).range(1..))]
+    pub older: Option<u16>,
+    /// Lock inputs for this many 512-second intervals, for an older() branch in units of time
+    #[arg(long, value_parser = clap::value_parser!(u16).range(1..), conflicts_with = "older")]
+    pub older_time: Option<u16>,
+    /// Lock time of the transaction, a block height or UNIX time, for an after() branch
+    #[arg(long)]
+    pub after: Option<absolute::LockTime>,
+}
+
+impl TimelockArgs {
+    pub fn older(&self) -> Option<relative::LockTime> {
+        match (self.older, self.older_time) {
+            (Some(blocks), _) => Some(relative::Height::from(blocks).into()),
+            (None, Some(intervals)) => {
+                Some(relative::Time::from_512_second_intervals(intervals).into())
+            }
+            (None, None) => None,
+        }
+    }
+}
+
+/// The payments of a batch and what to do with the transaction paying them.
+#[derive(Args, Debug)]
+pub struct BatchArgs {
+    /// CSV or JSON file of address and amount pairs or BIP21 URIs, amounts in BTC; `-` reads stdin
+    #[arg(short = 'f', long)]
+    pub payments: PathBuf,
+    /// Create a PSBT from a multisig wallet instead of signing
+    #[arg(long, conflicts_with = "broadcast")]
+    pub psbt: bool,
+    /// Broadcast the signed transaction
+    #[arg(long)]
+    pub broadcast: bool,
+    /// Also write the resulting PSBT to this file in BIP174 binary
+    #[arg(short = 'o', long, requires = "psbt")]
+    pub out: Option<PathBuf>,
+}
+
+impl FeeArgs {
+    pub fn target(&self) -> FeeTarget {
+        match self.fee_rate {
+            Some(rate) => FeeTarget::Rate(rate),
+            None => FeeTarget::ConfTarget(self.conf_target),
+        }
+    }
+}
+
 fn parse_amount(s: &str) -> Result<Amount, &'static str> {
     Amount::from_str_in(s, Bitcoin).map_err(|_| "invalid amount")
 }
 
-fn parse_network_type(s: &str) -> Result<Network, &'static str> {
-    match s {
-        "regtest" => Ok(Network::Regtest),
-        "testnet" => Ok(Network::Testnet),
-        "mainnet" => Ok(Network::Bitcoin),
-        _ => Err("Unknown network type"),
+fn parse_fee_rate(s: &str) -> Result<FeeRate, &'static str> {
+    // Keep fractional rates down to 1 sat/kwu, which is 0.004 sat/vB
+    match s.parse::<f64>() {
+        Ok(sat_per_vb) if sat_per_vb.is_finite() && sat_per_vb > 0.0 => Ok(
+            FeeRate::from_sat_per_kwu((sat_per_vb * 250.0).round() as u64),
+        ),
+        _ => Err("invalid fee rate"),
     }
 }
 
+fn parse_network_type(s: &str) -> Result<Chain, &'static str> {
+    s.parse::<Chain>().map_err(|_| "Unknown network type")
+}
+
+fn parse_backend(s: &str) -> Result<BackendKind, &'static str> {
+    s.parse::<BackendKind>().map_err(|_| "Unknown backend")
+}
+
+fn parse_output_format(s: &str) -> Result<OutputFormat, String> {
+    s.parse::<OutputFormat>()
+}
+
+fn parse_script_hex(s: &str) -> Result<ScriptBuf, &'static str> {
+    ScriptBuf::from_hex(s).map_err(|_| "invalid script hex")
+}
+
 fn parse_address_type(s: &str) -> Result<AddressType, &'static str> {
     match s {
         "legacy" => Ok(AddressType::Legacy),
@@ -381,12 +752,23 @@ fn parse_address_type(s: &str) -> Result<AddressType, &'static str> {
     }
 }
 
-fn parse_utxo_strategy(s: &str) -> Result<UTXOStrategy, &'static str> {
-    match s {
-        "branch-and-bound" => Ok(UTXOStrategy::BranchAndBound),
-        "fifo" => Ok(UTXOStrategy::Fifo),
-        "largest-first" => Ok(UTXOStrategy::LargestFirst),
-        "smallest-first" => Ok(UTXOStrategy::SmallestFirst),
-        _ => Err("Unknown UTXO selection strategy"),
+/// The coin selectors `--utxo-strat` chooses from, drawing random choices
+/// from `seed` when one is given. Selectors implemented outside the suite
+/// are registered here.
+pub fn coin_selectors(seed: Option<u64>) -> CoinSelectorRegistry {
+    match seed {
+        Some(seed) => CoinSelectorRegistry::seeded(seed),
+        None => CoinSelectorRegistry::default(),
+    }
+}
+
+fn parse_utxo_strategy(s: &str) -> Result<String, String> {
+    let selectors = coin_selectors(None);
+    match selectors.get(s) {
+        Some(_) => Ok(s.to_string()),
+        None => Err(format!(
+            "Unknown UTXO selection strategy, expected one of: {}",
+            selectors.names().collect::<Vec<_>>().join(", ")
+        )),
     }
 }
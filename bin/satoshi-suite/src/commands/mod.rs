@@ -1,215 +1,442 @@
-use std::{error::Error, str::FromStr};
+use std::{error::Error, fs, io, path::Path, str::FromStr};
 
 use ordinals::{Etching, Rune, Terms};
 use serde_json::json;
 
-use bitcoin::{Amount, Txid};
+use bitcoin::{
+    consensus::{deserialize, serialize},
+    Amount, FeeRate, OutPoint, Psbt, ScriptBuf, Transaction, Txid,
+};
 use bitcoincore_rpc::{json::AddressType, RawTx, RpcApi};
 
-use satoshi_suite_client::create_rpc_client;
+use satoshi_suite_client::{
+    create_chain_backend, create_rpc_client, format_sat_per_vb, resolve_fee_rate, ChainBackend,
+    FeeTarget, RpcBackend,
+};
 use satoshi_suite_config::Config;
-use satoshi_suite_signing::{sign_tx, verify_signed_tx};
-use satoshi_suite_utxo_selection::UTXOStrategy;
+use satoshi_suite_signing::{
+    build_cpfp, bump_fee as bump_tx_fee, cancel_tx, consolidate as consolidate_utxos,
+    sign_payments, sign_tx, sweep as sweep_wallet, verify_signed_tx, Replacement,
+};
+use satoshi_suite_utxo_selection::{CoinSelector, CoinSelectorRegistry};
 use satoshi_suite_wallet::{
-    get_scriptpubkey_from_address, string_to_address, MultisigWallet, Wallet,
+    get_scriptpubkey_from_address, parse_payments, string_to_address, Cosigner, MultisigScript,
+    MultisigWallet, PolicyKey, PolicyScript, PolicyWallet, SpendOptions, TaprootMultisig,
+    TimelockedLeaf, Wallet,
 };
 use tracing::info;
 
-use crate::cli::{Action, Cli};
+use crate::cli::{coin_selectors, Action, BatchArgs, Cli, FeeArgs, SelectionArgs, TimelockArgs};
+use crate::output::{
+    AddressOutput, BalancesOutput, BatchOutput, BlockHeightOutput, BootstrapOutput, BumpFeeOutput,
+    CommandOutput, ConsolidateOutput, ConsolidationOutput, CpfpOutput, DerivedAddressOutput,
+    EtchingOutput, InscriptionOutput, LockedOutput, MinedOutput, PsbtOutput, RescanOutput,
+    ScanOutput, SentOutput, SignedTxOutput, TxidOutput, VerifyOutput, WalletOutput,
+};
 
-pub fn handler(args: &Cli, config: &Config) -> Result<(), Box<dyn Error>> {
+pub fn handler(args: &Cli, config: &Config) -> Result<CommandOutput, Box<dyn Error>> {
     match &args.action {
-        Action::BootstrapEnv { address_type } => bootstrap_env(&address_type, config),
+        Action::BootstrapEnv { address_type } => bootstrap_env(address_type, config),
         Action::GetBlockHeight => get_block_height(config),
         Action::NewWallet { wallet_name } => new_wallet(wallet_name.as_str(), config),
         Action::NewMultisig {
             wallet_names,
+            xpubs,
             nrequired,
             multisig_name,
-        } => new_multisig_wallet(&wallet_names, *nrequired, multisig_name.as_str(), config),
+            taproot,
+            key_path_signer,
+            timelocked,
+        } => new_multisig_wallet(
+            wallet_names,
+            xpubs,
+            *nrequired,
+            multisig_name.as_str(),
+            *taproot,
+            key_path_signer.as_deref(),
+            timelocked,
+            config,
+        ),
+        Action::Policy {
+            policy,
+            keys,
+            wallet_name,
+            taproot,
+        } => new_policy_wallet(policy, keys, wallet_name, *taproot, config),
         Action::GetWalletInfo { wallet_name } => get_wallet_info(wallet_name.as_str(), config),
         Action::ListDescriptors { wallet_name } => list_descriptors(wallet_name.as_str(), config),
         Action::GetNewAddress {
             wallet_name,
             address_type,
-        } => get_new_address(wallet_name.as_str(), &address_type, config),
+        } => get_new_address(wallet_name.as_str(), address_type, config),
         Action::GetAddressInfo {
             wallet_name,
             address,
-        } => get_address_info(wallet_name.as_str(), &address, config),
+        } => get_address_info(wallet_name.as_str(), address, config),
         Action::DeriveAddresses {
             descriptor,
             start,
             end,
         } => derive_addresses(descriptor.as_str(), *start, *end, config),
         Action::RescanBlockchain { start } => rescan_blockchain(*start, config),
-        Action::GetBalance { wallet_name } => get_balance(wallet_name.as_str(), config),
-        Action::ListUnspent { wallet_name } => list_unspent(wallet_name.as_str(), config),
-        Action::GetTx { wallet_name, txid } => get_tx(wallet_name.as_str(), &txid, config),
-        Action::GetTxOut { txid, vout } => get_tx_out(&txid, *vout, config),
+        Action::GetBalance {
+            wallet_name,
+            descriptors,
+        } => get_balance(wallet_name.as_str(), descriptors, config),
+        Action::ListUnspent {
+            wallet_name,
+            descriptors,
+        } => list_unspent(wallet_name.as_str(), descriptors, config),
+        Action::GetHistory {
+            wallet_name,
+            descriptors,
+        } => get_history(wallet_name.as_str(), descriptors, config),
+        Action::GetTx { wallet_name, txid } => get_tx(wallet_name.as_str(), txid, config),
+        Action::GetTxOut { txid, vout } => get_tx_out(txid, *vout, config),
+        Action::ScanUtxos {
+            address,
+            script_pubkey,
+        } => scan_utxos(address.as_deref(), script_pubkey.as_ref(), config),
         Action::SendBtc {
             wallet_name,
             recipient,
             amount,
-        } => send_btc(wallet_name.as_str(), &recipient, *amount, config),
+        } => send_btc(wallet_name.as_str(), recipient, *amount, config),
+        Action::SendMany {
+            wallet_name,
+            batch,
+            fee,
+            selection,
+        } => send_many(wallet_name.as_str(), batch, fee, selection, config),
+        Action::BumpFee {
+            wallet_name,
+            txid,
+            fee,
+            cancel,
+            selection,
+            broadcast,
+            out,
+        } => bump_fee(
+            wallet_name.as_str(),
+            txid,
+            fee,
+            *cancel,
+            selection,
+            *broadcast,
+            out.as_deref(),
+            config,
+        ),
+        Action::Cpfp {
+            wallet_name,
+            txid,
+            tx,
+            vout,
+            fee,
+            broadcast,
+        } => cpfp(
+            wallet_name.as_str(),
+            txid.as_ref(),
+            tx.as_ref(),
+            *vout,
+            fee,
+            *broadcast,
+            config,
+        ),
+        Action::Consolidate {
+            wallet_name,
+            below,
+            fee_rate,
+            conf_target,
+            max_inputs,
+            broadcast,
+        } => {
+            let target = match fee_rate {
+                Some(rate) => FeeTarget::Rate(*rate),
+                None => FeeTarget::ConfTarget(*conf_target),
+            };
+            consolidate(
+                wallet_name.as_str(),
+                *below,
+                target,
+                max_inputs.map(usize::from),
+                *broadcast,
+                config,
+            )
+        }
+        Action::Sweep {
+            wallet_name,
+            recipients,
+            fee,
+            broadcast,
+        } => sweep(wallet_name.as_str(), recipients, fee, *broadcast, config),
+        Action::LockUnspent {
+            wallet_name,
+            outpoints,
+            freeze,
+        } => lock_unspent(wallet_name.as_str(), outpoints, *freeze, config),
+        Action::UnlockUnspent {
+            wallet_name,
+            outpoints,
+            all,
+        } => unlock_unspent(wallet_name.as_str(), outpoints, *all, config),
+        Action::ListLocked { wallet_name } => list_locked(wallet_name.as_str(), config),
         Action::SignTx {
             wallet_name,
             recipient,
             amount,
-            fee_amount,
-            utxo_strat,
+            fee,
+            selection,
         } => sign_transaction(
             wallet_name.as_str(),
-            &recipient,
+            recipient,
             *amount,
-            *fee_amount,
-            *utxo_strat,
+            fee,
+            selection,
             config,
         ),
-        Action::DecodeTx { tx_hex } => decode_raw_tx(tx_hex.as_str(), config),
-        Action::VerifySignedTx { tx_hex } => verify_signed_transaction(tx_hex.as_str(), config),
-        Action::BroadcastTx { tx_hex } => broadcast_tx(tx_hex.as_str(), config),
+        Action::DecodeTx { tx } => decode_raw_tx(tx),
+        Action::VerifySignedTx { tx } => verify_signed_transaction(tx, config),
+        Action::BroadcastTx { tx } => broadcast_tx(tx, config),
         Action::CreatePsbt {
             wallet_name,
             recipient,
             amount,
-            fee_amount,
-            utxo_strat,
+            fee,
+            selection,
+            timelock,
+            out,
         } => create_psbt(
             wallet_name.as_str(),
-            &recipient,
+            recipient,
             *amount,
-            *fee_amount,
-            *utxo_strat,
+            fee,
+            selection,
+            timelock,
+            out.as_deref(),
             config,
         ),
         Action::ProcessPsbt {
             wallet_name,
-            psbt_hex,
-        } => process_psbt(wallet_name.as_str(), psbt_hex.as_str(), config),
-        Action::DecodePsbt { psbt_hex } => decode_psbt(psbt_hex.as_str(), config),
-        Action::AnalyzePsbt { psbt_hex } => analyze_psbt(psbt_hex.as_str(), config),
-        Action::CombinePsbts { psbts } => combine_psbts(&psbts, config),
-        Action::FinalizePsbt { psbt_hex } => finalize_psbt(psbt_hex.as_str(), config),
-        Action::FinalizePsbtAndBroadcast { psbt_hex } => {
-            finalize_psbt_and_broadcast(&psbt_hex, config)
-        }
+            psbt,
+            out,
+        } => process_psbt(wallet_name.as_str(), psbt, out.as_deref(), config),
+        Action::DecodePsbt { psbt } => decode_psbt(psbt, config),
+        Action::AnalyzePsbt { psbt } => analyze_psbt(psbt, config),
+        Action::CombinePsbts { psbts } => combine_psbts(psbts, config),
+        Action::FinalizePsbt { psbt } => finalize_psbt(psbt, config),
+        Action::FinalizePsbtAndBroadcast { psbt } => finalize_psbt_and_broadcast(psbt, config),
         Action::InscribeOrdinal {
             wallet_name,
             postage,
             file_path,
-        } => inscribe_ordinal(wallet_name.as_str(), &postage, &file_path, config),
+            fee,
+        } => inscribe_ordinal(wallet_name.as_str(), postage, file_path, fee, config),
         Action::EtchRune {
             wallet_name,
             postage,
             file_path,
-        } => etch_rune(wallet_name.as_str(), &postage, &file_path, config),
+            fee,
+        } => etch_rune(wallet_name.as_str(), postage, file_path, fee, config),
         Action::MineBlocks {
             wallet_name,
             blocks,
             address_type,
-        } => wallet_mine_blocks(wallet_name.as_str(), *blocks, &address_type, config),
+        } => wallet_mine_blocks(wallet_name.as_str(), *blocks, address_type, config),
     }
 }
 
-pub fn bootstrap_env(address_type: &AddressType, config: &Config) -> Result<(), Box<dyn Error>> {
-    for i in 1..11 {
-        let wallet = Wallet::new(&format!("wallet{}", i), &config)?;
-        let _ = wallet.mine_blocks(&address_type, 1)?;
+pub fn bootstrap_env(
+    address_type: &AddressType,
+    config: &Config,
+) -> Result<CommandOutput, Box<dyn Error>> {
+    let wallet_names: Vec<String> = (1..11).map(|i| format!("wallet{}", i)).collect();
+    for wallet_name in &wallet_names {
+        let wallet = Wallet::new(wallet_name, config)?;
+        let _ = wallet.mine_blocks(address_type, 1)?;
     }
 
-    let miner = Wallet::new("miner", &config)?;
-    let _ = miner.mine_blocks(&address_type, 100)?;
+    let miner = Wallet::new("miner", config)?;
+    let _ = miner.mine_blocks(address_type, 100)?;
 
-    for i in 1..11 {
-        let wallet = Wallet::new(&format!("wallet{}", i), &config)?;
+    for wallet_name in &wallet_names {
+        let wallet = Wallet::new(wallet_name, config)?;
         let balance = wallet.get_balances()?;
 
         let expected_balance = bitcoin::Amount::from_btc(50.0).unwrap();
         if !balance.mine.trusted.eq(&expected_balance) {
             return Err(format!(
                 "Wallet {} balance mismatch. Expected: {}, Actual: {}",
-                i, expected_balance, balance.mine.trusted
+                wallet_name, expected_balance, balance.mine.trusted
             )
             .into());
         }
     }
 
-    Ok(())
+    CommandOutput::new(
+        format!(
+            "Funded {} with 50 BTC each, mined by miner",
+            wallet_names.join(", ")
+        ),
+        BootstrapOutput {
+            wallets: wallet_names,
+            miner: "miner".to_string(),
+        },
+    )
 }
 
-pub fn get_block_height(config: &Config) -> Result<(), Box<dyn Error>> {
-    let client = create_rpc_client(config, None)?;
-    let height = client.get_block_count()?;
-    info!("Current block height: {}", height);
-    Ok(())
+pub fn get_block_height(config: &Config) -> Result<CommandOutput, Box<dyn Error>> {
+    let backend = create_chain_backend(config)?;
+    let height = backend.get_block_count()?;
+    CommandOutput::new(
+        format!("Current block height: {}", height),
+        BlockHeightOutput { height },
+    )
 }
 
-pub fn new_wallet(wallet_name: &str, config: &Config) -> Result<(), Box<dyn Error>> {
+pub fn new_wallet(wallet_name: &str, config: &Config) -> Result<CommandOutput, Box<dyn Error>> {
     let _ = Wallet::new(wallet_name, config)?;
-    Ok(())
+    CommandOutput::new(
+        format!("Wallet loaded: {}", wallet_name),
+        WalletOutput {
+            wallet_name: wallet_name.to_string(),
+        },
+    )
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn new_multisig_wallet(
-    wallet_names: &Vec<String>,
+    wallet_names: &[String],
+    xpubs: &[String],
     nrequired: u32,
     multisig_name: &str,
+    taproot: bool,
+    key_path_signer: Option<&str>,
+    timelocked: &[TimelockedLeaf],
     config: &Config,
-) -> Result<(), Box<dyn Error>> {
-    let multisig = MultisigWallet::new(wallet_names, nrequired, multisig_name, config)?;
-    info!("Multisig wallet created");
-    info!("Multisig wallet name: {}", multisig.name);
-    info!("Multisig wallet signers: {}", multisig.signers.join(", "));
-    info!("Multisig wallet nrequired: {}", multisig.nrequired);
-    Ok(())
+) -> Result<CommandOutput, Box<dyn Error>> {
+    let node = RpcBackend::new(config, None)?;
+    let mut cosigners: Vec<Cosigner> = wallet_names.iter().cloned().map(Cosigner::Wallet).collect();
+    for xpub in xpubs {
+        cosigners.push(Cosigner::xpub(xpub)?);
+    }
+    let script = match taproot {
+        true => MultisigScript::Tr(TaprootMultisig {
+            key_path_signer: key_path_signer
+                .map(|signer| {
+                    cosigners
+                        .iter()
+                        .position(|cosigner| cosigner.matches(signer))
+                        .ok_or_else(|| format!("Key-path signer {} is not a cosigner", signer))
+                })
+                .transpose()?,
+            timelocked: timelocked.to_vec(),
+        }),
+        false => MultisigScript::Wsh,
+    };
+    let multisig = MultisigWallet::new(&node, &cosigners, nrequired, script, multisig_name)?;
+    CommandOutput::new(
+        format!(
+            "Multisig wallet created\nMultisig wallet name: {}\nMultisig wallet signers: {}\nMultisig wallet nrequired: {}\nMultisig wallet descriptor: {}",
+            multisig.name,
+            multisig.signers.join(", "),
+            multisig.nrequired,
+            multisig.descriptor
+        ),
+        multisig,
+    )
+}
+
+pub fn new_policy_wallet(
+    policy: &str,
+    keys: &[PolicyKey],
+    wallet_name: &str,
+    taproot: bool,
+    config: &Config,
+) -> Result<CommandOutput, Box<dyn Error>> {
+    let node = RpcBackend::new(config, None)?;
+    let script = match taproot {
+        true => PolicyScript::Tr,
+        false => PolicyScript::Wsh,
+    };
+    let wallet = PolicyWallet::new(&node, policy, keys, script, wallet_name)?;
+    let signers: Vec<String> = wallet
+        .keys
+        .iter()
+        .map(|(name, signer)| format!("{}={}", name, signer))
+        .collect();
+    CommandOutput::new(
+        format!(
+            "Policy wallet created\nPolicy wallet name: {}\nPolicy wallet policy: {}\nPolicy wallet signers: {}\nPolicy wallet descriptor: {}",
+            wallet.name,
+            wallet.policy,
+            signers.join(", "),
+            wallet.descriptor
+        ),
+        wallet,
+    )
 }
 
-pub fn get_wallet_info(wallet_name: &str, config: &Config) -> Result<(), Box<dyn Error>> {
+pub fn get_wallet_info(
+    wallet_name: &str,
+    config: &Config,
+) -> Result<CommandOutput, Box<dyn Error>> {
     let wallet = Wallet::new(wallet_name, config)?;
     let info = wallet.get_wallet_info()?;
-    info!("{:#?}", info);
-    Ok(())
+    CommandOutput::new(format!("{:#?}", info), info)
 }
 
-pub fn rescan_blockchain(start_height: u32, config: &Config) -> Result<(), Box<dyn Error>> {
+pub fn rescan_blockchain(
+    start_height: u32,
+    config: &Config,
+) -> Result<CommandOutput, Box<dyn Error>> {
     let client = create_rpc_client(config, None)?;
-    client.rescan_blockchain(Some(start_height as usize), None)?;
-    info!("Blockchain rescanned");
-    Ok(())
+    let (start_height, stop_height) =
+        client.rescan_blockchain(Some(start_height as usize), None)?;
+    CommandOutput::new(
+        "Blockchain rescanned",
+        RescanOutput {
+            start_height,
+            stop_height,
+        },
+    )
 }
 
-pub fn list_descriptors(wallet_name: &str, config: &Config) -> Result<(), Box<dyn Error>> {
+pub fn list_descriptors(
+    wallet_name: &str,
+    config: &Config,
+) -> Result<CommandOutput, Box<dyn Error>> {
     let client = create_rpc_client(config, Some(wallet_name))?;
     let descriptors: serde_json::Value = client.call("listdescriptors", &[])?;
-    info!("{:#?}", descriptors);
-    Ok(())
+    CommandOutput::new(serde_json::to_string_pretty(&descriptors)?, descriptors)
 }
 
 pub fn get_new_address(
     wallet_name: &str,
     address_type: &AddressType,
     config: &Config,
-) -> Result<(), Box<dyn Error>> {
+) -> Result<CommandOutput, Box<dyn Error>> {
     let wallet = Wallet::new(wallet_name, config)?;
     let address = wallet.new_address(address_type)?;
-    info!("New address: {}", address);
-    Ok(())
+    CommandOutput::new(
+        format!("New address: {}", address),
+        AddressOutput { address },
+    )
 }
 
 pub fn get_address_info(
     wallet_name: &str,
-    address: &String,
+    address: &str,
     config: &Config,
-) -> Result<(), Box<dyn Error>> {
+) -> Result<CommandOutput, Box<dyn Error>> {
     let wallet = Wallet::new(wallet_name, config)?;
     let addr = string_to_address(address, config.bitcoin_rpc.network())?;
 
     let pubkey = get_scriptpubkey_from_address(address, config.bitcoin_rpc.network())?;
-    info!("Address PubKey: {}", pubkey);
-
     let address_info = wallet.get_address_info(&addr)?;
-    info!("{:#?}", address_info);
-    Ok(())
+    CommandOutput::new(
+        format!("Address PubKey: {}\n{:#?}", pubkey, address_info),
+        address_info,
+    )
 }
 
 pub fn derive_addresses(
@@ -217,168 +444,646 @@ pub fn derive_addresses(
     start: u32,
     end: u32,
     config: &Config,
-) -> Result<(), Box<dyn Error>> {
+) -> Result<CommandOutput, Box<dyn Error>> {
     let client = create_rpc_client(config, None)?;
     let range: [u32; 2] = [start, end];
 
-    let addresses = client.derive_addresses(&descriptor.to_string(), Some(range))?;
-    info!("Derived addresses:");
-    for (i, address) in addresses.iter().enumerate() {
-        info!("  {}: {:#?}", i + start as usize, address);
+    let addresses = client
+        .derive_addresses(descriptor, Some(range))?
+        .into_iter()
+        .zip(start..)
+        .map(|(address, index)| {
+            Ok(DerivedAddressOutput {
+                index,
+                address: address.require_network(config.bitcoin_rpc.network())?,
+            })
+        })
+        .collect::<Result<Vec<_>, Box<dyn Error>>>()?;
+
+    let mut text = "Derived addresses:".to_string();
+    for derived in &addresses {
+        text.push_str(&format!("\n  {}: {}", derived.index, derived.address));
     }
-    Ok(())
+    CommandOutput::new(text, addresses)
 }
 
-pub fn get_balance(wallet_name: &str, config: &Config) -> Result<(), Box<dyn Error>> {
-    let wallet = Wallet::new(wallet_name, config)?;
-    let balance = wallet.get_balances()?;
-    info!("Balance: {:#?}", balance);
-    Ok(())
+pub fn get_balance(
+    wallet_name: &str,
+    descriptors: &[String],
+    config: &Config,
+) -> Result<CommandOutput, Box<dyn Error>> {
+    let balance = if descriptors.is_empty() {
+        Wallet::new(wallet_name, config)?.get_balances()?
+    } else {
+        Wallet::watch_only(wallet_name, descriptors, config)?.get_balances()?
+    };
+    CommandOutput::new(
+        format!("Balance: {:#?}", balance),
+        BalancesOutput::from(&balance),
+    )
 }
 
-pub fn list_unspent(wallet_name: &str, config: &Config) -> Result<(), Box<dyn Error>> {
-    let wallet = Wallet::new(wallet_name, config)?;
-    let unspent = wallet.list_all_unspent(None)?;
-    info!("Unspent: {:#?}", unspent);
-    Ok(())
+pub fn list_unspent(
+    wallet_name: &str,
+    descriptors: &[String],
+    config: &Config,
+) -> Result<CommandOutput, Box<dyn Error>> {
+    let unspent = if descriptors.is_empty() {
+        Wallet::new(wallet_name, config)?.list_all_unspent(None)?
+    } else {
+        Wallet::watch_only(wallet_name, descriptors, config)?.list_all_unspent(None)?
+    };
+    CommandOutput::new(format!("Unspent: {:#?}", unspent), unspent)
 }
 
-pub fn get_tx(wallet_name: &str, txid: &str, config: &Config) -> Result<(), Box<dyn Error>> {
+pub fn get_history(
+    wallet_name: &str,
+    descriptors: &[String],
+    config: &Config,
+) -> Result<CommandOutput, Box<dyn Error>> {
+    let wallet = Wallet::watch_only(wallet_name, descriptors, config)?;
+    let history = wallet.client.get_history()?;
+    CommandOutput::new(format!("History: {:#?}", history), history)
+}
+
+pub fn get_tx(
+    wallet_name: &str,
+    txid: &str,
+    config: &Config,
+) -> Result<CommandOutput, Box<dyn Error>> {
     let client = create_rpc_client(config, Some(wallet_name))?;
     let txid = Txid::from_str(txid)?;
-    let tx = client.get_transaction(&txid, None)?;
-    info!("{:?}", tx);
-    Ok(())
+    let tx: serde_json::Value = client.call("gettransaction", &[json!(txid)])?;
+    CommandOutput::new(serde_json::to_string_pretty(&tx)?, tx)
 }
 
-pub fn get_tx_out(txid: &str, vout: u32, config: &Config) -> Result<(), Box<dyn Error>> {
-    let client = create_rpc_client(config, None)?;
+pub fn get_tx_out(txid: &str, vout: u32, config: &Config) -> Result<CommandOutput, Box<dyn Error>> {
+    let backend = create_chain_backend(config)?;
     let txid_converted =
         bitcoin::Txid::from_str(txid).map_err(|_| Box::<dyn Error>::from("Invalid TxID"))?;
-    let tx_out = client
-        .get_tx_out(&txid_converted, vout, None)?
+    let tx_out = backend
+        .get_tx_out(&OutPoint::new(txid_converted, vout), true)?
         .ok_or_else(|| Box::<dyn Error>::from("TxOut not found"))?;
-    info!("{:#?}", tx_out);
-    Ok(())
+    CommandOutput::new(format!("{:#?}", tx_out), tx_out)
+}
+
+pub fn scan_utxos(
+    address: Option<&str>,
+    script_pubkey: Option<&ScriptBuf>,
+    config: &Config,
+) -> Result<CommandOutput, Box<dyn Error>> {
+    let script_pubkey = match (address, script_pubkey) {
+        (Some(address), _) => {
+            string_to_address(address, config.bitcoin_rpc.network())?.script_pubkey()
+        }
+        (None, Some(script_pubkey)) => script_pubkey.clone(),
+        (None, None) => return Err("Either an address or a script is required".into()),
+    };
+
+    let backend = create_chain_backend(config)?;
+    let utxos = backend.scan_utxos(&script_pubkey)?;
+    let total: Amount = utxos.iter().map(|utxo| utxo.amount).sum();
+    CommandOutput::new(
+        format!("UTXOs: {:#?}\nTotal: {}", utxos, total),
+        ScanOutput { utxos, total },
+    )
 }
 
 pub fn send_btc(
     wallet_name: &str,
-    recipient: &String,
+    recipient: &str,
     amount: bitcoin::Amount,
     config: &Config,
-) -> Result<(), Box<dyn Error>> {
+) -> Result<CommandOutput, Box<dyn Error>> {
     let wallet = Wallet::new(wallet_name, config)?;
     let recipient_addr = string_to_address(recipient, config.bitcoin_rpc.network())?;
 
-    let outpoint = wallet.send(&recipient_addr, amount)?;
-    info!("Sent: {}", outpoint);
-    Ok(())
+    let outpoint = wallet.send(&recipient_addr, amount, &SpendOptions::default())?;
+    CommandOutput::new(
+        format!("Sent: {}", outpoint),
+        SentOutput {
+            txid: outpoint.txid,
+            vout: outpoint.vout,
+        },
+    )
 }
 
 pub fn sign_transaction(
     wallet_name: &str,
-    recipient: &String,
+    recipient: &str,
     amount: bitcoin::Amount,
-    fee_amount: bitcoin::Amount,
-    utxo_strat: UTXOStrategy,
+    fee: &FeeArgs,
+    selection: &SelectionArgs,
     config: &Config,
-) -> Result<(), Box<dyn Error>> {
-    let client = create_rpc_client(config, None)?;
-    let wallet = Wallet::new(wallet_name, config)?;
+) -> Result<CommandOutput, Box<dyn Error>> {
+    let mut wallet = Wallet::new(wallet_name, config)?;
+    let options = spend_options(&mut wallet, selection);
     let recipient_addr = string_to_address(recipient, config.bitcoin_rpc.network())?;
+    let fee_rate = fee_rate(&wallet, fee.target())?;
+    let selectors = coin_selectors(selection.seed);
 
     let tx = sign_tx(
-        &client,
         &wallet,
         &recipient_addr,
         amount,
-        fee_amount,
-        utxo_strat,
+        fee_rate,
+        selection.long_term_fee_rate,
+        coin_selector(&selectors, &selection.utxo_strat)?,
+        &selection.change_policy(),
+        &options,
     )?;
-    info!("Signed transaction: {}", tx.raw_hex());
-    Ok(())
+    let txid = deserialize::<Transaction>(&tx)?.txid();
+    CommandOutput::new(
+        format!("Signed transaction: {}", tx.raw_hex()),
+        SignedTxOutput {
+            txid,
+            hex: tx.raw_hex(),
+        },
+    )
 }
 
-pub fn decode_raw_tx(tx_hex: &str, config: &Config) -> Result<(), Box<dyn Error>> {
-    let client = create_rpc_client(config, None)?;
-    let tx = client.decode_raw_transaction(tx_hex, None)?;
-    info!("{:#?}", tx);
-    Ok(())
+pub fn send_many(
+    wallet_name: &str,
+    batch: &BatchArgs,
+    fee: &FeeArgs,
+    selection: &SelectionArgs,
+    config: &Config,
+) -> Result<CommandOutput, Box<dyn Error>> {
+    let text = if batch.payments.as_os_str() == "-" {
+        io::read_to_string(io::stdin())?
+    } else {
+        fs::read_to_string(&batch.payments)
+            .map_err(|e| format!("Cannot read {}: {}", batch.payments.display(), e))?
+    };
+    let payments = parse_payments(&text, config.bitcoin_rpc.network())?;
+    let total: Amount = payments.iter().map(|payment| payment.amount).sum();
+
+    let mut wallet = Wallet::new(wallet_name, config)?;
+    let options = spend_options(&mut wallet, selection);
+    let fee_rate = fee_rate(&wallet, fee.target())?;
+    let selectors = coin_selectors(selection.seed);
+    let selector = coin_selector(&selectors, &selection.utxo_strat)?;
+    let summary = format!("Paying {} recipients {} in total", payments.len(), total);
+
+    if batch.psbt {
+        let psbt = MultisigWallet::create_batch_psbt(
+            &wallet,
+            &payments,
+            fee_rate,
+            selection.long_term_fee_rate,
+            selector,
+            &selection.change_policy(),
+            &options,
+        )?;
+        if let Some(out) = &batch.out {
+            write_psbt(out, &psbt.psbt)?;
+        }
+        return CommandOutput::new(
+            format!("{}, fee {}\nPSBT: {}", summary, psbt.fee, psbt.psbt),
+            BatchOutput {
+                recipients: payments.len(),
+                total,
+                fee: psbt.fee,
+                txid: None,
+                hex: None,
+                psbt: Some(psbt.psbt),
+                broadcast: false,
+            },
+        );
+    }
+
+    let signed = sign_payments(
+        &wallet,
+        &payments,
+        fee_rate,
+        selection.long_term_fee_rate,
+        selector,
+        &selection.change_policy(),
+        &options,
+    )?;
+    let hex = serialize(&signed.tx).raw_hex();
+    let txid = match batch.broadcast {
+        true => create_chain_backend(config)?.send_raw_transaction(&signed.tx)?,
+        false => signed.tx.txid(),
+    };
+    let text = match batch.broadcast {
+        true => format!(
+            "{}, fee {}\nBroadcasted transaction: {}",
+            summary, signed.fee, txid
+        ),
+        false => format!(
+            "{}, fee {}\nSigned transaction: {}",
+            summary, signed.fee, hex
+        ),
+    };
+    CommandOutput::new(
+        text,
+        BatchOutput {
+            recipients: payments.len(),
+            total,
+            fee: signed.fee,
+            txid: Some(txid),
+            hex: Some(hex),
+            psbt: None,
+            broadcast: batch.broadcast,
+        },
+    )
 }
 
-pub fn verify_signed_transaction(tx_hex: &str, config: &Config) -> Result<(), Box<dyn Error>> {
-    let client = create_rpc_client(config, None)?;
-    verify_signed_tx(&client, tx_hex)?;
-    info!("Transaction is valid");
-    Ok(())
+#[allow(clippy::too_many_arguments)]
+pub fn bump_fee(
+    wallet_name: &str,
+    txid: &Txid,
+    fee: &FeeArgs,
+    cancel: bool,
+    selection: &SelectionArgs,
+    broadcast: bool,
+    out: Option<&Path>,
+    config: &Config,
+) -> Result<CommandOutput, Box<dyn Error>> {
+    // The replacement keeps the original inputs and change output
+    if !selection.inputs.is_empty() {
+        return Err("--input cannot be used when bumping a fee".into());
+    }
+    if selection.match_change_type
+        || selection.random_change_position
+        || selection.change_outputs > 1
+    {
+        return Err("Change options cannot be used when bumping a fee".into());
+    }
+    let mut wallet = Wallet::new(wallet_name, config)?;
+    // Only for the outpoints it allows to be spent
+    spend_options(&mut wallet, selection);
+    let fee_rate = fee_rate(&wallet, fee.target())?;
+    let bump = match cancel {
+        true => cancel_tx(&wallet, txid, fee_rate)?,
+        false => {
+            let selectors = coin_selectors(selection.seed);
+            let selector = coin_selector(&selectors, &selection.utxo_strat)?;
+            bump_tx_fee(
+                &wallet,
+                txid,
+                fee_rate,
+                selection.long_term_fee_rate,
+                selector,
+            )?
+        }
+    };
+    let summary = format!(
+        "Replacing {} at {} sat/vB, fee {} (was {})",
+        txid,
+        format_sat_per_vb(bump.fee_rate),
+        bump.fee,
+        bump.original_fee
+    );
+    let mut output = BumpFeeOutput {
+        replaced: *txid,
+        original_fee: bump.original_fee,
+        fee: bump.fee,
+        fee_rate: bump.fee_rate.to_sat_per_kwu() as f64 / 250.0,
+        txid: None,
+        hex: None,
+        psbt: None,
+        broadcast,
+    };
+
+    match bump.replacement {
+        Replacement::Psbt(psbt) => {
+            if broadcast {
+                return Err(
+                    "The wallet has no private keys; sign and finalize the PSBT to broadcast it"
+                        .into(),
+                );
+            }
+            if let Some(out) = out {
+                write_psbt(out, &psbt)?;
+            }
+            output.psbt = Some(psbt.clone());
+            CommandOutput::new(format!("{}\nPSBT: {}", summary, psbt), output)
+        }
+        Replacement::Signed(tx) => {
+            let hex = serialize(&tx).raw_hex();
+            let txid = match broadcast {
+                true => create_chain_backend(config)?.send_raw_transaction(&tx)?,
+                false => tx.txid(),
+            };
+            let text = match broadcast {
+                true => format!("{}\nBroadcasted transaction: {}", summary, txid),
+                false => format!("{}\nSigned transaction: {}", summary, hex),
+            };
+            output.txid = Some(txid);
+            output.hex = Some(hex);
+            CommandOutput::new(text, output)
+        }
+    }
 }
 
-pub fn broadcast_tx(tx_hex: &str, config: &Config) -> Result<(), Box<dyn Error>> {
-    let client = create_rpc_client(config, None)?;
-    let tx = client.send_raw_transaction(tx_hex)?;
-    info!("Broadcasted transaction: {}", tx);
-    Ok(())
+pub fn cpfp(
+    wallet_name: &str,
+    txid: Option<&Txid>,
+    tx: Option<&Transaction>,
+    vout: Option<u32>,
+    fee: &FeeArgs,
+    broadcast: bool,
+    config: &Config,
+) -> Result<CommandOutput, Box<dyn Error>> {
+    let wallet = Wallet::new(wallet_name, config)?;
+    let parent = match (tx, txid) {
+        (Some(tx), _) => tx.clone(),
+        (None, Some(txid)) => wallet.client.get_raw_transaction(txid)?,
+        (None, None) => return Err("No parent transaction given".into()),
+    };
+    let fee_rate = fee_rate(&wallet, fee.target())?;
+    let cpfp = build_cpfp(&wallet, &parent, vout, fee_rate)?;
+
+    let hex = serialize(&cpfp.child).raw_hex();
+    let txid = match broadcast {
+        true => cpfp.broadcast(create_chain_backend(config)?.as_ref())?,
+        false => cpfp.child.txid(),
+    };
+    let summary = format!(
+        "Child of {} pays {} (parent {}), {} sat/vB for both",
+        cpfp.parent.txid(),
+        cpfp.fee,
+        cpfp.parent_fee,
+        format_sat_per_vb(cpfp.package_fee_rate)
+    );
+    let text = match broadcast {
+        true => format!("{}\nBroadcasted transaction: {}", summary, txid),
+        false => format!("{}\nSigned transaction: {}", summary, hex),
+    };
+    CommandOutput::new(
+        text,
+        CpfpOutput {
+            parent: cpfp.parent.txid(),
+            parent_fee: cpfp.parent_fee,
+            txid,
+            hex,
+            fee: cpfp.fee,
+            package_fee_rate: cpfp.package_fee_rate.to_sat_per_kwu() as f64 / 250.0,
+            broadcast,
+        },
+    )
+}
+
+pub fn consolidate(
+    wallet_name: &str,
+    below: Amount,
+    fee: FeeTarget,
+    max_inputs: Option<usize>,
+    broadcast: bool,
+    config: &Config,
+) -> Result<CommandOutput, Box<dyn Error>> {
+    let wallet = Wallet::new(wallet_name, config)?;
+    let fee_rate = fee_rate(&wallet, fee)?;
+    let consolidations =
+        consolidate_utxos(&wallet, below, fee_rate, max_inputs.unwrap_or(usize::MAX))?;
+    let backend = match broadcast {
+        true => Some(create_chain_backend(config)?),
+        false => None,
+    };
+
+    let mut lines = Vec::new();
+    let mut transactions = Vec::new();
+    for signed in consolidations {
+        let hex = serialize(&signed.tx).raw_hex();
+        let txid = match &backend {
+            Some(backend) => backend.send_raw_transaction(&signed.tx)?,
+            None => signed.tx.txid(),
+        };
+        let merged = signed.tx.input.len();
+        let value = signed.tx.output[0].value;
+        let summary = format!(
+            "Merging {} UTXOs into {}, fee {}",
+            merged, value, signed.fee
+        );
+        lines.push(match broadcast {
+            true => format!("{}\nBroadcasted transaction: {}", summary, txid),
+            false => format!("{}\nSigned transaction: {}", summary, hex),
+        });
+        transactions.push(ConsolidationOutput {
+            txid,
+            hex,
+            inputs: merged,
+            value,
+            fee: signed.fee,
+        });
+    }
+    CommandOutput::new(
+        lines.join("\n"),
+        ConsolidateOutput {
+            transactions,
+            broadcast,
+        },
+    )
 }
 
+pub fn sweep(
+    wallet_name: &str,
+    recipients: &[String],
+    fee: &FeeArgs,
+    broadcast: bool,
+    config: &Config,
+) -> Result<CommandOutput, Box<dyn Error>> {
+    let recipients = recipients
+        .iter()
+        .map(|address| string_to_address(address, config.bitcoin_rpc.network()))
+        .collect::<Result<Vec<_>, _>>()?;
+    let wallet = Wallet::new(wallet_name, config)?;
+    let fee_rate = fee_rate(&wallet, fee.target())?;
+    let signed = sweep_wallet(&wallet, &recipients, fee_rate)?;
+
+    let total: Amount = signed.tx.output.iter().map(|output| output.value).sum();
+    let hex = serialize(&signed.tx).raw_hex();
+    let txid = match broadcast {
+        true => create_chain_backend(config)?.send_raw_transaction(&signed.tx)?,
+        false => signed.tx.txid(),
+    };
+    let summary = format!(
+        "Sweeping {} from {} UTXOs to {} recipients, fee {}",
+        total,
+        signed.tx.input.len(),
+        recipients.len(),
+        signed.fee
+    );
+    let text = match broadcast {
+        true => format!("{}\nBroadcasted transaction: {}", summary, txid),
+        false => format!("{}\nSigned transaction: {}", summary, hex),
+    };
+    CommandOutput::new(
+        text,
+        BatchOutput {
+            recipients: recipients.len(),
+            total,
+            fee: signed.fee,
+            txid: Some(txid),
+            hex: Some(hex),
+            psbt: None,
+            broadcast,
+        },
+    )
+}
+
+pub fn lock_unspent(
+    wallet_name: &str,
+    outpoints: &[OutPoint],
+    freeze: bool,
+    config: &Config,
+) -> Result<CommandOutput, Box<dyn Error>> {
+    let mut wallet = Wallet::new(wallet_name, config)?;
+    wallet.lock_unspent(outpoints, freeze)?;
+    locked_output(&wallet)
+}
+
+pub fn unlock_unspent(
+    wallet_name: &str,
+    outpoints: &[OutPoint],
+    all: bool,
+    config: &Config,
+) -> Result<CommandOutput, Box<dyn Error>> {
+    let mut wallet = Wallet::new(wallet_name, config)?;
+    let outpoints = match all {
+        true => {
+            let mut outpoints = wallet.list_locked()?;
+            outpoints.extend(wallet.frozen.outpoints());
+            outpoints
+        }
+        false => outpoints.to_vec(),
+    };
+    wallet.unlock_unspent(&outpoints)?;
+    locked_output(&wallet)
+}
+
+pub fn list_locked(wallet_name: &str, config: &Config) -> Result<CommandOutput, Box<dyn Error>> {
+    locked_output(&Wallet::new(wallet_name, config)?)
+}
+
+/// The wallet's locked and frozen outpoints.
+fn locked_output(wallet: &Wallet) -> Result<CommandOutput, Box<dyn Error>> {
+    let locked = wallet.list_locked()?;
+    let frozen = wallet.frozen.outpoints();
+    let list = |outpoints: &[OutPoint]| match outpoints.is_empty() {
+        true => " none".to_string(),
+        false => outpoints
+            .iter()
+            .map(|outpoint| format!("\n  {}", outpoint))
+            .collect(),
+    };
+    CommandOutput::new(
+        format!("Locked:{}\nFrozen:{}", list(&locked), list(&frozen)),
+        LockedOutput { locked, frozen },
+    )
+}
+
+pub fn decode_raw_tx(tx: &Transaction) -> Result<CommandOutput, Box<dyn Error>> {
+    CommandOutput::new(format!("{:#?}", tx), tx)
+}
+
+pub fn verify_signed_transaction(
+    tx: &Transaction,
+    config: &Config,
+) -> Result<CommandOutput, Box<dyn Error>> {
+    let backend = create_chain_backend(config)?;
+    verify_signed_tx(backend.as_ref(), tx)?;
+    CommandOutput::new("Transaction is valid", VerifyOutput { valid: true })
+}
+
+pub fn broadcast_tx(tx: &Transaction, config: &Config) -> Result<CommandOutput, Box<dyn Error>> {
+    let backend = create_chain_backend(config)?;
+    let txid = backend.send_raw_transaction(tx)?;
+    CommandOutput::new(
+        format!("Broadcasted transaction: {}", txid),
+        TxidOutput { txid },
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn create_psbt(
     wallet_name: &str,
-    recipient: &String,
+    recipient: &str,
     amount: bitcoin::Amount,
-    fee_amount: bitcoin::Amount,
-    utxo_strat: UTXOStrategy,
+    fee: &FeeArgs,
+    selection: &SelectionArgs,
+    timelock: &TimelockArgs,
+    out: Option<&Path>,
     config: &Config,
-) -> Result<(), Box<dyn Error>> {
+) -> Result<CommandOutput, Box<dyn Error>> {
+    let mut wallet = Wallet::new(wallet_name, config)?;
+    let options = SpendOptions {
+        older: timelock.older(),
+        after: timelock.after,
+        ..spend_options(&mut wallet, selection)
+    };
+    let fee_rate = fee_rate(&wallet, fee.target())?;
+    let selectors = coin_selectors(selection.seed);
     let psbt = MultisigWallet::create_psbt(
-        wallet_name,
+        &wallet,
         recipient,
         amount,
-        fee_amount,
-        utxo_strat,
-        config,
+        fee_rate,
+        selection.long_term_fee_rate,
+        coin_selector(&selectors, &selection.utxo_strat)?,
+        &selection.change_policy(),
+        &options,
     )?;
-    info!("PSBT: {:#?}", psbt);
-    Ok(())
+    if let Some(out) = out {
+        write_psbt(out, &psbt.psbt)?;
+    }
+    CommandOutput::new(format!("PSBT: {:#?}", psbt), psbt)
 }
 
-pub fn process_psbt(wallet_name: &str, psbt: &str, config: &Config) -> Result<(), Box<dyn Error>> {
+pub fn process_psbt(
+    wallet_name: &str,
+    psbt: &Psbt,
+    out: Option<&Path>,
+    config: &Config,
+) -> Result<CommandOutput, Box<dyn Error>> {
     let wallet = Wallet::new(wallet_name, config)?;
-    let psbt = wallet.process_psbt(psbt)?;
-    info!("PSBT: {:#?}", psbt);
+    let psbt = wallet.process_psbt(&psbt.to_string())?;
+    if let Some(out) = out {
+        write_psbt(out, &psbt.psbt)?;
+    }
+    CommandOutput::new(format!("PSBT: {:#?}", psbt), psbt)
+}
+
+/// Save a base64 PSBT from Bitcoin Core as a BIP174 binary file.
+fn write_psbt(path: &Path, psbt: &str) -> Result<(), Box<dyn Error>> {
+    fs::write(path, Psbt::from_str(psbt)?.serialize())?;
+    info!("PSBT written to {}", path.display());
     Ok(())
 }
 
-pub fn decode_psbt(psbt: &str, config: &Config) -> Result<(), Box<dyn Error>> {
+pub fn decode_psbt(psbt: &Psbt, config: &Config) -> Result<CommandOutput, Box<dyn Error>> {
     let client = create_rpc_client(config, None)?;
-    let psbt: serde_json::Value = client.call("decodepsbt", &[json!(psbt)])?;
-    info!("PSBT: {:#?}", psbt);
-    Ok(())
+    let psbt: serde_json::Value = client.call("decodepsbt", &[json!(psbt.to_string())])?;
+    CommandOutput::new(format!("PSBT: {:#}", psbt), psbt)
 }
 
-pub fn analyze_psbt(psbt: &str, config: &Config) -> Result<(), Box<dyn Error>> {
+pub fn analyze_psbt(psbt: &Psbt, config: &Config) -> Result<CommandOutput, Box<dyn Error>> {
     let client = create_rpc_client(config, None)?;
-    let psbt: serde_json::Value = client.call("analyzepsbt", &[json!(psbt)])?;
-    info!("PSBT: {:#?}", psbt);
-    Ok(())
+    let psbt: serde_json::Value = client.call("analyzepsbt", &[json!(psbt.to_string())])?;
+    CommandOutput::new(format!("PSBT: {:#}", psbt), psbt)
 }
 
-pub fn combine_psbts(psbts: &Vec<String>, config: &Config) -> Result<(), Box<dyn Error>> {
+pub fn combine_psbts(psbts: &[Psbt], config: &Config) -> Result<CommandOutput, Box<dyn Error>> {
     let client = create_rpc_client(config, None)?;
-    let res = client.combine_psbt(&psbts[..])?;
-    info!("CombinedPSBT: {:#?}", res);
-    Ok(())
+    let psbts: Vec<String> = psbts.iter().map(Psbt::to_string).collect();
+    let psbt = client.combine_psbt(&psbts)?;
+    CommandOutput::new(format!("CombinedPSBT: {}", psbt), PsbtOutput { psbt })
 }
 
-pub fn finalize_psbt(psbt: &str, config: &Config) -> Result<(), Box<dyn Error>> {
+pub fn finalize_psbt(psbt: &Psbt, config: &Config) -> Result<CommandOutput, Box<dyn Error>> {
     let client = create_rpc_client(config, None)?;
-    let res = client.finalize_psbt(psbt, None)?;
-    info!("FinalizedPSBT: {:#?}", res);
-    Ok(())
+    let res = client.finalize_psbt(&psbt.to_string(), None)?;
+    CommandOutput::new(format!("FinalizedPSBT: {:#?}", res), res)
 }
 
-pub fn finalize_psbt_and_broadcast(psbt: &str, config: &Config) -> Result<(), Box<dyn Error>> {
+pub fn finalize_psbt_and_broadcast(
+    psbt: &Psbt,
+    config: &Config,
+) -> Result<CommandOutput, Box<dyn Error>> {
     let client = create_rpc_client(config, None)?;
-    let res = client.finalize_psbt(psbt, None)?;
+    let res = client.finalize_psbt(&psbt.to_string(), None)?;
     if !res.complete {
         return Err("Incomplete PSBT".into());
     }
@@ -391,39 +1096,44 @@ pub fn finalize_psbt_and_broadcast(psbt: &str, config: &Config) -> Result<(), Bo
 
     info!("FinalizedPSBT: {}", raw_hex);
 
-    let tx = client.send_raw_transaction(raw_hex)?;
-    info!("Broadcasted transaction: {}", tx);
-    Ok(())
+    let txid = client.send_raw_transaction(raw_hex)?;
+    CommandOutput::new(
+        format!("Broadcasted transaction: {}", txid),
+        TxidOutput { txid },
+    )
 }
 
 pub fn inscribe_ordinal(
     wallet_name: &str,
     postage: &u64,
     file_path: &str,
+    fee: &FeeArgs,
     config: &Config,
-) -> Result<(), Box<dyn Error>> {
+) -> Result<CommandOutput, Box<dyn Error>> {
     let wallet = Wallet::new(wallet_name, config)?;
+    let fee_rate = fee_rate(&wallet, fee.target())?;
 
-    // For mainnet/testnet: these fees should be dynamically fetched
-    let commit_fee = Amount::from_sat(20000);
-    let reveal_fee = Amount::from_sat(20000);
-
-    if Amount::from_sat(*postage) < reveal_fee + Amount::from_sat(546) {
-        return Err("postage must be greater than reveal fee + min dust".into());
-    }
-
-    let inscription_info =
-        wallet.inscribe_ordinal(postage, commit_fee, reveal_fee, file_path, config)?;
-    info!("Inscription info: {:#?}", inscription_info);
-    Ok(())
+    let miner = Wallet::new("miner", config)?;
+    let inscription_info = wallet.inscribe_ordinal(postage, fee_rate, file_path, &miner)?;
+    CommandOutput::new(
+        format!("Inscription info: {:#?}", inscription_info),
+        InscriptionOutput {
+            // The inscription is the first, and only, one in the reveal transaction
+            inscription_id: format!("{}i0", inscription_info.base.reveal_txid),
+            commit_txid: inscription_info.base.commit_txid,
+            reveal_txid: inscription_info.base.reveal_txid,
+            total_fees: Amount::from_sat(inscription_info.base.total_fees),
+        },
+    )
 }
 
 pub fn etch_rune(
     wallet_name: &str,
     postage: &u64,
     file_path: &str,
+    fee: &FeeArgs,
     config: &Config,
-) -> Result<(), Box<dyn Error>> {
+) -> Result<CommandOutput, Box<dyn Error>> {
     let wallet = Wallet::new(wallet_name, config)?;
 
     let rune = "ZZZZZZZZZZZZZAAAA".parse::<Rune>().unwrap();
@@ -444,7 +1154,7 @@ pub fn etch_rune(
     let terms_cap = 90;
 
     // Validate supply
-    let supply = premine + (terms_cap as u128 * terms_amount as u128);
+    let supply = premine + (terms_cap * terms_amount);
     if supply == 0 {
         return Err("supply must be greater than zero".into());
     }
@@ -484,29 +1194,36 @@ pub fn etch_rune(
         }
     }
 
-    let commit_fee = Amount::from_sat(20000);
-    let reveal_fee = Amount::from_sat(20000);
     let premine_tx_amount = if premine > 0 {
         Amount::from_sat(10000)
     } else {
         Amount::ZERO
     };
 
-    if Amount::from_sat(*postage) < reveal_fee + premine_tx_amount {
-        return Err("postage must be greater than reveal fee + min dust".into());
+    if Amount::from_sat(*postage) <= premine_tx_amount {
+        return Err("postage must be greater than the premine output".into());
     }
 
+    let fee_rate = fee_rate(&wallet, fee.target())?;
+    let miner = Wallet::new("miner", config)?;
     let rune_info = wallet.etch_rune(
         etching,
         postage,
-        commit_fee,
-        reveal_fee,
+        fee_rate,
         premine_tx_amount,
         file_path,
-        config,
+        &miner,
     )?;
-    info!("Etching Info: {:#?}", rune_info);
-    Ok(())
+    CommandOutput::new(
+        format!("Etching Info: {:#?}", rune_info),
+        EtchingOutput {
+            rune: rune_info.rune_id.to_string(),
+            recipient: rune_info.recipient.clone(),
+            commit_txid: rune_info.base.commit_txid,
+            reveal_txid: rune_info.base.reveal_txid,
+            total_fees: Amount::from_sat(rune_info.base.total_fees),
+        },
+    )
 }
 
 pub fn wallet_mine_blocks(
@@ -514,9 +1231,49 @@ pub fn wallet_mine_blocks(
     blocks: u64,
     address_type: &AddressType,
     config: &Config,
-) -> Result<(), Box<dyn Error>> {
+) -> Result<CommandOutput, Box<dyn Error>> {
     let wallet = Wallet::new(wallet_name, config)?;
     let coinbase_recipient = wallet.mine_blocks(address_type, blocks)?;
-    info!("Mined {} blocks to {}", blocks, coinbase_recipient);
-    Ok(())
+    CommandOutput::new(
+        format!("Mined {} blocks to {}", blocks, coinbase_recipient),
+        MinedOutput {
+            blocks,
+            address: coinbase_recipient,
+        },
+    )
+}
+
+/// Spend the inputs chosen in `selection`, if any, and let its allowed
+/// outpoints be spent even if they hold inscriptions or runes.
+fn spend_options(wallet: &mut Wallet, selection: &SelectionArgs) -> SpendOptions {
+    if let Some(protection) = &mut wallet.asset_protection {
+        for outpoint in &selection.allow_protected {
+            protection.allow(*outpoint);
+        }
+    }
+    SpendOptions {
+        inputs: selection.inputs.clone(),
+        ..Default::default()
+    }
+}
+
+/// The registered coin selector called `name`.
+fn coin_selector<'a>(
+    selectors: &'a CoinSelectorRegistry,
+    name: &str,
+) -> Result<&'a dyn CoinSelector, Box<dyn Error>> {
+    selectors
+        .get(name)
+        .ok_or_else(|| format!("Unknown UTXO selection strategy: {}", name).into())
+}
+
+/// Resolve the fee rate for a new transaction from the wallet's node.
+fn fee_rate(wallet: &Wallet, target: FeeTarget) -> Result<FeeRate, Box<dyn Error>> {
+    let fee_rate = resolve_fee_rate(&wallet.client, target)?;
+    info!(
+        "Fee rate: {} sat/vB ({})",
+        format_sat_per_vb(fee_rate),
+        target
+    );
+    Ok(fee_rate)
 }
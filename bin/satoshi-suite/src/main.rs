@@ -1,22 +1,32 @@
-use std::error::Error;
+use std::{error::Error, io};
 
 use clap::Parser;
 
 use tracing_subscriber::EnvFilter;
 
+use satoshi_suite_client::verify_chain;
+
 pub mod cli;
 use cli::Cli;
 
 mod commands;
 use commands::handler;
 
+pub mod output;
+
 fn main() -> Result<(), Box<dyn Error>> {
+    // Keep stdout for command results so it can be piped
     tracing_subscriber::fmt()
         .with_env_filter(EnvFilter::from_default_env().add_directive(tracing::Level::INFO.into()))
+        .with_writer(io::stderr)
         .init();
 
     let args = Cli::parse();
 
-    let config = args.options.make_config();
-    handler(&args, &config)
+    let config = args.options.make_config()?;
+    verify_chain(&config)?;
+
+    let output = handler(&args, &config)?;
+    output.write(args.options.output)?;
+    Ok(())
 }
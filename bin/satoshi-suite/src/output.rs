@@ -0,0 +1,269 @@
+use std::{
+    error::Error,
+    fmt,
+    io::{self, Write},
+    str::FromStr,
+};
+
+use serde::Serialize;
+use serde_json::Value;
+
+use bitcoin::{Address, Amount, OutPoint, Txid};
+use bitcoincore_rpc::json::GetBalancesResult;
+
+/// How command results are written to stdout. Logs always go to stderr.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OutputFormat::Text => write!(f, "text"),
+            OutputFormat::Json => write!(f, "json"),
+        }
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            _ => Err(format!("Unknown output format: {}", s)),
+        }
+    }
+}
+
+/// The result of a command: a human readable summary for text mode and a
+/// serialized value, printed as a single line, for JSON mode.
+pub struct CommandOutput {
+    text: String,
+    value: Value,
+}
+
+impl CommandOutput {
+    pub fn new(text: impl Into<String>, value: impl Serialize) -> Result<Self, Box<dyn Error>> {
+        Ok(CommandOutput {
+            text: text.into(),
+            value: serde_json::to_value(value)?,
+        })
+    }
+
+    pub fn write(&self, format: OutputFormat) -> io::Result<()> {
+        let mut stdout = io::stdout().lock();
+        match format {
+            OutputFormat::Text => writeln!(stdout, "{}", self.text),
+            OutputFormat::Json => {
+                serde_json::to_writer(&mut stdout, &self.value)?;
+                writeln!(stdout)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct BootstrapOutput {
+    pub wallets: Vec<String>,
+    pub miner: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BlockHeightOutput {
+    pub height: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WalletOutput {
+    pub wallet_name: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RescanOutput {
+    pub start_height: usize,
+    pub stop_height: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AddressOutput {
+    pub address: Address,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DerivedAddressOutput {
+    pub index: u32,
+    pub address: Address,
+}
+
+/// `getbalances` with amounts in BTC, like Bitcoin Core reports them.
+#[derive(Debug, Serialize)]
+pub struct BalancesOutput {
+    pub mine: BalanceOutput,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub watchonly: Option<BalanceOutput>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BalanceOutput {
+    #[serde(with = "bitcoin::amount::serde::as_btc")]
+    pub trusted: Amount,
+    #[serde(with = "bitcoin::amount::serde::as_btc")]
+    pub untrusted_pending: Amount,
+    #[serde(with = "bitcoin::amount::serde::as_btc")]
+    pub immature: Amount,
+}
+
+impl From<&GetBalancesResult> for BalancesOutput {
+    fn from(balances: &GetBalancesResult) -> Self {
+        let entry = |entry: &bitcoincore_rpc::json::GetBalancesResultEntry| BalanceOutput {
+            trusted: entry.trusted,
+            untrusted_pending: entry.untrusted_pending,
+            immature: entry.immature,
+        };
+        BalancesOutput {
+            mine: entry(&balances.mine),
+            watchonly: balances.watchonly.as_ref().map(entry),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ScanOutput<T> {
+    pub utxos: Vec<T>,
+    #[serde(with = "bitcoin::amount::serde::as_btc")]
+    pub total: Amount,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SentOutput {
+    pub txid: Txid,
+    pub vout: u32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SignedTxOutput {
+    pub txid: Txid,
+    pub hex: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct VerifyOutput {
+    pub valid: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TxidOutput {
+    pub txid: Txid,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PsbtOutput {
+    pub psbt: String,
+}
+
+/// A transaction paying a batch of recipients, signed or as a PSBT.
+#[derive(Debug, Serialize)]
+pub struct BatchOutput {
+    pub recipients: usize,
+    #[serde(with = "bitcoin::amount::serde::as_btc")]
+    pub total: Amount,
+    #[serde(with = "bitcoin::amount::serde::as_btc")]
+    pub fee: Amount,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub txid: Option<Txid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hex: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub psbt: Option<String>,
+    pub broadcast: bool,
+}
+
+/// A replacement for a wallet transaction, signed or as a PSBT.
+#[derive(Debug, Serialize)]
+pub struct BumpFeeOutput {
+    pub replaced: Txid,
+    #[serde(with = "bitcoin::amount::serde::as_btc")]
+    pub original_fee: Amount,
+    #[serde(with = "bitcoin::amount::serde::as_btc")]
+    pub fee: Amount,
+    /// In sat/vB.
+    pub fee_rate: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub txid: Option<Txid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hex: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub psbt: Option<String>,
+    pub broadcast: bool,
+}
+
+/// A child paying for an unconfirmed parent.
+#[derive(Debug, Serialize)]
+pub struct CpfpOutput {
+    pub parent: Txid,
+    #[serde(with = "bitcoin::amount::serde::as_btc")]
+    pub parent_fee: Amount,
+    pub txid: Txid,
+    pub hex: String,
+    #[serde(with = "bitcoin::amount::serde::as_btc")]
+    pub fee: Amount,
+    /// Of parent and child together, in sat/vB.
+    pub package_fee_rate: f64,
+    pub broadcast: bool,
+}
+
+/// A transaction merging small UTXOs of the wallet into one output.
+#[derive(Debug, Serialize)]
+pub struct ConsolidationOutput {
+    pub txid: Txid,
+    pub hex: String,
+    pub inputs: usize,
+    #[serde(with = "bitcoin::amount::serde::as_btc")]
+    pub value: Amount,
+    #[serde(with = "bitcoin::amount::serde::as_btc")]
+    pub fee: Amount,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ConsolidateOutput {
+    pub transactions: Vec<ConsolidationOutput>,
+    pub broadcast: bool,
+}
+
+/// UTXOs kept out of coin selection.
+#[derive(Debug, Serialize)]
+pub struct LockedOutput {
+    /// Locked by the node until it unloads the wallet.
+    pub locked: Vec<OutPoint>,
+    pub frozen: Vec<OutPoint>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct InscriptionOutput {
+    pub inscription_id: String,
+    pub commit_txid: Txid,
+    pub reveal_txid: Txid,
+    #[serde(with = "bitcoin::amount::serde::as_btc")]
+    pub total_fees: Amount,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EtchingOutput {
+    pub rune: String,
+    pub recipient: Address,
+    pub commit_txid: Txid,
+    pub reveal_txid: Txid,
+    #[serde(with = "bitcoin::amount::serde::as_btc")]
+    pub total_fees: Amount,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MinedOutput {
+    pub blocks: u64,
+    pub address: Address,
+}
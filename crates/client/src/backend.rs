@@ -0,0 +1,127 @@
+use std::collections::HashMap;
+
+use bitcoin::{
+    address::NetworkUnchecked, Address, Amount, BlockHash, FeeRate, Network, OutPoint, Script,
+    Transaction, Txid,
+};
+use bitcoincore_rpc::{
+    json::{
+        AddressType, CreateRawTransactionInput, GetAddressInfoResult, GetBalancesResult,
+        GetDescriptorInfoResult, GetTxOutResult, GetWalletInfoResult, ListUnspentQueryOptions,
+        ListUnspentResultEntry, Utxo, WalletCreateFundedPsbtResult, WalletProcessPsbtResult,
+    },
+    jsonrpc::serde_json::Value,
+};
+
+use crate::ClientError;
+
+/// Chain queries and transaction relay.
+///
+/// Results use Bitcoin Core's RPC types so every backend can feed the same
+/// wallet, signing and coin selection code.
+pub trait ChainBackend {
+    fn network(&self) -> Network;
+
+    fn get_block_count(&self) -> Result<u64, ClientError>;
+
+    fn get_raw_transaction(&self, txid: &Txid) -> Result<Transaction, ClientError>;
+
+    /// Look up an unspent output. With `include_mempool` outputs created by
+    /// mempool transactions are visible and outputs they spend are not.
+    fn get_tx_out(
+        &self,
+        outpoint: &OutPoint,
+        include_mempool: bool,
+    ) -> Result<Option<GetTxOutResult>, ClientError>;
+
+    /// Whether `txid` is waiting in the mempool, rather than confirmed,
+    /// replaced or unknown.
+    fn in_mempool(&self, txid: &Txid) -> Result<bool, ClientError>;
+
+    /// Confirmed unspent outputs paying to `script_pubkey`, whether or not
+    /// any wallet tracks it.
+    fn scan_utxos(&self, script_pubkey: &Script) -> Result<Vec<Utxo>, ClientError>;
+
+    fn send_raw_transaction(&self, tx: &Transaction) -> Result<Txid, ClientError>;
+
+    /// Relay `txs`, a child after its unconfirmed parents, as one package,
+    /// so the child's fee counts towards parents paying too little to relay
+    /// on their own. Parents already in the mempool are skipped.
+    fn submit_package(&self, txs: &[Transaction]) -> Result<Vec<Txid>, ClientError>;
+
+    /// Fee rate expected to get a transaction confirmed within `conf_target`
+    /// blocks, or `None` when the backend has too little data to estimate.
+    fn estimate_fee_rate(&self, conf_target: u16) -> Result<Option<FeeRate>, ClientError>;
+
+    fn generate_to_address(
+        &self,
+        blocks: u64,
+        address: &Address,
+    ) -> Result<Vec<BlockHash>, ClientError>;
+}
+
+/// A wallet held by the backend.
+///
+/// A value is a handle scoped to one wallet, or to the backend's default
+/// wallet when it was created for the node as a whole, and can open handles
+/// to other wallets on the same backend.
+pub trait WalletBackend: ChainBackend + Sized {
+    /// Load the named wallet, creating it if it does not exist and the
+    /// backend allows wallet creation.
+    fn open_wallet(&self, name: &str) -> Result<Self, ClientError>;
+
+    /// Create a blank wallet without private keys.
+    fn create_watch_only_wallet(&self, name: &str) -> Result<Self, ClientError>;
+
+    fn get_new_address(
+        &self,
+        address_type: AddressType,
+    ) -> Result<Address<NetworkUnchecked>, ClientError>;
+
+    fn get_balances(&self) -> Result<GetBalancesResult, ClientError>;
+
+    fn get_wallet_info(&self) -> Result<GetWalletInfoResult, ClientError>;
+
+    fn get_address_info(&self, address: &Address) -> Result<GetAddressInfoResult, ClientError>;
+
+    /// Confirmed, spendable outputs of the wallet.
+    fn list_unspent(
+        &self,
+        query_options: Option<ListUnspentQueryOptions>,
+    ) -> Result<Vec<ListUnspentResultEntry>, ClientError>;
+
+    /// Fund, sign and broadcast a payment, returning its txid.
+    fn send(&self, address: &Address, amount: Amount) -> Result<Txid, ClientError>;
+
+    /// Sign every input the wallet has keys for.
+    fn sign_raw_transaction(&self, tx: &Transaction) -> Result<Transaction, ClientError>;
+
+    /// The wallet's descriptors in `listdescriptors` format.
+    fn list_descriptors(&self) -> Result<Value, ClientError>;
+
+    fn get_descriptor_info(&self, descriptor: &str)
+        -> Result<GetDescriptorInfoResult, ClientError>;
+
+    /// Import descriptors given as `importdescriptors` request objects.
+    fn import_descriptors(&self, descriptors: &[Value]) -> Result<(), ClientError>;
+
+    /// Fund a PSBT paying `outputs`, at `fee_rate` if given and otherwise at
+    /// the wallet's own estimate.
+    fn wallet_create_funded_psbt(
+        &self,
+        inputs: &[CreateRawTransactionInput],
+        outputs: &HashMap<String, Amount>,
+        fee_rate: Option<FeeRate>,
+    ) -> Result<WalletCreateFundedPsbtResult, ClientError>;
+
+    fn wallet_process_psbt(&self, psbt: &str) -> Result<WalletProcessPsbtResult, ClientError>;
+
+    /// Keep `outpoints` out of [`WalletBackend::list_unspent`] and the
+    /// wallet's own funding until they are unlocked. Locks last until the
+    /// wallet is unloaded.
+    fn lock_unspent(&self, outpoints: &[OutPoint]) -> Result<(), ClientError>;
+
+    fn unlock_unspent(&self, outpoints: &[OutPoint]) -> Result<(), ClientError>;
+
+    fn list_lock_unspent(&self) -> Result<Vec<OutPoint>, ClientError>;
+}
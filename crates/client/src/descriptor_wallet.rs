@@ -0,0 +1,580 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    str::FromStr,
+};
+
+use bitcoin::{
+    bip32::{DerivationPath, Xpriv, Xpub},
+    hashes::{sha256, Hash},
+    key::TapTweak,
+    psbt::{Input, Psbt},
+    secp256k1::{All, Message, Secp256k1},
+    sighash::{Prevouts, SighashCache},
+    taproot, Address, Network, OutPoint, PrivateKey, PublicKey, ScriptBuf, TapSighashType,
+    Transaction, TxOut, Txid,
+};
+use bitcoincore_rpc::{
+    json::{AddressType, GetAddressInfoResult, GetDescriptorInfoResult},
+    jsonrpc::serde_json::{json, Value},
+};
+use miniscript::{
+    descriptor::{DefiniteDescriptorKey, DescriptorPublicKey, DescriptorType},
+    psbt::PsbtExt,
+    Descriptor, ForEachKey,
+};
+
+use crate::{script_pubkey_type, ClientError, PublicDescriptor};
+
+/// Scripts derived ahead of the next unused index so payments to addresses
+/// handed out elsewhere (e.g. by `deriveaddresses`) are still recognised.
+const LOOKAHEAD: u32 = 20;
+
+pub(crate) struct WalletDescriptor {
+    pub descriptor: PublicDescriptor,
+    pub active: bool,
+    pub internal: bool,
+    pub next_index: u32,
+}
+
+/// A descriptor wallet: Bitcoin Core's default BIP 44/49/84/86 descriptors
+/// derived from a seeded master key, or a blank watch-only wallet that
+/// descriptors are imported into.
+pub(crate) struct DescriptorWallet {
+    master: Option<Xpriv>,
+    descriptors: Vec<WalletDescriptor>,
+    scripts: HashMap<ScriptBuf, (usize, u32)>,
+    /// Outputs `lockunspent` keeps out of listings and funding until they
+    /// are unlocked, for as long as the wallet is loaded.
+    locked: BTreeSet<OutPoint>,
+}
+
+impl DescriptorWallet {
+    pub fn with_keys(secp: &Secp256k1<All>, seed: &[u8], name: &str) -> Self {
+        let wallet_seed = sha256::Hash::hash(&[seed, name.as_bytes()].concat());
+        let master = Xpriv::new_master(Network::Regtest, wallet_seed.as_byte_array())
+            .expect("a 32 byte seed is valid");
+        let fingerprint = master.fingerprint(secp);
+
+        let mut wallet = DescriptorWallet {
+            master: Some(master),
+            descriptors: Vec::new(),
+            scripts: HashMap::new(),
+            locked: BTreeSet::new(),
+        };
+        for (purpose, template) in [
+            (44, "pkh({})"),
+            (49, "sh(wpkh({}))"),
+            (84, "wpkh({})"),
+            (86, "tr({})"),
+        ] {
+            let account_path = DerivationPath::from_str(&format!("m/{}'/1'/0'", purpose))
+                .expect("account path is valid");
+            let account = Xpub::from_priv(
+                secp,
+                &master
+                    .derive_priv(secp, &account_path)
+                    .expect("derivation is infallible for valid paths"),
+            );
+            for internal in [false, true] {
+                let key = format!(
+                    "[{}/{}h/1h/0h]{}/{}/*",
+                    fingerprint, purpose, account, internal as u8
+                );
+                let descriptor = Descriptor::from_str(&template.replace("{}", &key))
+                    .expect("default descriptors are valid");
+                wallet.add_descriptor(PublicDescriptor::from(descriptor), true, internal);
+            }
+        }
+        wallet
+    }
+
+    pub fn watch_only() -> Self {
+        DescriptorWallet {
+            master: None,
+            descriptors: Vec::new(),
+            scripts: HashMap::new(),
+            locked: BTreeSet::new(),
+        }
+    }
+
+    pub fn private_keys_enabled(&self) -> bool {
+        self.master.is_some()
+    }
+
+    /// Lock `outpoints`, which the caller has checked are unspent outputs
+    /// of the wallet. Like Bitcoin Core, none is locked if any already is.
+    pub fn lock(&mut self, outpoints: &[OutPoint]) -> Result<(), ClientError> {
+        if let Some(outpoint) = outpoints.iter().find(|o| self.locked.contains(o)) {
+            return Err(ClientError::Rejected(format!(
+                "Invalid parameter, output already locked: {}",
+                outpoint
+            )));
+        }
+        self.locked.extend(outpoints);
+        Ok(())
+    }
+
+    pub fn unlock(&mut self, outpoints: &[OutPoint]) -> Result<(), ClientError> {
+        if let Some(outpoint) = outpoints.iter().find(|o| !self.locked.contains(o)) {
+            return Err(ClientError::Rejected(format!(
+                "Invalid parameter, expected locked output: {}",
+                outpoint
+            )));
+        }
+        for outpoint in outpoints {
+            self.locked.remove(outpoint);
+        }
+        Ok(())
+    }
+
+    pub fn is_locked(&self, outpoint: &OutPoint) -> bool {
+        self.locked.contains(outpoint)
+    }
+
+    pub fn locked(&self) -> Vec<OutPoint> {
+        self.locked.iter().copied().collect()
+    }
+
+    pub fn descriptors(&self) -> &[WalletDescriptor] {
+        &self.descriptors
+    }
+
+    /// Add a descriptor, replacing the active one of the same address type and
+    /// keychain if `active` is set.
+    pub fn add_descriptor(&mut self, descriptor: PublicDescriptor, active: bool, internal: bool) {
+        if active {
+            let address_type = descriptor_address_type(descriptor.descriptor());
+            for existing in &mut self.descriptors {
+                if existing.internal == internal
+                    && descriptor_address_type(existing.descriptor.descriptor()) == address_type
+                {
+                    existing.active = false;
+                }
+            }
+        }
+
+        self.descriptors.push(WalletDescriptor {
+            descriptor,
+            active,
+            internal,
+            next_index: 0,
+        });
+        self.extend_lookahead(self.descriptors.len() - 1);
+    }
+
+    fn extend_lookahead(&mut self, index: usize) {
+        let entry = &self.descriptors[index];
+        let end = if entry.descriptor.descriptor().has_wildcard() {
+            entry.next_index + LOOKAHEAD
+        } else {
+            1
+        };
+        for child in 0..end {
+            if let Ok(definite) = entry.descriptor.at_derivation_index(child) {
+                self.scripts
+                    .insert(definite.script_pubkey(), (index, child));
+            }
+        }
+    }
+
+    /// Every script derived so far, including the lookahead.
+    pub fn scripts(&self) -> impl Iterator<Item = &ScriptBuf> {
+        self.scripts.keys()
+    }
+
+    /// Record that `script_pubkey` has been used on chain, so addresses are
+    /// never handed out again and the lookahead extends past it.
+    pub fn mark_used(&mut self, script_pubkey: &ScriptBuf) {
+        let Some(&(index, child)) = self.scripts.get(script_pubkey) else {
+            return;
+        };
+        let entry = &mut self.descriptors[index];
+        if entry.descriptor.descriptor().has_wildcard() && child >= entry.next_index {
+            entry.next_index = child + 1;
+            self.extend_lookahead(index);
+        }
+    }
+
+    /// Hand out the next script of the active descriptor for `address_type`.
+    pub fn derive_next(
+        &mut self,
+        address_type: AddressType,
+        internal: bool,
+    ) -> Result<Descriptor<DefiniteDescriptorKey>, ClientError> {
+        let index = self
+            .descriptors
+            .iter()
+            .position(|entry| {
+                entry.active
+                    && entry.internal == internal
+                    && descriptor_address_type(entry.descriptor.descriptor()) == Some(address_type)
+            })
+            .ok_or_else(|| {
+                ClientError::NotFound(format!(
+                    "no active {} descriptor for {:?}",
+                    if internal { "internal" } else { "external" },
+                    address_type
+                ))
+            })?;
+
+        let entry = &mut self.descriptors[index];
+        let child = entry.next_index;
+        let definite = entry.descriptor.at_derivation_index(child)?;
+        if entry.descriptor.descriptor().has_wildcard() {
+            entry.next_index += 1;
+        }
+        self.extend_lookahead(index);
+        Ok(definite)
+    }
+
+    /// A change script, preferring native segwit like Bitcoin Core does.
+    pub fn derive_change(&mut self) -> Result<Descriptor<DefiniteDescriptorKey>, ClientError> {
+        [
+            AddressType::Bech32,
+            AddressType::Bech32m,
+            AddressType::P2shSegwit,
+            AddressType::Legacy,
+        ]
+        .into_iter()
+        .find_map(|address_type| self.derive_next(address_type, true).ok())
+        .ok_or_else(|| ClientError::NotFound("no active change descriptor".to_string()))
+    }
+
+    /// The descriptor behind a script this wallet owns.
+    pub fn descriptor_for(
+        &self,
+        script_pubkey: &ScriptBuf,
+    ) -> Option<Descriptor<DefiniteDescriptorKey>> {
+        let (index, child) = self.scripts.get(script_pubkey)?;
+        self.descriptors[*index]
+            .descriptor
+            .at_derivation_index(*child)
+            .ok()
+    }
+
+    pub fn is_mine(&self, script_pubkey: &ScriptBuf) -> bool {
+        self.scripts.contains_key(script_pubkey)
+    }
+
+    /// The HD path of the key behind a single key script.
+    pub fn key_path(&self, script_pubkey: &ScriptBuf) -> Option<DerivationPath> {
+        let descriptor = self.descriptor_for(script_pubkey)?;
+        let mut paths = Vec::new();
+        descriptor.for_each_key(|key| {
+            paths.push(key.full_derivation_path());
+            true
+        });
+        match paths.as_slice() {
+            [path] => path.clone(),
+            _ => None,
+        }
+    }
+
+    /// The wallet's descriptors in `listdescriptors` format.
+    pub fn list_descriptors(&self, wallet_name: &str) -> Value {
+        let descriptors: Vec<Value> = self
+            .descriptors
+            .iter()
+            .map(|entry| {
+                let mut descriptor = json!({
+                    "desc": entry.descriptor.to_string(),
+                    "timestamp": 0,
+                    "active": entry.active,
+                    "internal": entry.internal,
+                });
+                if entry.descriptor.descriptor().has_wildcard() {
+                    descriptor["range"] = json!([0, entry.next_index + 999]);
+                    descriptor["next"] = json!(entry.next_index);
+                }
+                descriptor
+            })
+            .collect();
+
+        json!({
+            "wallet_name": wallet_name,
+            "descriptors": descriptors,
+        })
+    }
+
+    /// Add descriptors given as `importdescriptors` request objects. Like
+    /// Bitcoin Core, only wallets without private keys accept public
+    /// descriptors.
+    pub fn import_descriptors(&mut self, requests: &[Value]) -> Result<(), ClientError> {
+        if self.private_keys_enabled() {
+            return Err(ClientError::Rejected(
+                "Cannot import descriptor without private keys to a wallet with private keys enabled"
+                    .to_string(),
+            ));
+        }
+
+        for request in requests {
+            let desc = request["desc"]
+                .as_str()
+                .ok_or_else(|| ClientError::Rejected("Descriptor not found".to_string()))?;
+            let descriptor = PublicDescriptor::from_str(desc)?;
+            self.add_descriptor(
+                descriptor,
+                request["active"].as_bool().unwrap_or(false),
+                request["internal"].as_bool().unwrap_or(false),
+            );
+        }
+        Ok(())
+    }
+
+    /// What `getaddressinfo` reports about `address` for this wallet.
+    pub fn address_info(&self, address: &Address) -> GetAddressInfoResult {
+        let script_pubkey = address.script_pubkey();
+        let witness_version = script_pubkey.witness_version();
+
+        #[allow(deprecated)]
+        GetAddressInfoResult {
+            address: address.as_unchecked().clone(),
+            is_mine: Some(self.is_mine(&script_pubkey)),
+            is_watchonly: Some(false),
+            is_script: Some(script_pubkey.is_p2sh() || script_pubkey.is_p2wsh()),
+            is_witness: Some(witness_version.is_some()),
+            witness_version: witness_version.map(|version| version.to_num() as u32),
+            witness_program: witness_version.map(|_| script_pubkey.as_bytes()[2..].to_vec()),
+            script: script_pubkey_type(&script_pubkey),
+            hex: None,
+            pubkeys: None,
+            n_signatures_required: None,
+            pubkey: None,
+            embedded: None,
+            is_compressed: None,
+            timestamp: None,
+            hd_key_path: self.key_path(&script_pubkey),
+            hd_seed_id: None,
+            labels: Vec::new(),
+            label: None,
+            script_pub_key: script_pubkey,
+        }
+    }
+
+    /// Add the UTXO and key origin data for every input spending one of this
+    /// wallet's scripts. Legacy inputs need the full previous transaction.
+    pub fn update_psbt(
+        &self,
+        psbt: &mut Psbt,
+        prevouts: &[TxOut],
+        previous_tx: impl Fn(&Txid) -> Option<Transaction>,
+    ) {
+        for (index, prevout) in prevouts.iter().enumerate() {
+            let descriptor = self.descriptor_for(&prevout.script_pubkey);
+            let input = &mut psbt.inputs[index];
+            match &descriptor {
+                Some(descriptor) if descriptor.desc_type().segwit_version().is_none() => {
+                    let txid = psbt.unsigned_tx.input[index].previous_output.txid;
+                    input.non_witness_utxo = previous_tx(&txid);
+                }
+                _ => {
+                    if input.witness_utxo.is_none() {
+                        input.witness_utxo = Some(prevout.clone());
+                    }
+                }
+            }
+            if let Some(descriptor) = descriptor {
+                let _ = psbt.update_input_with_descriptor(index, &descriptor);
+            }
+        }
+    }
+
+    /// Sign every input this wallet holds keys for and finalize the inputs
+    /// that are then complete. `prevouts` must cover every input.
+    pub fn sign_psbt(&self, secp: &Secp256k1<All>, psbt: &mut Psbt, prevouts: &[TxOut]) {
+        let Some(master) = self.master else {
+            return;
+        };
+
+        let _ = psbt.sign(&master, secp);
+        let _ = psbt.sign(&self.foreign_origin_keys(secp, &master, psbt), secp);
+        for index in 0..psbt.inputs.len() {
+            sign_taproot_input(secp, &master, psbt, index, prevouts);
+        }
+
+        // Like `walletprocesspsbt`, finalize every input that now has enough
+        // signatures, including shared multisig inputs the wallet only signed.
+        for index in 0..psbt.inputs.len() {
+            if !is_finalized(&psbt.inputs[index]) {
+                let _ = psbt.finalize_inp_mut(secp, index);
+            }
+        }
+    }
+
+    /// Private keys for derivations recorded relative to one of this wallet's
+    /// account xpubs rather than its master key, as happens when the xpub was
+    /// exported without its origin into another wallet's descriptor.
+    fn foreign_origin_keys(
+        &self,
+        secp: &Secp256k1<All>,
+        master: &Xpriv,
+        psbt: &Psbt,
+    ) -> BTreeMap<PublicKey, PrivateKey> {
+        let mut accounts = HashMap::new();
+        for entry in &self.descriptors {
+            entry.descriptor.descriptor().for_each_key(|key| {
+                if let DescriptorPublicKey::XPub(xkey) = key {
+                    if let Some((_, origin_path)) = &xkey.origin {
+                        accounts.insert(xkey.xkey.fingerprint(), origin_path.clone());
+                    }
+                }
+                true
+            });
+        }
+
+        let mut keys = BTreeMap::new();
+        for input in &psbt.inputs {
+            for (pubkey, (fingerprint, path)) in &input.bip32_derivation {
+                let Some(origin_path) = accounts.get(fingerprint) else {
+                    continue;
+                };
+                let full_path = origin_path.extend(path);
+                let Ok(child) = master.derive_priv(secp, &full_path) else {
+                    continue;
+                };
+                let private_key = child.to_priv();
+                if private_key.public_key(secp).inner == *pubkey {
+                    keys.insert(PublicKey::new(*pubkey), private_key);
+                }
+            }
+        }
+        keys
+    }
+}
+
+pub(crate) fn is_finalized(input: &Input) -> bool {
+    input.final_script_sig.is_some() || input.final_script_witness.is_some()
+}
+
+/// `Psbt::sign` only produces ECDSA signatures, so key and script path
+/// Schnorr signatures are added here from the input's taproot key origins.
+fn sign_taproot_input(
+    secp: &Secp256k1<All>,
+    master: &Xpriv,
+    psbt: &mut Psbt,
+    index: usize,
+    prevouts: &[TxOut],
+) {
+    let fingerprint = master.fingerprint(secp);
+    let input = &psbt.inputs[index];
+    if input.tap_key_origins.is_empty() {
+        return;
+    }
+    let hash_ty = input
+        .sighash_type
+        .and_then(|ty| ty.taproot_hash_ty().ok())
+        .unwrap_or(TapSighashType::Default);
+
+    let mut key_sig = None;
+    let mut script_sigs = Vec::new();
+    let mut cache = SighashCache::new(&psbt.unsigned_tx);
+    for (xonly, (leaf_hashes, (key_fingerprint, path))) in &input.tap_key_origins {
+        if *key_fingerprint != fingerprint {
+            continue;
+        }
+        let Ok(child) = master.derive_priv(secp, path) else {
+            continue;
+        };
+        let keypair = child.to_keypair(secp);
+        if keypair.x_only_public_key().0 != *xonly {
+            continue;
+        }
+
+        if input.tap_internal_key == Some(*xonly) {
+            let Ok(sighash) =
+                cache.taproot_key_spend_signature_hash(index, &Prevouts::All(prevouts), hash_ty)
+            else {
+                continue;
+            };
+            let tweaked = keypair.tap_tweak(secp, input.tap_merkle_root);
+            let sig = secp.sign_schnorr(
+                &Message::from_digest(sighash.to_byte_array()),
+                &tweaked.to_inner(),
+            );
+            key_sig = Some(taproot::Signature { sig, hash_ty });
+        }
+
+        for leaf_hash in leaf_hashes {
+            let Ok(sighash) = cache.taproot_script_spend_signature_hash(
+                index,
+                &Prevouts::All(prevouts),
+                *leaf_hash,
+                hash_ty,
+            ) else {
+                continue;
+            };
+            let sig = secp.sign_schnorr(&Message::from_digest(sighash.to_byte_array()), &keypair);
+            script_sigs.push(((*xonly, *leaf_hash), taproot::Signature { sig, hash_ty }));
+        }
+    }
+
+    let input = &mut psbt.inputs[index];
+    if key_sig.is_some() {
+        input.tap_key_sig = key_sig;
+    }
+    input.tap_script_sigs.extend(script_sigs);
+}
+
+/// The `getnewaddress` type a descriptor hands out addresses for.
+pub(crate) fn descriptor_address_type<Pk: miniscript::MiniscriptKey>(
+    descriptor: &Descriptor<Pk>,
+) -> Option<AddressType> {
+    match descriptor.desc_type() {
+        DescriptorType::Pkh | DescriptorType::Sh | DescriptorType::ShSortedMulti => {
+            Some(AddressType::Legacy)
+        }
+        DescriptorType::ShWpkh | DescriptorType::ShWsh | DescriptorType::ShWshSortedMulti => {
+            Some(AddressType::P2shSegwit)
+        }
+        DescriptorType::Wpkh | DescriptorType::Wsh | DescriptorType::WshSortedMulti => {
+            Some(AddressType::Bech32)
+        }
+        DescriptorType::Tr => Some(AddressType::Bech32m),
+        DescriptorType::Bare => None,
+    }
+}
+
+pub(crate) fn descriptor_address(
+    descriptor: &Descriptor<DefiniteDescriptorKey>,
+    network: Network,
+) -> Result<Address, ClientError> {
+    descriptor
+        .address(network)
+        .map_err(|e| ClientError::InvalidResponse(e.to_string()))
+}
+
+/// What `getdescriptorinfo` reports for a public descriptor.
+pub(crate) fn descriptor_info(descriptor: &str) -> Result<GetDescriptorInfoResult, ClientError> {
+    let parsed = PublicDescriptor::from_str(descriptor)?;
+    let canonical = parsed.to_string();
+
+    Ok(GetDescriptorInfoResult {
+        checksum: canonical
+            .split_once('#')
+            .map(|(_, checksum)| checksum.to_string()),
+        descriptor: canonical,
+        is_range: parsed.descriptor().has_wildcard(),
+        is_solvable: true,
+        has_private_keys: false,
+    })
+}
+
+/// The redeem and witness scripts Bitcoin Core reports for an output.
+pub(crate) fn descriptor_scripts(
+    descriptor: &Descriptor<DefiniteDescriptorKey>,
+) -> (Option<ScriptBuf>, Option<ScriptBuf>) {
+    let explicit = descriptor.explicit_script().ok();
+    match descriptor.desc_type() {
+        DescriptorType::Sh | DescriptorType::ShWpkh | DescriptorType::ShSortedMulti => {
+            (explicit, None)
+        }
+        DescriptorType::ShWsh | DescriptorType::ShWshSortedMulti => (
+            explicit
+                .as_ref()
+                .map(|script| ScriptBuf::new_p2wsh(&script.wscript_hash())),
+            explicit,
+        ),
+        DescriptorType::Wsh | DescriptorType::WshSortedMulti => (None, explicit),
+        _ => (None, None),
+    }
+}
@@ -0,0 +1,242 @@
+use std::{
+    collections::HashMap,
+    io::{self, BufRead, BufReader, Read, Write},
+    net::TcpStream,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    time::Duration,
+};
+
+use bitcoincore_rpc::jsonrpc::serde_json::{self, json, Value};
+use rustls::{pki_types::ServerName, ClientConfig, ClientConnection, RootCertStore, StreamOwned};
+use url::Url;
+
+use crate::ClientError;
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+const CLIENT_NAME: &str = concat!("satoshi-suite/", env!("CARGO_PKG_VERSION"));
+const PROTOCOL_VERSION: &str = "1.4";
+
+enum Stream {
+    Tcp(TcpStream),
+    Tls(Box<StreamOwned<ClientConnection, TcpStream>>),
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Stream::Tcp(stream) => stream.read(buf),
+            Stream::Tls(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Stream::Tcp(stream) => stream.write(buf),
+            Stream::Tls(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Stream::Tcp(stream) => stream.flush(),
+            Stream::Tls(stream) => stream.flush(),
+        }
+    }
+}
+
+struct Connection {
+    reader: BufReader<Stream>,
+    next_id: u64,
+    /// Latest status of every subscribed script hash, kept current by the
+    /// server's notifications.
+    statuses: HashMap<String, Option<String>>,
+}
+
+/// A connection to an Electrum server speaking newline delimited JSON-RPC
+/// over TCP or TLS.
+pub struct ElectrumClient {
+    connection: Mutex<Connection>,
+}
+
+impl ElectrumClient {
+    /// Connect to `tcp://host:port` or `ssl://host:port` and negotiate the
+    /// protocol version.
+    pub fn connect(url: &Url) -> Result<Self, ClientError> {
+        let host = url
+            .host_str()
+            .ok_or_else(|| ClientError::InvalidConfiguration(format!("{} has no host", url)))?;
+        let port = url
+            .port()
+            .ok_or_else(|| ClientError::InvalidConfiguration(format!("{} has no port", url)))?;
+
+        let tcp = TcpStream::connect((host, port)).map_err(ClientError::Io)?;
+        tcp.set_read_timeout(Some(TIMEOUT))
+            .map_err(ClientError::Io)?;
+        tcp.set_write_timeout(Some(TIMEOUT))
+            .map_err(ClientError::Io)?;
+
+        let stream = match url.scheme() {
+            "tcp" => Stream::Tcp(tcp),
+            _ => {
+                let roots = RootCertStore {
+                    roots: webpki_roots::TLS_SERVER_ROOTS.into(),
+                };
+                let config = ClientConfig::builder()
+                    .with_root_certificates(roots)
+                    .with_no_client_auth();
+                let server_name = ServerName::try_from(host.to_string())
+                    .map_err(|e| ClientError::InvalidConfiguration(e.to_string()))?;
+                let connection = ClientConnection::new(Arc::new(config), server_name)
+                    .map_err(|e| ClientError::Io(io::Error::other(e)))?;
+                Stream::Tls(Box::new(StreamOwned::new(connection, tcp)))
+            }
+        };
+
+        let client = ElectrumClient {
+            connection: Mutex::new(Connection {
+                reader: BufReader::new(stream),
+                next_id: 0,
+                statuses: HashMap::new(),
+            }),
+        };
+        client.call("server.version", json!([CLIENT_NAME, PROTOCOL_VERSION]))?;
+        Ok(client)
+    }
+
+    pub fn call(&self, method: &str, params: Value) -> Result<Value, ClientError> {
+        let mut results = self.call_many(method, vec![params])?;
+        Ok(results.pop().unwrap_or_default())
+    }
+
+    /// Send one request per entry of `params` before reading any response,
+    /// so scanning many scripts costs a single round trip.
+    pub fn call_many(&self, method: &str, params: Vec<Value>) -> Result<Vec<Value>, ClientError> {
+        let mut connection = self.connection();
+        connection.call_many(method, params)
+    }
+
+    /// Subscribe to every script hash not subscribed to yet and return the
+    /// current status of each, `None` for scripts without history. Statuses
+    /// of earlier subscriptions come from the notifications received since.
+    pub fn subscribe_script_hashes(
+        &self,
+        script_hashes: &[String],
+    ) -> Result<Vec<Option<String>>, ClientError> {
+        let mut connection = self.connection();
+
+        let new: Vec<&String> = script_hashes
+            .iter()
+            .filter(|script_hash| !connection.statuses.contains_key(*script_hash))
+            .collect();
+        let statuses = connection.call_many(
+            "blockchain.scripthash.subscribe",
+            new.iter().map(|script_hash| json!([script_hash])).collect(),
+        )?;
+        for (script_hash, status) in new.into_iter().zip(statuses) {
+            connection
+                .statuses
+                .insert(script_hash.clone(), status.as_str().map(str::to_string));
+        }
+
+        // Any notification sent before the ping's response has been applied
+        connection.call_many("server.ping", vec![json!([])])?;
+
+        Ok(script_hashes
+            .iter()
+            .map(|script_hash| connection.statuses.get(script_hash).cloned().flatten())
+            .collect())
+    }
+
+    fn connection(&self) -> MutexGuard<'_, Connection> {
+        self.connection
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Connection {
+    fn call_many(&mut self, method: &str, params: Vec<Value>) -> Result<Vec<Value>, ClientError> {
+        if params.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let first_id = self.next_id;
+        let mut requests = String::new();
+        for params in params {
+            let request = json!({
+                "jsonrpc": "2.0",
+                "id": self.next_id,
+                "method": method,
+                "params": params,
+            });
+            requests.push_str(&request.to_string());
+            requests.push('\n');
+            self.next_id += 1;
+        }
+        let stream = self.reader.get_mut();
+        stream
+            .write_all(requests.as_bytes())
+            .and_then(|_| stream.flush())
+            .map_err(ClientError::Io)?;
+
+        let count = (self.next_id - first_id) as usize;
+        let mut results = vec![Value::Null; count];
+        let mut received = 0;
+        let mut error = None;
+        while received < count {
+            let message = self.read_message()?;
+            let Some(id) = message["id"].as_u64() else {
+                self.handle_notification(&message);
+                continue;
+            };
+            let Some(slot) = id
+                .checked_sub(first_id)
+                .and_then(|index| results.get_mut(index as usize))
+            else {
+                continue;
+            };
+
+            received += 1;
+            if message["error"].is_null() {
+                *slot = message["result"].clone();
+            } else if error.is_none() {
+                // Keep reading so the responses of this batch do not leak into the next
+                error = Some(error_message(&message["error"]));
+            }
+        }
+
+        match error {
+            Some(message) => Err(ClientError::Rejected(message)),
+            None => Ok(results),
+        }
+    }
+
+    fn read_message(&mut self) -> Result<Value, ClientError> {
+        let mut line = String::new();
+        let read = self.reader.read_line(&mut line).map_err(ClientError::Io)?;
+        if read == 0 {
+            return Err(ClientError::Io(io::ErrorKind::UnexpectedEof.into()));
+        }
+        serde_json::from_str(&line).map_err(|e| ClientError::InvalidResponse(e.to_string()))
+    }
+
+    fn handle_notification(&mut self, message: &Value) {
+        if message["method"] == "blockchain.scripthash.subscribe" {
+            if let Some(script_hash) = message["params"][0].as_str() {
+                let status = message["params"][1].as_str().map(str::to_string);
+                self.statuses.insert(script_hash.to_string(), status);
+            }
+        }
+    }
+}
+
+/// Servers send errors either as plain strings or as `{code, message}`.
+fn error_message(error: &Value) -> String {
+    error["message"]
+        .as_str()
+        .or_else(|| error.as_str())
+        .map(str::to_string)
+        .unwrap_or_else(|| error.to_string())
+}
@@ -0,0 +1,762 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    str::FromStr,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
+
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use bitcoin::{
+    address::NetworkUnchecked,
+    block::Header,
+    consensus::encode::{deserialize, serialize_hex},
+    hashes::{hex::FromHex, sha256, Hash},
+    Address, Amount, BlockHash, FeeRate, Network, OutPoint, Script, ScriptBuf, Transaction, Txid,
+};
+use bitcoincore_rpc::{
+    json::{
+        AddressType, CreateRawTransactionInput, GetAddressInfoResult, GetBalancesResult,
+        GetBalancesResultEntry, GetDescriptorInfoResult, GetTxOutResult, GetWalletInfoResult,
+        ListUnspentQueryOptions, ListUnspentResultEntry, ScanningDetails, Utxo,
+        WalletCreateFundedPsbtResult, WalletProcessPsbtResult,
+    },
+    jsonrpc::serde_json::{json, Value},
+};
+
+use crate::{
+    descriptor_address, descriptor_info, descriptor_scripts, script_pubkey_result, ChainBackend,
+    ClientError, DescriptorWallet, WalletBackend,
+};
+
+mod client;
+pub use client::*;
+
+/// Coinbase outputs can be spent once they are this many blocks deep.
+const COINBASE_MATURITY: u64 = 100;
+
+#[derive(Clone, Deserialize)]
+struct HistoryItem {
+    tx_hash: Txid,
+    /// 0 for mempool transactions, -1 for ones with unconfirmed parents
+    height: i64,
+}
+
+#[derive(Clone, Deserialize)]
+struct UnspentItem {
+    tx_hash: Txid,
+    tx_pos: u32,
+    height: i64,
+    value: u64,
+}
+
+#[derive(Default)]
+struct ScriptState {
+    status: Option<String>,
+    history: Vec<HistoryItem>,
+    unspent: Vec<UnspentItem>,
+}
+
+/// A wallet output as the server last reported it.
+struct Coin {
+    outpoint: OutPoint,
+    script_pubkey: ScriptBuf,
+    value: Amount,
+    height: Option<u64>,
+}
+
+/// A transaction touching one of a wallet's scripts.
+#[derive(Clone, Debug, Serialize)]
+pub struct WalletTransaction {
+    pub txid: Txid,
+    /// `None` while the transaction is in the mempool.
+    pub height: Option<u64>,
+    pub confirmations: u32,
+    /// Sum of the outputs paying to the wallet.
+    #[serde(with = "bitcoin::amount::serde::as_btc")]
+    pub received: Amount,
+    /// Sum of the wallet outputs the transaction spends.
+    #[serde(with = "bitcoin::amount::serde::as_btc")]
+    pub sent: Amount,
+}
+
+/// A wallet's coins split the way `getbalances` reports them.
+struct WalletCoins {
+    tip: u64,
+    mature: Vec<Coin>,
+    pending: Vec<Coin>,
+    immature: Vec<Coin>,
+}
+
+#[derive(Default)]
+struct ElectrumState {
+    wallets: BTreeMap<String, DescriptorWallet>,
+    scripts: HashMap<ScriptBuf, ScriptState>,
+    txs: HashMap<Txid, Transaction>,
+}
+
+/// An Electrum server. It holds no wallets of its own: watch-only
+/// descriptor wallets are tracked client side by subscribing to the script
+/// hashes they derive, extending the lookahead past every used script like
+/// Bitcoin Core's keypool does.
+#[derive(Clone)]
+pub struct ElectrumBackend {
+    client: Arc<ElectrumClient>,
+    network: Network,
+    state: Arc<Mutex<ElectrumState>>,
+    wallet: Option<String>,
+}
+
+impl ElectrumBackend {
+    pub fn new(url: &Url, network: Network) -> Result<Self, ClientError> {
+        Ok(ElectrumBackend {
+            client: Arc::new(ElectrumClient::connect(url)?),
+            network,
+            state: Arc::new(Mutex::new(ElectrumState::default())),
+            wallet: None,
+        })
+    }
+
+    /// Hash of the block at `height` on the server's best chain.
+    pub fn get_block_hash(&self, height: u64) -> Result<BlockHash, ClientError> {
+        let header = self
+            .client
+            .call("blockchain.block.header", json!([height]))?;
+        Ok(decode_header(&header)?.block_hash())
+    }
+
+    /// Every transaction touching the wallet, oldest first with mempool
+    /// transactions last.
+    pub fn get_history(&self) -> Result<Vec<WalletTransaction>, ClientError> {
+        let name = self.wallet_name()?;
+        let tip = self.tip_height()?;
+        let mut state = self.state();
+        state.sync(&self.client, &name)?;
+
+        let mut items: Vec<HistoryItem> = Vec::new();
+        let mut seen = BTreeSet::new();
+        for script_pubkey in state.wallet_scripts(&name) {
+            for item in &state.scripts[&script_pubkey].history {
+                if seen.insert(item.tx_hash) {
+                    items.push(item.clone());
+                }
+            }
+        }
+        items.sort_by_key(|item| (item.height <= 0, item.height));
+
+        let mut history = Vec::new();
+        for item in items {
+            let tx = state.transaction(&self.client, &item.tx_hash)?;
+            let wallet = &state.wallets[&name];
+
+            let received = tx
+                .output
+                .iter()
+                .filter(|output| wallet.is_mine(&output.script_pubkey))
+                .map(|output| output.value)
+                .sum();
+            let mut sent = Amount::ZERO;
+            for input in &tx.input {
+                // Wallet outputs can only come from transactions in the wallet's history
+                if !seen.contains(&input.previous_output.txid) {
+                    continue;
+                }
+                let previous = state.transaction(&self.client, &input.previous_output.txid)?;
+                if let Some(output) = previous.output.get(input.previous_output.vout as usize) {
+                    if state.wallets[&name].is_mine(&output.script_pubkey) {
+                        sent += output.value;
+                    }
+                }
+            }
+
+            let height = confirmed_height(item.height);
+            history.push(WalletTransaction {
+                txid: item.tx_hash,
+                height,
+                confirmations: depth(tip, height),
+                received,
+                sent,
+            });
+        }
+        Ok(history)
+    }
+
+    fn state(&self) -> MutexGuard<'_, ElectrumState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn scoped(&self, wallet_name: &str) -> Self {
+        ElectrumBackend {
+            wallet: Some(wallet_name.to_string()),
+            ..self.clone()
+        }
+    }
+
+    fn wallet_name(&self) -> Result<String, ClientError> {
+        self.wallet
+            .clone()
+            .ok_or_else(|| ClientError::WalletNotFound("no wallet selected".to_string()))
+    }
+
+    fn tip(&self) -> Result<(u64, BlockHash), ClientError> {
+        let tip = self
+            .client
+            .call("blockchain.headers.subscribe", json!([]))?;
+        let height = tip["height"]
+            .as_u64()
+            .ok_or_else(|| ClientError::InvalidResponse(format!("tip {}", tip)))?;
+        Ok((height, decode_header(&tip["hex"])?.block_hash()))
+    }
+
+    fn tip_height(&self) -> Result<u64, ClientError> {
+        Ok(self.tip()?.0)
+    }
+
+    /// Sync the wallet and sort its coins by spendability.
+    fn wallet_coins(&self, name: &str) -> Result<WalletCoins, ClientError> {
+        let tip = self.tip_height()?;
+        let mut state = self.state();
+        state.sync(&self.client, name)?;
+
+        let (mut mature, mut pending, mut immature) = (Vec::new(), Vec::new(), Vec::new());
+        for coin in state.coins(name) {
+            let confirmations = depth(tip, coin.height) as u64;
+            if coin.height.is_none() {
+                pending.push(coin);
+            } else if confirmations <= COINBASE_MATURITY
+                && state
+                    .transaction(&self.client, &coin.outpoint.txid)?
+                    .is_coinbase()
+            {
+                immature.push(coin);
+            } else {
+                mature.push(coin);
+            }
+        }
+        Ok(WalletCoins {
+            tip,
+            mature,
+            pending,
+            immature,
+        })
+    }
+
+    fn balances(&self, name: &str) -> Result<GetBalancesResultEntry, ClientError> {
+        let coins = self.wallet_coins(name)?;
+        let total = |coins: &[Coin]| coins.iter().map(|coin| coin.value).sum();
+        Ok(GetBalancesResultEntry {
+            trusted: total(&coins.mature),
+            untrusted_pending: total(&coins.pending),
+            immature: total(&coins.immature),
+        })
+    }
+}
+
+impl ElectrumState {
+    fn wallet_scripts(&self, name: &str) -> Vec<ScriptBuf> {
+        self.wallets[name].scripts().cloned().collect()
+    }
+
+    /// Subscribe to every script the wallet derives, refresh the ones whose
+    /// status changed and extend the lookahead past used scripts until no new
+    /// scripts are derived.
+    fn sync(&mut self, client: &ElectrumClient, name: &str) -> Result<(), ClientError> {
+        if !self.wallets.contains_key(name) {
+            return Err(ClientError::WalletNotFound(name.to_string()));
+        }
+
+        loop {
+            let scripts = self.wallet_scripts(name);
+            let script_hashes: Vec<String> = scripts.iter().map(|s| script_hash(s)).collect();
+            let statuses = client.subscribe_script_hashes(&script_hashes)?;
+
+            let stale: Vec<(usize, Option<String>)> = statuses
+                .into_iter()
+                .enumerate()
+                .filter(|(index, status)| {
+                    self.scripts
+                        .get(&scripts[*index])
+                        .map_or(true, |cached| cached.status != *status)
+                })
+                .collect();
+
+            let requests: Vec<Value> = stale
+                .iter()
+                .map(|(index, _)| json!([script_hashes[*index]]))
+                .collect();
+            let histories =
+                client.call_many("blockchain.scripthash.get_history", requests.clone())?;
+            let unspents = client.call_many("blockchain.scripthash.listunspent", requests)?;
+
+            for (((index, status), history), unspent) in
+                stale.into_iter().zip(histories).zip(unspents)
+            {
+                let parse_err = |e: bitcoincore_rpc::jsonrpc::serde_json::Error| {
+                    ClientError::InvalidResponse(e.to_string())
+                };
+                self.scripts.insert(
+                    scripts[index].clone(),
+                    ScriptState {
+                        status,
+                        history: Vec::deserialize(history).map_err(parse_err)?,
+                        unspent: Vec::deserialize(unspent).map_err(parse_err)?,
+                    },
+                );
+            }
+
+            let wallet = self.wallets.get_mut(name).expect("wallet exists");
+            for script_pubkey in &scripts {
+                if self
+                    .scripts
+                    .get(script_pubkey)
+                    .is_some_and(|state| !state.history.is_empty())
+                {
+                    wallet.mark_used(script_pubkey);
+                }
+            }
+            if wallet.scripts().count() == scripts.len() {
+                return Ok(());
+            }
+        }
+    }
+
+    fn coins(&self, name: &str) -> Vec<Coin> {
+        let mut seen = BTreeSet::new();
+        let mut coins = Vec::new();
+        for script_pubkey in self.wallet_scripts(name) {
+            let Some(state) = self.scripts.get(&script_pubkey) else {
+                continue;
+            };
+            for item in &state.unspent {
+                let outpoint = OutPoint::new(item.tx_hash, item.tx_pos);
+                if seen.insert(outpoint) {
+                    coins.push(Coin {
+                        outpoint,
+                        script_pubkey: script_pubkey.clone(),
+                        value: Amount::from_sat(item.value),
+                        height: confirmed_height(item.height),
+                    });
+                }
+            }
+        }
+        // Oldest first, mempool last, independent of how scripts are stored
+        coins.sort_by_key(|coin| (coin.height.is_none(), coin.height, coin.outpoint));
+        coins
+    }
+
+    fn transaction(
+        &mut self,
+        client: &ElectrumClient,
+        txid: &Txid,
+    ) -> Result<Transaction, ClientError> {
+        if let Some(tx) = self.txs.get(txid) {
+            return Ok(tx.clone());
+        }
+        let tx = fetch_transaction(client, txid)?;
+        self.txs.insert(*txid, tx.clone());
+        Ok(tx)
+    }
+}
+
+impl ChainBackend for ElectrumBackend {
+    fn network(&self) -> Network {
+        self.network
+    }
+
+    fn get_block_count(&self) -> Result<u64, ClientError> {
+        self.tip_height()
+    }
+
+    fn get_raw_transaction(&self, txid: &Txid) -> Result<Transaction, ClientError> {
+        self.state().transaction(&self.client, txid)
+    }
+
+    /// Electrum servers only list unspent outputs with the mempool applied,
+    /// so outputs spent by mempool transactions are missing even without
+    /// `include_mempool`.
+    fn get_tx_out(
+        &self,
+        outpoint: &OutPoint,
+        include_mempool: bool,
+    ) -> Result<Option<GetTxOutResult>, ClientError> {
+        let Ok(tx) = self.get_raw_transaction(&outpoint.txid) else {
+            return Ok(None);
+        };
+        let Some(output) = tx.output.get(outpoint.vout as usize) else {
+            return Ok(None);
+        };
+
+        let unspent: Vec<UnspentItem> = Vec::deserialize(self.client.call(
+            "blockchain.scripthash.listunspent",
+            json!([script_hash(&output.script_pubkey)]),
+        )?)
+        .map_err(|e| ClientError::InvalidResponse(e.to_string()))?;
+        let Some(item) = unspent
+            .iter()
+            .find(|item| item.tx_hash == outpoint.txid && item.tx_pos == outpoint.vout)
+        else {
+            return Ok(None);
+        };
+        let height = confirmed_height(item.height);
+        if height.is_none() && !include_mempool {
+            return Ok(None);
+        }
+
+        let (tip, bestblock) = self.tip()?;
+        Ok(Some(GetTxOutResult {
+            bestblock,
+            confirmations: depth(tip, height),
+            value: output.value,
+            script_pub_key: script_pubkey_result(&output.script_pubkey, self.network),
+            coinbase: tx.is_coinbase(),
+        }))
+    }
+
+    /// Electrum servers have no lookup by txid, so the history of one of
+    /// the transaction's outputs tells whether it is unconfirmed.
+    fn in_mempool(&self, txid: &Txid) -> Result<bool, ClientError> {
+        let Ok(tx) = self.get_raw_transaction(txid) else {
+            return Ok(false);
+        };
+        let Some(output) = tx
+            .output
+            .iter()
+            .find(|output| !output.script_pubkey.is_op_return())
+        else {
+            return Err(ClientError::Unsupported(
+                "mempool lookup of transactions without spendable outputs".to_string(),
+            ));
+        };
+
+        let history: Vec<HistoryItem> = Vec::deserialize(self.client.call(
+            "blockchain.scripthash.get_history",
+            json!([script_hash(&output.script_pubkey)]),
+        )?)
+        .map_err(|e| ClientError::InvalidResponse(e.to_string()))?;
+        Ok(history
+            .iter()
+            .any(|item| item.tx_hash == *txid && confirmed_height(item.height).is_none()))
+    }
+
+    fn scan_utxos(&self, script_pubkey: &Script) -> Result<Vec<Utxo>, ClientError> {
+        let unspent: Vec<UnspentItem> = Vec::deserialize(self.client.call(
+            "blockchain.scripthash.listunspent",
+            json!([script_hash(script_pubkey)]),
+        )?)
+        .map_err(|e| ClientError::InvalidResponse(e.to_string()))?;
+
+        Ok(unspent
+            .into_iter()
+            .filter_map(|item| {
+                Some(Utxo {
+                    txid: item.tx_hash,
+                    vout: item.tx_pos,
+                    script_pub_key: script_pubkey.to_owned(),
+                    descriptor: format!("raw({})", script_pubkey.to_hex_string()),
+                    amount: Amount::from_sat(item.value),
+                    height: confirmed_height(item.height)?,
+                })
+            })
+            .collect())
+    }
+
+    fn send_raw_transaction(&self, tx: &Transaction) -> Result<Txid, ClientError> {
+        let txid = self.client.call(
+            "blockchain.transaction.broadcast",
+            json!([serialize_hex(tx)]),
+        )?;
+        txid.as_str()
+            .and_then(|txid| Txid::from_str(txid).ok())
+            .ok_or_else(|| ClientError::InvalidResponse(format!("txid {}", txid)))
+    }
+
+    fn submit_package(&self, _txs: &[Transaction]) -> Result<Vec<Txid>, ClientError> {
+        Err(ClientError::Unsupported("submitpackage".to_string()))
+    }
+
+    fn estimate_fee_rate(&self, conf_target: u16) -> Result<Option<FeeRate>, ClientError> {
+        // BTC per kilobyte, or -1 when the server's node has no estimate
+        let estimate = self
+            .client
+            .call("blockchain.estimatefee", json!([conf_target]))?;
+        let btc_per_kvb = estimate
+            .as_f64()
+            .ok_or_else(|| ClientError::InvalidResponse(format!("fee estimate {}", estimate)))?;
+        if btc_per_kvb <= 0.0 {
+            return Ok(None);
+        }
+        let sat_per_kvb = Amount::from_btc(btc_per_kvb)
+            .map_err(|e| ClientError::InvalidResponse(e.to_string()))?;
+        Ok(Some(FeeRate::from_sat_per_kwu(sat_per_kvb.to_sat() / 4)))
+    }
+
+    fn generate_to_address(
+        &self,
+        _blocks: u64,
+        _address: &Address,
+    ) -> Result<Vec<BlockHash>, ClientError> {
+        Err(ClientError::Unsupported("generatetoaddress".to_string()))
+    }
+}
+
+impl WalletBackend for ElectrumBackend {
+    fn open_wallet(&self, name: &str) -> Result<Self, ClientError> {
+        if !self.state().wallets.contains_key(name) {
+            return Err(ClientError::WalletNotFound(name.to_string()));
+        }
+        Ok(self.scoped(name))
+    }
+
+    fn create_watch_only_wallet(&self, name: &str) -> Result<Self, ClientError> {
+        let mut state = self.state();
+        if state.wallets.contains_key(name) {
+            return Err(ClientError::Rejected(format!(
+                "Wallet {} already exists",
+                name
+            )));
+        }
+        state
+            .wallets
+            .insert(name.to_string(), DescriptorWallet::watch_only());
+        Ok(self.scoped(name))
+    }
+
+    fn get_new_address(
+        &self,
+        address_type: AddressType,
+    ) -> Result<Address<NetworkUnchecked>, ClientError> {
+        let name = self.wallet_name()?;
+        let mut state = self.state();
+        // Skip addresses that received payments since the wallet was loaded
+        state.sync(&self.client, &name)?;
+        let wallet = state.wallets.get_mut(&name).expect("wallet exists");
+        let descriptor = wallet.derive_next(address_type, false)?;
+        Ok(descriptor_address(&descriptor, self.network)?
+            .as_unchecked()
+            .clone())
+    }
+
+    fn get_balances(&self) -> Result<GetBalancesResult, ClientError> {
+        Ok(GetBalancesResult {
+            mine: self.balances(&self.wallet_name()?)?,
+            watchonly: None,
+        })
+    }
+
+    fn get_wallet_info(&self) -> Result<GetWalletInfoResult, ClientError> {
+        let name = self.wallet_name()?;
+        let balances = self.balances(&name)?;
+        let state = self.state();
+        let wallet = &state.wallets[&name];
+
+        let tx_count = state
+            .wallet_scripts(&name)
+            .iter()
+            .filter_map(|script_pubkey| state.scripts.get(script_pubkey))
+            .flat_map(|script| script.history.iter().map(|item| item.tx_hash))
+            .collect::<BTreeSet<Txid>>()
+            .len();
+        let keypool_size = |internal: bool| {
+            wallet
+                .descriptors()
+                .iter()
+                .filter(|entry| entry.active && entry.internal == internal)
+                .count()
+        };
+
+        Ok(GetWalletInfoResult {
+            wallet_name: name.clone(),
+            wallet_version: 169900,
+            balance: balances.trusted,
+            unconfirmed_balance: balances.untrusted_pending,
+            immature_balance: balances.immature,
+            tx_count,
+            keypool_oldest: None,
+            keypool_size: keypool_size(false),
+            keypool_size_hd_internal: keypool_size(true),
+            unlocked_until: None,
+            pay_tx_fee: Amount::ZERO,
+            hd_seed_id: None,
+            private_keys_enabled: false,
+            avoid_reuse: Some(false),
+            scanning: Some(ScanningDetails::NotScanning(false)),
+        })
+    }
+
+    fn get_address_info(&self, address: &Address) -> Result<GetAddressInfoResult, ClientError> {
+        let name = self.wallet_name()?;
+        let state = self.state();
+        Ok(state.wallets[&name].address_info(address))
+    }
+
+    fn list_unspent(
+        &self,
+        query_options: Option<ListUnspentQueryOptions>,
+    ) -> Result<Vec<ListUnspentResultEntry>, ClientError> {
+        let options = query_options.unwrap_or_default();
+        let name = self.wallet_name()?;
+        let WalletCoins { tip, mature, .. } = self.wallet_coins(&name)?;
+        let state = self.state();
+        let wallet = &state.wallets[&name];
+
+        let mut unspent = Vec::new();
+        let mut sum = Amount::ZERO;
+        for coin in mature {
+            if wallet.is_locked(&coin.outpoint) {
+                continue;
+            }
+            if options.minimum_amount.is_some_and(|min| coin.value < min)
+                || options.maximum_amount.is_some_and(|max| coin.value > max)
+            {
+                continue;
+            }
+            let Some(descriptor) = wallet.descriptor_for(&coin.script_pubkey) else {
+                continue;
+            };
+            let (redeem_script, witness_script) = descriptor_scripts(&descriptor);
+
+            unspent.push(ListUnspentResultEntry {
+                txid: coin.outpoint.txid,
+                vout: coin.outpoint.vout,
+                address: descriptor_address(&descriptor, self.network)
+                    .ok()
+                    .map(|address| address.as_unchecked().clone()),
+                label: None,
+                redeem_script,
+                witness_script,
+                script_pub_key: coin.script_pubkey,
+                amount: coin.value,
+                confirmations: depth(tip, coin.height),
+                spendable: false,
+                solvable: true,
+                descriptor: Some(descriptor.to_string()),
+                safe: true,
+            });
+            sum += coin.value;
+
+            if options
+                .maximum_count
+                .is_some_and(|count| unspent.len() >= count)
+                || options.minimum_sum_amount.is_some_and(|min| sum >= min)
+            {
+                break;
+            }
+        }
+        Ok(unspent)
+    }
+
+    fn send(&self, _address: &Address, _amount: Amount) -> Result<Txid, ClientError> {
+        Err(ClientError::Unsupported(
+            "send from a watch-only wallet".to_string(),
+        ))
+    }
+
+    fn sign_raw_transaction(&self, _tx: &Transaction) -> Result<Transaction, ClientError> {
+        Err(ClientError::Unsupported(
+            "signing with a watch-only wallet".to_string(),
+        ))
+    }
+
+    fn list_descriptors(&self) -> Result<Value, ClientError> {
+        let name = self.wallet_name()?;
+        Ok(self.state().wallets[&name].list_descriptors(&name))
+    }
+
+    fn get_descriptor_info(
+        &self,
+        descriptor: &str,
+    ) -> Result<GetDescriptorInfoResult, ClientError> {
+        descriptor_info(descriptor)
+    }
+
+    fn import_descriptors(&self, descriptors: &[Value]) -> Result<(), ClientError> {
+        let name = self.wallet_name()?;
+        let mut state = self.state();
+        let wallet = state.wallets.get_mut(&name).expect("wallet exists");
+        wallet.import_descriptors(descriptors)
+    }
+
+    fn wallet_create_funded_psbt(
+        &self,
+        _inputs: &[CreateRawTransactionInput],
+        _outputs: &HashMap<String, Amount>,
+        _fee_rate: Option<FeeRate>,
+    ) -> Result<WalletCreateFundedPsbtResult, ClientError> {
+        Err(ClientError::Unsupported(
+            "walletcreatefundedpsbt".to_string(),
+        ))
+    }
+
+    fn wallet_process_psbt(&self, _psbt: &str) -> Result<WalletProcessPsbtResult, ClientError> {
+        Err(ClientError::Unsupported("walletprocesspsbt".to_string()))
+    }
+
+    fn lock_unspent(&self, outpoints: &[OutPoint]) -> Result<(), ClientError> {
+        let name = self.wallet_name()?;
+        let coins = self.wallet_coins(&name)?;
+        let unspent: BTreeSet<OutPoint> = [coins.mature, coins.pending, coins.immature]
+            .iter()
+            .flatten()
+            .map(|coin| coin.outpoint)
+            .collect();
+        if let Some(outpoint) = outpoints.iter().find(|o| !unspent.contains(o)) {
+            return Err(ClientError::Rejected(format!(
+                "Invalid parameter, unknown or spent output: {}",
+                outpoint
+            )));
+        }
+        let mut state = self.state();
+        state
+            .wallets
+            .get_mut(&name)
+            .expect("wallet exists")
+            .lock(outpoints)
+    }
+
+    fn unlock_unspent(&self, outpoints: &[OutPoint]) -> Result<(), ClientError> {
+        let name = self.wallet_name()?;
+        let mut state = self.state();
+        state
+            .wallets
+            .get_mut(&name)
+            .expect("wallet exists")
+            .unlock(outpoints)
+    }
+
+    fn list_lock_unspent(&self) -> Result<Vec<OutPoint>, ClientError> {
+        let name = self.wallet_name()?;
+        Ok(self.state().wallets[&name].locked())
+    }
+}
+
+/// Electrum identifies scripts by their SHA256 in reversed byte order.
+fn script_hash(script_pubkey: &Script) -> String {
+    let mut hash = sha256::Hash::hash(script_pubkey.as_bytes()).to_byte_array();
+    hash.reverse();
+    hash.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn confirmed_height(height: i64) -> Option<u64> {
+    u64::try_from(height).ok().filter(|height| *height > 0)
+}
+
+fn depth(tip: u64, height: Option<u64>) -> u32 {
+    height.map_or(0, |height| (tip + 1).saturating_sub(height) as u32)
+}
+
+fn fetch_transaction(client: &ElectrumClient, txid: &Txid) -> Result<Transaction, ClientError> {
+    let tx_hex = client.call("blockchain.transaction.get", json!([txid, false]))?;
+    let tx_bytes = tx_hex
+        .as_str()
+        .and_then(|tx_hex| Vec::from_hex(tx_hex).ok())
+        .ok_or_else(|| ClientError::InvalidResponse(format!("transaction {}", tx_hex)))?;
+    deserialize(&tx_bytes).map_err(|e| ClientError::InvalidResponse(e.to_string()))
+}
+
+fn decode_header(header_hex: &Value) -> Result<Header, ClientError> {
+    let header_bytes = header_hex
+        .as_str()
+        .and_then(|header_hex| Vec::from_hex(header_hex).ok())
+        .ok_or_else(|| ClientError::InvalidResponse(format!("block header {}", header_hex)))?;
+    deserialize(&header_bytes).map_err(|e| ClientError::InvalidResponse(e.to_string()))
+}
@@ -0,0 +1,262 @@
+use std::{collections::HashMap, str::FromStr, time::Duration};
+
+use serde::{de::DeserializeOwned, Deserialize};
+use url::Url;
+
+use bitcoin::{
+    consensus::encode::{deserialize, serialize_hex},
+    hashes::{hex::FromHex, sha256, Hash},
+    Address, Amount, BlockHash, FeeRate, Network, OutPoint, Script, ScriptBuf, Transaction, Txid,
+};
+use bitcoincore_rpc::{
+    json::{GetTxOutResult, Utxo},
+    jsonrpc::serde_json::Value,
+};
+
+use crate::{check_package_result, script_pubkey_result, ChainBackend, ClientError};
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Deserialize)]
+struct TxStatus {
+    confirmed: bool,
+    block_height: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct EsploraTx {
+    vin: Vec<EsploraTxIn>,
+    vout: Vec<EsploraTxOut>,
+    status: TxStatus,
+}
+
+#[derive(Deserialize)]
+struct EsploraTxIn {
+    is_coinbase: bool,
+}
+
+#[derive(Deserialize)]
+struct EsploraTxOut {
+    scriptpubkey: ScriptBuf,
+    value: u64,
+}
+
+#[derive(Deserialize)]
+struct OutSpend {
+    spent: bool,
+    status: Option<TxStatus>,
+}
+
+#[derive(Deserialize)]
+struct EsploraUtxo {
+    txid: Txid,
+    vout: u32,
+    value: u64,
+    status: TxStatus,
+}
+
+/// An Esplora HTTP API, as served by electrs, mempool.space or
+/// blockstream.info. It indexes the chain but holds no wallets and cannot
+/// mine blocks.
+pub struct EsploraBackend {
+    agent: ureq::Agent,
+    base_url: Url,
+    network: Network,
+}
+
+impl EsploraBackend {
+    /// `base_url` is the API root, e.g. `https://blockstream.info/api/`.
+    pub fn new(base_url: Url, network: Network) -> Self {
+        EsploraBackend {
+            agent: ureq::AgentBuilder::new().timeout(TIMEOUT).build(),
+            base_url,
+            network,
+        }
+    }
+
+    /// Hash of the block at `height` on the server's best chain.
+    pub fn get_block_hash(&self, height: u64) -> Result<BlockHash, ClientError> {
+        let hash = self.get_text(&format!("block-height/{}", height))?;
+        BlockHash::from_str(hash.trim()).map_err(|e| ClientError::InvalidResponse(e.to_string()))
+    }
+
+    /// Confirmed unspent outputs paying to `address`.
+    pub fn address_utxos(&self, address: &Address) -> Result<Vec<Utxo>, ClientError> {
+        self.scan_utxos(&address.script_pubkey())
+    }
+
+    fn url(&self, path: &str) -> Result<Url, ClientError> {
+        self.base_url
+            .join(path)
+            .map_err(|e| ClientError::InvalidConfiguration(e.to_string()))
+    }
+
+    fn get_text(&self, path: &str) -> Result<String, ClientError> {
+        self.agent
+            .request_url("GET", &self.url(path)?)
+            .call()
+            .map_err(|e| http_error(e, path))?
+            .into_string()
+            .map_err(|e| ClientError::InvalidResponse(e.to_string()))
+    }
+
+    fn get_json<T: DeserializeOwned>(&self, path: &str) -> Result<T, ClientError> {
+        self.agent
+            .request_url("GET", &self.url(path)?)
+            .call()
+            .map_err(|e| http_error(e, path))?
+            .into_json()
+            .map_err(|e| ClientError::InvalidResponse(e.to_string()))
+    }
+}
+
+impl ChainBackend for EsploraBackend {
+    fn network(&self) -> Network {
+        self.network
+    }
+
+    fn get_block_count(&self) -> Result<u64, ClientError> {
+        let height = self.get_text("blocks/tip/height")?;
+        height
+            .trim()
+            .parse()
+            .map_err(|_| ClientError::InvalidResponse(format!("block height {}", height)))
+    }
+
+    fn get_raw_transaction(&self, txid: &Txid) -> Result<Transaction, ClientError> {
+        let tx_hex = self.get_text(&format!("tx/{}/hex", txid))?;
+        let tx_bytes = Vec::from_hex(tx_hex.trim())
+            .map_err(|e| ClientError::InvalidResponse(e.to_string()))?;
+        deserialize(&tx_bytes).map_err(|e| ClientError::InvalidResponse(e.to_string()))
+    }
+
+    fn get_tx_out(
+        &self,
+        outpoint: &OutPoint,
+        include_mempool: bool,
+    ) -> Result<Option<GetTxOutResult>, ClientError> {
+        let tx: EsploraTx = match self.get_json(&format!("tx/{}", outpoint.txid)) {
+            Ok(tx) => tx,
+            Err(ClientError::NotFound(_)) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let Some(output) = tx.vout.get(outpoint.vout as usize) else {
+            return Ok(None);
+        };
+        if !tx.status.confirmed && !include_mempool {
+            return Ok(None);
+        }
+
+        let outspend: OutSpend =
+            self.get_json(&format!("tx/{}/outspend/{}", outpoint.txid, outpoint.vout))?;
+        let spend_confirmed = outspend.status.is_some_and(|status| status.confirmed);
+        if outspend.spent && (include_mempool || spend_confirmed) {
+            return Ok(None);
+        }
+
+        let bestblock = BlockHash::from_str(self.get_text("blocks/tip/hash")?.trim())
+            .map_err(|e| ClientError::InvalidResponse(e.to_string()))?;
+        let confirmations = match tx.status.block_height {
+            Some(height) if tx.status.confirmed => {
+                (self.get_block_count()? + 1).saturating_sub(height) as u32
+            }
+            _ => 0,
+        };
+
+        Ok(Some(GetTxOutResult {
+            bestblock,
+            confirmations,
+            value: Amount::from_sat(output.value),
+            script_pub_key: script_pubkey_result(&output.scriptpubkey, self.network),
+            coinbase: tx.vin.first().is_some_and(|input| input.is_coinbase),
+        }))
+    }
+
+    fn in_mempool(&self, txid: &Txid) -> Result<bool, ClientError> {
+        match self.get_json::<TxStatus>(&format!("tx/{}/status", txid)) {
+            Ok(status) => Ok(!status.confirmed),
+            Err(ClientError::NotFound(_)) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn scan_utxos(&self, script_pubkey: &Script) -> Result<Vec<Utxo>, ClientError> {
+        // Esplora indexes scripts by their SHA256, hex encoded in natural byte order
+        let script_hash = sha256::Hash::hash(script_pubkey.as_bytes());
+        let utxos: Vec<EsploraUtxo> = self.get_json(&format!("scripthash/{}/utxo", script_hash))?;
+
+        Ok(utxos
+            .into_iter()
+            .filter(|utxo| utxo.status.confirmed)
+            .map(|utxo| Utxo {
+                txid: utxo.txid,
+                vout: utxo.vout,
+                script_pub_key: script_pubkey.to_owned(),
+                descriptor: format!("raw({})", script_pubkey.to_hex_string()),
+                amount: Amount::from_sat(utxo.value),
+                height: utxo.status.block_height.unwrap_or_default(),
+            })
+            .collect())
+    }
+
+    fn send_raw_transaction(&self, tx: &Transaction) -> Result<Txid, ClientError> {
+        let txid = self
+            .agent
+            .request_url("POST", &self.url("tx")?)
+            .send_string(&serialize_hex(tx))
+            .map_err(|e| http_error(e, "tx"))?
+            .into_string()
+            .map_err(|e| ClientError::InvalidResponse(e.to_string()))?;
+        Txid::from_str(txid.trim()).map_err(|e| ClientError::InvalidResponse(e.to_string()))
+    }
+
+    fn submit_package(&self, txs: &[Transaction]) -> Result<Vec<Txid>, ClientError> {
+        // Answered with Bitcoin Core's `submitpackage` result where supported,
+        // as by mempool.space
+        let hex: Vec<String> = txs.iter().map(serialize_hex).collect();
+        let result: Value = self
+            .agent
+            .request_url("POST", &self.url("txs/package")?)
+            .send_json(hex)
+            .map_err(|e| match http_error(e, "txs/package") {
+                ClientError::NotFound(_) => ClientError::Unsupported("submitpackage".to_string()),
+                err => err,
+            })?
+            .into_json()
+            .map_err(|e| ClientError::InvalidResponse(e.to_string()))?;
+        check_package_result(&result)?;
+        Ok(txs.iter().map(Transaction::txid).collect())
+    }
+
+    fn estimate_fee_rate(&self, conf_target: u16) -> Result<Option<FeeRate>, ClientError> {
+        // sat/vB keyed by confirmation target; servers without fee data return an empty map
+        let estimates: HashMap<String, f64> = self.get_json("fee-estimates")?;
+        let sat_per_vb = estimates
+            .into_iter()
+            .filter_map(|(target, rate)| Some((target.parse::<u16>().ok()?, rate)))
+            .filter(|(target, _)| *target <= conf_target)
+            .max_by_key(|(target, _)| *target)
+            .map(|(_, rate)| rate);
+        Ok(sat_per_vb.map(|rate| FeeRate::from_sat_per_kwu((rate * 250.0).ceil() as u64)))
+    }
+
+    fn generate_to_address(
+        &self,
+        _blocks: u64,
+        _address: &Address,
+    ) -> Result<Vec<BlockHash>, ClientError> {
+        Err(ClientError::Unsupported("generatetoaddress".to_string()))
+    }
+}
+
+/// Esplora answers unknown objects with 404 and invalid or rejected requests
+/// (including broadcasts failing policy) with 400 and a plain text reason.
+fn http_error(err: ureq::Error, path: &str) -> ClientError {
+    match err {
+        ureq::Error::Status(404, _) => ClientError::NotFound(path.to_string()),
+        ureq::Error::Status(400, response) => {
+            ClientError::Rejected(response.into_string().unwrap_or_default())
+        }
+        err => ClientError::Http(Box::new(err)),
+    }
+}
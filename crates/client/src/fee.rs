@@ -0,0 +1,146 @@
+use std::{fmt, str::FromStr};
+
+use bitcoin::{
+    consensus::encode::VarInt, taproot::ControlBlock, Amount, FeeRate, Network, Script,
+    Transaction, Weight,
+};
+use bitcoincore_rpc::json::ListUnspentResultEntry;
+
+use crate::{ChainBackend, ClientError, PublicDescriptor};
+
+/// Fee rate used on regtest, where nodes have no fee history to estimate
+/// from. Twice the minimum relay fee, so transactions still relay when an
+/// estimate comes out a little small.
+pub const REGTEST_FEE_RATE: FeeRate = FeeRate::from_sat_per_vb_unchecked(2);
+
+/// Where the fee rate of a transaction comes from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FeeTarget {
+    /// A fixed rate.
+    Rate(FeeRate),
+    /// Whatever the backend estimates confirms within this many blocks.
+    ConfTarget(u16),
+}
+
+impl Default for FeeTarget {
+    fn default() -> Self {
+        FeeTarget::ConfTarget(6)
+    }
+}
+
+impl fmt::Display for FeeTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FeeTarget::Rate(rate) => write!(f, "{} sat/vB", format_sat_per_vb(*rate)),
+            FeeTarget::ConfTarget(blocks) => write!(f, "confirmation within {} blocks", blocks),
+        }
+    }
+}
+
+/// A fee rate in sat/vB with up to three decimals.
+pub fn format_sat_per_vb(rate: FeeRate) -> String {
+    let formatted = format!("{:.3}", rate.to_sat_per_kwu() as f64 / 250.0);
+    formatted
+        .trim_end_matches('0')
+        .trim_end_matches('.')
+        .to_string()
+}
+
+/// The fee rate to build a transaction with. Estimates fall back to
+/// [`REGTEST_FEE_RATE`] on regtest and are an error elsewhere.
+pub fn resolve_fee_rate<B: ChainBackend + ?Sized>(
+    backend: &B,
+    target: FeeTarget,
+) -> Result<FeeRate, ClientError> {
+    let conf_target = match target {
+        FeeTarget::Rate(rate) => return Ok(rate),
+        FeeTarget::ConfTarget(conf_target) => conf_target,
+    };
+
+    match backend.estimate_fee_rate(conf_target)? {
+        Some(rate) => Ok(rate),
+        None if backend.network() == Network::Regtest => Ok(REGTEST_FEE_RATE),
+        None => Err(ClientError::NotFound(format!(
+            "fee estimate for confirmation within {} blocks; pass a fee rate instead",
+            conf_target
+        ))),
+    }
+}
+
+/// Most weight the script_sig and witness spending `utxo` add to its input,
+/// in the sense of miniscript's `max_weight_to_satisfy`. Wallet outputs are
+/// sized from their descriptor; other outputs from their script type,
+/// assuming a single key.
+pub fn utxo_satisfaction_weight(utxo: &ListUnspentResultEntry) -> Option<Weight> {
+    utxo.descriptor
+        .as_deref()
+        .and_then(|descriptor| PublicDescriptor::from_str(descriptor).ok())
+        .and_then(|descriptor| descriptor.descriptor().max_weight_to_satisfy().ok())
+        .map(|weight| Weight::from_wu(weight as u64))
+        .or_else(|| script_satisfaction_weight(&utxo.script_pub_key))
+}
+
+/// Satisfaction weight of a single key spend of `script_pubkey`, with
+/// worst-case 73 byte ECDSA signatures.
+pub fn script_satisfaction_weight(script_pubkey: &Script) -> Option<Weight> {
+    // length prefixed signature and compressed public key
+    let sig_and_key = 1 + 73 + 1 + 33;
+
+    let weight = if script_pubkey.is_p2wpkh() {
+        sig_and_key
+    } else if script_pubkey.is_p2tr() {
+        // key path: a length prefixed Schnorr signature with a sighash byte
+        1 + 65
+    } else if script_pubkey.is_p2pkh() {
+        4 * sig_and_key
+    } else if script_pubkey.is_p2sh() {
+        // assume p2sh-p2wpkh: the script_sig pushes the 22 byte witness program
+        4 * (1 + 22) + sig_and_key
+    } else {
+        return None;
+    };
+    Some(Weight::from_wu(weight as u64))
+}
+
+/// Satisfaction weight of a taproot script path spend of `leaf_script`
+/// with `signatures` Schnorr signatures using the default sighash.
+pub fn tap_script_satisfaction_weight(
+    leaf_script: &Script,
+    control_block: &ControlBlock,
+    signatures: usize,
+) -> Weight {
+    let script_len = leaf_script.len();
+    let control_block_len = control_block.size();
+    let items = signatures + 2;
+
+    let weight = VarInt(items as u64).size() - 1
+        + signatures * (1 + 64)
+        + VarInt(script_len as u64).size()
+        + script_len
+        + VarInt(control_block_len as u64).size()
+        + control_block_len;
+    Weight::from_wu(weight as u64)
+}
+
+/// Weight of `tx` once its inputs, which must still be unsigned, carry
+/// script_sigs and witnesses of the given satisfaction weights.
+pub fn estimate_weight(tx: &Transaction, satisfaction_weights: &[Weight]) -> Weight {
+    // The unsigned transaction serializes without the segwit marker, flag and
+    // the empty witness each input gets once any input has a witness
+    let segwit_overhead = Weight::from_wu(2 + tx.input.len() as u64);
+    satisfaction_weights
+        .iter()
+        .fold(tx.weight() + segwit_overhead, |total, weight| {
+            total + *weight
+        })
+}
+
+/// Fee for `tx` at `fee_rate` once its inputs are signed.
+pub fn estimate_fee(
+    tx: &Transaction,
+    satisfaction_weights: &[Weight],
+    fee_rate: FeeRate,
+) -> Amount {
+    let vsize = estimate_weight(tx, satisfaction_weights).to_vbytes_ceil();
+    fee_rate.fee_vb(vsize).unwrap_or(Amount::MAX_MONEY)
+}
@@ -0,0 +1,77 @@
+use std::{fmt, time::Duration};
+
+use bitcoin::base64::{engine::general_purpose::STANDARD, Engine};
+use bitcoincore_rpc::{
+    jsonrpc::{self, serde_json, Request, Response, Transport},
+    Auth,
+};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::ClientError;
+
+/// Bitcoin Core's own client gives up on a call after this long.
+const TIMEOUT: Duration = Duration::from_secs(15);
+
+/// JSON-RPC over `https`, for nodes behind a TLS-terminating proxy. The
+/// transport of `bitcoincore-rpc` speaks plain HTTP only.
+pub(crate) struct HttpsTransport {
+    agent: ureq::Agent,
+    url: String,
+    authorization: Option<String>,
+}
+
+impl HttpsTransport {
+    pub fn new(url: &str, auth: Auth) -> Result<Self, ClientError> {
+        let (user, pass) = auth.get_user_pass().map_err(ClientError::CannotConnect)?;
+        let authorization = user.map(|user| {
+            let credentials = format!("{}:{}", user, pass.unwrap_or_default());
+            format!("Basic {}", STANDARD.encode(credentials))
+        });
+        Ok(HttpsTransport {
+            agent: ureq::AgentBuilder::new().timeout(TIMEOUT).build(),
+            url: url.to_string(),
+            authorization,
+        })
+    }
+
+    fn post<T: Serialize + ?Sized, R: DeserializeOwned>(
+        &self,
+        body: &T,
+    ) -> Result<R, jsonrpc::Error> {
+        let mut request = self.agent.post(&self.url);
+        if let Some(authorization) = &self.authorization {
+            request = request.set("Authorization", authorization);
+        }
+        // Bitcoin Core answers failed calls with an error status and the
+        // error in a JSON-RPC response
+        let response = match request.send_json(body) {
+            Ok(response) => response,
+            Err(ureq::Error::Status(status, response)) => {
+                let text = response.into_string().unwrap_or_default();
+                return serde_json::from_str(&text).map_err(|_| {
+                    transport_error(format!("HTTP {} from {}: {}", status, self.url, text))
+                });
+            }
+            Err(err) => return Err(transport_error(err)),
+        };
+        response.into_json().map_err(transport_error)
+    }
+}
+
+impl Transport for HttpsTransport {
+    fn send_request(&self, request: Request) -> Result<Response, jsonrpc::Error> {
+        self.post(&request)
+    }
+
+    fn send_batch(&self, requests: &[Request]) -> Result<Vec<Response>, jsonrpc::Error> {
+        self.post(requests)
+    }
+
+    fn fmt_target(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.url)
+    }
+}
+
+fn transport_error(err: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> jsonrpc::Error {
+    jsonrpc::Error::Transport(err.into())
+}
@@ -1,13 +1,56 @@
-use std::{error::Error, fmt};
+use std::{error::Error, fmt, io};
 
-use bitcoincore_rpc::{Client, Error as RpcError};
+use bitcoincore_rpc::{
+    jsonrpc::{self, serde_json::Value},
+    Client, Error as RpcError, RpcApi,
+};
 
-use satoshi_suite_config::Config;
+use url::Url;
+
+use satoshi_suite_config::{BackendConfig, BitcoinRpcConfig, Config};
+
+mod backend;
+pub use backend::*;
+
+mod descriptor_wallet;
+pub(crate) use descriptor_wallet::*;
+
+mod electrum;
+pub use electrum::*;
+
+mod esplora;
+pub use esplora::*;
+
+mod fee;
+pub use fee::*;
+
+mod https;
+pub(crate) use https::*;
+
+mod rpc;
+pub use rpc::*;
+
+mod script;
+pub(crate) use script::*;
+
+mod simulator;
+pub use simulator::*;
+
+mod sorted_multi_a;
+pub(crate) use sorted_multi_a::*;
 
 #[derive(Debug)]
 pub enum ClientError {
     CannotConnect(RpcError),
     InvalidConfiguration(String),
+    Rpc(RpcError),
+    Http(Box<ureq::Error>),
+    Io(io::Error),
+    WalletNotFound(String),
+    NotFound(String),
+    Rejected(String),
+    InvalidResponse(String),
+    Unsupported(String),
 }
 
 impl fmt::Display for ClientError {
@@ -15,6 +58,14 @@ impl fmt::Display for ClientError {
         match self {
             ClientError::CannotConnect(err) => write!(f, "Cannot connect to Bitcoin Core: {}", err),
             ClientError::InvalidConfiguration(msg) => write!(f, "Invalid configuration: {}", msg),
+            ClientError::Rpc(err) => write!(f, "RPC error: {}", err),
+            ClientError::Http(err) => write!(f, "HTTP error: {}", err),
+            ClientError::Io(err) => write!(f, "I/O error: {}", err),
+            ClientError::WalletNotFound(name) => write!(f, "Wallet not found: {}", name),
+            ClientError::NotFound(what) => write!(f, "Not found: {}", what),
+            ClientError::Rejected(reason) => write!(f, "Rejected: {}", reason),
+            ClientError::InvalidResponse(msg) => write!(f, "Invalid response: {}", msg),
+            ClientError::Unsupported(what) => write!(f, "Not supported by this backend: {}", what),
         }
     }
 }
@@ -23,14 +74,17 @@ impl Error for ClientError {
     fn source(&self) -> Option<&(dyn Error + 'static)> {
         match self {
             ClientError::CannotConnect(err) => Some(err),
-            ClientError::InvalidConfiguration(_) => None,
+            ClientError::Rpc(err) => Some(err),
+            ClientError::Http(err) => Some(err.as_ref()),
+            ClientError::Io(err) => Some(err),
+            _ => None,
         }
     }
 }
 
 impl From<RpcError> for ClientError {
     fn from(err: RpcError) -> Self {
-        ClientError::CannotConnect(err)
+        ClientError::Rpc(err)
     }
 }
 
@@ -38,8 +92,102 @@ pub fn create_rpc_client(
     config: &Config,
     wallet_name: Option<&str>,
 ) -> Result<Client, ClientError> {
-    let url = config.bitcoin_rpc.format_url(wallet_name);
-    let auth = config.bitcoin_rpc.auth();
+    new_client(&config.bitcoin_rpc, wallet_name)
+}
+
+pub(crate) fn new_client(
+    rpc: &BitcoinRpcConfig,
+    wallet_name: Option<&str>,
+) -> Result<Client, ClientError> {
+    let url = rpc.format_url(wallet_name);
+    let auth = rpc.auth();
 
+    if url.starts_with("https://") {
+        let transport = HttpsTransport::new(&url, auth)?;
+        return Ok(Client::from_jsonrpc(jsonrpc::Client::with_transport(
+            transport,
+        )));
+    }
     Client::new(&url, auth).map_err(ClientError::CannotConnect)
 }
+
+/// The backend chain queries and broadcasts go to, as selected by the config.
+pub fn create_chain_backend(config: &Config) -> Result<Box<dyn ChainBackend>, ClientError> {
+    match &config.backend {
+        BackendConfig::BitcoinCore => Ok(Box::new(RpcBackend::new(config, None)?)),
+        BackendConfig::Esplora { url } => Ok(Box::new(EsploraBackend::new(
+            url.clone(),
+            config.bitcoin_rpc.network(),
+        ))),
+        BackendConfig::Electrum { url } => Ok(Box::new(ElectrumBackend::new(
+            url,
+            config.bitcoin_rpc.network(),
+        )?)),
+    }
+}
+
+/// Check that the backend serves the configured chain and, for Bitcoin Core on
+/// signet, the configured challenge, so commands never run against the wrong
+/// network.
+pub fn verify_chain(config: &Config) -> Result<(), ClientError> {
+    match &config.backend {
+        BackendConfig::Esplora { url } => return verify_esplora_chain(config, url.clone()),
+        BackendConfig::Electrum { url } => return verify_electrum_chain(config, url),
+        BackendConfig::BitcoinCore => {}
+    }
+
+    let client = create_rpc_client(config, None)?;
+    let info: Value = client.call("getblockchaininfo", &[])?;
+
+    let expected = config.bitcoin_rpc.chain();
+    let actual = info["chain"].as_str().unwrap_or_default();
+    if actual != expected.core_name() {
+        return Err(ClientError::InvalidConfiguration(format!(
+            "configured for {} but the node is running {}",
+            expected, actual
+        )));
+    }
+
+    if let Some(challenge) = config.bitcoin_rpc.signet_challenge() {
+        let node_challenge = info["signet_challenge"].as_str().unwrap_or_default();
+        if node_challenge != challenge.to_hex_string() {
+            return Err(ClientError::InvalidConfiguration(format!(
+                "signet challenge mismatch: expected {}, node has {}",
+                challenge.to_hex_string(),
+                node_challenge
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// Esplora has no chain name to ask for, so compare genesis blocks instead.
+fn verify_esplora_chain(config: &Config, url: Url) -> Result<(), ClientError> {
+    let expected = config.bitcoin_rpc.chain();
+    let esplora = EsploraBackend::new(url, expected.network());
+    let genesis = esplora.get_block_hash(0)?;
+    if genesis != expected.genesis_hash() {
+        return Err(ClientError::InvalidConfiguration(format!(
+            "configured for {} but the Esplora server has genesis block {}",
+            expected, genesis
+        )));
+    }
+
+    Ok(())
+}
+
+/// Electrum servers do not name their chain either.
+fn verify_electrum_chain(config: &Config, url: &Url) -> Result<(), ClientError> {
+    let expected = config.bitcoin_rpc.chain();
+    let electrum = ElectrumBackend::new(url, expected.network())?;
+    let genesis = electrum.get_block_hash(0)?;
+    if genesis != expected.genesis_hash() {
+        return Err(ClientError::InvalidConfiguration(format!(
+            "configured for {} but the Electrum server has genesis block {}",
+            expected, genesis
+        )));
+    }
+
+    Ok(())
+}
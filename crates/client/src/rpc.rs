@@ -0,0 +1,328 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+use tracing::info;
+
+use bitcoin::{
+    address::NetworkUnchecked, consensus::encode::serialize_hex, Address, Amount, BlockHash,
+    FeeRate, Network, OutPoint, Script, Transaction, Txid,
+};
+use bitcoincore_rpc::{
+    json::{
+        AddressType, CreateRawTransactionInput, GetAddressInfoResult, GetBalancesResult,
+        GetDescriptorInfoResult, GetTxOutResult, GetWalletInfoResult, ListUnspentQueryOptions,
+        ListUnspentResultEntry, ScanTxOutRequest, Utxo, WalletCreateFundedPsbtOptions,
+        WalletCreateFundedPsbtResult, WalletProcessPsbtResult,
+    },
+    jsonrpc::{
+        error::Error as JsonRpcError,
+        serde_json::{json, Value},
+    },
+    Client, Error as RpcError, RpcApi,
+};
+
+use satoshi_suite_config::{BitcoinRpcConfig, Config};
+
+use crate::{new_client, ChainBackend, ClientError, WalletBackend};
+
+/// JSON-RPC error code for a method the server does not have.
+const METHOD_NOT_FOUND: i32 = -32601;
+/// Bitcoin Core's error code for an unknown transaction, among others.
+const INVALID_ADDRESS_OR_KEY: i32 = -5;
+
+#[derive(Deserialize)]
+struct SendResult {
+    txid: Txid,
+}
+
+#[derive(Deserialize)]
+struct LockedOutput {
+    txid: Txid,
+    vout: u32,
+}
+
+/// Bitcoin Core over JSON-RPC.
+pub struct RpcBackend {
+    pub client: Client,
+    rpc: BitcoinRpcConfig,
+    create_wallets: bool,
+}
+
+impl RpcBackend {
+    pub fn new(config: &Config, wallet_name: Option<&str>) -> Result<Self, ClientError> {
+        Ok(RpcBackend {
+            client: new_client(&config.bitcoin_rpc, wallet_name)?,
+            rpc: config.bitcoin_rpc.clone(),
+            create_wallets: config.create_wallets,
+        })
+    }
+
+    fn scoped(&self, wallet_name: &str) -> Result<Self, ClientError> {
+        Ok(RpcBackend {
+            client: new_client(&self.rpc, Some(wallet_name))?,
+            rpc: self.rpc.clone(),
+            create_wallets: self.create_wallets,
+        })
+    }
+}
+
+impl ChainBackend for RpcBackend {
+    fn network(&self) -> Network {
+        self.rpc.network()
+    }
+
+    fn get_block_count(&self) -> Result<u64, ClientError> {
+        Ok(self.client.get_block_count()?)
+    }
+
+    fn get_raw_transaction(&self, txid: &Txid) -> Result<Transaction, ClientError> {
+        Ok(self.client.get_raw_transaction(txid, None)?)
+    }
+
+    fn get_tx_out(
+        &self,
+        outpoint: &OutPoint,
+        include_mempool: bool,
+    ) -> Result<Option<GetTxOutResult>, ClientError> {
+        Ok(self
+            .client
+            .get_tx_out(&outpoint.txid, outpoint.vout, Some(include_mempool))?)
+    }
+
+    fn in_mempool(&self, txid: &Txid) -> Result<bool, ClientError> {
+        match self.client.call::<Value>("getmempoolentry", &[json!(txid)]) {
+            Ok(_) => Ok(true),
+            Err(RpcError::JsonRpc(JsonRpcError::Rpc(ref rpc)))
+                if rpc.code == INVALID_ADDRESS_OR_KEY =>
+            {
+                Ok(false)
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn scan_utxos(&self, script_pubkey: &Script) -> Result<Vec<Utxo>, ClientError> {
+        let descriptor = format!("raw({})", script_pubkey.to_hex_string());
+        let result = self
+            .client
+            .scan_tx_out_set_blocking(&[ScanTxOutRequest::Single(descriptor)])?;
+        Ok(result.unspents)
+    }
+
+    fn send_raw_transaction(&self, tx: &Transaction) -> Result<Txid, ClientError> {
+        Ok(self.client.send_raw_transaction(tx)?)
+    }
+
+    fn submit_package(&self, txs: &[Transaction]) -> Result<Vec<Txid>, ClientError> {
+        let hex: Vec<String> = txs.iter().map(serialize_hex).collect();
+        let result: Value = self
+            .client
+            .call("submitpackage", &[json!(hex)])
+            .map_err(|err| match err {
+                // Nodes before v26 have no package relay
+                RpcError::JsonRpc(JsonRpcError::Rpc(ref rpc)) if rpc.code == METHOD_NOT_FOUND => {
+                    ClientError::Unsupported("submitpackage".to_string())
+                }
+                err => err.into(),
+            })?;
+        check_package_result(&result)?;
+        Ok(txs.iter().map(Transaction::txid).collect())
+    }
+
+    fn estimate_fee_rate(&self, conf_target: u16) -> Result<Option<FeeRate>, ClientError> {
+        // Bitcoin Core reports BTC per 1000 vbytes, which is 4000 weight units
+        let estimate = self.client.estimate_smart_fee(conf_target, None)?;
+        Ok(estimate
+            .fee_rate
+            .map(|per_kvb| FeeRate::from_sat_per_kwu(per_kvb.to_sat() / 4)))
+    }
+
+    fn generate_to_address(
+        &self,
+        blocks: u64,
+        address: &Address,
+    ) -> Result<Vec<BlockHash>, ClientError> {
+        Ok(self.client.generate_to_address(blocks, address)?)
+    }
+}
+
+impl WalletBackend for RpcBackend {
+    fn open_wallet(&self, name: &str) -> Result<Self, ClientError> {
+        let name = name.to_string();
+        let wallet_list = self.client.list_wallet_dir()?;
+
+        if wallet_list.contains(&name) {
+            let loaded_wallets = self.client.list_wallets()?;
+
+            if !loaded_wallets.contains(&name) {
+                info!("Loading wallet {}", name);
+                self.client.load_wallet(&name)?;
+            } else {
+                info!("Wallet {} already loaded", name);
+            }
+        } else {
+            if !self.create_wallets {
+                return Err(ClientError::WalletNotFound(name));
+            }
+            info!("Creating wallet {}", name);
+            self.client.create_wallet(&name, None, None, None, None)?;
+        }
+
+        self.scoped(&name)
+    }
+
+    fn create_watch_only_wallet(&self, name: &str) -> Result<Self, ClientError> {
+        self.client
+            .create_wallet(name, Some(true), Some(true), None, None)?;
+        self.scoped(name)
+    }
+
+    fn get_new_address(
+        &self,
+        address_type: AddressType,
+    ) -> Result<Address<NetworkUnchecked>, ClientError> {
+        Ok(self.client.get_new_address(None, Some(address_type))?)
+    }
+
+    fn get_balances(&self) -> Result<GetBalancesResult, ClientError> {
+        Ok(self.client.get_balances()?)
+    }
+
+    fn get_wallet_info(&self) -> Result<GetWalletInfoResult, ClientError> {
+        Ok(self.client.get_wallet_info()?)
+    }
+
+    fn get_address_info(&self, address: &Address) -> Result<GetAddressInfoResult, ClientError> {
+        Ok(self.client.get_address_info(address)?)
+    }
+
+    fn list_unspent(
+        &self,
+        query_options: Option<ListUnspentQueryOptions>,
+    ) -> Result<Vec<ListUnspentResultEntry>, ClientError> {
+        Ok(self
+            .client
+            .list_unspent(Some(1), Some(9999999), None, None, query_options)?)
+    }
+
+    fn send(&self, address: &Address, amount: Amount) -> Result<Txid, ClientError> {
+        let output = json!([{
+            address.to_string(): amount.to_btc()
+        }]);
+        let send_result: SendResult = self
+            .client
+            .call("send", &[output, Value::Null, "unset".into(), 1.into()])?;
+        Ok(send_result.txid)
+    }
+
+    fn sign_raw_transaction(&self, tx: &Transaction) -> Result<Transaction, ClientError> {
+        let signed = self
+            .client
+            .sign_raw_transaction_with_wallet(tx, None, None)?;
+        signed
+            .transaction()
+            .map_err(|e| ClientError::InvalidResponse(e.to_string()))
+    }
+
+    fn list_descriptors(&self) -> Result<Value, ClientError> {
+        Ok(self.client.call("listdescriptors", &[])?)
+    }
+
+    fn get_descriptor_info(
+        &self,
+        descriptor: &str,
+    ) -> Result<GetDescriptorInfoResult, ClientError> {
+        Ok(self.client.get_descriptor_info(descriptor)?)
+    }
+
+    fn import_descriptors(&self, descriptors: &[Value]) -> Result<(), ClientError> {
+        let results: Value = self
+            .client
+            .call("importdescriptors", &[json!(descriptors)])?;
+
+        let failures: Vec<&Value> = results
+            .as_array()
+            .map(|results| {
+                results
+                    .iter()
+                    .filter(|result| !result["success"].as_bool().unwrap_or(false))
+                    .collect()
+            })
+            .unwrap_or_default();
+        if let Some(failure) = failures.first() {
+            return Err(ClientError::Rejected(failure["error"].to_string()));
+        }
+
+        Ok(())
+    }
+
+    fn wallet_create_funded_psbt(
+        &self,
+        inputs: &[CreateRawTransactionInput],
+        outputs: &HashMap<String, Amount>,
+        fee_rate: Option<FeeRate>,
+    ) -> Result<WalletCreateFundedPsbtResult, ClientError> {
+        let locktime = None;
+        let options = fee_rate.map(|fee_rate| WalletCreateFundedPsbtOptions {
+            fee_rate: Some(Amount::from_sat(fee_rate.to_sat_per_kwu() * 4)),
+            ..Default::default()
+        });
+        let bip32derivs = None;
+        Ok(self.client.wallet_create_funded_psbt(
+            inputs,
+            outputs,
+            locktime,
+            options,
+            bip32derivs,
+        )?)
+    }
+
+    fn wallet_process_psbt(&self, psbt: &str) -> Result<WalletProcessPsbtResult, ClientError> {
+        Ok(self.client.wallet_process_psbt(psbt, None, None, None)?)
+    }
+
+    fn lock_unspent(&self, outpoints: &[OutPoint]) -> Result<(), ClientError> {
+        match self.client.lock_unspent(outpoints)? {
+            true => Ok(()),
+            false => Err(ClientError::Rejected("lockunspent failed".to_string())),
+        }
+    }
+
+    fn unlock_unspent(&self, outpoints: &[OutPoint]) -> Result<(), ClientError> {
+        match self.client.unlock_unspent(outpoints)? {
+            true => Ok(()),
+            false => Err(ClientError::Rejected("lockunspent failed".to_string())),
+        }
+    }
+
+    fn list_lock_unspent(&self) -> Result<Vec<OutPoint>, ClientError> {
+        let locked: Vec<LockedOutput> = self.client.call("listlockunspent", &[])?;
+        Ok(locked
+            .into_iter()
+            .map(|output| OutPoint::new(output.txid, output.vout))
+            .collect())
+    }
+}
+
+/// Fail on a `submitpackage` result reporting that the package or any of
+/// its transactions was rejected.
+pub(crate) fn check_package_result(result: &Value) -> Result<(), ClientError> {
+    let message = result["package_msg"].as_str().unwrap_or("success");
+    if message == "success" {
+        return Ok(());
+    }
+    let errors: Vec<&str> = result["tx-results"]
+        .as_object()
+        .into_iter()
+        .flat_map(|results| results.values())
+        .filter_map(|tx| tx["error"].as_str())
+        .collect();
+    match errors.is_empty() {
+        true => Err(ClientError::Rejected(message.to_string())),
+        false => Err(ClientError::Rejected(format!(
+            "{}: {}",
+            message,
+            errors.join(", ")
+        ))),
+    }
+}
@@ -0,0 +1,43 @@
+use bitcoin::{Address, Network, Script};
+use bitcoincore_rpc::json::{GetRawTransactionResultVoutScriptPubKey, ScriptPubkeyType};
+
+/// The output type Bitcoin Core reports for `script`.
+pub(crate) fn script_pubkey_type(script: &Script) -> Option<ScriptPubkeyType> {
+    let script_type = if script.is_p2pkh() {
+        ScriptPubkeyType::PubkeyHash
+    } else if script.is_p2sh() {
+        ScriptPubkeyType::ScriptHash
+    } else if script.is_p2wpkh() {
+        ScriptPubkeyType::Witness_v0_KeyHash
+    } else if script.is_p2wsh() {
+        ScriptPubkeyType::Witness_v0_ScriptHash
+    } else if script.is_p2tr() {
+        ScriptPubkeyType::Witness_v1_Taproot
+    } else if script.is_witness_program() {
+        ScriptPubkeyType::Witness_Unknown
+    } else if script.is_op_return() {
+        ScriptPubkeyType::NullData
+    } else if script.is_p2pk() {
+        ScriptPubkeyType::Pubkey
+    } else {
+        ScriptPubkeyType::Nonstandard
+    };
+    Some(script_type)
+}
+
+/// A script in the shape of Bitcoin Core's `scriptPubKey` result objects.
+pub(crate) fn script_pubkey_result(
+    script: &Script,
+    network: Network,
+) -> GetRawTransactionResultVoutScriptPubKey {
+    GetRawTransactionResultVoutScriptPubKey {
+        asm: script.to_asm_string(),
+        hex: script.to_bytes(),
+        req_sigs: None,
+        type_: script_pubkey_type(script),
+        addresses: Vec::new(),
+        address: Address::from_script(script, network)
+            .ok()
+            .map(|address| address.as_unchecked().clone()),
+    }
+}
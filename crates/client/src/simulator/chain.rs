@@ -0,0 +1,608 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use bitcoin::{
+    absolute::LockTime,
+    block::{Header, Version as BlockVersion},
+    blockdata::constants::genesis_block,
+    hashes::Hash,
+    opcodes::OP_0,
+    relative,
+    script::{Builder, PushBytesBuf},
+    secp256k1::{All, Message, Secp256k1, XOnlyPublicKey},
+    sighash::{Prevouts, SighashCache},
+    taproot::{self, TAPROOT_ANNEX_PREFIX},
+    transaction::Version,
+    Amount, Block, BlockHash, Network, OutPoint, ScriptBuf, Sequence, Transaction, TxIn, TxOut,
+    Txid, Witness,
+};
+use miniscript::Interpreter;
+
+/// Blocks a coinbase output has to be buried under before consensus allows spending it.
+pub(crate) const COINBASE_MATURITY: u32 = 100;
+
+const SUBSIDY_HALVING_INTERVAL: u32 = 150;
+const BLOCK_SPACING: u32 = 600;
+const MIN_RELAY_FEE_RATE: u64 = 1;
+/// Fee rate in sat/vB a replacement pays on top of the fees it evicts.
+const INCREMENTAL_RELAY_FEE_RATE: u64 = 1;
+/// Most transactions a single replacement may evict, with descendants.
+const MAX_REPLACEMENT_CANDIDATES: usize = 100;
+const WITNESS_COMMITMENT_HEADER: [u8; 4] = [0xaa, 0x21, 0xa9, 0xed];
+
+#[derive(Clone, Debug)]
+pub(crate) struct Coin {
+    pub output: TxOut,
+    /// Confirmation height, `None` while the creating transaction is unconfirmed.
+    pub height: Option<u32>,
+    pub coinbase: bool,
+}
+
+/// Blocks, UTXO set and mempool of the simulated chain.
+pub(crate) struct ChainState {
+    blocks: Vec<Block>,
+    utxos: BTreeMap<OutPoint, Coin>,
+    mempool: Vec<(Transaction, Amount)>,
+    txs: HashMap<Txid, (Transaction, Option<u32>)>,
+}
+
+impl ChainState {
+    pub fn new() -> Self {
+        ChainState {
+            blocks: vec![genesis_block(Network::Regtest)],
+            utxos: BTreeMap::new(),
+            mempool: Vec::new(),
+            txs: HashMap::new(),
+        }
+    }
+
+    pub fn tip_height(&self) -> u32 {
+        (self.blocks.len() - 1) as u32
+    }
+
+    pub fn tip_hash(&self) -> BlockHash {
+        self.blocks[self.blocks.len() - 1].block_hash()
+    }
+
+    pub fn transaction(&self, txid: &Txid) -> Option<&(Transaction, Option<u32>)> {
+        self.txs.get(txid)
+    }
+
+    pub fn transactions(&self) -> impl Iterator<Item = &Transaction> {
+        self.txs.values().map(|(tx, _)| tx)
+    }
+
+    pub fn mempool(&self) -> impl Iterator<Item = &Transaction> {
+        self.mempool.iter().map(|(tx, _)| tx)
+    }
+
+    pub fn spent_in_mempool(&self, outpoint: &OutPoint) -> bool {
+        self.mempool().any(|tx| {
+            tx.input
+                .iter()
+                .any(|input| input.previous_output == *outpoint)
+        })
+    }
+
+    /// Unspent outputs as seen by the mempool: confirmed coins not spent by an
+    /// unconfirmed transaction, plus the outputs of unconfirmed transactions.
+    pub fn coins(&self, include_mempool: bool) -> BTreeMap<OutPoint, Coin> {
+        match include_mempool {
+            true => self.coins_without(&HashSet::new()),
+            false => self.utxos.clone(),
+        }
+    }
+
+    /// Unspent outputs as seen by the mempool once the `evicted`
+    /// transactions are gone from it.
+    fn coins_without(&self, evicted: &HashSet<Txid>) -> BTreeMap<OutPoint, Coin> {
+        let mut coins = self.utxos.clone();
+        for (tx, _) in &self.mempool {
+            if evicted.contains(&tx.txid()) {
+                continue;
+            }
+            for input in &tx.input {
+                coins.remove(&input.previous_output);
+            }
+            coins.extend(unspent_outputs(tx, None, false));
+        }
+        coins
+    }
+
+    /// Mempool transactions spending an input of `tx`, and every mempool
+    /// transaction descending from them, which replacing them evicts too.
+    fn conflicts(&self, tx: &Transaction) -> (Vec<Txid>, Vec<Txid>) {
+        let spends_input = |pending: &Transaction| {
+            pending.input.iter().any(|input| {
+                tx.input
+                    .iter()
+                    .any(|new| new.previous_output == input.previous_output)
+            })
+        };
+        let conflicts: Vec<Txid> = self
+            .mempool()
+            .filter(|pending| spends_input(pending))
+            .map(Transaction::txid)
+            .collect();
+
+        let mut evicted = conflicts.clone();
+        let mut next = 0;
+        while next < evicted.len() {
+            let parent = evicted[next];
+            for pending in self.mempool() {
+                let txid = pending.txid();
+                if !evicted.contains(&txid)
+                    && pending
+                        .input
+                        .iter()
+                        .any(|input| input.previous_output.txid == parent)
+                {
+                    evicted.push(txid);
+                }
+            }
+            next += 1;
+        }
+        (conflicts, evicted)
+    }
+
+    /// Check a transaction against the next block's consensus rules and the
+    /// mempool, then add it to the mempool.
+    pub fn accept(&mut self, tx: Transaction, secp: &Secp256k1<All>) -> Result<Txid, String> {
+        self.admit(tx, secp, MIN_RELAY_FEE_RATE)
+            .map(|(txid, _)| txid)
+    }
+
+    /// Accept a child with its unconfirmed parents, in that order, into the
+    /// mempool as `submitpackage` does. Parents already in the mempool are
+    /// skipped, and the rest may pay less than the minimum relay fee on
+    /// their own as long as the package meets it as a whole.
+    pub fn accept_package(
+        &mut self,
+        txs: Vec<Transaction>,
+        secp: &Secp256k1<All>,
+    ) -> Result<Vec<Txid>, String> {
+        let Some((child, parents)) = txs.split_last() else {
+            return Err("package-empty".to_string());
+        };
+        let parent_of_child = |parent: &Transaction| {
+            let txid = parent.txid();
+            child
+                .input
+                .iter()
+                .any(|input| input.previous_output.txid == txid)
+        };
+        if !parents.iter().all(parent_of_child) {
+            return Err("package-not-child-with-parents".to_string());
+        }
+
+        let saved = (self.mempool.clone(), self.txs.clone());
+        let mut txids = Vec::with_capacity(txs.len());
+        let mut fees = Amount::ZERO;
+        let mut vsize = 0;
+        for tx in txs {
+            let txid = tx.txid();
+            txids.push(txid);
+            if self.mempool().any(|pending| pending.txid() == txid) {
+                continue;
+            }
+            vsize += tx.vsize() as u64;
+            match self.admit(tx, secp, 0) {
+                Ok((_, fee)) => fees += fee,
+                Err(reason) => {
+                    (self.mempool, self.txs) = saved;
+                    return Err(reason);
+                }
+            }
+        }
+        if fees.to_sat() < vsize * MIN_RELAY_FEE_RATE {
+            (self.mempool, self.txs) = saved;
+            return Err("package-fee-too-low".to_string());
+        }
+        Ok(txids)
+    }
+
+    /// Add `tx` to the mempool if it pays at least `min_fee_rate` in sat/vB,
+    /// returning its fee.
+    fn admit(
+        &mut self,
+        tx: Transaction,
+        secp: &Secp256k1<All>,
+        min_fee_rate: u64,
+    ) -> Result<(Txid, Amount), String> {
+        let txid = tx.txid();
+        if self.mempool().any(|pending| pending.txid() == txid) {
+            return Err("txn-already-in-mempool".to_string());
+        }
+        if self.txs.contains_key(&txid) {
+            return Err("txn-already-known".to_string());
+        }
+
+        let (conflicts, evicted) = self.conflicts(&tx);
+        let evicted: HashSet<Txid> = evicted.into_iter().collect();
+        let fee = self.check_transaction(&tx, secp, &evicted)?;
+        if fee.to_sat() < tx.vsize() as u64 * min_fee_rate {
+            return Err("min relay fee not met".to_string());
+        }
+        if !conflicts.is_empty() {
+            self.check_replacement(&tx, fee, &conflicts, &evicted)?;
+        }
+
+        // Replaced transactions are forgotten, as by a node's mempool
+        self.mempool
+            .retain(|(pending, _)| !evicted.contains(&pending.txid()));
+        self.txs.retain(|txid, _| !evicted.contains(txid));
+
+        self.txs.insert(txid, (tx.clone(), None));
+        self.mempool.push((tx, fee));
+        Ok((txid, fee))
+    }
+
+    /// BIP 125 rules for `tx`, paying `fee`, to replace the mempool
+    /// transactions it `conflicts` with and evict them with their
+    /// descendants.
+    fn check_replacement(
+        &self,
+        tx: &Transaction,
+        fee: Amount,
+        conflicts: &[Txid],
+        evicted: &HashSet<Txid>,
+    ) -> Result<(), String> {
+        let entry = |txid: &Txid| {
+            self.mempool
+                .iter()
+                .find(|(pending, _)| pending.txid() == *txid)
+                .expect("conflicts are in the mempool")
+        };
+
+        for txid in conflicts {
+            let (original, original_fee) = entry(txid);
+            if !original.input.iter().any(|input| input.sequence.is_rbf()) {
+                return Err("txn-mempool-conflict".to_string());
+            }
+            // Compare fee rates without rounding: fee / vsize > original_fee / original_vsize
+            if fee.to_sat() * original.vsize() as u64 <= original_fee.to_sat() * tx.vsize() as u64 {
+                return Err(format!(
+                    "insufficient fee, rejecting replacement {}; new feerate is not above the feerate of {}",
+                    tx.txid(),
+                    txid
+                ));
+            }
+        }
+        if evicted.len() > MAX_REPLACEMENT_CANDIDATES {
+            return Err(format!(
+                "too many potential replacements, rejecting replacement {}",
+                tx.txid()
+            ));
+        }
+
+        let spent_by_conflicts = |outpoint: &OutPoint| {
+            conflicts.iter().any(|txid| {
+                entry(txid)
+                    .0
+                    .input
+                    .iter()
+                    .any(|input| input.previous_output == *outpoint)
+            })
+        };
+        let coins = self.coins_without(evicted);
+        for input in &tx.input {
+            let unconfirmed = coins
+                .get(&input.previous_output)
+                .is_some_and(|coin| coin.height.is_none());
+            if unconfirmed && !spent_by_conflicts(&input.previous_output) {
+                return Err(format!(
+                    "replacement-adds-unconfirmed, replacement {} adds unconfirmed input",
+                    tx.txid()
+                ));
+            }
+        }
+
+        let evicted_fees = self
+            .mempool
+            .iter()
+            .filter(|(pending, _)| evicted.contains(&pending.txid()))
+            .fold(Amount::ZERO, |total, (_, fee)| total + *fee);
+        let relay_fee = Amount::from_sat(tx.vsize() as u64 * INCREMENTAL_RELAY_FEE_RATE);
+        if fee < evicted_fees + relay_fee {
+            return Err(format!(
+                "insufficient fee, rejecting replacement {}, not enough additional fees to relay",
+                tx.txid()
+            ));
+        }
+        Ok(())
+    }
+
+    fn check_transaction(
+        &self,
+        tx: &Transaction,
+        secp: &Secp256k1<All>,
+        evicted: &HashSet<Txid>,
+    ) -> Result<Amount, String> {
+        if tx.input.is_empty() {
+            return Err("bad-txns-vin-empty".to_string());
+        }
+        if tx.output.is_empty() {
+            return Err("bad-txns-vout-empty".to_string());
+        }
+        if tx.is_coinbase() {
+            return Err("coinbase".to_string());
+        }
+
+        let mut seen = HashSet::new();
+        if !tx
+            .input
+            .iter()
+            .all(|input| seen.insert(input.previous_output))
+        {
+            return Err("bad-txns-inputs-duplicate".to_string());
+        }
+
+        let total_out = tx
+            .output
+            .iter()
+            .try_fold(Amount::ZERO, |total, output| {
+                total.checked_add(output.value)
+            })
+            .filter(|total| *total <= Amount::MAX_MONEY)
+            .ok_or("bad-txns-txouttotal-toolarge")?;
+
+        let next_height = self.tip_height() + 1;
+        let tip_mtp = self.median_time_past(self.tip_height());
+        if !self.is_final(tx, next_height, tip_mtp) {
+            return Err("non-final".to_string());
+        }
+
+        let coins = self.coins_without(evicted);
+        let mut prevouts = Vec::with_capacity(tx.input.len());
+        for input in &tx.input {
+            let coin = match coins.get(&input.previous_output) {
+                Some(coin) => coin,
+                None if self.spent_in_mempool(&input.previous_output) => {
+                    return Err("txn-mempool-conflict".to_string())
+                }
+                None => return Err("bad-txns-inputs-missingorspent".to_string()),
+            };
+
+            let coin_height = coin.height.unwrap_or(next_height);
+            if coin.coinbase && next_height - coin_height < COINBASE_MATURITY {
+                return Err("bad-txns-premature-spend-of-coinbase".to_string());
+            }
+            if !self.sequence_lock_satisfied(tx.version, input.sequence, coin_height) {
+                return Err("non-BIP68-final".to_string());
+            }
+
+            prevouts.push(coin.output.clone());
+        }
+
+        let total_in = prevouts
+            .iter()
+            .map(|output| output.value)
+            .fold(Amount::ZERO, |total, value| total + value);
+        let fee = total_in
+            .checked_sub(total_out)
+            .ok_or("bad-txns-in-belowout")?;
+
+        tx.verify(|outpoint| coins.get(outpoint).map(|coin| coin.output.clone()))
+            .map_err(|e| {
+                format!(
+                    "mandatory-script-verify-flag-failed (Script evaluation failed: {})",
+                    e
+                )
+            })?;
+        for index in 0..tx.input.len() {
+            verify_taproot_input(secp, tx, index, &prevouts)?;
+        }
+
+        Ok(fee)
+    }
+
+    /// Whether `tx` may be included in a block at `height` (BIP 113 locktime
+    /// semantics, comparing time locks with the median time past).
+    fn is_final(&self, tx: &Transaction, height: u32, median_time_past: u32) -> bool {
+        if tx.input.iter().all(|input| input.sequence == Sequence::MAX) {
+            return true;
+        }
+        match tx.lock_time {
+            LockTime::Blocks(lock) => lock.to_consensus_u32() < height,
+            LockTime::Seconds(lock) => lock.to_consensus_u32() < median_time_past,
+        }
+    }
+
+    /// BIP 68 relative lock check for an input spending a coin confirmed at
+    /// `coin_height`, against inclusion in the next block.
+    fn sequence_lock_satisfied(
+        &self,
+        version: Version,
+        sequence: Sequence,
+        coin_height: u32,
+    ) -> bool {
+        if version < Version::TWO {
+            return true;
+        }
+        let next_height = self.tip_height() + 1;
+        match sequence.to_relative_lock_time() {
+            None => true,
+            Some(relative::LockTime::Blocks(blocks)) => {
+                next_height >= coin_height + u32::from(blocks.value())
+            }
+            Some(relative::LockTime::Time(time)) => {
+                let coin_time = self.median_time_past(coin_height.saturating_sub(1));
+                self.median_time_past(self.tip_height()) >= coin_time + time.value() as u32 * 512
+            }
+        }
+    }
+
+    fn median_time_past(&self, height: u32) -> u32 {
+        let start = height.saturating_sub(10) as usize;
+        let mut times: Vec<u32> = self.blocks[start..=height as usize]
+            .iter()
+            .map(|block| block.header.time)
+            .collect();
+        times.sort_unstable();
+        times[times.len() / 2]
+    }
+
+    /// Mine a block paying the subsidy and all mempool fees to `script_pubkey`.
+    pub fn mine_block(&mut self, script_pubkey: ScriptBuf) -> BlockHash {
+        let height = self.tip_height() + 1;
+        let mempool = std::mem::take(&mut self.mempool);
+        let fees = mempool
+            .iter()
+            .fold(Amount::ZERO, |total, (_, fee)| total + *fee);
+
+        let halvings = height / SUBSIDY_HALVING_INTERVAL;
+        let subsidy = if halvings >= 64 {
+            Amount::ZERO
+        } else {
+            Amount::from_sat(Amount::from_int_btc(50).to_sat() >> halvings)
+        };
+
+        let witness_reserved_value = [0u8; 32];
+        let coinbase = Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::null(),
+                script_sig: Builder::new()
+                    .push_int(height as i64)
+                    .push_opcode(OP_0)
+                    .into_script(),
+                sequence: Sequence::MAX,
+                witness: Witness::from_slice(&[witness_reserved_value]),
+            }],
+            output: vec![TxOut {
+                value: subsidy + fees,
+                script_pubkey,
+            }],
+        };
+
+        let prev = &self.blocks[self.blocks.len() - 1].header;
+        let mut block = Block {
+            header: Header {
+                version: BlockVersion::from_consensus(0x2000_0000),
+                prev_blockhash: prev.block_hash(),
+                merkle_root: Hash::all_zeros(),
+                time: prev.time + BLOCK_SPACING,
+                bits: prev.bits,
+                nonce: 0,
+            },
+            txdata: std::iter::once(coinbase)
+                .chain(mempool.into_iter().map(|(tx, _)| tx))
+                .collect(),
+        };
+
+        let witness_root = block.witness_root().expect("block has a coinbase");
+        let commitment = Block::compute_witness_commitment(&witness_root, &witness_reserved_value);
+        let mut commitment_push = PushBytesBuf::from(WITNESS_COMMITMENT_HEADER);
+        commitment_push
+            .extend_from_slice(commitment.as_byte_array())
+            .expect("commitment fits in a push");
+        block.txdata[0].output.push(TxOut {
+            value: Amount::ZERO,
+            script_pubkey: ScriptBuf::new_op_return(&commitment_push),
+        });
+
+        block.header.merkle_root = block.compute_merkle_root().expect("block has a coinbase");
+        while block.header.validate_pow(block.header.target()).is_err() {
+            block.header.nonce += 1;
+        }
+
+        for (index, tx) in block.txdata.iter().enumerate() {
+            let coinbase = index == 0;
+            if !coinbase {
+                for input in &tx.input {
+                    self.utxos.remove(&input.previous_output);
+                }
+            }
+            self.utxos
+                .extend(unspent_outputs(tx, Some(height), coinbase));
+            self.txs.insert(tx.txid(), (tx.clone(), Some(height)));
+        }
+
+        let hash = block.block_hash();
+        self.blocks.push(block);
+        hash
+    }
+}
+
+fn unspent_outputs(
+    tx: &Transaction,
+    height: Option<u32>,
+    coinbase: bool,
+) -> impl Iterator<Item = (OutPoint, Coin)> + '_ {
+    let txid = tx.txid();
+    tx.output
+        .iter()
+        .enumerate()
+        .filter(|(_, output)| !output.script_pubkey.is_op_return())
+        .map(move |(vout, output)| {
+            (
+                OutPoint::new(txid, vout as u32),
+                Coin {
+                    output: output.clone(),
+                    height,
+                    coinbase,
+                },
+            )
+        })
+}
+
+/// libbitcoinconsensus predates taproot and treats witness v1 programs as
+/// anyone-can-spend, so key path signatures are checked here. Script path
+/// spends are run through miniscript's interpreter, which checks the leaf's
+/// commitment and executes it; leaves that are not miniscript are rejected.
+fn verify_taproot_input(
+    secp: &Secp256k1<All>,
+    tx: &Transaction,
+    index: usize,
+    prevouts: &[TxOut],
+) -> Result<(), String> {
+    let script_pubkey = &prevouts[index].script_pubkey;
+    if !script_pubkey.is_p2tr() {
+        return Ok(());
+    }
+    let failed = |reason: &str| format!("mandatory-script-verify-flag-failed ({})", reason);
+
+    let output_key = XOnlyPublicKey::from_slice(&script_pubkey.as_bytes()[2..])
+        .map_err(|_| failed("Witness program has incorrect length"))?;
+    let stack: Vec<&[u8]> = tx.input[index].witness.iter().collect();
+    if stack.len() >= 2 && stack[stack.len() - 1].first() == Some(&TAPROOT_ANNEX_PREFIX) {
+        return Err("bad-witness-nonstandard".to_string());
+    }
+
+    match stack.len() {
+        0 => Err(failed("Witness program was passed an empty witness")),
+        1 => {
+            let signature = taproot::Signature::from_slice(stack[0])
+                .map_err(|_| failed("Invalid Schnorr signature size"))?;
+            let sighash = SighashCache::new(tx)
+                .taproot_key_spend_signature_hash(
+                    index,
+                    &Prevouts::All(prevouts),
+                    signature.hash_ty,
+                )
+                .map_err(|_| failed("Invalid Schnorr signature hash type"))?;
+            secp.verify_schnorr(
+                &signature.sig,
+                &Message::from_digest(sighash.to_byte_array()),
+                &output_key,
+            )
+            .map_err(|_| failed("Invalid Schnorr signature"))
+        }
+        _ => {
+            let input = &tx.input[index];
+            let interpreter = Interpreter::from_txdata(
+                script_pubkey,
+                &input.script_sig,
+                &input.witness,
+                input.sequence,
+                tx.lock_time,
+            )
+            .map_err(|e| format!("unsupported taproot script path spend: {}", e))?;
+            let prevouts = Prevouts::All(prevouts);
+            for constraint in interpreter.iter(secp, tx, index, &prevouts) {
+                constraint.map_err(|e| failed(&e.to_string()))?;
+            }
+            Ok(())
+        }
+    }
+}
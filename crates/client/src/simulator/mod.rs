@@ -0,0 +1,759 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    str::FromStr,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
+
+use bitcoin::{
+    absolute::LockTime,
+    address::NetworkUnchecked,
+    psbt::Psbt,
+    secp256k1::{All, Secp256k1},
+    transaction::Version,
+    Address, Amount, BlockHash, FeeRate, Network, OutPoint, Script, ScriptBuf, Sequence,
+    Transaction, TxIn, TxOut, Txid, Weight, Witness,
+};
+use bitcoincore_rpc::{
+    json::{
+        AddressType, CreateRawTransactionInput, GetAddressInfoResult, GetBalancesResult,
+        GetBalancesResultEntry, GetDescriptorInfoResult, GetTxOutResult, GetWalletInfoResult,
+        ListUnspentQueryOptions, ListUnspentResultEntry, ScanningDetails, Utxo,
+        WalletCreateFundedPsbtResult, WalletProcessPsbtResult,
+    },
+    jsonrpc::serde_json::Value,
+};
+use miniscript::psbt::PsbtExt;
+
+use crate::{
+    descriptor_address, descriptor_info, descriptor_scripts, estimate_fee, is_finalized,
+    script_pubkey_result, ChainBackend, ClientError, DescriptorWallet, WalletBackend,
+};
+
+mod chain;
+use chain::*;
+
+/// Fee rate used to fund wallet transactions when the caller names none,
+/// matching the `send` calls made against Bitcoin Core.
+const DEFAULT_FEE_RATE: FeeRate = FeeRate::from_sat_per_vb_unchecked(1);
+
+struct SimState {
+    secp: Secp256k1<All>,
+    seed: u64,
+    chain: ChainState,
+    wallets: BTreeMap<String, DescriptorWallet>,
+}
+
+/// An in-memory regtest node for running wallet flows without bitcoind.
+///
+/// Blocks are mined on demand, transactions are checked against the UTXO set,
+/// coinbase maturity, BIP 68 sequence locks and their scripts before entering
+/// the mempool, and wallets are descriptor wallets with keys derived from the
+/// simulator's seed, so the same seed always produces the same chain.
+///
+/// Handles are cheap to clone and share state; [`WalletBackend::open_wallet`]
+/// returns a handle scoped to one wallet. The simulator is library-only, for
+/// tests: its chain lives in one process, so the CLI has no backend for it.
+#[derive(Clone)]
+pub struct Simulator {
+    state: Arc<Mutex<SimState>>,
+    wallet: Option<String>,
+}
+
+impl Default for Simulator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Simulator {
+    pub fn new() -> Self {
+        Self::with_seed(0)
+    }
+
+    pub fn with_seed(seed: u64) -> Self {
+        Simulator {
+            state: Arc::new(Mutex::new(SimState {
+                secp: Secp256k1::new(),
+                seed,
+                chain: ChainState::new(),
+                wallets: BTreeMap::new(),
+            })),
+            wallet: None,
+        }
+    }
+
+    /// Transactions waiting in the mempool, in the order they were accepted.
+    pub fn mempool(&self) -> Vec<Transaction> {
+        self.state().chain.mempool().cloned().collect()
+    }
+
+    fn state(&self) -> MutexGuard<'_, SimState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn scoped(&self, wallet_name: &str) -> Self {
+        Simulator {
+            state: self.state.clone(),
+            wallet: Some(wallet_name.to_string()),
+        }
+    }
+
+    /// Run `f` against this handle's wallet. Like Bitcoin Core, a handle not
+    /// scoped to a wallet uses the only wallet if exactly one exists.
+    fn with_wallet<T>(
+        &self,
+        f: impl FnOnce(&mut SimState, &str) -> Result<T, ClientError>,
+    ) -> Result<T, ClientError> {
+        let mut state = self.state();
+        let name = match &self.wallet {
+            Some(name) if state.wallets.contains_key(name) => name.clone(),
+            Some(name) => return Err(ClientError::WalletNotFound(name.clone())),
+            None if state.wallets.len() == 1 => state.wallets.keys().next().cloned().unwrap(),
+            None => {
+                return Err(ClientError::NotFound(
+                    "wallet not specified and more than one wallet exists".to_string(),
+                ))
+            }
+        };
+        f(&mut state, &name)
+    }
+}
+
+impl SimState {
+    fn wallet(&self, name: &str) -> &DescriptorWallet {
+        &self.wallets[name]
+    }
+
+    /// The output an outpoint refers to, whether or not it is still unspent.
+    fn prevout(&self, outpoint: &OutPoint) -> Option<TxOut> {
+        self.chain
+            .transaction(&outpoint.txid)
+            .and_then(|(tx, _)| tx.output.get(outpoint.vout as usize).cloned())
+    }
+
+    fn depth(&self, coin: &Coin) -> u32 {
+        coin.height
+            .map_or(0, |height| self.chain.tip_height() - height + 1)
+    }
+
+    fn is_mature(&self, coin: &Coin) -> bool {
+        // Bitcoin Core's wallet waits one block longer than consensus requires
+        !coin.coinbase || self.depth(coin) > COINBASE_MATURITY
+    }
+
+    /// Unconfirmed outputs are trusted when the wallet funded every input of
+    /// the transaction that created them, i.e. they are its own change.
+    fn is_trusted(&self, wallet: &DescriptorWallet, outpoint: &OutPoint, coin: &Coin) -> bool {
+        if coin.height.is_some() {
+            return true;
+        }
+        self.chain
+            .transaction(&outpoint.txid)
+            .is_some_and(|(tx, _)| {
+                tx.input.iter().all(|input| {
+                    self.prevout(&input.previous_output)
+                        .is_some_and(|prevout| wallet.is_mine(&prevout.script_pubkey))
+                })
+            })
+    }
+
+    fn wallet_coins(&self, wallet: &DescriptorWallet) -> Vec<(OutPoint, Coin)> {
+        self.chain
+            .coins(true)
+            .into_iter()
+            .filter(|(_, coin)| wallet.is_mine(&coin.output.script_pubkey))
+            .collect()
+    }
+
+    fn balances(&self, wallet: &DescriptorWallet) -> GetBalancesResultEntry {
+        let mut balances = GetBalancesResultEntry {
+            trusted: Amount::ZERO,
+            untrusted_pending: Amount::ZERO,
+            immature: Amount::ZERO,
+        };
+        for (outpoint, coin) in self.wallet_coins(wallet) {
+            let value = coin.output.value;
+            if !self.is_mature(&coin) {
+                balances.immature += value;
+            } else if self.is_trusted(wallet, &outpoint, &coin) {
+                balances.trusted += value;
+            } else {
+                balances.untrusted_pending += value;
+            }
+        }
+        balances
+    }
+
+    /// Build an unsigned transaction paying `outputs` at `fee_rate` from
+    /// `inputs` plus as many of the wallet's coins as needed, largest first,
+    /// with change back to the wallet unless it would be dust.
+    fn fund(
+        &mut self,
+        wallet_name: &str,
+        inputs: &[OutPoint],
+        outputs: Vec<TxOut>,
+        fee_rate: FeeRate,
+    ) -> Result<(Psbt, Amount, Option<usize>), ClientError> {
+        let coins = self.chain.coins(true);
+        let wallet = self.wallet(wallet_name);
+
+        let mut selected = Vec::new();
+        for outpoint in inputs {
+            let coin = coins.get(outpoint).ok_or_else(|| {
+                ClientError::Rejected(format!("Input not found or already spent: {}", outpoint))
+            })?;
+            let weight = wallet
+                .descriptor_for(&coin.output.script_pubkey)
+                .and_then(|descriptor| descriptor.max_weight_to_satisfy().ok())
+                .map(|weight| Weight::from_wu(weight as u64))
+                .ok_or_else(|| {
+                    ClientError::Rejected(format!("Not solvable pre-selected input {}", outpoint))
+                })?;
+            selected.push((*outpoint, coin.output.clone(), weight));
+        }
+
+        let mut candidates: Vec<(OutPoint, TxOut, Weight)> = self
+            .wallet_coins(wallet)
+            .into_iter()
+            .filter(|(outpoint, coin)| {
+                self.is_mature(coin)
+                    && self.is_trusted(wallet, outpoint, coin)
+                    && !wallet.is_locked(outpoint)
+                    && !inputs.contains(outpoint)
+            })
+            .filter_map(|(outpoint, coin)| {
+                let weight = wallet
+                    .descriptor_for(&coin.output.script_pubkey)?
+                    .max_weight_to_satisfy()
+                    .ok()?;
+                Some((outpoint, coin.output, Weight::from_wu(weight as u64)))
+            })
+            .collect();
+        candidates.sort_by_key(|(_, output, _)| std::cmp::Reverse(output.value));
+        let mut candidates = candidates.into_iter();
+
+        let change_script = self
+            .wallets
+            .get_mut(wallet_name)
+            .expect("wallet exists")
+            .derive_change()?
+            .script_pubkey();
+        let change_output = TxOut {
+            value: Amount::ZERO,
+            script_pubkey: change_script,
+        };
+
+        let total_out = outputs
+            .iter()
+            .fold(Amount::ZERO, |total, output| total + output.value);
+        let (fee, change) = loop {
+            let total_in = selected
+                .iter()
+                .fold(Amount::ZERO, |total, (_, output, _)| total + output.value);
+            let satisfaction: Vec<Weight> = selected.iter().map(|(_, _, weight)| *weight).collect();
+            let fee_for = |outputs: Vec<TxOut>| {
+                let template = Transaction {
+                    version: Version::TWO,
+                    lock_time: LockTime::ZERO,
+                    input: selected
+                        .iter()
+                        .map(|(outpoint, _, _)| TxIn {
+                            previous_output: *outpoint,
+                            ..Default::default()
+                        })
+                        .collect(),
+                    output: outputs,
+                };
+                estimate_fee(&template, &satisfaction, fee_rate)
+            };
+
+            let fee = fee_for(outputs.clone());
+            if !selected.is_empty() && total_in >= total_out + fee {
+                let fee_with_change =
+                    fee_for([outputs.clone(), vec![change_output.clone()]].concat());
+                let change = total_in
+                    .checked_sub(total_out + fee_with_change)
+                    .filter(|change| *change >= change_output.script_pubkey.dust_value());
+                match change {
+                    Some(change) => break (fee_with_change, Some(change)),
+                    None => break (total_in - total_out, None),
+                }
+            }
+
+            match candidates.next() {
+                Some(candidate) => selected.push(candidate),
+                None => return Err(ClientError::Rejected("Insufficient funds".to_string())),
+            }
+        };
+
+        let mut outputs = outputs;
+        let change_position = change.map(|value| {
+            outputs.push(TxOut {
+                value,
+                script_pubkey: change_output.script_pubkey.clone(),
+            });
+            outputs.len() - 1
+        });
+
+        // Anti fee sniping: only valid for inclusion after the current tip
+        let lock_time = LockTime::from_height(self.chain.tip_height())
+            .expect("regtest heights are valid lock times");
+        let tx = Transaction {
+            version: Version::TWO,
+            lock_time,
+            input: selected
+                .iter()
+                .map(|(outpoint, _, _)| TxIn {
+                    previous_output: *outpoint,
+                    script_sig: ScriptBuf::new(),
+                    sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                    witness: Witness::new(),
+                })
+                .collect(),
+            output: outputs,
+        };
+        let prevouts: Vec<TxOut> = selected.into_iter().map(|(_, output, _)| output).collect();
+
+        let mut psbt =
+            Psbt::from_unsigned_tx(tx).map_err(|e| ClientError::InvalidResponse(e.to_string()))?;
+        self.wallet(wallet_name)
+            .update_psbt(&mut psbt, &prevouts, |txid| {
+                self.chain.transaction(txid).map(|(tx, _)| tx.clone())
+            });
+
+        Ok((psbt, fee, change_position))
+    }
+
+    /// Add what the wallet knows to `psbt`, sign it and finalize complete inputs.
+    fn process_psbt(&self, wallet_name: &str, psbt: &mut Psbt) -> Result<(), ClientError> {
+        // Like Bitcoin Core, fall back to the UTXOs the PSBT carries for
+        // inputs the chain does not know, such as outputs of an unrelayed parent
+        let prevouts = psbt
+            .unsigned_tx
+            .input
+            .iter()
+            .zip(&psbt.inputs)
+            .map(|(input, psbt_input)| {
+                let outpoint = input.previous_output;
+                self.prevout(&outpoint)
+                    .or_else(|| psbt_input.witness_utxo.clone())
+                    .or_else(|| {
+                        psbt_input
+                            .non_witness_utxo
+                            .as_ref()
+                            .and_then(|tx| tx.output.get(outpoint.vout as usize).cloned())
+                    })
+                    .ok_or_else(|| ClientError::NotFound(format!("input {}", outpoint)))
+            })
+            .collect::<Result<Vec<TxOut>, ClientError>>()?;
+        let wallet = self.wallet(wallet_name);
+        wallet.update_psbt(psbt, &prevouts, |txid| {
+            self.chain.transaction(txid).map(|(tx, _)| tx.clone())
+        });
+        wallet.sign_psbt(&self.secp, psbt, &prevouts);
+        Ok(())
+    }
+}
+
+impl ChainBackend for Simulator {
+    fn network(&self) -> Network {
+        Network::Regtest
+    }
+
+    fn get_block_count(&self) -> Result<u64, ClientError> {
+        Ok(self.state().chain.tip_height() as u64)
+    }
+
+    fn get_raw_transaction(&self, txid: &Txid) -> Result<Transaction, ClientError> {
+        self.state()
+            .chain
+            .transaction(txid)
+            .map(|(tx, _)| tx.clone())
+            .ok_or_else(|| ClientError::NotFound(format!("transaction {}", txid)))
+    }
+
+    fn get_tx_out(
+        &self,
+        outpoint: &OutPoint,
+        include_mempool: bool,
+    ) -> Result<Option<GetTxOutResult>, ClientError> {
+        let state = self.state();
+        let coins = state.chain.coins(include_mempool);
+        Ok(coins.get(outpoint).map(|coin| GetTxOutResult {
+            bestblock: state.chain.tip_hash(),
+            confirmations: state.depth(coin),
+            value: coin.output.value,
+            script_pub_key: script_pubkey_result(&coin.output.script_pubkey, Network::Regtest),
+            coinbase: coin.coinbase,
+        }))
+    }
+
+    fn in_mempool(&self, txid: &Txid) -> Result<bool, ClientError> {
+        Ok(self.state().chain.mempool().any(|tx| tx.txid() == *txid))
+    }
+
+    fn scan_utxos(&self, script_pubkey: &Script) -> Result<Vec<Utxo>, ClientError> {
+        let state = self.state();
+        Ok(state
+            .chain
+            .coins(false)
+            .into_iter()
+            .filter(|(_, coin)| coin.output.script_pubkey.as_script() == script_pubkey)
+            .map(|(outpoint, coin)| Utxo {
+                txid: outpoint.txid,
+                vout: outpoint.vout,
+                script_pub_key: coin.output.script_pubkey,
+                descriptor: format!("raw({})", script_pubkey.to_hex_string()),
+                amount: coin.output.value,
+                height: coin.height.unwrap_or_default() as u64,
+            })
+            .collect())
+    }
+
+    fn send_raw_transaction(&self, tx: &Transaction) -> Result<Txid, ClientError> {
+        let mut state = self.state();
+        let SimState { secp, chain, .. } = &mut *state;
+        chain
+            .accept(tx.clone(), secp)
+            .map_err(ClientError::Rejected)
+    }
+
+    fn submit_package(&self, txs: &[Transaction]) -> Result<Vec<Txid>, ClientError> {
+        let mut state = self.state();
+        let SimState { secp, chain, .. } = &mut *state;
+        chain
+            .accept_package(txs.to_vec(), secp)
+            .map_err(ClientError::Rejected)
+    }
+
+    fn estimate_fee_rate(&self, _conf_target: u16) -> Result<Option<FeeRate>, ClientError> {
+        // Blocks are mined on demand from whatever is in the mempool
+        Ok(None)
+    }
+
+    fn generate_to_address(
+        &self,
+        blocks: u64,
+        address: &Address,
+    ) -> Result<Vec<BlockHash>, ClientError> {
+        let mut state = self.state();
+        Ok((0..blocks)
+            .map(|_| state.chain.mine_block(address.script_pubkey()))
+            .collect())
+    }
+}
+
+impl WalletBackend for Simulator {
+    fn open_wallet(&self, name: &str) -> Result<Self, ClientError> {
+        let mut state = self.state();
+        if !state.wallets.contains_key(name) {
+            let wallet = DescriptorWallet::with_keys(&state.secp, &state.seed.to_le_bytes(), name);
+            state.wallets.insert(name.to_string(), wallet);
+        }
+        Ok(self.scoped(name))
+    }
+
+    fn create_watch_only_wallet(&self, name: &str) -> Result<Self, ClientError> {
+        let mut state = self.state();
+        if state.wallets.contains_key(name) {
+            return Err(ClientError::Rejected(format!(
+                "Wallet {} already exists",
+                name
+            )));
+        }
+        state
+            .wallets
+            .insert(name.to_string(), DescriptorWallet::watch_only());
+        Ok(self.scoped(name))
+    }
+
+    fn get_new_address(
+        &self,
+        address_type: AddressType,
+    ) -> Result<Address<NetworkUnchecked>, ClientError> {
+        self.with_wallet(|state, name| {
+            let wallet = state.wallets.get_mut(name).expect("wallet exists");
+            let descriptor = wallet.derive_next(address_type, false)?;
+            Ok(descriptor_address(&descriptor, Network::Regtest)?
+                .as_unchecked()
+                .clone())
+        })
+    }
+
+    fn get_balances(&self) -> Result<GetBalancesResult, ClientError> {
+        self.with_wallet(|state, name| {
+            Ok(GetBalancesResult {
+                mine: state.balances(state.wallet(name)),
+                watchonly: None,
+            })
+        })
+    }
+
+    fn get_wallet_info(&self) -> Result<GetWalletInfoResult, ClientError> {
+        self.with_wallet(|state, name| {
+            let wallet = state.wallet(name);
+            let balances = state.balances(wallet);
+            let keypool_size = |internal: bool| {
+                wallet
+                    .descriptors()
+                    .iter()
+                    .filter(|entry| entry.active && entry.internal == internal)
+                    .count()
+            };
+
+            Ok(GetWalletInfoResult {
+                wallet_name: name.to_string(),
+                wallet_version: 169900,
+                balance: balances.trusted,
+                unconfirmed_balance: balances.untrusted_pending,
+                immature_balance: balances.immature,
+                tx_count: state
+                    .chain
+                    .transactions()
+                    .filter(|tx| {
+                        tx.output
+                            .iter()
+                            .any(|output| wallet.is_mine(&output.script_pubkey))
+                            || tx.input.iter().any(|input| {
+                                state
+                                    .prevout(&input.previous_output)
+                                    .is_some_and(|prevout| wallet.is_mine(&prevout.script_pubkey))
+                            })
+                    })
+                    .count(),
+                keypool_oldest: None,
+                keypool_size: keypool_size(false),
+                keypool_size_hd_internal: keypool_size(true),
+                unlocked_until: None,
+                pay_tx_fee: Amount::ZERO,
+                hd_seed_id: None,
+                private_keys_enabled: wallet.private_keys_enabled(),
+                avoid_reuse: Some(false),
+                scanning: Some(ScanningDetails::NotScanning(false)),
+            })
+        })
+    }
+
+    fn get_address_info(&self, address: &Address) -> Result<GetAddressInfoResult, ClientError> {
+        self.with_wallet(|state, name| Ok(state.wallet(name).address_info(address)))
+    }
+
+    fn list_unspent(
+        &self,
+        query_options: Option<ListUnspentQueryOptions>,
+    ) -> Result<Vec<ListUnspentResultEntry>, ClientError> {
+        let options = query_options.unwrap_or_default();
+        self.with_wallet(|state, name| {
+            let wallet = state.wallet(name);
+            let mut unspent = Vec::new();
+            let mut sum = Amount::ZERO;
+
+            for (outpoint, coin) in state.wallet_coins(wallet) {
+                if coin.height.is_none() || !state.is_mature(&coin) || wallet.is_locked(&outpoint) {
+                    continue;
+                }
+                let value = coin.output.value;
+                if options.minimum_amount.is_some_and(|min| value < min)
+                    || options.maximum_amount.is_some_and(|max| value > max)
+                {
+                    continue;
+                }
+
+                let Some(descriptor) = wallet.descriptor_for(&coin.output.script_pubkey) else {
+                    continue;
+                };
+                let (redeem_script, witness_script) = descriptor_scripts(&descriptor);
+
+                unspent.push(ListUnspentResultEntry {
+                    txid: outpoint.txid,
+                    vout: outpoint.vout,
+                    address: descriptor_address(&descriptor, Network::Regtest)
+                        .ok()
+                        .map(|address| address.as_unchecked().clone()),
+                    label: None,
+                    redeem_script,
+                    witness_script,
+                    script_pub_key: coin.output.script_pubkey.clone(),
+                    amount: value,
+                    confirmations: state.depth(&coin),
+                    spendable: wallet.private_keys_enabled(),
+                    solvable: true,
+                    descriptor: Some(descriptor.to_string()),
+                    safe: true,
+                });
+                sum += value;
+
+                if options
+                    .maximum_count
+                    .is_some_and(|count| unspent.len() >= count)
+                    || options.minimum_sum_amount.is_some_and(|min| sum >= min)
+                {
+                    break;
+                }
+            }
+
+            Ok(unspent)
+        })
+    }
+
+    fn send(&self, address: &Address, amount: Amount) -> Result<Txid, ClientError> {
+        self.with_wallet(|state, name| {
+            let output = TxOut {
+                value: amount,
+                script_pubkey: address.script_pubkey(),
+            };
+            let (mut psbt, _, _) = state.fund(name, &[], vec![output], DEFAULT_FEE_RATE)?;
+            state.process_psbt(name, &mut psbt)?;
+            let tx = psbt
+                .extract(&state.secp)
+                .map_err(|e| ClientError::Rejected(format!("Signing failed: {}", e)))?;
+
+            let SimState { secp, chain, .. } = state;
+            chain.accept(tx, secp).map_err(ClientError::Rejected)
+        })
+    }
+
+    fn sign_raw_transaction(&self, tx: &Transaction) -> Result<Transaction, ClientError> {
+        self.with_wallet(|state, name| {
+            let mut unsigned = tx.clone();
+            for input in &mut unsigned.input {
+                input.script_sig = ScriptBuf::new();
+                input.witness = Witness::new();
+            }
+            let mut psbt = Psbt::from_unsigned_tx(unsigned)
+                .map_err(|e| ClientError::InvalidResponse(e.to_string()))?;
+            state.process_psbt(name, &mut psbt)?;
+
+            // Keep the existing signatures of inputs this wallet could not sign
+            let mut signed = tx.clone();
+            for (input, psbt_input) in signed.input.iter_mut().zip(&psbt.inputs) {
+                if is_finalized(psbt_input) {
+                    input.script_sig = psbt_input.final_script_sig.clone().unwrap_or_default();
+                    input.witness = psbt_input.final_script_witness.clone().unwrap_or_default();
+                }
+            }
+            Ok(signed)
+        })
+    }
+
+    fn list_descriptors(&self) -> Result<Value, ClientError> {
+        self.with_wallet(|state, name| Ok(state.wallet(name).list_descriptors(name)))
+    }
+
+    fn get_descriptor_info(
+        &self,
+        descriptor: &str,
+    ) -> Result<GetDescriptorInfoResult, ClientError> {
+        descriptor_info(descriptor)
+    }
+
+    fn import_descriptors(&self, descriptors: &[Value]) -> Result<(), ClientError> {
+        self.with_wallet(|state, name| {
+            let wallet = state.wallets.get_mut(name).expect("wallet exists");
+            wallet.import_descriptors(descriptors)
+        })
+    }
+
+    fn wallet_create_funded_psbt(
+        &self,
+        inputs: &[CreateRawTransactionInput],
+        outputs: &HashMap<String, Amount>,
+        fee_rate: Option<FeeRate>,
+    ) -> Result<WalletCreateFundedPsbtResult, ClientError> {
+        let sequences: Vec<(OutPoint, Sequence)> = inputs
+            .iter()
+            .filter_map(|input| {
+                let sequence = Sequence::from_consensus(input.sequence?);
+                Some((OutPoint::new(input.txid, input.vout), sequence))
+            })
+            .collect();
+        let inputs: Vec<OutPoint> = inputs
+            .iter()
+            .map(|input| OutPoint::new(input.txid, input.vout))
+            .collect();
+
+        // Order outputs by address so the result does not depend on map order
+        let mut recipients: Vec<(&String, &Amount)> = outputs.iter().collect();
+        recipients.sort();
+        let outputs = recipients
+            .into_iter()
+            .map(|(address, amount)| {
+                let invalid = || ClientError::Rejected(format!("Invalid address: {}", address));
+                let address = Address::from_str(address)
+                    .map_err(|_| invalid())?
+                    .require_network(Network::Regtest)
+                    .map_err(|_| invalid())?;
+                Ok(TxOut {
+                    value: *amount,
+                    script_pubkey: address.script_pubkey(),
+                })
+            })
+            .collect::<Result<Vec<TxOut>, ClientError>>()?;
+
+        self.with_wallet(|state, name| {
+            let fee_rate = fee_rate.unwrap_or(DEFAULT_FEE_RATE);
+            let (mut psbt, fee, change_position) = state.fund(name, &inputs, outputs, fee_rate)?;
+            for input in &mut psbt.unsigned_tx.input {
+                if let Some((_, sequence)) = sequences
+                    .iter()
+                    .find(|(outpoint, _)| *outpoint == input.previous_output)
+                {
+                    input.sequence = *sequence;
+                }
+            }
+            Ok(WalletCreateFundedPsbtResult {
+                psbt: psbt.to_string(),
+                fee,
+                change_position: change_position.map_or(-1, |position| position as i32),
+            })
+        })
+    }
+
+    fn wallet_process_psbt(&self, psbt: &str) -> Result<WalletProcessPsbtResult, ClientError> {
+        let mut psbt = Psbt::from_str(psbt)
+            .map_err(|e| ClientError::Rejected(format!("TX decode failed {}", e)))?;
+        self.with_wallet(|state, name| {
+            state.process_psbt(name, &mut psbt)?;
+            let complete = psbt.inputs.iter().all(is_finalized);
+            Ok(WalletProcessPsbtResult {
+                psbt: psbt.to_string(),
+                complete,
+            })
+        })
+    }
+
+    fn lock_unspent(&self, outpoints: &[OutPoint]) -> Result<(), ClientError> {
+        self.with_wallet(|state, name| {
+            let unspent: Vec<OutPoint> = state
+                .wallet_coins(state.wallet(name))
+                .into_iter()
+                .map(|(outpoint, _)| outpoint)
+                .collect();
+            if let Some(outpoint) = outpoints.iter().find(|o| !unspent.contains(o)) {
+                return Err(ClientError::Rejected(format!(
+                    "Invalid parameter, unknown or spent output: {}",
+                    outpoint
+                )));
+            }
+            state
+                .wallets
+                .get_mut(name)
+                .expect("wallet exists")
+                .lock(outpoints)
+        })
+    }
+
+    fn unlock_unspent(&self, outpoints: &[OutPoint]) -> Result<(), ClientError> {
+        self.with_wallet(|state, name| {
+            state
+                .wallets
+                .get_mut(name)
+                .expect("wallet exists")
+                .unlock(outpoints)
+        })
+    }
+
+    fn list_lock_unspent(&self) -> Result<Vec<OutPoint>, ClientError> {
+        self.with_wallet(|state, name| Ok(state.wallet(name).locked()))
+    }
+}
@@ -0,0 +1,176 @@
+use std::{fmt, str::FromStr, sync::Arc};
+
+use bitcoin::secp256k1::Secp256k1;
+use miniscript::{
+    descriptor::{checksum::desc_checksum, DefiniteDescriptorKey, DescriptorPublicKey, TapTree},
+    Descriptor, Miniscript, Terminal,
+};
+
+use crate::ClientError;
+
+/// A public descriptor that may have BIP 390 `sortedmulti_a` leaves, which
+/// miniscript here cannot parse. They are read as `multi_a` leaves whose
+/// keys are sorted again for every child derived, as Bitcoin Core does.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct PublicDescriptor {
+    descriptor: Descriptor<DescriptorPublicKey>,
+    /// For every `multi_a` in the descriptor, in order, whether it was
+    /// written `sortedmulti_a`.
+    sorted: Vec<bool>,
+}
+
+impl PublicDescriptor {
+    /// The descriptor with `sortedmulti_a` leaves as `multi_a`, which
+    /// scripts the same way apart from the order of the keys.
+    pub fn descriptor(&self) -> &Descriptor<DescriptorPublicKey> {
+        &self.descriptor
+    }
+
+    /// The child at `index`, with the keys of `sortedmulti_a` leaves sorted
+    /// by their x-only encoding.
+    pub fn at_derivation_index(
+        &self,
+        index: u32,
+    ) -> Result<Descriptor<DefiniteDescriptorKey>, ClientError> {
+        let definite = self
+            .descriptor
+            .at_derivation_index(index)
+            .map_err(|e| ClientError::InvalidResponse(e.to_string()))?;
+        let Descriptor::Tr(tr) = &definite else {
+            return Ok(definite);
+        };
+        let Some(tree) = tr.tap_tree() else {
+            return Ok(definite);
+        };
+        if !self.sorted.contains(&true) {
+            return Ok(definite);
+        }
+        let mut sorted = self.sorted.iter().copied();
+        let tree = sort_leaves(tree, &mut sorted)?;
+        Descriptor::new_tr(tr.internal_key().clone(), Some(tree))
+            .map_err(|e| ClientError::InvalidResponse(e.to_string()))
+    }
+}
+
+impl From<Descriptor<DescriptorPublicKey>> for PublicDescriptor {
+    fn from(descriptor: Descriptor<DescriptorPublicKey>) -> Self {
+        let sorted = vec![false; multi_a_count(&descriptor)];
+        PublicDescriptor { descriptor, sorted }
+    }
+}
+
+impl FromStr for PublicDescriptor {
+    type Err = ClientError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let rejected = |reason: &str| ClientError::Rejected(format!("{}: {}", reason, s));
+        let body = match s.split_once('#') {
+            Some((body, checksum)) => {
+                if desc_checksum(body).ok().as_deref() != Some(checksum) {
+                    return Err(rejected("Invalid checksum"));
+                }
+                body
+            }
+            None => s,
+        };
+
+        // Every multi_a of either kind, in the order they are written
+        let mut sorted = Vec::new();
+        let mut rest = body;
+        while let Some(at) = rest.find("multi_a(") {
+            sorted.push(rest[..at].ends_with("sorted"));
+            rest = &rest[at + "multi_a(".len()..];
+        }
+        let descriptor = Descriptor::<DescriptorPublicKey>::from_str(
+            &body.replace("sortedmulti_a(", "multi_a("),
+        )
+        .map_err(|e| ClientError::Rejected(e.to_string()))?;
+
+        if sorted.contains(&true) {
+            let Descriptor::Tr(tr) = &descriptor else {
+                return Err(rejected("sortedmulti_a is only allowed in tr()"));
+            };
+            let mut flags = sorted.iter().copied();
+            for (_, leaf) in tr.iter_scripts() {
+                for (position, node) in leaf.iter().enumerate() {
+                    let Terminal::MultiA(..) = node.node else {
+                        continue;
+                    };
+                    if flags.next() == Some(true) && position != 0 {
+                        return Err(rejected("sortedmulti_a must be a whole leaf"));
+                    }
+                }
+            }
+        }
+        Ok(PublicDescriptor { descriptor, sorted })
+    }
+}
+
+impl fmt::Display for PublicDescriptor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let text = self.descriptor.to_string();
+        let body = text.split_once('#').map_or(text.as_str(), |(body, _)| body);
+        let mut written = String::new();
+        let mut pieces = body.split("multi_a(");
+        written.push_str(pieces.next().unwrap_or_default());
+        for (piece, sorted) in pieces.zip(&self.sorted) {
+            if *sorted {
+                written.push_str("sorted");
+            }
+            written.push_str("multi_a(");
+            written.push_str(piece);
+        }
+        let checksum = desc_checksum(&written).map_err(|_| fmt::Error)?;
+        write!(f, "{}#{}", written, checksum)
+    }
+}
+
+fn multi_a_count(descriptor: &Descriptor<DescriptorPublicKey>) -> usize {
+    let Descriptor::Tr(tr) = descriptor else {
+        return 0;
+    };
+    tr.iter_scripts()
+        .flat_map(|(_, leaf)| leaf.iter())
+        .filter(|node| matches!(node.node, Terminal::MultiA(..)))
+        .count()
+}
+
+/// `tree` with the keys of the leaves `sorted` flags sorted, taking one
+/// flag for every `multi_a` of every leaf.
+fn sort_leaves(
+    tree: &TapTree<DefiniteDescriptorKey>,
+    sorted: &mut impl Iterator<Item = bool>,
+) -> Result<TapTree<DefiniteDescriptorKey>, ClientError> {
+    let leaf = match tree {
+        TapTree::Tree { left, right, .. } => {
+            let left = sort_leaves(left, sorted)?;
+            return Ok(TapTree::combine(left, sort_leaves(right, sorted)?));
+        }
+        TapTree::Leaf(leaf) => leaf,
+    };
+    let multi_a = leaf
+        .iter()
+        .filter(|node| matches!(node.node, Terminal::MultiA(..)))
+        .count();
+    let flags: Vec<bool> = sorted.take(multi_a).collect();
+    let Terminal::MultiA(k, keys) = &leaf.node else {
+        return Ok(tree.clone());
+    };
+    if flags.first() != Some(&true) {
+        return Ok(tree.clone());
+    }
+
+    let secp = Secp256k1::verification_only();
+    let mut keyed = Vec::new();
+    for key in keys {
+        let pubkey = key
+            .derive_public_key(&secp)
+            .map_err(|e| ClientError::InvalidResponse(e.to_string()))?;
+        keyed.push((pubkey.inner.x_only_public_key().0.serialize(), key.clone()));
+    }
+    keyed.sort_by_key(|(xonly, _)| *xonly);
+    let keys = keyed.into_iter().map(|(_, key)| key).collect();
+    let leaf = Miniscript::from_ast(Terminal::MultiA(*k, keys))
+        .map_err(|e| ClientError::InvalidResponse(e.to_string()))?;
+    Ok(TapTree::Leaf(Arc::new(leaf)))
+}
@@ -0,0 +1,159 @@
+use bitcoin::{
+    absolute::LockTime,
+    hashes::Hash,
+    key::{Keypair, Secp256k1},
+    opcodes::all::OP_CHECKSIG,
+    script::Builder,
+    secp256k1::{Message, SecretKey},
+    sighash::{Prevouts, SighashCache, TapSighashType},
+    taproot::{LeafVersion, TapLeafHash, TaprootBuilder},
+    transaction::Version,
+    Address, Amount, OutPoint, Sequence, Transaction, TxIn, TxOut, Witness,
+};
+use bitcoincore_rpc::json::AddressType;
+
+use satoshi_suite_client::{ChainBackend, Simulator, WalletBackend};
+
+fn new_address(wallet: &Simulator) -> Address {
+    wallet
+        .get_new_address(AddressType::Bech32)
+        .unwrap()
+        .assume_checked()
+}
+
+#[test]
+fn payment_is_replaced_and_the_replacement_confirms() {
+    let sim = Simulator::new();
+    let alice = sim.open_wallet("alice").unwrap();
+    let bob = sim.open_wallet("bob").unwrap();
+
+    let mining = new_address(&alice);
+    sim.generate_to_address(101, &mining).unwrap();
+    assert_eq!(alice.list_unspent(None).unwrap().len(), 1);
+    assert!(bob.list_unspent(None).unwrap().is_empty());
+
+    let destination = new_address(&bob);
+    let amount = Amount::from_sat(1_000_000);
+    let txid = alice.send(&destination, amount).unwrap();
+    assert_eq!(sim.mempool().len(), 1);
+    // The spent coinbase is gone and the change is still unconfirmed
+    assert!(alice.list_unspent(None).unwrap().is_empty());
+
+    let original = sim.get_raw_transaction(&txid).unwrap();
+    let change = original
+        .output
+        .iter()
+        .position(|output| output.script_pubkey != destination.script_pubkey())
+        .expect("payment has change");
+    let replace_with_change = |change_value: Amount| {
+        let mut replacement = original.clone();
+        replacement.output[change].value = change_value;
+        for input in &mut replacement.input {
+            input.witness = Witness::new();
+        }
+        alice.sign_raw_transaction(&replacement).unwrap()
+    };
+
+    // BIP 125 wants the replacement to pay for its own relay on top
+    let change_value = original.output[change].value;
+    let underpaying = replace_with_change(change_value - Amount::from_sat(1));
+    assert!(sim.send_raw_transaction(&underpaying).is_err());
+
+    let replacement = replace_with_change(change_value - Amount::from_sat(1_000));
+    let replacement_txid = sim.send_raw_transaction(&replacement).unwrap();
+    assert_eq!(sim.mempool(), vec![replacement.clone()]);
+
+    sim.generate_to_address(1, &mining).unwrap();
+    assert!(sim.mempool().is_empty());
+    assert!(sim
+        .get_tx_out(&OutPoint::new(txid, change as u32), false)
+        .unwrap()
+        .is_none());
+    let confirmed = sim
+        .get_tx_out(&OutPoint::new(replacement_txid, change as u32), false)
+        .unwrap()
+        .expect("replacement change is unspent");
+    assert_eq!(confirmed.confirmations, 1);
+
+    let received = bob.list_unspent(None).unwrap();
+    assert_eq!(received.len(), 1);
+    assert_eq!(received[0].txid, replacement_txid);
+    assert_eq!(received[0].amount, amount);
+    assert!(alice
+        .list_unspent(None)
+        .unwrap()
+        .iter()
+        .any(|utxo| utxo.txid == replacement_txid
+            && utxo.amount == change_value - Amount::from_sat(1_000)));
+}
+
+#[test]
+fn taproot_script_path_spends_execute_the_leaf() {
+    let secp = Secp256k1::new();
+    let sim = Simulator::new();
+    let wallet = sim.open_wallet("w0").unwrap();
+    let mining = new_address(&wallet);
+    sim.generate_to_address(101, &mining).unwrap();
+
+    let internal_key = Keypair::from_secret_key(&secp, &SecretKey::from_slice(&[1; 32]).unwrap());
+    let leaf_key = Keypair::from_secret_key(&secp, &SecretKey::from_slice(&[2; 32]).unwrap());
+    let leaf = Builder::new()
+        .push_x_only_key(&leaf_key.x_only_public_key().0)
+        .push_opcode(OP_CHECKSIG)
+        .into_script();
+    let spend_info = TaprootBuilder::new()
+        .add_leaf(0, leaf.clone())
+        .unwrap()
+        .finalize(&secp, internal_key.x_only_public_key().0)
+        .unwrap();
+    let address = Address::p2tr_tweaked(spend_info.output_key(), wallet.network());
+
+    let funding_txid = wallet.send(&address, Amount::from_sat(100_000)).unwrap();
+    sim.generate_to_address(1, &mining).unwrap();
+    let funding = sim.get_raw_transaction(&funding_txid).unwrap();
+    let vout = funding
+        .output
+        .iter()
+        .position(|output| output.script_pubkey == address.script_pubkey())
+        .unwrap();
+    let prevout = funding.output[vout].clone();
+
+    let mut tx = Transaction {
+        version: Version::TWO,
+        lock_time: LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: OutPoint::new(funding_txid, vout as u32),
+            sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+            ..Default::default()
+        }],
+        output: vec![TxOut {
+            value: Amount::from_sat(99_000),
+            script_pubkey: mining.script_pubkey(),
+        }],
+    };
+    let control_block = spend_info
+        .control_block(&(leaf.clone(), LeafVersion::TapScript))
+        .unwrap();
+    let with_signature = |tx: &Transaction, signature: &[u8]| {
+        let mut tx = tx.clone();
+        tx.input[0].witness =
+            Witness::from_slice(&[signature, leaf.as_bytes(), &control_block.serialize()]);
+        tx
+    };
+
+    // Commits to the leaf, but the leaf's signature check fails
+    let forged = with_signature(&tx, &[1; 64]);
+    assert!(sim.send_raw_transaction(&forged).is_err());
+
+    let sighash = SighashCache::new(&tx)
+        .taproot_script_spend_signature_hash(
+            0,
+            &Prevouts::All(&[prevout]),
+            TapLeafHash::from_script(&leaf, LeafVersion::TapScript),
+            TapSighashType::Default,
+        )
+        .unwrap();
+    let signature = secp.sign_schnorr(&Message::from_digest(sighash.to_byte_array()), &leaf_key);
+    tx = with_signature(&tx, signature.as_ref());
+    assert_eq!(sim.send_raw_transaction(&tx).unwrap(), tx.txid());
+}
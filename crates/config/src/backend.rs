@@ -0,0 +1,121 @@
+use std::{fmt, str::FromStr};
+
+use url::Url;
+
+use crate::{Chain, ConfigError};
+
+/// Which kind of service chain queries and broadcasts go to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BackendKind {
+    BitcoinCore,
+    Esplora,
+    Electrum,
+}
+
+impl fmt::Display for BackendKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            BackendKind::BitcoinCore => "bitcoin-core",
+            BackendKind::Esplora => "esplora",
+            BackendKind::Electrum => "electrum",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for BackendKind {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bitcoin-core" | "core" => Ok(BackendKind::BitcoinCore),
+            "esplora" => Ok(BackendKind::Esplora),
+            "electrum" => Ok(BackendKind::Electrum),
+            _ => Err(ConfigError::UnknownBackend(s.to_string())),
+        }
+    }
+}
+
+/// The chain backend read-only commands and broadcasts use. Wallet commands
+/// need Bitcoin Core, except for watch-only descriptor queries, which an
+/// Electrum server can answer.
+#[derive(Clone, Debug)]
+pub enum BackendConfig {
+    BitcoinCore,
+    Esplora {
+        url: Url,
+    },
+    /// `tcp://host:port` or `ssl://host:port`.
+    Electrum {
+        url: Url,
+    },
+}
+
+impl BackendConfig {
+    pub fn kind(&self) -> BackendKind {
+        match self {
+            BackendConfig::BitcoinCore => BackendKind::BitcoinCore,
+            BackendConfig::Esplora { .. } => BackendKind::Esplora,
+            BackendConfig::Electrum { .. } => BackendKind::Electrum,
+        }
+    }
+
+    /// An Esplora backend at `esplora_url`, or at the chain's default
+    /// instance when no URL is given.
+    pub fn esplora(esplora_url: Option<&str>, chain: Chain) -> Result<Self, ConfigError> {
+        let esplora_url = esplora_url.unwrap_or(chain.default_esplora_url());
+        let invalid =
+            |reason: &str| ConfigError::InvalidEsploraUrl(esplora_url.to_string(), reason.into());
+
+        let mut url = Url::parse(esplora_url).map_err(|e| invalid(&e.to_string()))?;
+        if url.scheme() != "http" && url.scheme() != "https" {
+            return Err(invalid("scheme should be http or https"));
+        }
+        if url.host_str().is_none() {
+            return Err(invalid("missing host"));
+        }
+        if url.query().is_some() || url.fragment().is_some() {
+            return Err(invalid("query strings and fragments are not supported"));
+        }
+
+        // API paths are appended to the URL, so `/api` and `/api/` are the same base
+        if !url.path().ends_with('/') {
+            let path = format!("{}/", url.path());
+            url.set_path(&path);
+        }
+
+        Ok(BackendConfig::Esplora { url })
+    }
+
+    /// An Electrum server at `electrum_url`, or at the default port of a
+    /// local electrs when no URL is given. electrs only speaks plain TCP, so
+    /// only `tcp` URLs fall back to its port.
+    pub fn electrum(electrum_url: Option<&str>, chain: Chain) -> Result<Self, ConfigError> {
+        let default_url = format!("tcp://127.0.0.1:{}", chain.default_electrum_port());
+        let electrum_url = electrum_url.unwrap_or(&default_url);
+        let invalid =
+            |reason: &str| ConfigError::InvalidElectrumUrl(electrum_url.to_string(), reason.into());
+
+        let mut url = Url::parse(electrum_url).map_err(|e| invalid(&e.to_string()))?;
+        if !matches!(url.scheme(), "tcp" | "ssl" | "tls") {
+            return Err(invalid("scheme should be tcp or ssl"));
+        }
+        if url.host_str().is_none() {
+            return Err(invalid("missing host"));
+        }
+        if !matches!(url.path(), "" | "/") || url.query().is_some() || url.fragment().is_some() {
+            return Err(invalid(
+                "paths, query strings and fragments are not supported",
+            ));
+        }
+        if url.port().is_none() {
+            if url.scheme() != "tcp" {
+                return Err(invalid("missing port"));
+            }
+            url.set_port(Some(chain.default_electrum_port()))
+                .map_err(|_| invalid("cannot set port"))?;
+        }
+
+        Ok(BackendConfig::Electrum { url })
+    }
+}
@@ -0,0 +1,116 @@
+use std::{fmt, str::FromStr};
+
+use bitcoin::{blockdata::constants::genesis_block, BlockHash, Network};
+
+use crate::ConfigError;
+
+const TESTNET4_GENESIS_HASH: &str =
+    "00000000da84f2bafbbc53dee25a72ae507ff4914b867c565be350b0da8bf043";
+
+/// The chain a Bitcoin Core node runs on.
+///
+/// This is finer grained than [`Network`]: testnet3 and testnet4 share address
+/// and key encodings but listen on different RPC ports.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Chain {
+    Mainnet,
+    Testnet,
+    Testnet4,
+    Signet,
+    Regtest,
+}
+
+impl Chain {
+    pub fn network(self) -> Network {
+        match self {
+            Chain::Mainnet => Network::Bitcoin,
+            Chain::Testnet | Chain::Testnet4 => Network::Testnet,
+            Chain::Signet => Network::Signet,
+            Chain::Regtest => Network::Regtest,
+        }
+    }
+
+    pub fn default_rpc_port(self) -> u16 {
+        match self {
+            Chain::Mainnet => 8332,
+            Chain::Testnet => 18332,
+            Chain::Testnet4 => 48332,
+            Chain::Signet => 38332,
+            Chain::Regtest => 18443,
+        }
+    }
+
+    /// A public Esplora instance for the chain, or the default port of a
+    /// local electrs on regtest.
+    pub fn default_esplora_url(self) -> &'static str {
+        match self {
+            Chain::Mainnet => "https://blockstream.info/api/",
+            Chain::Testnet => "https://blockstream.info/testnet/api/",
+            Chain::Testnet4 => "https://mempool.space/testnet4/api/",
+            Chain::Signet => "https://mempool.space/signet/api/",
+            Chain::Regtest => "http://127.0.0.1:3002/",
+        }
+    }
+
+    /// The port electrs listens on for the Electrum protocol by default.
+    pub fn default_electrum_port(self) -> u16 {
+        match self {
+            Chain::Mainnet => 50001,
+            Chain::Testnet => 60001,
+            Chain::Testnet4 => 40001,
+            Chain::Signet => 60601,
+            Chain::Regtest => 60401,
+        }
+    }
+
+    /// Hash of the chain's genesis block. Custom signets share the default
+    /// signet's genesis block, so this cannot tell them apart.
+    pub fn genesis_hash(self) -> BlockHash {
+        match self {
+            // Not known to rust-bitcoin yet
+            Chain::Testnet4 => {
+                BlockHash::from_str(TESTNET4_GENESIS_HASH).expect("genesis hash is valid")
+            }
+            _ => genesis_block(self.network()).block_hash(),
+        }
+    }
+
+    /// The `chain` value Bitcoin Core reports in `getblockchaininfo`.
+    pub fn core_name(self) -> &'static str {
+        match self {
+            Chain::Mainnet => "main",
+            Chain::Testnet => "test",
+            Chain::Testnet4 => "testnet4",
+            Chain::Signet => "signet",
+            Chain::Regtest => "regtest",
+        }
+    }
+}
+
+impl fmt::Display for Chain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Chain::Mainnet => "mainnet",
+            Chain::Testnet => "testnet",
+            Chain::Testnet4 => "testnet4",
+            Chain::Signet => "signet",
+            Chain::Regtest => "regtest",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Chain {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "regtest" => Ok(Chain::Regtest),
+            "testnet" => Ok(Chain::Testnet),
+            "testnet4" => Ok(Chain::Testnet4),
+            "signet" => Ok(Chain::Signet),
+            "mainnet" => Ok(Chain::Mainnet),
+            _ => Err(ConfigError::UnknownNetwork(s.to_string())),
+        }
+    }
+}
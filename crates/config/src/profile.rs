@@ -0,0 +1,392 @@
+use std::{
+    collections::HashMap,
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+use bitcoin::ScriptBuf;
+
+use crate::{BackendConfig, BackendKind, Chain, Config, ConfigError};
+
+pub const CONFIG_FILE_NAME: &str = "satoshi-suite.toml";
+
+pub const CONFIG_PATH_ENV: &str = "SATOSHI_SUITE_CONFIG";
+pub const PROFILE_ENV: &str = "SATOSHI_SUITE_PROFILE";
+pub const NETWORK_ENV: &str = "SATOSHI_SUITE_NETWORK";
+pub const SIGNET_CHALLENGE_ENV: &str = "SATOSHI_SUITE_SIGNET_CHALLENGE";
+pub const RPC_URL_ENV: &str = "SATOSHI_SUITE_RPC_URL";
+pub const RPC_USERNAME_ENV: &str = "SATOSHI_SUITE_RPC_USERNAME";
+pub const RPC_PASSWORD_ENV: &str = "SATOSHI_SUITE_RPC_PASSWORD";
+pub const COOKIE_FILE_ENV: &str = "SATOSHI_SUITE_COOKIE_FILE";
+pub const BITCOIN_DATA_DIR_ENV: &str = "SATOSHI_SUITE_BITCOIN_DATA_DIR";
+pub const CREATE_WALLETS_ENV: &str = "SATOSHI_SUITE_CREATE_WALLETS";
+pub const BACKEND_ENV: &str = "SATOSHI_SUITE_BACKEND";
+pub const ESPLORA_URL_ENV: &str = "SATOSHI_SUITE_ESPLORA_URL";
+pub const ELECTRUM_URL_ENV: &str = "SATOSHI_SUITE_ELECTRUM_URL";
+pub const ORD_URL_ENV: &str = "SATOSHI_SUITE_ORD_URL";
+
+/// A partial set of settings. Profiles are layered on top of each other
+/// (CLI flags, then environment variables, then the config file) and the
+/// first layer that sets a value wins.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    #[serde(default, deserialize_with = "deserialize_chain")]
+    pub network: Option<Chain>,
+    #[serde(default, deserialize_with = "deserialize_script")]
+    pub signet_challenge: Option<ScriptBuf>,
+    pub rpc_url: Option<String>,
+    pub rpc_username: Option<String>,
+    pub rpc_password: Option<String>,
+    pub cookie_file: Option<PathBuf>,
+    pub bitcoin_data_dir: Option<PathBuf>,
+    pub create_wallets: Option<bool>,
+    #[serde(default, deserialize_with = "deserialize_backend")]
+    pub backend: Option<BackendKind>,
+    pub esplora_url: Option<String>,
+    pub electrum_url: Option<String>,
+    pub ord_url: Option<String>,
+}
+
+impl Profile {
+    /// Read overrides from `SATOSHI_SUITE_*` environment variables.
+    pub fn from_env() -> Result<Self, ConfigError> {
+        let network = match env_var(NETWORK_ENV) {
+            Some(value) => Some(value.parse::<Chain>()?),
+            None => None,
+        };
+
+        let signet_challenge =
+            match env_var(SIGNET_CHALLENGE_ENV) {
+                Some(value) => Some(ScriptBuf::from_hex(&value).map_err(|_| {
+                    ConfigError::InvalidValue(SIGNET_CHALLENGE_ENV.to_string(), value)
+                })?),
+                None => None,
+            };
+
+        let create_wallets =
+            match env_var(CREATE_WALLETS_ENV) {
+                Some(value) => Some(value.parse::<bool>().map_err(|_| {
+                    ConfigError::InvalidValue(CREATE_WALLETS_ENV.to_string(), value)
+                })?),
+                None => None,
+            };
+
+        let backend = match env_var(BACKEND_ENV) {
+            Some(value) => Some(value.parse::<BackendKind>()?),
+            None => None,
+        };
+
+        Ok(Profile {
+            network,
+            signet_challenge,
+            rpc_url: env_var(RPC_URL_ENV),
+            rpc_username: env_var(RPC_USERNAME_ENV),
+            rpc_password: env_var(RPC_PASSWORD_ENV),
+            cookie_file: env_var(COOKIE_FILE_ENV).map(PathBuf::from),
+            bitcoin_data_dir: env_var(BITCOIN_DATA_DIR_ENV).map(PathBuf::from),
+            create_wallets,
+            backend,
+            esplora_url: env_var(ESPLORA_URL_ENV),
+            electrum_url: env_var(ELECTRUM_URL_ENV),
+            ord_url: env_var(ORD_URL_ENV),
+        })
+    }
+
+    /// Fill in every value not set in `self` from `fallback`.
+    pub fn merge(self, fallback: Profile) -> Profile {
+        Profile {
+            network: self.network.or(fallback.network),
+            signet_challenge: self.signet_challenge.or(fallback.signet_challenge),
+            rpc_url: self.rpc_url.or(fallback.rpc_url),
+            rpc_username: self.rpc_username.or(fallback.rpc_username),
+            rpc_password: self.rpc_password.or(fallback.rpc_password),
+            cookie_file: self.cookie_file.or(fallback.cookie_file),
+            bitcoin_data_dir: self.bitcoin_data_dir.or(fallback.bitcoin_data_dir),
+            create_wallets: self.create_wallets.or(fallback.create_wallets),
+            backend: self.backend.or(fallback.backend),
+            esplora_url: self.esplora_url.or(fallback.esplora_url),
+            electrum_url: self.electrum_url.or(fallback.electrum_url),
+            ord_url: self.ord_url.or(fallback.ord_url),
+        }
+    }
+
+    pub fn into_config(self) -> Result<Config, ConfigError> {
+        let chain = self.network.unwrap_or(Chain::Regtest);
+        let create_wallets = self.create_wallets.unwrap_or(true);
+
+        let backend = match self.backend {
+            Some(BackendKind::Esplora) => {
+                BackendConfig::esplora(self.esplora_url.as_deref(), chain)?
+            }
+            Some(BackendKind::Electrum) => {
+                BackendConfig::electrum(self.electrum_url.as_deref(), chain)?
+            }
+            Some(BackendKind::BitcoinCore) | None => BackendConfig::BitcoinCore,
+        };
+
+        // If rpc_url is provided, treat as external Bitcoin Core
+        let config = match self.rpc_url {
+            Some(rpc_url) => Config::new_external(
+                chain,
+                self.signet_challenge,
+                rpc_url,
+                self.rpc_username,
+                self.rpc_password,
+                self.cookie_file,
+                create_wallets,
+            ),
+            // Otherwise use internal Bitcoin Core with default or specified settings
+            None => Config::new_internal(
+                chain,
+                self.signet_challenge,
+                "http://127.0.0.1".to_string(),
+                self.rpc_username.unwrap_or_else(|| "user".to_string()),
+                self.rpc_password.unwrap_or_else(|| "password".to_string()),
+                self.bitcoin_data_dir
+                    .unwrap_or_else(|| PathBuf::from("./data/bitcoin")),
+                create_wallets,
+            ),
+        }?;
+
+        config
+            .with_backend(backend)
+            .with_ord_url(self.ord_url.as_deref())
+    }
+}
+
+/// Contents of a `satoshi-suite.toml` file.
+///
+/// ```toml
+/// default_profile = "regtest-local"
+///
+/// [profile.regtest-local]
+/// network = "regtest"
+///
+/// [profile.team-signet]
+/// network = "signet"
+/// signet_challenge = "512103ad5e0edad18cb1f0fc0d28a3d4f1f3e445640337489abb10404f2d1e086be43051ae"
+/// rpc_url = "https://signet.example.com/bitcoind"
+/// cookie_file = "/var/lib/bitcoind/signet/.cookie"
+///
+/// [profile.mainnet-esplora]
+/// network = "mainnet"
+/// backend = "esplora"
+/// esplora_url = "https://blockstream.info/api"
+/// ```
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigFile {
+    pub default_profile: Option<String>,
+    #[serde(default)]
+    pub profile: HashMap<String, Profile>,
+}
+
+impl ConfigFile {
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        let contents =
+            fs::read_to_string(path).map_err(|e| ConfigError::Io(path.to_path_buf(), e))?;
+        toml::from_str(&contents).map_err(|e| ConfigError::Parse(path.to_path_buf(), e))
+    }
+
+    /// Locate and load the config file. An explicit path (from `--config` or
+    /// `SATOSHI_SUITE_CONFIG`) must exist; otherwise `./satoshi-suite.toml`
+    /// and then `$XDG_CONFIG_HOME/satoshi-suite/satoshi-suite.toml` are tried.
+    pub fn discover(explicit_path: Option<&Path>) -> Result<Option<Self>, ConfigError> {
+        let explicit_path = explicit_path
+            .map(Path::to_path_buf)
+            .or_else(|| env_var(CONFIG_PATH_ENV).map(PathBuf::from));
+        if let Some(path) = explicit_path {
+            return Self::load(&path).map(Some);
+        }
+
+        match default_search_paths()
+            .into_iter()
+            .find(|path| path.is_file())
+        {
+            Some(path) => Self::load(&path).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Look up a profile by name, falling back to `default_profile` when no
+    /// name is given. Having no profile selected at all is not an error.
+    pub fn profile(&self, name: Option<&str>) -> Result<Profile, ConfigError> {
+        match name.or(self.default_profile.as_deref()) {
+            Some(name) => self
+                .profile
+                .get(name)
+                .cloned()
+                .ok_or_else(|| ConfigError::ProfileNotFound(name.to_string())),
+            None => Ok(Profile::default()),
+        }
+    }
+}
+
+/// Resolve the profile the config file contributes, selected by `name`,
+/// `SATOSHI_SUITE_PROFILE` or the file's `default_profile`, in that order.
+pub fn load_file_profile(
+    explicit_path: Option<&Path>,
+    name: Option<&str>,
+) -> Result<Profile, ConfigError> {
+    let name = name.map(str::to_string).or_else(|| env_var(PROFILE_ENV));
+
+    match ConfigFile::discover(explicit_path)? {
+        Some(file) => file.profile(name.as_deref()),
+        None => match name {
+            Some(name) => Err(ConfigError::ProfileNotFound(name)),
+            None => Ok(Profile::default()),
+        },
+    }
+}
+
+/// Layer `cli` over the `SATOSHI_SUITE_*` environment variables and those
+/// over the config file profile [`load_file_profile`] selects.
+pub fn resolve_profile(
+    cli: Profile,
+    explicit_path: Option<&Path>,
+    name: Option<&str>,
+) -> Result<Profile, ConfigError> {
+    let file_profile = load_file_profile(explicit_path, name)?;
+    Ok(cli.merge(Profile::from_env()?).merge(file_profile))
+}
+
+fn default_search_paths() -> Vec<PathBuf> {
+    search_paths(env_var("XDG_CONFIG_HOME"), env_var("HOME"))
+}
+
+fn search_paths(xdg_config_home: Option<String>, home: Option<String>) -> Vec<PathBuf> {
+    let mut paths = vec![PathBuf::from(CONFIG_FILE_NAME)];
+
+    let config_home = xdg_config_home
+        .map(PathBuf::from)
+        .or_else(|| home.map(|home| Path::new(&home).join(".config")));
+    if let Some(config_home) = config_home {
+        paths.push(config_home.join("satoshi-suite").join(CONFIG_FILE_NAME));
+    }
+
+    paths
+}
+
+fn env_var(key: &str) -> Option<String> {
+    env::var(key).ok().filter(|value| !value.is_empty())
+}
+
+fn deserialize_chain<'de, D>(deserializer: D) -> Result<Option<Chain>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let value = Option::<String>::deserialize(deserializer)?;
+    value
+        .map(|s| s.parse::<Chain>().map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+fn deserialize_backend<'de, D>(deserializer: D) -> Result<Option<BackendKind>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let value = Option::<String>::deserialize(deserializer)?;
+    value
+        .map(|s| s.parse::<BackendKind>().map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+fn deserialize_script<'de, D>(deserializer: D) -> Result<Option<ScriptBuf>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let value = Option::<String>::deserialize(deserializer)?;
+    value
+        .map(|s| ScriptBuf::from_hex(&s).map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    /// Tests that set environment variables, which the whole process shares.
+    static ENV: Mutex<()> = Mutex::new(());
+
+    fn write_config(name: &str, contents: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("satoshi-suite-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(CONFIG_FILE_NAME);
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn cli_overrides_env_which_overrides_file() {
+        let _guard = ENV.lock().unwrap();
+        let path = write_config(
+            "precedence",
+            r#"
+default_profile = "local"
+
+[profile.local]
+network = "signet"
+rpc_url = "http://file.example.com"
+rpc_username = "file-user"
+rpc_password = "file-password"
+"#,
+        );
+        env::set_var(RPC_URL_ENV, "http://env.example.com");
+        env::set_var(RPC_USERNAME_ENV, "env-user");
+        let cli = Profile {
+            rpc_url: Some("http://cli.example.com".to_string()),
+            ..Profile::default()
+        };
+        let resolved = resolve_profile(cli, Some(&path), None);
+        env::remove_var(RPC_URL_ENV);
+        env::remove_var(RPC_USERNAME_ENV);
+
+        let resolved = resolved.unwrap();
+        assert_eq!(resolved.rpc_url.as_deref(), Some("http://cli.example.com"));
+        assert_eq!(resolved.rpc_username.as_deref(), Some("env-user"));
+        assert_eq!(resolved.rpc_password.as_deref(), Some("file-password"));
+        assert_eq!(resolved.network, Some(Chain::Signet));
+    }
+
+    #[test]
+    fn named_profile_must_exist() {
+        let _guard = ENV.lock().unwrap();
+        let path = write_config(
+            "missing-profile",
+            "[profile.local]\nnetwork = \"regtest\"\n",
+        );
+        assert!(matches!(
+            load_file_profile(Some(&path), Some("remote")),
+            Err(ConfigError::ProfileNotFound(name)) if name == "remote"
+        ));
+        assert_eq!(
+            load_file_profile(Some(&path), Some("local"))
+                .unwrap()
+                .network,
+            Some(Chain::Regtest)
+        );
+    }
+
+    #[test]
+    fn config_home_falls_back_to_home() {
+        let in_config_home =
+            |dir: &str| Path::new(dir).join("satoshi-suite").join(CONFIG_FILE_NAME);
+        assert_eq!(
+            search_paths(Some("/xdg".to_string()), Some("/home/satoshi".to_string())),
+            [PathBuf::from(CONFIG_FILE_NAME), in_config_home("/xdg")]
+        );
+        assert_eq!(
+            search_paths(None, Some("/home/satoshi".to_string())),
+            [
+                PathBuf::from(CONFIG_FILE_NAME),
+                in_config_home("/home/satoshi/.config")
+            ]
+        );
+        assert_eq!(search_paths(None, None), [PathBuf::from(CONFIG_FILE_NAME)]);
+    }
+}
@@ -0,0 +1,200 @@
+use std::{error::Error, str::FromStr};
+
+use tracing::{info, warn};
+
+use bitcoin::{
+    absolute::LockTime, transaction::Version, Amount, FeeRate, OutPoint, Psbt, Sequence,
+    Transaction, TxIn, TxOut, Txid,
+};
+use bitcoincore_rpc::json::AddressType;
+
+use satoshi_suite_client::{
+    estimate_weight, format_sat_per_vb, script_satisfaction_weight, ChainBackend, ClientError,
+    WalletBackend,
+};
+use satoshi_suite_wallet::Wallet;
+
+use crate::is_mine;
+
+/// Fee rate every transaction must pay on its own to relay, Bitcoin Core's
+/// default `-minrelaytxfee`.
+const MIN_RELAY_FEE: FeeRate = FeeRate::from_sat_per_vb_unchecked(1);
+
+/// A signed child spending a wallet output of an unconfirmed parent, paying
+/// enough for both to confirm together.
+#[derive(Debug)]
+pub struct Cpfp {
+    pub parent: Transaction,
+    /// Whether the parent is in the mempool already, rather than waiting
+    /// to be relayed along with the child.
+    pub parent_in_mempool: bool,
+    pub parent_fee: Amount,
+    pub child: Transaction,
+    pub fee: Amount,
+    /// Fee rate of parent and child together.
+    pub package_fee_rate: FeeRate,
+}
+
+impl Cpfp {
+    /// Relay the child, with the parent as a package when the parent is not
+    /// in the mempool. Backends without package relay get the parent and
+    /// then the child, which only works if the parent pays enough to relay
+    /// on its own.
+    pub fn broadcast<B: ChainBackend + ?Sized>(&self, client: &B) -> Result<Txid, Box<dyn Error>> {
+        if self.parent_in_mempool {
+            return Ok(client.send_raw_transaction(&self.child)?);
+        }
+        match client.submit_package(&[self.parent.clone(), self.child.clone()]) {
+            Ok(_) => Ok(self.child.txid()),
+            Err(ClientError::Unsupported(_)) => {
+                warn!("The backend cannot relay packages; sending the parent on its own first");
+                client.send_raw_transaction(&self.parent)?;
+                Ok(client.send_raw_transaction(&self.child)?)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// Build and sign a child of `parent` that spends the wallet's output at
+/// `vout`, or its largest output of `parent`, back to a fresh address of
+/// the wallet. The child pays for the two together to reach `fee_rate`
+/// over their combined size.
+pub fn build_cpfp<B: WalletBackend>(
+    wallet: &Wallet<B>,
+    parent: &Transaction,
+    vout: Option<u32>,
+    fee_rate: FeeRate,
+) -> Result<Cpfp, Box<dyn Error>> {
+    if !wallet.get_wallet_info()?.private_keys_enabled {
+        return Err("Child-pays-for-parent needs a wallet with private keys".into());
+    }
+    let txid = parent.txid();
+
+    let mut ours = Vec::new();
+    for (index, output) in parent.output.iter().enumerate() {
+        if vout.map_or(true, |vout| vout as usize == index)
+            && is_mine(wallet, &output.script_pubkey)?
+        {
+            ours.push((index as u32, output));
+        }
+    }
+    let (vout, output) = ours
+        .into_iter()
+        .max_by_key(|(_, output)| output.value)
+        .ok_or_else(|| match vout {
+            Some(vout) => format!("Output {}:{} does not pay the wallet", txid, vout),
+            None => format!("No output of {} pays the wallet", txid),
+        })?;
+    let outpoint = OutPoint::new(txid, vout);
+
+    let parent_in_mempool = match wallet.client.get_tx_out(&outpoint, true)? {
+        Some(out) if out.confirmations > 0 => {
+            return Err(format!("Transaction {} is already confirmed", txid).into())
+        }
+        Some(_) => true,
+        // A parent the backend knows of has had the output spent already
+        None if wallet.client.get_raw_transaction(&txid).is_ok() => {
+            return Err(format!("Output {} is already spent", outpoint).into())
+        }
+        None => false,
+    };
+
+    let mut total_in = Amount::ZERO;
+    for input in &parent.input {
+        total_in += prevout_value(wallet, &input.previous_output)?;
+    }
+    let parent_fee = total_in
+        .checked_sub(parent.output.iter().map(|output| output.value).sum())
+        .ok_or_else(|| format!("Transaction {} spends less than it pays", txid))?;
+    let parent_vsize = parent.vsize() as u64;
+    if parent_fee >= fee_rate.fee_vb(parent_vsize).ok_or("Fee rate too high")? {
+        return Err(format!(
+            "Transaction {} already pays at least {} sat/vB",
+            txid,
+            format_sat_per_vb(fee_rate)
+        )
+        .into());
+    }
+
+    let mut child = Transaction {
+        version: Version::TWO,
+        lock_time: LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: outpoint,
+            sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+            ..Default::default()
+        }],
+        output: vec![TxOut {
+            value: Amount::ZERO,
+            script_pubkey: wallet.new_address(&AddressType::Bech32)?.script_pubkey(),
+        }],
+    };
+    let satisfaction_weight = script_satisfaction_weight(&output.script_pubkey)
+        .ok_or_else(|| format!("Cannot estimate the size of spending {}", outpoint))?;
+    let child_vsize = estimate_weight(&child, &[satisfaction_weight]).to_vbytes_ceil();
+
+    let package_fee = fee_rate
+        .fee_vb(parent_vsize + child_vsize)
+        .ok_or("Fee rate too high")?;
+    let fee = (package_fee - parent_fee).max(
+        MIN_RELAY_FEE
+            .fee_vb(child_vsize)
+            .expect("minimum relay fee of a single input"),
+    );
+    let min_value = child.output[0].script_pubkey.dust_value();
+    child.output[0].value = output
+        .value
+        .checked_sub(fee)
+        .filter(|value| *value >= min_value)
+        .ok_or_else(|| format!("Output {} cannot pay {} for the child", outpoint, fee))?;
+    info!(
+        "Child of {} pays {} for {} vB on top of {} for {} vB",
+        txid, fee, child_vsize, parent_fee, parent_vsize
+    );
+
+    let child = match parent_in_mempool {
+        true => wallet.sign_tx(&child)?,
+        // The wallet cannot look up an output of a parent it has not seen,
+        // so a PSBT carries it
+        false => {
+            let mut psbt = Psbt::from_unsigned_tx(child)?;
+            psbt.inputs[0].witness_utxo = Some(output.clone());
+            psbt.inputs[0].non_witness_utxo = Some(parent.clone());
+            let processed = wallet.process_psbt(&psbt.to_string())?;
+            Psbt::from_str(&processed.psbt)?.extract_tx()?
+        }
+    };
+    if child.input[0].witness.is_empty() && child.input[0].script_sig.is_empty() {
+        return Err(format!("The wallet cannot sign for {}", outpoint).into());
+    }
+    let package_vsize = parent_vsize + child.vsize() as u64;
+    Ok(Cpfp {
+        parent: parent.clone(),
+        parent_in_mempool,
+        parent_fee,
+        child,
+        fee,
+        package_fee_rate: FeeRate::from_sat_per_kwu(
+            (parent_fee + fee).to_sat() * 250 / package_vsize,
+        ),
+    })
+}
+
+/// Value of the output `outpoint`, from the UTXO set while it is unspent by
+/// a block and otherwise from the transaction creating it.
+fn prevout_value<B: WalletBackend>(
+    wallet: &Wallet<B>,
+    outpoint: &OutPoint,
+) -> Result<Amount, Box<dyn Error>> {
+    if let Some(out) = wallet.client.get_tx_out(outpoint, false)? {
+        return Ok(out.value);
+    }
+    wallet
+        .client
+        .get_raw_transaction(&outpoint.txid)?
+        .output
+        .get(outpoint.vout as usize)
+        .map(|output| output.value)
+        .ok_or_else(|| format!("Input {} not found", outpoint).into())
+}
@@ -1,73 +1,136 @@
-use std::{collections::HashMap, error::Error};
+use std::error::Error;
 
 use tracing::{info, warn};
 
 use bitcoin::{
-    consensus::{deserialize, serialize},
-    Address, Amount, OutPoint, Transaction, TxOut,
+    consensus::serialize, transaction::Version, Address, Amount, FeeRate, OutPoint, ScriptBuf,
+    Sequence, Transaction, TxIn, TxOut, Witness,
 };
 
-use bitcoincore_rpc::{
-    json::{AddressType, CreateRawTransactionInput, ListUnspentResultEntry},
-    Client, RpcApi,
-};
+use satoshi_suite_client::{ChainBackend, WalletBackend};
+use satoshi_suite_utxo_selection::CoinSelector;
+use satoshi_suite_wallet::{ChangePolicy, Payment, SpendOptions, Wallet};
+
+mod cpfp;
+pub use cpfp::*;
+
+mod musig;
+pub use musig::*;
+
+mod rbf;
+pub use rbf::*;
 
-use satoshi_suite_utxo_selection::{strat_handler, UTXOStrategy};
-use satoshi_suite_wallet::Wallet;
+mod sweep;
+pub use sweep::*;
 
-pub fn sign_tx(
-    client: &Client,
-    wallet: &Wallet,
+#[allow(clippy::too_many_arguments)]
+pub fn sign_tx<B: WalletBackend>(
+    wallet: &Wallet<B>,
     recipient: &Address,
     amount: Amount,
-    fee_amount: Amount,
-    utxo_strat: UTXOStrategy,
+    fee_rate: FeeRate,
+    long_term_fee_rate: FeeRate,
+    selector: &dyn CoinSelector,
+    change_policy: &ChangePolicy,
+    options: &SpendOptions,
 ) -> Result<Vec<u8>, Box<dyn Error>> {
+    let payment = Payment {
+        address: recipient.clone(),
+        amount,
+    };
+    let signed = sign_payments(
+        wallet,
+        &[payment],
+        fee_rate,
+        long_term_fee_rate,
+        selector,
+        change_policy,
+        options,
+    )?;
+    Ok(serialize(&signed.tx))
+}
+
+/// A signed transaction and the fee it pays.
+#[derive(Debug)]
+pub struct SignedPayments {
+    pub tx: Transaction,
+    pub fee: Amount,
+}
+
+/// Sign a single transaction paying every one of `payments`, with coins
+/// selected once for all of them. Change matching the recipient's type
+/// follows the first payment.
+pub fn sign_payments<B: WalletBackend>(
+    wallet: &Wallet<B>,
+    payments: &[Payment],
+    fee_rate: FeeRate,
+    long_term_fee_rate: FeeRate,
+    selector: &dyn CoinSelector,
+    change_policy: &ChangePolicy,
+    options: &SpendOptions,
+) -> Result<SignedPayments, Box<dyn Error>> {
+    let first = payments.first().ok_or("No payments given")?;
+    let total: Amount = payments.iter().map(|payment| payment.amount).sum();
+
     let balances = wallet.get_balances()?;
 
-    if balances.mine.trusted.to_sat() < amount.to_sat() {
+    if balances.mine.trusted.to_sat() < total.to_sat() {
         return Err("Insufficient balance".into());
     }
 
-    let unspent_txs: Vec<ListUnspentResultEntry> = wallet.list_all_unspent(None)?;
-    if unspent_txs.is_empty() {
-        return Err("No unspent transactions".into());
-    }
-
-    let selected_utxos =
-        strat_handler(&unspent_txs, amount, fee_amount, utxo_strat).map_err(|e| e.to_string())?;
-
-    let mut utxo_inputs: Vec<CreateRawTransactionInput> = Vec::new();
-    let mut total_amount = Amount::from_sat(0);
-    for utxo in &selected_utxos {
-        utxo_inputs.push(CreateRawTransactionInput {
-            txid: utxo.txid,
-            vout: utxo.vout,
-            sequence: Some(0),
+    let mut outputs: Vec<TxOut> = payments.iter().map(Payment::to_txout).collect();
+
+    let change_address = change_policy.change_address(wallet, &first.address.script_pubkey())?;
+    let funding = wallet.fund(
+        &outputs,
+        &change_address.script_pubkey(),
+        fee_rate,
+        long_term_fee_rate,
+        selector,
+        options,
+    )?;
+
+    let mut utxo_inputs: Vec<TxIn> = Vec::new();
+    for utxo in &funding.utxos {
+        utxo_inputs.push(TxIn {
+            previous_output: OutPoint {
+                txid: utxo.txid,
+                vout: utxo.vout,
+            },
+            script_sig: ScriptBuf::new(),
+            sequence: options.sequence(Sequence::ZERO),
+            witness: Witness::new(),
         });
-        total_amount += utxo.amount;
     }
 
-    let mut outputs: HashMap<String, Amount> = HashMap::new();
-    outputs.insert(recipient.to_string(), amount);
-
-    let change_amount = total_amount - amount - fee_amount;
-    if change_amount.to_sat() > 0 {
-        let change_address: Address = wallet.new_address(&AddressType::Bech32)?;
-        outputs.insert(change_address.to_string(), change_amount);
+    if let Some(change_amount) = funding.change {
+        let change =
+            change_policy.change_outputs(wallet, change_address, change_amount, fee_rate)?;
+        change_policy.place_change(&mut outputs, change);
     }
 
-    let tx = client.create_raw_transaction(&utxo_inputs[..], &outputs, None, None)?;
+    // Splitting change pays for the extra outputs out of it
+    let total_in: Amount = funding.utxos.iter().map(|utxo| utxo.amount).sum();
+    let total_out: Amount = outputs.iter().map(|output| output.value).sum();
+    let fee = total_in - total_out;
+    info!("Fee: {}", fee);
+
+    let tx = Transaction {
+        version: Version::TWO,
+        lock_time: options.lock_time(),
+        input: utxo_inputs,
+        output: outputs,
+    };
 
     let signed_tx = wallet.sign_tx(&tx)?;
-    let raw_tx = serialize(&signed_tx);
 
-    Ok(raw_tx)
+    Ok(SignedPayments { tx: signed_tx, fee })
 }
 
-pub fn verify_signed_tx(client: &Client, tx_hex: &str) -> Result<(), Box<dyn Error>> {
-    let tx: Transaction = deserialize(&hex::decode(tx_hex)?)?;
-
+pub fn verify_signed_tx<B: ChainBackend + ?Sized>(
+    client: &B,
+    tx: &Transaction,
+) -> Result<(), Box<dyn Error>> {
     info!("Verifying transaction: {}", tx.txid());
     info!("Number of inputs: {}", tx.input.len());
 
@@ -83,7 +146,7 @@ pub fn verify_signed_tx(client: &Client, tx_hex: &str) -> Result<(), Box<dyn Err
 
     // Closure to fetch previous transaction output (TxOut) for each input
     let mut spent = |outpoint: &OutPoint| -> Option<TxOut> {
-        match client.get_raw_transaction(&outpoint.txid, None) {
+        match client.get_raw_transaction(&outpoint.txid) {
             Ok(raw_tx) => raw_tx.output.get(outpoint.vout as usize).cloned(),
             Err(e) => {
                 warn!("Failed to fetch raw transaction {}: {}", outpoint.txid, e);
@@ -102,8 +165,11 @@ pub fn verify_signed_tx(client: &Client, tx_hex: &str) -> Result<(), Box<dyn Err
     }
 }
 
-fn is_utxo_unspent(client: &Client, outpoint: &OutPoint) -> Result<bool, Box<dyn Error>> {
-    match client.get_tx_out(&outpoint.txid, outpoint.vout, Some(false))? {
+fn is_utxo_unspent<B: ChainBackend + ?Sized>(
+    client: &B,
+    outpoint: &OutPoint,
+) -> Result<bool, Box<dyn Error>> {
+    match client.get_tx_out(outpoint, false)? {
         Some(_) => Ok(true), // UTXO exists and is unspent
         None => Ok(false),   // UTXO doesn't exist (already spent)
     }
@@ -0,0 +1,781 @@
+use std::error::Error;
+
+use bitcoin::{
+    hashes::{sha256, Hash, HashEngine},
+    key::{Secp256k1, TweakedPublicKey},
+    psbt::raw,
+    secp256k1::{
+        constants::CURVE_ORDER,
+        rand::{thread_rng, Rng},
+        schnorr, Message, PublicKey, Scalar, SecretKey, Signing, Verification, XOnlyPublicKey,
+    },
+    sighash::{Prevouts, SighashCache},
+    taproot, Address, Network, Psbt, ScriptBuf, TapNodeHash, TapSighashType, TapTweakHash, TxOut,
+    Witness,
+};
+
+/// BIP373 input field listing the participants of a MuSig2 aggregate key,
+/// keyed by that key.
+pub const PSBT_IN_MUSIG2_PARTICIPANT_PUBKEYS: u8 = 0x1a;
+
+/// BIP373 input field holding a participant's public nonce, keyed by the
+/// participant's key and the aggregate key.
+pub const PSBT_IN_MUSIG2_PUB_NONCE: u8 = 0x1b;
+
+/// BIP373 input field holding a participant's partial signature, keyed
+/// like its nonce.
+pub const PSBT_IN_MUSIG2_PARTIAL_SIG: u8 = 0x1c;
+
+/// An aggregate public key of several signers, per BIP327's `KeyAgg`, and
+/// the tweaks applied to it since.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeyAggContext {
+    pubkeys: Vec<PublicKey>,
+    list_hash: [u8; 32],
+    second_key: Option<PublicKey>,
+    aggregate: PublicKey,
+    /// The aggregate key after tweaking, `Q`.
+    output: PublicKey,
+    /// Whether the accumulated sign `gacc` is -1.
+    negated: bool,
+    /// The accumulated tweak `tacc`.
+    tweak: Scalar,
+}
+
+impl KeyAggContext {
+    /// Aggregate `pubkeys` in the order given. Any order works as long as
+    /// every signer uses the same one; [`KeyAggContext::sorted`] picks one.
+    pub fn new<C: Verification>(
+        secp: &Secp256k1<C>,
+        pubkeys: &[PublicKey],
+    ) -> Result<Self, Box<dyn Error>> {
+        let first = pubkeys.first().ok_or("No keys to aggregate")?;
+        let serialized: Vec<u8> = pubkeys.iter().flat_map(PublicKey::serialize).collect();
+        let mut ctx = KeyAggContext {
+            pubkeys: pubkeys.to_vec(),
+            list_hash: tagged_hash("KeyAgg list", &[&serialized]),
+            second_key: pubkeys.iter().find(|pubkey| *pubkey != first).copied(),
+            aggregate: *first,
+            output: *first,
+            negated: false,
+            tweak: Scalar::ZERO,
+        };
+
+        let mut aggregate = None;
+        for pubkey in pubkeys {
+            let term = point_mul(secp, pubkey, ctx.coefficient(pubkey));
+            aggregate = point_add(aggregate, term);
+        }
+        ctx.aggregate = aggregate.ok_or("Keys aggregate to the point at infinity")?;
+        ctx.output = ctx.aggregate;
+        Ok(ctx)
+    }
+
+    /// Aggregate `pubkeys` sorted as BIP327's `KeySort` does, like
+    /// `musig()` descriptors.
+    pub fn sorted<C: Verification>(
+        secp: &Secp256k1<C>,
+        pubkeys: &[PublicKey],
+    ) -> Result<Self, Box<dyn Error>> {
+        let mut sorted = pubkeys.to_vec();
+        sorted.sort_by_key(PublicKey::serialize);
+        KeyAggContext::new(secp, &sorted)
+    }
+
+    pub fn pubkeys(&self) -> &[PublicKey] {
+        &self.pubkeys
+    }
+
+    /// The aggregate key before any tweak, which BIP373 fields are keyed by
+    /// and a taproot output uses as its internal key.
+    pub fn aggregate_pubkey(&self) -> PublicKey {
+        self.aggregate
+    }
+
+    /// The tweaked key signatures verify against.
+    pub fn output_key(&self) -> XOnlyPublicKey {
+        self.output.x_only_public_key().0
+    }
+
+    /// Add `tweak` times the generator to the key, as an x-only key when
+    /// `x_only`, per BIP327's `ApplyTweak`.
+    pub fn tweak<C: Signing + Verification>(
+        &self,
+        secp: &Secp256k1<C>,
+        tweak: Scalar,
+        x_only: bool,
+    ) -> Result<Self, Box<dyn Error>> {
+        let negate = x_only && !has_even_y(&self.output);
+        let output = if negate {
+            self.output.negate(secp)
+        } else {
+            self.output
+        };
+        let output = point_add(Some(output), generator_mul(secp, tweak))
+            .ok_or("Tweak takes the key to the point at infinity")?;
+        let tweak_acc = match negate {
+            true => scalar_neg(self.tweak),
+            false => self.tweak,
+        };
+        Ok(KeyAggContext {
+            output,
+            negated: self.negated != negate,
+            tweak: scalar_add(tweak, tweak_acc),
+            ..self.clone()
+        })
+    }
+
+    /// The key tweaked to the output key of a taproot output with the
+    /// aggregate as its internal key and `merkle_root` as its script tree.
+    pub fn taproot_tweaked<C: Signing + Verification>(
+        &self,
+        secp: &Secp256k1<C>,
+        merkle_root: Option<TapNodeHash>,
+    ) -> Result<Self, Box<dyn Error>> {
+        let internal_key = self.aggregate.x_only_public_key().0;
+        let tweak = TapTweakHash::from_key_and_tweak(internal_key, merkle_root).to_scalar();
+        self.tweak(secp, tweak, true)
+    }
+
+    /// The key-path only taproot address of the aggregate, which spends
+    /// like a single-key output.
+    pub fn address<C: Verification>(&self, secp: &Secp256k1<C>, network: Network) -> Address {
+        Address::p2tr(secp, self.aggregate.x_only_public_key().0, None, network)
+    }
+
+    /// `KeyAggCoeff` of `pubkey`: one for the second distinct key, which
+    /// saves a multiplication, and a hash of the key list otherwise.
+    fn coefficient(&self, pubkey: &PublicKey) -> Scalar {
+        if self.second_key.as_ref() == Some(pubkey) {
+            return Scalar::ONE;
+        }
+        scalar_reduce(tagged_hash(
+            "KeyAgg coefficient",
+            &[&self.list_hash, &pubkey.serialize()],
+        ))
+    }
+}
+
+/// A signer's secret nonce for one signing session. It is consumed by
+/// signing, as signing twice with the same nonce reveals the secret key.
+#[derive(Debug)]
+pub struct SecNonce {
+    k1: SecretKey,
+    k2: SecretKey,
+    pubkey: PublicKey,
+}
+
+impl SecNonce {
+    /// A secret nonce serialized as BIP327's `secnonce`, both scalars and
+    /// then the signer's key. Only for nonces kept outside this process
+    /// between the rounds; each must still be signed with only once.
+    pub fn from_slice(bytes: &[u8]) -> Result<Self, Box<dyn Error>> {
+        if bytes.len() != 97 {
+            return Err(format!("Secret nonce of {} bytes, not 97", bytes.len()).into());
+        }
+        Ok(SecNonce {
+            k1: SecretKey::from_slice(&bytes[..32])?,
+            k2: SecretKey::from_slice(&bytes[32..64])?,
+            pubkey: PublicKey::from_slice(&bytes[64..])?,
+        })
+    }
+}
+
+/// The public half of a [`SecNonce`], shared with the other signers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PubNonce {
+    r1: PublicKey,
+    r2: PublicKey,
+}
+
+impl PubNonce {
+    pub fn serialize(&self) -> [u8; 66] {
+        let mut bytes = [0; 66];
+        bytes[..33].copy_from_slice(&self.r1.serialize());
+        bytes[33..].copy_from_slice(&self.r2.serialize());
+        bytes
+    }
+
+    pub fn from_slice(bytes: &[u8]) -> Result<Self, Box<dyn Error>> {
+        if bytes.len() != 66 {
+            return Err(format!("Public nonce of {} bytes, not 66", bytes.len()).into());
+        }
+        Ok(PubNonce {
+            r1: PublicKey::from_slice(&bytes[..33])?,
+            r2: PublicKey::from_slice(&bytes[33..])?,
+        })
+    }
+}
+
+/// The sum of every signer's [`PubNonce`]. Either half may be the point at
+/// infinity, which the signers' nonces can only cancel out to on purpose.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AggNonce {
+    r1: Option<PublicKey>,
+    r2: Option<PublicKey>,
+}
+
+impl AggNonce {
+    /// Sum `nonces`, per BIP327's `NonceAgg`.
+    pub fn new(nonces: &[PubNonce]) -> Result<Self, Box<dyn Error>> {
+        if nonces.is_empty() {
+            return Err("No nonces to aggregate".into());
+        }
+        let sum = |half: fn(&PubNonce) -> PublicKey| {
+            nonces
+                .iter()
+                .fold(None, |sum, nonce| point_add(sum, Some(half(nonce))))
+        };
+        Ok(AggNonce {
+            r1: sum(|nonce| nonce.r1),
+            r2: sum(|nonce| nonce.r2),
+        })
+    }
+
+    /// Both halves compressed, with 33 zero bytes for infinity.
+    pub fn serialize(&self) -> [u8; 66] {
+        let mut bytes = [0; 66];
+        if let Some(r1) = self.r1 {
+            bytes[..33].copy_from_slice(&r1.serialize());
+        }
+        if let Some(r2) = self.r2 {
+            bytes[33..].copy_from_slice(&r2.serialize());
+        }
+        bytes
+    }
+}
+
+/// A signer's share of the aggregate signature.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PartialSignature(Scalar);
+
+impl PartialSignature {
+    pub fn serialize(&self) -> [u8; 32] {
+        self.0.to_be_bytes()
+    }
+
+    pub fn from_slice(bytes: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let bytes: [u8; 32] = bytes
+            .try_into()
+            .map_err(|_| format!("Partial signature of {} bytes, not 32", bytes.len()))?;
+        Ok(PartialSignature(Scalar::from_be_bytes(bytes)?))
+    }
+}
+
+/// A fresh nonce for `pubkey` to sign with, per BIP327's `NonceGen`. The
+/// optional secret key, aggregate key and message only harden the nonce
+/// against a weak random number generator.
+pub fn nonce_gen<C: Signing>(
+    secp: &Secp256k1<C>,
+    secret_key: Option<&SecretKey>,
+    pubkey: &PublicKey,
+    output_key: Option<&XOnlyPublicKey>,
+    msg: Option<&[u8]>,
+    extra_in: Option<&[u8]>,
+) -> Result<(SecNonce, PubNonce), Box<dyn Error>> {
+    let mut rand: [u8; 32] = thread_rng().gen();
+    if let Some(secret_key) = secret_key {
+        let aux = tagged_hash("MuSig/aux", &[&rand]);
+        for (byte, (key, aux)) in rand
+            .iter_mut()
+            .zip(secret_key.secret_bytes().iter().zip(aux))
+        {
+            *byte = key ^ aux;
+        }
+    }
+
+    let pubkey_bytes = pubkey.serialize();
+    let output_key = output_key.map_or(Vec::new(), |key| key.serialize().to_vec());
+    let msg_prefixed = match msg {
+        Some(msg) => [&[1][..], &(msg.len() as u64).to_be_bytes(), msg].concat(),
+        None => vec![0],
+    };
+    let extra_in = extra_in.unwrap_or_default();
+    let k = |i: u8| -> Result<SecretKey, Box<dyn Error>> {
+        let hash = tagged_hash(
+            "MuSig/nonce",
+            &[
+                &rand,
+                &[pubkey_bytes.len() as u8],
+                &pubkey_bytes,
+                &[output_key.len() as u8],
+                &output_key,
+                &msg_prefixed,
+                &(extra_in.len() as u32).to_be_bytes(),
+                extra_in,
+                &[i],
+            ],
+        );
+        SecretKey::from_slice(&scalar_reduce(hash).to_be_bytes())
+            .map_err(|_| "Nonce generation produced zero".into())
+    };
+    let (k1, k2) = (k(0)?, k(1)?);
+
+    let pub_nonce = PubNonce {
+        r1: k1.public_key(secp),
+        r2: k2.public_key(secp),
+    };
+    let sec_nonce = SecNonce {
+        k1,
+        k2,
+        pubkey: *pubkey,
+    };
+    Ok((sec_nonce, pub_nonce))
+}
+
+/// What every signer of `msg` under a key derives from the aggregate nonce,
+/// per BIP327's `GetSessionValues`.
+#[derive(Clone, Debug)]
+pub struct Session {
+    key: KeyAggContext,
+    /// The nonce coefficient `b`.
+    nonce_coefficient: Scalar,
+    /// The final nonce `R`.
+    nonce: PublicKey,
+    /// The challenge `e`.
+    challenge: Scalar,
+}
+
+impl Session {
+    pub fn new<C: Signing + Verification>(
+        secp: &Secp256k1<C>,
+        key: &KeyAggContext,
+        agg_nonce: &AggNonce,
+        msg: &[u8],
+    ) -> Self {
+        let output_key = key.output_key().serialize();
+        let nonce_coefficient = scalar_reduce(tagged_hash(
+            "MuSig/noncecoef",
+            &[&agg_nonce.serialize(), &output_key, msg],
+        ));
+        let nonce = agg_nonce
+            .r2
+            .and_then(|r2| point_mul(secp, &r2, nonce_coefficient));
+        let nonce = point_add(agg_nonce.r1, nonce)
+            .unwrap_or_else(|| generator_mul(secp, Scalar::ONE).expect("one is a valid key"));
+        let challenge = scalar_reduce(tagged_hash(
+            "BIP0340/challenge",
+            &[&nonce.x_only_public_key().0.serialize(), &output_key, msg],
+        ));
+        Session {
+            key: key.clone(),
+            nonce_coefficient,
+            nonce,
+            challenge,
+        }
+    }
+
+    /// Sign with `secret_key`, one of the aggregated keys, per BIP327's
+    /// `Sign`. The partial signature is verified before it is returned.
+    pub fn sign<C: Signing + Verification>(
+        &self,
+        secp: &Secp256k1<C>,
+        sec_nonce: SecNonce,
+        secret_key: &SecretKey,
+    ) -> Result<PartialSignature, Box<dyn Error>> {
+        let pubkey = secret_key.public_key(secp);
+        if pubkey != sec_nonce.pubkey {
+            return Err("The nonce was made for another key".into());
+        }
+        if !self.key.pubkeys.contains(&pubkey) {
+            return Err(format!("Key {} is not one of the aggregated keys", pubkey).into());
+        }
+
+        let (mut k1, mut k2) = (sec_nonce.k1, sec_nonce.k2);
+        if !has_even_y(&self.nonce) {
+            k1 = k1.negate();
+            k2 = k2.negate();
+        }
+        let mut d = *secret_key;
+        if has_even_y(&self.key.output) == self.key.negated {
+            d = d.negate();
+        }
+        let a = self.key.coefficient(&pubkey);
+        let s = scalar_add(
+            scalar_add(
+                Scalar::from(k1),
+                scalar_mul(self.nonce_coefficient, Scalar::from(k2)),
+            ),
+            scalar_mul(self.challenge, scalar_mul(a, Scalar::from(d))),
+        );
+
+        let partial = PartialSignature(s);
+        let pub_nonce = PubNonce {
+            r1: sec_nonce.k1.public_key(secp),
+            r2: sec_nonce.k2.public_key(secp),
+        };
+        if !self.verify(secp, &partial, &pub_nonce, &pubkey) {
+            return Err("Partial signature does not verify".into());
+        }
+        Ok(partial)
+    }
+
+    /// Whether `partial` is the share of the signer of `pubkey` who sent
+    /// `pub_nonce`, per BIP327's `PartialSigVerify`.
+    pub fn verify<C: Signing + Verification>(
+        &self,
+        secp: &Secp256k1<C>,
+        partial: &PartialSignature,
+        pub_nonce: &PubNonce,
+        pubkey: &PublicKey,
+    ) -> bool {
+        if !self.key.pubkeys.contains(pubkey) {
+            return false;
+        }
+        let Some(mut effective_nonce) = point_add(
+            Some(pub_nonce.r1),
+            point_mul(secp, &pub_nonce.r2, self.nonce_coefficient),
+        ) else {
+            return false;
+        };
+        if !has_even_y(&self.nonce) {
+            effective_nonce = effective_nonce.negate(secp);
+        }
+        let mut factor = scalar_mul(self.challenge, self.key.coefficient(pubkey));
+        if has_even_y(&self.key.output) == self.key.negated {
+            factor = scalar_neg(factor);
+        }
+        let expected = point_add(Some(effective_nonce), point_mul(secp, pubkey, factor));
+        generator_mul(secp, partial.0) == expected
+    }
+
+    /// The BIP340 signature of every signer's `partials` together, per
+    /// BIP327's `PartialSigAgg`.
+    pub fn aggregate(
+        &self,
+        partials: &[PartialSignature],
+    ) -> Result<schnorr::Signature, Box<dyn Error>> {
+        let mut tweak = scalar_mul(self.challenge, self.key.tweak);
+        if !has_even_y(&self.key.output) {
+            tweak = scalar_neg(tweak);
+        }
+        let s = partials
+            .iter()
+            .fold(tweak, |sum, partial| scalar_add(sum, partial.0));
+        let mut bytes = [0; 64];
+        bytes[..32].copy_from_slice(&self.nonce.x_only_public_key().0.serialize());
+        bytes[32..].copy_from_slice(&s.to_be_bytes());
+        Ok(schnorr::Signature::from_slice(&bytes)?)
+    }
+}
+
+/// The first signing round on a PSBT: a nonce for every key-path input
+/// whose BIP373 participants include the key of `secret_key`, added to the
+/// PSBT. The secret nonces come back by input index for
+/// [`musig_sign_round`]; they must stay in memory and never be reused. The
+/// transaction must not change between the rounds.
+pub fn musig_nonce_round<C: Signing + Verification>(
+    secp: &Secp256k1<C>,
+    psbt: &mut Psbt,
+    secret_key: &SecretKey,
+) -> Result<Vec<(usize, SecNonce)>, Box<dyn Error>> {
+    let pubkey = secret_key.public_key(secp);
+    let mut sec_nonces = Vec::new();
+    for index in 0..psbt.inputs.len() {
+        let Some(key) = participants(secp, psbt, index)? else {
+            continue;
+        };
+        if !key.pubkeys.contains(&pubkey) {
+            continue;
+        }
+        let sighash = key_spend_sighash(psbt, index)?;
+        let output_key = key.output_key();
+        let (sec_nonce, pub_nonce) = nonce_gen(
+            secp,
+            Some(secret_key),
+            &pubkey,
+            Some(&output_key),
+            Some(sighash.as_ref()),
+            None,
+        )?;
+        psbt.inputs[index].unknown.insert(
+            signer_field(PSBT_IN_MUSIG2_PUB_NONCE, &pubkey, &key),
+            pub_nonce.serialize().to_vec(),
+        );
+        sec_nonces.push((index, sec_nonce));
+    }
+    if sec_nonces.is_empty() {
+        return Err(format!("No MuSig2 input has {} as a participant", pubkey).into());
+    }
+    Ok(sec_nonces)
+}
+
+/// The second signing round: once every participant's nonce is in the PSBT,
+/// a partial signature with `secret_key` for each input of `sec_nonces`.
+pub fn musig_sign_round<C: Signing + Verification>(
+    secp: &Secp256k1<C>,
+    psbt: &mut Psbt,
+    secret_key: &SecretKey,
+    sec_nonces: Vec<(usize, SecNonce)>,
+) -> Result<(), Box<dyn Error>> {
+    let pubkey = secret_key.public_key(secp);
+    for (index, sec_nonce) in sec_nonces {
+        let key = participants(secp, psbt, index)?
+            .ok_or_else(|| format!("Input {} has no MuSig2 participants", index))?;
+        let session = session(secp, psbt, index, &key)?;
+        let partial = session.sign(secp, sec_nonce, secret_key)?;
+        psbt.inputs[index].unknown.insert(
+            signer_field(PSBT_IN_MUSIG2_PARTIAL_SIG, &pubkey, &key),
+            partial.serialize().to_vec(),
+        );
+    }
+    Ok(())
+}
+
+/// Aggregate the partial signatures of every MuSig2 input into its key-path
+/// signature and finalize it. Each partial signature is verified first, so
+/// a bad one names its signer.
+pub fn musig_finalize<C: Signing + Verification>(
+    secp: &Secp256k1<C>,
+    psbt: &mut Psbt,
+) -> Result<(), Box<dyn Error>> {
+    for index in 0..psbt.inputs.len() {
+        let Some(key) = participants(secp, psbt, index)? else {
+            continue;
+        };
+        let session = session(secp, psbt, index, &key)?;
+        let mut partials = Vec::new();
+        for pubkey in &key.pubkeys {
+            let field = signer_field(PSBT_IN_MUSIG2_PARTIAL_SIG, pubkey, &key);
+            let partial = psbt.inputs[index]
+                .unknown
+                .get(&field)
+                .ok_or_else(|| format!("Input {}: no partial signature from {}", index, pubkey))?;
+            let partial = PartialSignature::from_slice(partial)?;
+            if !session.verify(
+                secp,
+                &partial,
+                &pub_nonce(psbt, index, pubkey, &key)?,
+                pubkey,
+            ) {
+                return Err(
+                    format!("Input {}: bad partial signature from {}", index, pubkey).into(),
+                );
+            }
+            partials.push(partial);
+        }
+
+        let signature = session.aggregate(&partials)?;
+        let sighash = key_spend_sighash(psbt, index)?;
+        secp.verify_schnorr(
+            &signature,
+            &Message::from_digest(sighash.to_byte_array()),
+            &key.output_key(),
+        )?;
+        let signature = taproot::Signature {
+            sig: signature,
+            hash_ty: TapSighashType::Default,
+        };
+        let input = &mut psbt.inputs[index];
+        input.tap_key_sig = Some(signature);
+        input.final_script_witness = Some(Witness::from_slice(&[signature.to_vec()]));
+    }
+    Ok(())
+}
+
+/// Record on input `index` that it is spent by the key path of `key`'s
+/// taproot output, with `merkle_root` its script tree, for the signers of
+/// the aggregate to find.
+pub fn add_musig_participants(
+    psbt: &mut Psbt,
+    index: usize,
+    key: &KeyAggContext,
+    merkle_root: Option<TapNodeHash>,
+) -> Result<(), Box<dyn Error>> {
+    let input = psbt
+        .inputs
+        .get_mut(index)
+        .ok_or_else(|| format!("No input {}", index))?;
+    input.unknown.insert(
+        raw::Key {
+            type_value: PSBT_IN_MUSIG2_PARTICIPANT_PUBKEYS,
+            key: key.aggregate.serialize().to_vec(),
+        },
+        key.pubkeys.iter().flat_map(PublicKey::serialize).collect(),
+    );
+    input.tap_internal_key = Some(key.aggregate.x_only_public_key().0);
+    input.tap_merkle_root = merkle_root;
+    Ok(())
+}
+
+/// The aggregate key input `index` is spent with, tweaked to the output
+/// key it pays, if the input lists MuSig2 participants.
+fn participants<C: Signing + Verification>(
+    secp: &Secp256k1<C>,
+    psbt: &Psbt,
+    index: usize,
+) -> Result<Option<KeyAggContext>, Box<dyn Error>> {
+    let input = &psbt.inputs[index];
+    let Some((field, value)) = input
+        .unknown
+        .iter()
+        .find(|(field, _)| field.type_value == PSBT_IN_MUSIG2_PARTICIPANT_PUBKEYS)
+    else {
+        return Ok(None);
+    };
+    if value.is_empty() || value.len() % 33 != 0 {
+        return Err(format!("Input {}: malformed MuSig2 participants", index).into());
+    }
+    let pubkeys = value
+        .chunks(33)
+        .map(PublicKey::from_slice)
+        .collect::<Result<Vec<_>, _>>()?;
+    let key = KeyAggContext::new(secp, &pubkeys)?;
+    if key.aggregate.serialize()[..] != field.key[..] {
+        return Err(format!(
+            "Input {}: participants do not aggregate to their key",
+            index
+        )
+        .into());
+    }
+
+    let key = key.taproot_tweaked(secp, input.tap_merkle_root)?;
+    let spent = input
+        .witness_utxo
+        .as_ref()
+        .ok_or_else(|| format!("Input {} has no witness UTXO", index))?;
+    let output_key = TweakedPublicKey::dangerous_assume_tweaked(key.output_key());
+    if spent.script_pubkey != ScriptBuf::new_p2tr_tweaked(output_key) {
+        return Err(format!("Input {} does not pay the aggregate key", index).into());
+    }
+    Ok(Some(key))
+}
+
+/// The session of input `index`, from every participant's nonce.
+fn session<C: Signing + Verification>(
+    secp: &Secp256k1<C>,
+    psbt: &Psbt,
+    index: usize,
+    key: &KeyAggContext,
+) -> Result<Session, Box<dyn Error>> {
+    let nonces = key
+        .pubkeys
+        .iter()
+        .map(|pubkey| pub_nonce(psbt, index, pubkey, key))
+        .collect::<Result<Vec<_>, _>>()?;
+    let sighash = key_spend_sighash(psbt, index)?;
+    Ok(Session::new(
+        secp,
+        key,
+        &AggNonce::new(&nonces)?,
+        sighash.as_ref(),
+    ))
+}
+
+fn pub_nonce(
+    psbt: &Psbt,
+    index: usize,
+    pubkey: &PublicKey,
+    key: &KeyAggContext,
+) -> Result<PubNonce, Box<dyn Error>> {
+    let field = signer_field(PSBT_IN_MUSIG2_PUB_NONCE, pubkey, key);
+    let nonce = psbt.inputs[index]
+        .unknown
+        .get(&field)
+        .ok_or_else(|| format!("Input {}: no nonce from {}", index, pubkey))?;
+    PubNonce::from_slice(nonce)
+}
+
+/// The BIP373 field of the signer of `pubkey` for the aggregate `key`.
+fn signer_field(type_value: u8, pubkey: &PublicKey, key: &KeyAggContext) -> raw::Key {
+    raw::Key {
+        type_value,
+        key: [pubkey.serialize(), key.aggregate.serialize()].concat(),
+    }
+}
+
+fn key_spend_sighash(psbt: &Psbt, index: usize) -> Result<bitcoin::TapSighash, Box<dyn Error>> {
+    let prevouts = psbt
+        .inputs
+        .iter()
+        .enumerate()
+        .map(|(i, input)| {
+            input
+                .witness_utxo
+                .clone()
+                .ok_or_else(|| format!("Input {} has no witness UTXO", i))
+        })
+        .collect::<Result<Vec<TxOut>, _>>()?;
+    Ok(
+        SighashCache::new(&psbt.unsigned_tx).taproot_key_spend_signature_hash(
+            index,
+            &Prevouts::All(&prevouts),
+            TapSighashType::Default,
+        )?,
+    )
+}
+
+fn tagged_hash(tag: &str, data: &[&[u8]]) -> [u8; 32] {
+    let tag = sha256::Hash::hash(tag.as_bytes());
+    let mut engine = sha256::Hash::engine();
+    engine.input(tag.as_ref());
+    engine.input(tag.as_ref());
+    for data in data {
+        engine.input(data);
+    }
+    sha256::Hash::from_engine(engine).to_byte_array()
+}
+
+fn has_even_y(point: &PublicKey) -> bool {
+    point.serialize()[0] == 0x02
+}
+
+/// `bytes` as an integer modulo the curve order. Integers of 256 bits are
+/// below twice the order, so one subtraction does.
+fn scalar_reduce(bytes: [u8; 32]) -> Scalar {
+    Scalar::from_be_bytes(bytes).unwrap_or_else(|_| {
+        let mut reduced = [0; 32];
+        let mut borrow = 0;
+        for i in (0..32).rev() {
+            let difference = bytes[i] as i16 - CURVE_ORDER[i] as i16 - borrow;
+            reduced[i] = difference.rem_euclid(256) as u8;
+            borrow = (difference < 0) as i16;
+        }
+        Scalar::from_be_bytes(reduced).expect("below the curve order")
+    })
+}
+
+// Scalars go through `SecretKey`, which cannot be zero, for their
+// arithmetic
+fn nonzero(scalar: Scalar) -> Option<SecretKey> {
+    SecretKey::from_slice(&scalar.to_be_bytes()).ok()
+}
+
+fn scalar_add(a: Scalar, b: Scalar) -> Scalar {
+    match nonzero(a) {
+        Some(a) => a.add_tweak(&b).map_or(Scalar::ZERO, Scalar::from),
+        None => b,
+    }
+}
+
+fn scalar_mul(a: Scalar, b: Scalar) -> Scalar {
+    match (nonzero(a), nonzero(b)) {
+        (Some(a), Some(_)) => a.mul_tweak(&b).map_or(Scalar::ZERO, Scalar::from),
+        _ => Scalar::ZERO,
+    }
+}
+
+fn scalar_neg(a: Scalar) -> Scalar {
+    nonzero(a).map_or(Scalar::ZERO, |a| Scalar::from(a.negate()))
+}
+
+// Points are `None` at infinity
+fn point_add(a: Option<PublicKey>, b: Option<PublicKey>) -> Option<PublicKey> {
+    match (a, b) {
+        (Some(a), Some(b)) => a.combine(&b).ok(),
+        (a, None) => a,
+        (None, b) => b,
+    }
+}
+
+fn point_mul<C: Verification>(
+    secp: &Secp256k1<C>,
+    point: &PublicKey,
+    scalar: Scalar,
+) -> Option<PublicKey> {
+    point.mul_tweak(secp, &scalar).ok()
+}
+
+fn generator_mul<C: Signing>(secp: &Secp256k1<C>, scalar: Scalar) -> Option<PublicKey> {
+    nonzero(scalar).map(|scalar| scalar.public_key(secp))
+}
@@ -0,0 +1,324 @@
+use std::error::Error;
+
+use tracing::info;
+
+use bitcoin::{
+    script::Instruction, Address, Amount, FeeRate, OutPoint, Psbt, Script, ScriptBuf, Sequence,
+    Transaction, TxIn, TxOut, Txid, Weight, Witness,
+};
+use bitcoincore_rpc::json::AddressType;
+
+use satoshi_suite_client::{format_sat_per_vb, WalletBackend};
+use satoshi_suite_utxo_selection::{
+    strat_handler, CoinSelectionParams, CoinSelector, INPUT_BASE_WEIGHT,
+};
+use satoshi_suite_wallet::{change_satisfaction_weight, Wallet};
+
+/// Fee rate a replacement pays for its own size on top of the fee of the
+/// transaction it replaces, Bitcoin Core's default `-incrementalrelayfee`.
+pub const INCREMENTAL_RELAY_FEE: FeeRate = FeeRate::from_sat_per_vb_unchecked(1);
+
+/// A replacement transaction, signed when the wallet holds the keys and
+/// otherwise a PSBT for the signers of a multisig wallet.
+#[derive(Debug)]
+pub enum Replacement {
+    Signed(Transaction),
+    Psbt(String),
+}
+
+/// A replacement and the fees it pays against the transaction it replaces.
+#[derive(Debug)]
+pub struct FeeBump {
+    pub replacement: Replacement,
+    pub original_fee: Amount,
+    pub fee: Amount,
+    pub fee_rate: FeeRate,
+}
+
+/// A wallet transaction waiting in the mempool.
+struct Original {
+    tx: Transaction,
+    prevouts: Vec<TxOut>,
+    fee: Amount,
+}
+
+/// Replace the wallet transaction `txid` with one paying the same
+/// recipients at `fee_rate`, raised if need be to the least BIP 125
+/// accepts. The higher fee comes out of change, which is dropped when what
+/// remains would be dust, and otherwise from more confirmed coins chosen by
+/// `selector`. Every output paying the wallet counts as change and is
+/// merged into one.
+pub fn bump_fee<B: WalletBackend>(
+    wallet: &Wallet<B>,
+    txid: &Txid,
+    fee_rate: FeeRate,
+    long_term_fee_rate: FeeRate,
+    selector: &dyn CoinSelector,
+) -> Result<FeeBump, Box<dyn Error>> {
+    let original = replaceable(wallet, txid)?;
+    let mut tx = original.tx.clone();
+
+    // A transaction paying only the wallet keeps its first output as the payment
+    let mut change = Vec::new();
+    for (vout, output) in tx.output.iter().enumerate() {
+        if is_mine(wallet, &output.script_pubkey)? {
+            change.push(vout);
+        }
+    }
+    if change.len() == tx.output.len() {
+        change.remove(0);
+    }
+    let position = change.first().copied();
+    let change_script = match position {
+        Some(vout) => tx.output[vout].script_pubkey.clone(),
+        None => wallet.new_address(&AddressType::Bech32)?.script_pubkey(),
+    };
+    let mut vout = 0;
+    tx.output.retain(|_| {
+        vout += 1;
+        !change.contains(&(vout - 1))
+    });
+
+    let base_weight = resigned_weight(&tx);
+    let fee_rate = replacement_fee_rate(fee_rate, original.fee, base_weight);
+    let mut change_output = TxOut {
+        value: Amount::ZERO,
+        script_pubkey: change_script,
+    };
+    let change_fee = fee_rate
+        .fee_wu(change_output.weight())
+        .ok_or("Fee rate too high")?;
+    let min_change = change_output.script_pubkey.dust_value();
+
+    let mut total_in: Amount = original.prevouts.iter().map(|prevout| prevout.value).sum();
+    let payments: Amount = tx.output.iter().map(|output| output.value).sum();
+    let needed = payments + fee_rate.fee_wu(base_weight).ok_or("Fee rate too high")?;
+
+    match total_in.checked_sub(needed) {
+        Some(surplus) if surplus >= change_fee + min_change => {
+            change_output.value = surplus - change_fee;
+            let position = position.unwrap_or(tx.output.len()).min(tx.output.len());
+            tx.output.insert(position, change_output);
+        }
+        Some(surplus) => info!("Dropping change, leaving {} to the fee", surplus),
+        None => {
+            // Change shares the wallet's descriptors, so its coins size it
+            let utxos = wallet.spendable_unspent()?;
+            let change_spend_weight =
+                change_satisfaction_weight(&utxos, &change_output.script_pubkey)
+                    .map(|weight| INPUT_BASE_WEIGHT + weight)
+                    .ok_or("Cannot estimate the size of spending the change output")?;
+            let params = CoinSelectionParams {
+                fee_rate,
+                long_term_fee_rate,
+                // The transaction itself is paid for in `needed`
+                base_fee: Amount::ZERO,
+                change_fee,
+                change_spend_fee: long_term_fee_rate
+                    .fee_wu(change_spend_weight)
+                    .ok_or("Long-term fee rate too high")?,
+                min_change,
+            };
+            let selection = strat_handler(&utxos, needed - total_in, &params, selector)
+                .map_err(|e| format!("Cannot fund the higher fee: {}", e))?;
+            info!(
+                "Adding {} inputs to pay the higher fee",
+                selection.utxos.len()
+            );
+
+            // Only confirmed coins are listed, as BIP 125 requires of new inputs
+            for utxo in &selection.utxos {
+                tx.input.push(TxIn {
+                    previous_output: OutPoint::new(utxo.txid, utxo.vout),
+                    sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                    ..Default::default()
+                });
+                total_in += utxo.amount;
+            }
+            if let Some(value) = selection.change {
+                change_output.value = value;
+                tx.output.push(change_output);
+            }
+        }
+    }
+
+    let total_out: Amount = tx.output.iter().map(|output| output.value).sum();
+    Ok(FeeBump {
+        replacement: replacement(wallet, tx)?,
+        original_fee: original.fee,
+        fee: total_in - total_out,
+        fee_rate,
+    })
+}
+
+/// Replace the wallet transaction `txid` with one spending the same inputs
+/// back to the wallet at `fee_rate`, raised if need be to the least BIP 125
+/// accepts, so the original can no longer confirm.
+pub fn cancel_tx<B: WalletBackend>(
+    wallet: &Wallet<B>,
+    txid: &Txid,
+    fee_rate: FeeRate,
+) -> Result<FeeBump, Box<dyn Error>> {
+    let original = replaceable(wallet, txid)?;
+    let mut tx = original.tx.clone();
+
+    let mut script_pubkey = None;
+    for output in &tx.output {
+        if is_mine(wallet, &output.script_pubkey)? {
+            script_pubkey = Some(output.script_pubkey.clone());
+            break;
+        }
+    }
+    let script_pubkey = match script_pubkey {
+        Some(script_pubkey) => script_pubkey,
+        None => wallet.new_address(&AddressType::Bech32)?.script_pubkey(),
+    };
+    let min_value = script_pubkey.dust_value();
+    tx.output = vec![TxOut {
+        value: Amount::ZERO,
+        script_pubkey,
+    }];
+
+    let weight = resigned_weight(&tx);
+    let fee_rate = replacement_fee_rate(fee_rate, original.fee, weight);
+    let fee = fee_rate.fee_wu(weight).ok_or("Fee rate too high")?;
+    let total_in: Amount = original.prevouts.iter().map(|prevout| prevout.value).sum();
+    tx.output[0].value = total_in
+        .checked_sub(fee)
+        .filter(|value| *value >= min_value)
+        .ok_or_else(|| format!("Inputs of {} cannot pay {} to cancel it", txid, fee))?;
+
+    Ok(FeeBump {
+        replacement: replacement(wallet, tx)?,
+        original_fee: original.fee,
+        fee,
+        fee_rate,
+    })
+}
+
+/// The wallet transaction `txid`, if it is in the mempool, signals BIP 125
+/// replaceability, has no descendants to evict, and spends only coins of
+/// the wallet.
+fn replaceable<B: WalletBackend>(
+    wallet: &Wallet<B>,
+    txid: &Txid,
+) -> Result<Original, Box<dyn Error>> {
+    if !wallet.client.in_mempool(txid)? {
+        return Err(format!(
+            "Transaction {} is not in the mempool; it is confirmed or was replaced",
+            txid
+        )
+        .into());
+    }
+    let tx = wallet.client.get_raw_transaction(txid)?;
+    // OP_RETURN outputs are never in the UTXO set
+    for (vout, output) in tx.output.iter().enumerate() {
+        if output.script_pubkey.is_op_return() {
+            continue;
+        }
+        let outpoint = OutPoint::new(*txid, vout as u32);
+        if wallet.client.get_tx_out(&outpoint, true)?.is_none() {
+            return Err(format!(
+                "Output {} is already spent by another transaction",
+                outpoint
+            )
+            .into());
+        }
+    }
+    if !tx.is_explicitly_rbf() {
+        return Err(format!("Transaction {} does not signal replaceability", txid).into());
+    }
+
+    let mut prevouts = Vec::new();
+    for input in &tx.input {
+        let outpoint = input.previous_output;
+        let prevout = wallet
+            .client
+            .get_raw_transaction(&outpoint.txid)?
+            .output
+            .get(outpoint.vout as usize)
+            .cloned()
+            .ok_or_else(|| format!("Input {} not found", outpoint))?;
+        if !is_mine(wallet, &prevout.script_pubkey)? {
+            return Err(format!("Input {} does not belong to the wallet", outpoint).into());
+        }
+        prevouts.push(prevout);
+    }
+
+    let total_in: Amount = prevouts.iter().map(|prevout| prevout.value).sum();
+    let total_out: Amount = tx.output.iter().map(|output| output.value).sum();
+    Ok(Original {
+        fee: total_in - total_out,
+        tx,
+        prevouts,
+    })
+}
+
+pub(crate) fn is_mine<B: WalletBackend>(
+    wallet: &Wallet<B>,
+    script_pubkey: &Script,
+) -> Result<bool, Box<dyn Error>> {
+    let Ok(address) = Address::from_script(script_pubkey, wallet.network) else {
+        return Ok(false);
+    };
+    Ok(wallet.get_address_info(&address)?.is_mine.unwrap_or(false))
+}
+
+/// `requested`, or if that is lower the least fee rate at which a
+/// transaction of at least `weight` replaces one paying `original_fee`. It
+/// pays the original fee again plus [`INCREMENTAL_RELAY_FEE`] for its own
+/// size, which also beats the original's fee rate.
+fn replacement_fee_rate(requested: FeeRate, original_fee: Amount, weight: Weight) -> FeeRate {
+    let original = (original_fee.to_sat() * 1000).div_ceil(weight.to_wu());
+    let min = FeeRate::from_sat_per_kwu(original + INCREMENTAL_RELAY_FEE.to_sat_per_kwu());
+    if requested >= min {
+        return requested;
+    }
+    info!(
+        "Raising the fee rate to {} sat/vB, the least that replaces the original",
+        format_sat_per_vb(min)
+    );
+    min
+}
+
+/// Weight of `tx` once signed again, with every ECDSA signature among its
+/// script_sigs and witnesses grown to the worst case of 73 bytes.
+fn resigned_weight(tx: &Transaction) -> Weight {
+    // DER encoded, with a sighash byte after the sequence it wraps
+    let is_signature = |push: &[u8]| {
+        push.len() < 73 && push.len() > 8 && push[0] == 0x30 && push[1] as usize == push.len() - 3
+    };
+    let mut padding = 0;
+    for input in &tx.input {
+        for element in input.witness.iter().filter(|element| is_signature(element)) {
+            padding += 73 - element.len();
+        }
+        for instruction in input.script_sig.instructions().flatten() {
+            if let Instruction::PushBytes(push) = instruction {
+                if is_signature(push.as_bytes()) {
+                    padding += 4 * (73 - push.len());
+                }
+            }
+        }
+    }
+    tx.weight() + Weight::from_wu(padding as u64)
+}
+
+/// `tx` signed by the wallet, or as a PSBT when the wallet has no private
+/// keys.
+fn replacement<B: WalletBackend>(
+    wallet: &Wallet<B>,
+    mut tx: Transaction,
+) -> Result<Replacement, Box<dyn Error>> {
+    for input in &mut tx.input {
+        input.script_sig = ScriptBuf::new();
+        input.witness = Witness::new();
+    }
+    if wallet.get_wallet_info()?.private_keys_enabled {
+        return Ok(Replacement::Signed(wallet.sign_tx(&tx)?));
+    }
+    let psbt = Psbt::from_unsigned_tx(tx)?;
+    Ok(Replacement::Psbt(
+        wallet.process_psbt(&psbt.to_string())?.psbt,
+    ))
+}
@@ -0,0 +1,211 @@
+use std::error::Error;
+
+use tracing::{info, warn};
+
+use bitcoin::{
+    absolute::LockTime, hashes::Hash, transaction::Version, Address, Amount, FeeRate, OutPoint,
+    ScriptBuf, Sequence, Transaction, TxIn, TxOut, WPubkeyHash, Weight,
+};
+use bitcoincore_rpc::json::{AddressType, ListUnspentResultEntry};
+
+use satoshi_suite_client::{estimate_weight, utxo_satisfaction_weight, WalletBackend};
+use satoshi_suite_utxo_selection::input_weight;
+use satoshi_suite_wallet::Wallet;
+
+use crate::SignedPayments;
+
+/// Heaviest transaction nodes relay, Bitcoin Core's `MAX_STANDARD_TX_WEIGHT`.
+pub const MAX_STANDARD_TX_WEIGHT: Weight = Weight::from_wu(400_000);
+
+/// Spend every coin the wallet may spend in one transaction to
+/// `recipients`. The fee comes out of the outputs, which split what is left
+/// evenly, the first taking any remainder. Coins costing more to spend than
+/// they are worth are left behind.
+pub fn sweep<B: WalletBackend>(
+    wallet: &Wallet<B>,
+    recipients: &[Address],
+    fee_rate: FeeRate,
+) -> Result<SignedPayments, Box<dyn Error>> {
+    if recipients.is_empty() {
+        return Err("No recipients given".into());
+    }
+    let utxos = worth_spending(wallet.spendable_unspent()?, fee_rate);
+    if utxos.is_empty() {
+        return Err("No coins worth spending to sweep".into());
+    }
+
+    let outputs = recipients
+        .iter()
+        .map(|address| TxOut {
+            value: Amount::ZERO,
+            script_pubkey: address.script_pubkey(),
+        })
+        .collect();
+    let mut tx = spending(&utxos, outputs);
+    let weight = estimate_weight(&tx, &satisfaction_weights(&utxos));
+    if weight > MAX_STANDARD_TX_WEIGHT {
+        return Err(format!(
+            "Sweeping {} coins weighs {} wu, over the standard {} wu; consolidate them first",
+            utxos.len(),
+            weight.to_wu(),
+            MAX_STANDARD_TX_WEIGHT.to_wu()
+        )
+        .into());
+    }
+
+    let total: Amount = utxos.iter().map(|utxo| utxo.amount).sum();
+    let fee = fee_rate.fee_wu(weight).ok_or("Fee rate too high")?;
+    let count = recipients.len() as u64;
+    let left = total
+        .checked_sub(fee)
+        .ok_or_else(|| format!("Balance of {} cannot pay the fee of {}", total, fee))?;
+    let share = left / count;
+    for output in &mut tx.output {
+        if share < output.script_pubkey.dust_value() {
+            return Err(format!(
+                "Balance of {} is too small to pay {} recipients after the fee of {}",
+                total, count, fee
+            )
+            .into());
+        }
+        output.value = share;
+    }
+    tx.output[0].value += left - share * count;
+    info!("Sweeping {} coins, {} in total", utxos.len(), total);
+
+    Ok(SignedPayments {
+        tx: wallet.sign_tx(&tx)?,
+        fee,
+    })
+}
+
+/// Merge the wallet's coins worth less than `threshold` into fresh outputs
+/// of the wallet, one per transaction of at most `max_inputs` inputs that
+/// stays within [`MAX_STANDARD_TX_WEIGHT`]. Smallest coins go first. Coins
+/// costing more to spend than they are worth are left alone, as is a last
+/// coin with nothing to merge into.
+pub fn consolidate<B: WalletBackend>(
+    wallet: &Wallet<B>,
+    threshold: Amount,
+    fee_rate: FeeRate,
+    max_inputs: usize,
+) -> Result<Vec<SignedPayments>, Box<dyn Error>> {
+    let small = wallet
+        .spendable_unspent()?
+        .into_iter()
+        .filter(|utxo| utxo.amount < threshold)
+        .collect();
+    let mut utxos = worth_spending(small, fee_rate);
+    utxos.sort_by_key(|utxo| utxo.amount);
+
+    // Room for the inputs' count to outgrow one byte
+    let output = TxOut {
+        value: Amount::ZERO,
+        script_pubkey: ScriptBuf::new_p2wpkh(&WPubkeyHash::all_zeros()),
+    };
+    let base_weight = estimate_weight(&spending(&[], vec![output]), &[]) + Weight::from_wu(4 * 2);
+    let mut chunks = vec![Vec::new()];
+    let mut weight = base_weight;
+    for utxo in utxos {
+        let input = input_weight(&utxo).expect("coins worth spending are sized");
+        let chunk = chunks.last_mut().expect("at least one chunk");
+        if !chunk.is_empty()
+            && (chunk.len() == max_inputs || weight + input > MAX_STANDARD_TX_WEIGHT)
+        {
+            chunks.push(Vec::new());
+            weight = base_weight;
+        }
+        weight += input;
+        chunks.last_mut().expect("at least one chunk").push(utxo);
+    }
+
+    let mut consolidations = Vec::new();
+    for chunk in chunks.into_iter().filter(|chunk| chunk.len() > 1) {
+        let output = TxOut {
+            value: Amount::ZERO,
+            script_pubkey: wallet.new_address(&AddressType::Bech32)?.script_pubkey(),
+        };
+        let mut tx = spending(&chunk, vec![output]);
+        let fee = fee_rate
+            .fee_wu(estimate_weight(&tx, &satisfaction_weights(&chunk)))
+            .ok_or("Fee rate too high")?;
+        let total: Amount = chunk.iter().map(|utxo| utxo.amount).sum();
+        match total
+            .checked_sub(fee)
+            .filter(|value| *value >= tx.output[0].script_pubkey.dust_value())
+        {
+            Some(value) => tx.output[0].value = value,
+            None => {
+                warn!(
+                    "Skipping {} coins worth {}, too little to pay the fee of {}",
+                    chunk.len(),
+                    total,
+                    fee
+                );
+                continue;
+            }
+        }
+        info!("Merging {} coins worth {}", chunk.len(), total);
+        consolidations.push(SignedPayments {
+            tx: wallet.sign_tx(&tx)?,
+            fee,
+        });
+    }
+
+    if consolidations.is_empty() {
+        return Err(format!(
+            "Fewer than two coins worth consolidating below {}",
+            threshold
+        )
+        .into());
+    }
+    Ok(consolidations)
+}
+
+/// `utxos` less those costing more to spend at `fee_rate` than they hold,
+/// or whose size is unknown.
+fn worth_spending(
+    utxos: Vec<ListUnspentResultEntry>,
+    fee_rate: FeeRate,
+) -> Vec<ListUnspentResultEntry> {
+    let count = utxos.len();
+    let kept: Vec<_> = utxos
+        .into_iter()
+        .filter(|utxo| {
+            input_weight(utxo)
+                .and_then(|weight| fee_rate.fee_wu(weight))
+                .is_some_and(|fee| fee < utxo.amount)
+        })
+        .collect();
+    if kept.len() < count {
+        info!(
+            "Leaving {} coins that cost more to spend than they are worth",
+            count - kept.len()
+        );
+    }
+    kept
+}
+
+fn satisfaction_weights(utxos: &[ListUnspentResultEntry]) -> Vec<Weight> {
+    utxos
+        .iter()
+        .map(|utxo| utxo_satisfaction_weight(utxo).expect("coins worth spending are sized"))
+        .collect()
+}
+
+/// An unsigned transaction spending every one of `utxos` to `outputs`.
+fn spending(utxos: &[ListUnspentResultEntry], outputs: Vec<TxOut>) -> Transaction {
+    Transaction {
+        version: Version::TWO,
+        lock_time: LockTime::ZERO,
+        input: utxos
+            .iter()
+            .map(|utxo| TxIn {
+                previous_output: OutPoint::new(utxo.txid, utxo.vout),
+                sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                ..Default::default()
+            })
+            .collect(),
+        output: outputs,
+    }
+}
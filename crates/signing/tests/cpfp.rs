@@ -0,0 +1,201 @@
+use bitcoin::{
+    absolute::LockTime, transaction::Version, Address, Amount, BlockHash, FeeRate, Network,
+    OutPoint, Script, Sequence, Transaction, TxIn, TxOut, Txid,
+};
+use bitcoincore_rpc::json::{AddressType, GetTxOutResult, Utxo};
+
+use satoshi_suite_client::{ChainBackend, ClientError, Simulator};
+use satoshi_suite_signing::build_cpfp;
+use satoshi_suite_wallet::Wallet;
+
+const PAYMENT: Amount = Amount::from_sat(100_000);
+
+/// A signed transaction of the miner paying [`PAYMENT`] to `recipient` and
+/// leaving `fee`, not yet broadcast.
+fn parent(miner: &Wallet<Simulator>, recipient: &Address, fee: Amount) -> Transaction {
+    let coin = miner
+        .spendable_unspent()
+        .unwrap()
+        .into_iter()
+        .max_by_key(|coin| coin.amount)
+        .unwrap();
+    let change = miner.new_address(&AddressType::Bech32).unwrap();
+    let tx = Transaction {
+        version: Version::TWO,
+        lock_time: LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: OutPoint::new(coin.txid, coin.vout),
+            sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+            ..Default::default()
+        }],
+        output: vec![
+            TxOut {
+                value: PAYMENT,
+                script_pubkey: recipient.script_pubkey(),
+            },
+            TxOut {
+                value: coin.amount - PAYMENT - fee,
+                script_pubkey: change.script_pubkey(),
+            },
+        ],
+    };
+    miner.sign_tx(&tx).unwrap()
+}
+
+fn setup() -> (Simulator, Wallet<Simulator>, Wallet<Simulator>) {
+    let sim = Simulator::new();
+    let miner = Wallet::open(&sim, "miner").unwrap();
+    let mining = miner.new_address(&AddressType::Bech32).unwrap();
+    // Two mature coinbase outputs to fund parents from
+    sim.generate_to_address(102, &mining).unwrap();
+    let alice = Wallet::open(&sim, "alice").unwrap();
+    (sim, miner, alice)
+}
+
+#[test]
+fn child_brings_the_package_to_the_fee_rate() {
+    let (sim, miner, alice) = setup();
+    let recipient = alice.new_address(&AddressType::Bech32).unwrap();
+    let parent_fee = Amount::from_sat(200);
+    let parent = parent(&miner, &recipient, parent_fee);
+    sim.send_raw_transaction(&parent).unwrap();
+
+    let fee_rate = FeeRate::from_sat_per_vb_unchecked(10);
+    let cpfp = build_cpfp(&alice, &parent, None, fee_rate).unwrap();
+    assert!(cpfp.parent_in_mempool);
+    assert_eq!(cpfp.parent_fee, parent_fee);
+    assert_eq!(
+        cpfp.child.input[0].previous_output,
+        OutPoint::new(parent.txid(), 0)
+    );
+    assert_eq!(cpfp.child.output[0].value, PAYMENT - cpfp.fee);
+
+    // Sized from the parent's vsize and fee and the child as signed
+    let package_vsize = (parent.vsize() + cpfp.child.vsize()) as u64;
+    assert_eq!(
+        cpfp.package_fee_rate,
+        FeeRate::from_sat_per_kwu((parent_fee + cpfp.fee).to_sat() * 250 / package_vsize)
+    );
+    assert!(cpfp.package_fee_rate >= fee_rate);
+    assert!(cpfp.package_fee_rate < FeeRate::from_sat_per_vb_unchecked(11));
+
+    let child = cpfp.broadcast(&sim).unwrap();
+    assert_eq!(child, cpfp.child.txid());
+    assert!(sim.in_mempool(&child).unwrap());
+}
+
+#[test]
+fn parent_paying_the_fee_rate_already_is_refused() {
+    let (sim, miner, alice) = setup();
+    let recipient = alice.new_address(&AddressType::Bech32).unwrap();
+    let parent = parent(&miner, &recipient, Amount::from_sat(5_000));
+    sim.send_raw_transaction(&parent).unwrap();
+
+    let err = build_cpfp(
+        &alice,
+        &parent,
+        None,
+        FeeRate::from_sat_per_vb_unchecked(10),
+    )
+    .unwrap_err()
+    .to_string();
+    assert!(err.contains("already pays at least 10 sat/vB"), "{}", err);
+}
+
+#[test]
+fn parent_without_a_wallet_output_is_refused() {
+    let (sim, miner, alice) = setup();
+    let recipient = miner.new_address(&AddressType::Bech32).unwrap();
+    let parent = parent(&miner, &recipient, Amount::from_sat(200));
+    sim.send_raw_transaction(&parent).unwrap();
+    let fee_rate = FeeRate::from_sat_per_vb_unchecked(10);
+
+    let err = build_cpfp(&alice, &parent, None, fee_rate)
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("No output of"), "{}", err);
+    let err = build_cpfp(&alice, &parent, Some(1), fee_rate)
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("does not pay the wallet"), "{}", err);
+}
+
+/// The simulator without package relay, like backends whose servers have
+/// no `submitpackage`.
+struct NoPackageRelay<'a>(&'a Simulator);
+
+impl ChainBackend for NoPackageRelay<'_> {
+    fn network(&self) -> Network {
+        self.0.network()
+    }
+
+    fn get_block_count(&self) -> Result<u64, ClientError> {
+        self.0.get_block_count()
+    }
+
+    fn get_raw_transaction(&self, txid: &Txid) -> Result<Transaction, ClientError> {
+        self.0.get_raw_transaction(txid)
+    }
+
+    fn get_tx_out(
+        &self,
+        outpoint: &OutPoint,
+        include_mempool: bool,
+    ) -> Result<Option<GetTxOutResult>, ClientError> {
+        self.0.get_tx_out(outpoint, include_mempool)
+    }
+
+    fn in_mempool(&self, txid: &Txid) -> Result<bool, ClientError> {
+        self.0.in_mempool(txid)
+    }
+
+    fn scan_utxos(&self, script_pubkey: &Script) -> Result<Vec<Utxo>, ClientError> {
+        self.0.scan_utxos(script_pubkey)
+    }
+
+    fn send_raw_transaction(&self, tx: &Transaction) -> Result<Txid, ClientError> {
+        self.0.send_raw_transaction(tx)
+    }
+
+    fn submit_package(&self, _txs: &[Transaction]) -> Result<Vec<Txid>, ClientError> {
+        Err(ClientError::Unsupported("submitpackage".to_string()))
+    }
+
+    fn estimate_fee_rate(&self, conf_target: u16) -> Result<Option<FeeRate>, ClientError> {
+        self.0.estimate_fee_rate(conf_target)
+    }
+
+    fn generate_to_address(
+        &self,
+        blocks: u64,
+        address: &Address,
+    ) -> Result<Vec<BlockHash>, ClientError> {
+        self.0.generate_to_address(blocks, address)
+    }
+}
+
+#[test]
+fn unrelayed_parent_goes_first_without_package_relay() {
+    let (sim, miner, alice) = setup();
+    let recipient = alice.new_address(&AddressType::Bech32).unwrap();
+    let fee_rate = FeeRate::from_sat_per_vb_unchecked(10);
+
+    // Below the minimum relay fee the parent only relays as a package
+    let free = parent(&miner, &recipient, Amount::ZERO);
+    let cpfp = build_cpfp(&alice, &free, None, fee_rate).unwrap();
+    assert!(!cpfp.parent_in_mempool);
+    assert!(cpfp.broadcast(&NoPackageRelay(&sim)).is_err());
+    assert!(sim.mempool().is_empty());
+    cpfp.broadcast(&sim).unwrap();
+    assert!(sim.in_mempool(&free.txid()).unwrap());
+    assert!(sim.in_mempool(&cpfp.child.txid()).unwrap());
+
+    // Paying the minimum itself it is sent on its own, then the child
+    let cheap = parent(&miner, &recipient, Amount::from_sat(200));
+    let cpfp = build_cpfp(&alice, &cheap, None, fee_rate).unwrap();
+    assert!(!cpfp.parent_in_mempool);
+    let child = cpfp.broadcast(&NoPackageRelay(&sim)).unwrap();
+    assert_eq!(child, cpfp.child.txid());
+    assert!(sim.in_mempool(&cheap.txid()).unwrap());
+    assert!(sim.in_mempool(&child).unwrap());
+}
@@ -0,0 +1,377 @@
+use std::str::FromStr;
+
+use bitcoin::{
+    absolute::LockTime,
+    hex::FromHex,
+    secp256k1::{rand::thread_rng, schnorr, Message, PublicKey, Scalar, Secp256k1, SecretKey},
+    transaction::Version,
+    Amount, Psbt, Sequence, Transaction, TxIn, TxOut,
+};
+use bitcoincore_rpc::json::AddressType;
+
+use satoshi_suite_client::{ChainBackend, Simulator};
+use satoshi_suite_signing::{
+    add_musig_participants, musig_finalize, musig_nonce_round, musig_sign_round, nonce_gen,
+    AggNonce, KeyAggContext, PartialSignature, PubNonce, SecNonce, Session,
+};
+use satoshi_suite_wallet::{SpendOptions, Wallet};
+
+fn pubkey(hex: &str) -> PublicKey {
+    PublicKey::from_str(hex).unwrap()
+}
+
+fn bytes(hex: &str) -> Vec<u8> {
+    Vec::from_hex(hex).unwrap()
+}
+
+fn pub_nonce(hex: &str) -> PubNonce {
+    PubNonce::from_slice(&bytes(hex)).unwrap()
+}
+
+fn partial(hex: &str) -> PartialSignature {
+    PartialSignature::from_slice(&bytes(hex)).unwrap()
+}
+
+#[test]
+fn key_aggregation_matches_bip327_vectors() {
+    let secp = Secp256k1::new();
+    let keys = [
+        pubkey("02F9308A019258C31049344F85F89D5229B531C845836F99B08601F113BCE036F9"),
+        pubkey("03DFF1D77F2A671C5F36183726DB2341BE58FEAE1DA2DECED843240F7B502BA659"),
+        pubkey("023590A94E768F8E1815C2F24B4D80A8E3149316C3518CE7B7AD338368D038CA66"),
+    ];
+    let vectors: [(&[usize], &str); 4] = [
+        (
+            &[0, 1, 2],
+            "90539EEDE565F5D054F32CC0C220126889ED1E5D193BAF15AEF344FE59D4610C",
+        ),
+        (
+            &[2, 1, 0],
+            "6204DE8B083426DC6EAF9502D27024D53FC826BF7D2012148A0575435DF54B2B",
+        ),
+        (
+            &[0, 0, 0],
+            "B436E3BAD62B8CD409969A224731C193D051162D8C5AE8B109306127DA3AA935",
+        ),
+        (
+            &[0, 0, 1, 1],
+            "69BC22BFA5D106306E48A20679DE1D7389386124D07571D0D872686028C26A3E",
+        ),
+    ];
+    for (indices, expected) in vectors {
+        let pubkeys: Vec<_> = indices.iter().map(|i| keys[*i]).collect();
+        let ctx = KeyAggContext::new(&secp, &pubkeys).unwrap();
+        let (aggregate, _) = ctx.aggregate_pubkey().x_only_public_key();
+        assert_eq!(aggregate.to_string(), expected.to_lowercase());
+    }
+
+    let sorted = KeyAggContext::sorted(&secp, &[keys[1], keys[2], keys[0]]).unwrap();
+    let reversed = KeyAggContext::sorted(&secp, &[keys[0], keys[2], keys[1]]).unwrap();
+    assert_eq!(sorted.aggregate_pubkey(), reversed.aggregate_pubkey());
+}
+
+#[test]
+fn signing_matches_bip327_vectors() {
+    let secp = Secp256k1::new();
+    let secret_key =
+        SecretKey::from_str("7FB9E0E687ADA1EEBF7ECFE2F21E73EBDB51A7D450948DFE8D76D7F2D1007671")
+            .unwrap();
+    let keys = [
+        pubkey("03935F972DA013F80AE011890FA89B67A27B7BE6CCB24D3274D18B2D4067F261A9"),
+        pubkey("02F9308A019258C31049344F85F89D5229B531C845836F99B08601F113BCE036F9"),
+        pubkey("02DFF1D77F2A671C5F36183726DB2341BE58FEAE1DA2DECED843240F7B502BA661"),
+    ];
+    let sec_nonce = concat!(
+        "508B81A611F100A6B2B6B29656590898AF488BCF2E1F55CF22E5CFB84421FE61",
+        "FA27FD49B1D50085B481285E1CA205D55C82CC1B31FF5CD54A489829355901F7",
+        "03935F972DA013F80AE011890FA89B67A27B7BE6CCB24D3274D18B2D4067F261A9",
+    );
+    let nonces = [
+        pub_nonce("0337C87821AFD50A8644D820A8F3E02E499C931865C2360FB43D0A0D20DAFE07EA0287BF891D2A6DEAEBADC909352AA9405D1428C15F4B75F04DAE642A95C2548480"),
+        pub_nonce("0279BE667EF9DCBBAC55A06295CE870B07029BFCDB2DCE28D959F2815B16F817980279BE667EF9DCBBAC55A06295CE870B07029BFCDB2DCE28D959F2815B16F81798"),
+        pub_nonce("032DE2662628C90B03F5E720284EB52FF7D71F4284F627B68A853D78C78E1FFE9303E4C5524E83FFE1493B9077CF1CA6BEB2090C93D930321071AD40B2F44E599046"),
+        pub_nonce("0237C87821AFD50A8644D820A8F3E02E499C931865C2360FB43D0A0D20DAFE07EA0387BF891D2A6DEAEBADC909352AA9405D1428C15F4B75F04DAE642A95C2548480"),
+    ];
+    let msgs = [
+        bytes("F95466D086770E689964664219266FE5ED215C92AE20BAB5C9D79ADDDDF3C0CF"),
+        Vec::new(),
+        bytes("2626262626262626262626262626262626262626262626262626262626262626262626262626"),
+    ];
+    // Key and nonce indices with the signer's first, message index and
+    // partial signature
+    let vectors: [(&[usize], &[usize], usize, &str); 6] = [
+        (
+            &[0, 1, 2],
+            &[0, 1, 2],
+            0,
+            "012ABBCB52B3016AC03AD82395A1A415C48B93DEF78718E62A7A90052FE224FB",
+        ),
+        (
+            &[1, 0, 2],
+            &[1, 0, 2],
+            0,
+            "9FF2F7AAA856150CC8819254218D3ADEEB0535269051897724F9DB3789513A52",
+        ),
+        (
+            &[1, 2, 0],
+            &[1, 2, 0],
+            0,
+            "FA23C359F6FAC4E7796BB93BC9F0532A95468C539BA20FF86D7C76ED92227900",
+        ),
+        // The nonces cancel out to the point at infinity
+        (
+            &[0, 1],
+            &[0, 3],
+            0,
+            "AE386064B26105404798F75DE2EB9AF5EDA5387B064B83D049CB7C5E08879531",
+        ),
+        (
+            &[0, 1, 2],
+            &[0, 1, 2],
+            1,
+            "D7D63FFD644CCDA4E62BC2BC0B1D02DD32A1DC3030E155195810231D1037D82D",
+        ),
+        (
+            &[0, 1, 2],
+            &[0, 1, 2],
+            2,
+            "E184351828DA5094A97C79CABDAAA0BFB87608C32E8829A4DF5340A6F243B78C",
+        ),
+    ];
+    for (key_indices, nonce_indices, msg, expected) in vectors {
+        let pubkeys: Vec<_> = key_indices.iter().map(|i| keys[*i]).collect();
+        let pub_nonces: Vec<_> = nonce_indices.iter().map(|i| nonces[*i]).collect();
+        let signer = key_indices.iter().position(|i| *i == 0).unwrap();
+        let ctx = KeyAggContext::new(&secp, &pubkeys).unwrap();
+        let agg_nonce = AggNonce::new(&pub_nonces).unwrap();
+        let session = Session::new(&secp, &ctx, &agg_nonce, &msgs[msg]);
+
+        let sec_nonce = SecNonce::from_slice(&bytes(sec_nonce)).unwrap();
+        let signature = session.sign(&secp, sec_nonce, &secret_key).unwrap();
+        assert_eq!(signature, partial(expected));
+        assert!(session.verify(&secp, &signature, &pub_nonces[signer], &keys[0]));
+        // Nor does it verify as another signer's
+        let other = (signer + 1) % pubkeys.len();
+        assert!(!session.verify(&secp, &signature, &pub_nonces[other], &pubkeys[other]));
+    }
+
+    // A partial signature of the group order itself
+    assert!(PartialSignature::from_slice(&bytes(
+        "FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFEBAAEDCE6AF48A03BBFD25E8CD0364141"
+    ))
+    .is_err());
+}
+
+#[test]
+fn nonce_aggregation_matches_bip327_vectors() {
+    let nonces = [
+        pub_nonce("020151C80F435648DF67A22B749CD798CE54E0321D034B92B709B567D60A42E66603BA47FBC1834437B3212E89A84D8425E7BF12E0245D98262268EBDCB385D50641"),
+        pub_nonce("03FF406FFD8ADB9CD29877E4985014F66A59F6CD01C0E88CAA8E5F3166B1F676A60248C264CDD57D3C24D79990B0F865674EB62A0F9018277A95011B41BFC193B833"),
+        pub_nonce("020151C80F435648DF67A22B749CD798CE54E0321D034B92B709B567D60A42E6660279BE667EF9DCBBAC55A06295CE870B07029BFCDB2DCE28D959F2815B16F81798"),
+        pub_nonce("03FF406FFD8ADB9CD29877E4985014F66A59F6CD01C0E88CAA8E5F3166B1F676A60379BE667EF9DCBBAC55A06295CE870B07029BFCDB2DCE28D959F2815B16F81798"),
+    ];
+    let vectors = [
+        (
+            &nonces[..2],
+            "035FE1873B4F2967F52FEA4A06AD5A8ECCBE9D0FD73068012C894E2E87CCB5804B024725377345BDE0E9C33AF3C43C0A29A9249F2F2956FA8CFEB55C8573D0262DC8",
+        ),
+        // The second halves cancel out to the point at infinity
+        (
+            &nonces[2..],
+            "035FE1873B4F2967F52FEA4A06AD5A8ECCBE9D0FD73068012C894E2E87CCB5804B000000000000000000000000000000000000000000000000000000000000000000",
+        ),
+    ];
+    for (pub_nonces, expected) in vectors {
+        let agg_nonce = AggNonce::new(pub_nonces).unwrap();
+        assert_eq!(agg_nonce.serialize().to_vec(), bytes(expected));
+    }
+
+    // Not a compressed point
+    assert!(PubNonce::from_slice(&bytes(
+        "04FF406FFD8ADB9CD29877E4985014F66A59F6CD01C0E88CAA8E5F3166B1F676A60248C264CDD57D3C24D79990B0F865674EB62A0F9018277A95011B41BFC193B833"
+    ))
+    .is_err());
+}
+
+#[test]
+fn signature_aggregation_matches_bip327_vectors() {
+    let secp = Secp256k1::new();
+    let keys = [
+        pubkey("03935F972DA013F80AE011890FA89B67A27B7BE6CCB24D3274D18B2D4067F261A9"),
+        pubkey("02D2DC6F5DF7C56ACF38C7FA0AE7A759AE30E19B37359DFDE015872324C7EF6E05"),
+        pubkey("03C7FB101D97FF930ACD0C6760852EF64E69083DE0B06AC6335724754BB4B0522C"),
+        pubkey("02352433B21E7E05D3B452B81CAE566E06D2E003ECE16D1074AABA4289E0E3D581"),
+    ];
+    let tweaks = [
+        "B511DA492182A91B0FFB9A98020D55F260AE86D7ECBD0399C7383D59A5F2AF7C",
+        "A815FE049EE3C5AAB66310477FBC8BCCCAC2F3395F59F921C364ACD78A2F48DC",
+        "75448A87274B056468B977BE06EB1E9F657577B7320B0A3376EA51FD420D18A8",
+    ]
+    .map(|hex| Scalar::from_be_bytes(bytes(hex).try_into().unwrap()).unwrap());
+    let partials = [
+        "B15D2CD3C3D22B04DAE438CE653F6B4ECF042F42CFDED7C41B64AAF9B4AF53FB",
+        "6193D6AC61B354E9105BBDC8937A3454A6D705B6D57322A5A472A02CE99FCB64",
+        "9A87D3B79EC67228CB97878B76049B15DBD05B8158D17B5B9114D3C226887505",
+        "66F82EA90923689B855D36C6B7E032FB9970301481B99E01CDB4D6AC7C347A15",
+        "4F5AEE41510848A6447DCD1BBC78457EF69024944C87F40250D3EF2C25D33EFE",
+        "DDEF427BBB847CC027BEFF4EDB01038148917832253EBC355FC33F4A8E2FCCE4",
+        "97B890A26C981DA8102D3BC294159D171D72810FDF7C6A691DEF02F0F7AF3FDC",
+        "53FA9E08BA5243CBCB0D797C5EE83BC6728E539EB76C2D0BF0F971EE4E909971",
+    ]
+    .map(partial);
+    let msg = bytes("599C67EA410D005B9DA90817CF03ED3B1C868E4DA4EDF00A5880B0082C237869");
+    // Aggregate nonce, key indices, tweak indices with whether each is
+    // x-only, partial signature indices and signature
+    type Vector<'a> = (
+        &'a str,
+        &'a [usize],
+        &'a [(usize, bool)],
+        &'a [usize],
+        &'a str,
+    );
+    let vectors: [Vector; 4] = [
+        (
+            "0341432722C5CD0268D829C702CF0D1CBCE57033EED201FD335191385227C3210C03D377F2D258B64AADC0E16F26462323D701D286046A2EA93365656AFD9875982B",
+            &[0, 1],
+            &[],
+            &[0, 1],
+            "041DA22223CE65C92C9A0D6C2CAC828AAF1EEE56304FEC371DDF91EBB2B9EF0912F1038025857FEDEB3FF696F8B99FA4BB2C5812F6095A2E0004EC99CE18DE1E",
+        ),
+        (
+            "0224AFD36C902084058B51B5D36676BBA4DC97C775873768E58822F87FE437D792028CB15929099EEE2F5DAE404CD39357591BA32E9AF4E162B8D3E7CB5EFE31CB20",
+            &[0, 2],
+            &[],
+            &[2, 3],
+            "1069B67EC3D2F3C7C08291ACCB17A9C9B8F2819A52EB5DF8726E17E7D6B52E9F01800260A7E9DAC450F4BE522DE4CE12BA91AEAF2B4279219EF74BE1D286ADD9",
+        ),
+        (
+            "0208C5C438C710F4F96A61E9FF3C37758814B8C3AE12BFEA0ED2C87FF6954FF186020B1816EA104B4FCA2D304D733E0E19CEAD51303FF6420BFD222335CAA402916D",
+            &[0, 2],
+            &[(0, false)],
+            &[4, 5],
+            "5C558E1DCADE86DA0B2F02626A512E30A22CF5255CAEA7EE32C38E9A71A0E9148BA6C0E6EC7683B64220F0298696F1B878CD47B107B81F7188812D593971E0CC",
+        ),
+        (
+            "02B5AD07AFCD99B6D92CB433FBD2A28FDEB98EAE2EB09B6014EF0F8197CD58403302E8616910F9293CF692C49F351DB86B25E352901F0E237BAFDA11F1C1CEF29FFD",
+            &[0, 3],
+            &[(0, true), (1, false), (2, true)],
+            &[6, 7],
+            "839B08820B681DBA8DAF4CC7B104E8F2638F9388F8D7A555DC17B6E6971D7426CE07BF6AB01F1DB50E4E33719295F4094572B79868E440FB3DEFD3FAC1DB589E",
+        ),
+    ];
+    for (agg_nonce, key_indices, tweak_indices, partial_indices, expected) in vectors {
+        let pubkeys: Vec<_> = key_indices.iter().map(|i| keys[*i]).collect();
+        let mut ctx = KeyAggContext::new(&secp, &pubkeys).unwrap();
+        for (i, x_only) in tweak_indices {
+            ctx = ctx.tweak(&secp, tweaks[*i], *x_only).unwrap();
+        }
+        // Both halves are points, so the nonce aggregates to itself
+        let agg_nonce = AggNonce::new(&[pub_nonce(agg_nonce)]).unwrap();
+        let session = Session::new(&secp, &ctx, &agg_nonce, &msg);
+
+        let shares: Vec<_> = partial_indices.iter().map(|i| partials[*i]).collect();
+        let signature = session.aggregate(&shares).unwrap();
+        assert_eq!(
+            signature,
+            schnorr::Signature::from_slice(&bytes(expected)).unwrap()
+        );
+        let msg = Message::from_digest(msg.clone().try_into().unwrap());
+        secp.verify_schnorr(&signature, &msg, &ctx.output_key())
+            .unwrap();
+    }
+}
+
+#[test]
+fn partial_signatures_aggregate_to_a_bip340_signature() {
+    let secp = Secp256k1::new();
+    let secret_keys: Vec<_> = (0..3).map(|_| SecretKey::new(&mut thread_rng())).collect();
+    let pubkeys: Vec<_> = secret_keys.iter().map(|sk| sk.public_key(&secp)).collect();
+    let msg = [7; 32];
+
+    for ctx in [
+        KeyAggContext::sorted(&secp, &pubkeys).unwrap(),
+        KeyAggContext::sorted(&secp, &pubkeys)
+            .unwrap()
+            .taproot_tweaked(&secp, None)
+            .unwrap(),
+    ] {
+        let output_key = ctx.output_key();
+        let (sec_nonces, pub_nonces): (Vec<_>, Vec<_>) = secret_keys
+            .iter()
+            .zip(&pubkeys)
+            .map(|(sk, pk)| {
+                nonce_gen(&secp, Some(sk), pk, Some(&output_key), Some(&msg), None).unwrap()
+            })
+            .unzip();
+        let session = Session::new(&secp, &ctx, &AggNonce::new(&pub_nonces).unwrap(), &msg);
+
+        let partials: Vec<_> = sec_nonces
+            .into_iter()
+            .zip(&secret_keys)
+            .map(|(sec_nonce, sk)| session.sign(&secp, sec_nonce, sk).unwrap())
+            .collect();
+        assert!(!session.verify(&secp, &partials[0], &pub_nonces[1], &pubkeys[1]));
+
+        let signature = session.aggregate(&partials).unwrap();
+        secp.verify_schnorr(&signature, &Message::from_digest(msg), &output_key)
+            .unwrap();
+    }
+}
+
+#[test]
+fn key_path_spend_of_the_aggregate_looks_like_single_sig() {
+    let secp = Secp256k1::new();
+    let sim = Simulator::new();
+    let wallet = Wallet::open(&sim, "w0").unwrap();
+    let mining = wallet.new_address(&AddressType::Bech32).unwrap();
+    sim.generate_to_address(101, &mining).unwrap();
+
+    let secret_keys: Vec<_> = (0..3).map(|_| SecretKey::new(&mut thread_rng())).collect();
+    let pubkeys: Vec<_> = secret_keys.iter().map(|sk| sk.public_key(&secp)).collect();
+    let ctx = KeyAggContext::sorted(&secp, &pubkeys).unwrap();
+    let address = ctx.address(&secp, wallet.network);
+    let outpoint = wallet
+        .send(
+            &address,
+            Amount::from_sat(100_000),
+            &SpendOptions::default(),
+        )
+        .unwrap();
+    sim.generate_to_address(1, &mining).unwrap();
+
+    let tx = Transaction {
+        version: Version::TWO,
+        lock_time: LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: outpoint,
+            sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+            ..Default::default()
+        }],
+        output: vec![TxOut {
+            value: Amount::from_sat(99_000),
+            script_pubkey: mining.script_pubkey(),
+        }],
+    };
+    let mut psbt = Psbt::from_unsigned_tx(tx).unwrap();
+    psbt.inputs[0].witness_utxo = Some(TxOut {
+        value: Amount::from_sat(100_000),
+        script_pubkey: address.script_pubkey(),
+    });
+    add_musig_participants(&mut psbt, 0, &ctx, None).unwrap();
+
+    // Each party only sees the PSBT, as if it were passed around
+    let mut sec_nonces = Vec::new();
+    for sk in &secret_keys {
+        sec_nonces.push(musig_nonce_round(&secp, &mut psbt, sk).unwrap());
+    }
+    assert!(musig_finalize(&secp, &mut psbt.clone()).is_err());
+    for (sk, sec_nonces) in secret_keys.iter().zip(sec_nonces) {
+        musig_sign_round(&secp, &mut psbt, sk, sec_nonces).unwrap();
+    }
+    musig_finalize(&secp, &mut psbt).unwrap();
+
+    let tx = psbt.extract_tx().unwrap();
+    assert_eq!(tx.input[0].witness.len(), 1);
+    assert_eq!(tx.input[0].witness.to_vec()[0].len(), 64);
+    assert_eq!(sim.send_raw_transaction(&tx).unwrap(), tx.txid());
+}
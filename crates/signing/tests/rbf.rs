@@ -0,0 +1,102 @@
+use std::str::FromStr;
+
+use bitcoin::{Amount, FeeRate, Psbt};
+use bitcoincore_rpc::json::AddressType;
+
+use satoshi_suite_client::{ChainBackend, Simulator};
+use satoshi_suite_signing::{bump_fee, Replacement};
+use satoshi_suite_utxo_selection::{UTXOStrategy, DEFAULT_LONG_TERM_FEE_RATE};
+use satoshi_suite_wallet::{
+    ChangePolicy, Cosigner, MultisigScript, MultisigWallet, Payment, SpendOptions, Wallet,
+};
+
+/// Have every cosigner sign `psbt` and finalize it through the watch-only
+/// wallet `multisig`.
+fn sign(sim: &Simulator, multisig: &Wallet<Simulator>, mut psbt: String) -> Psbt {
+    for cosigner in ["alice", "bob"] {
+        psbt = Wallet::open(sim, cosigner)
+            .unwrap()
+            .process_psbt(&psbt)
+            .unwrap()
+            .psbt;
+    }
+    let processed = multisig.process_psbt(&psbt).unwrap();
+    assert!(processed.complete);
+    Psbt::from_str(&processed.psbt).unwrap()
+}
+
+#[test]
+fn bump_of_multisig_payment_adds_inputs_sized_like_the_wallet() {
+    let sim = Simulator::new();
+    let miner = Wallet::open(&sim, "miner").unwrap();
+    let mining = miner.new_address(&AddressType::Bech32).unwrap();
+    sim.generate_to_address(101, &mining).unwrap();
+
+    let cosigners = [
+        Cosigner::Wallet("alice".to_string()),
+        Cosigner::Wallet("bob".to_string()),
+    ];
+    for cosigner in ["alice", "bob"] {
+        Wallet::open(&sim, cosigner).unwrap();
+    }
+    MultisigWallet::new(&sim, &cosigners, 2, MultisigScript::Wsh, "multi").unwrap();
+    let multisig = Wallet::open(&sim, "multi").unwrap();
+    for sats in [200_000, 100_000] {
+        let address = multisig.new_address(&AddressType::Bech32).unwrap();
+        miner
+            .send(&address, Amount::from_sat(sats), &SpendOptions::default())
+            .unwrap();
+    }
+    sim.generate_to_address(1, &mining).unwrap();
+
+    // Little enough change that a much higher fee needs another coin
+    let payment = Payment {
+        address: miner.new_address(&AddressType::Bech32).unwrap(),
+        amount: Amount::from_sat(199_000),
+    };
+    let funded = MultisigWallet::create_batch_psbt(
+        &multisig,
+        &[payment],
+        FeeRate::from_sat_per_vb_unchecked(1),
+        DEFAULT_LONG_TERM_FEE_RATE,
+        &UTXOStrategy::LargestFirst,
+        &ChangePolicy::default(),
+        &SpendOptions::default(),
+    )
+    .unwrap();
+    let original = sign(&sim, &multisig, funded.psbt).extract_tx().unwrap();
+    let txid = sim.send_raw_transaction(&original).unwrap();
+
+    let bump = bump_fee(
+        &multisig,
+        &txid,
+        FeeRate::from_sat_per_vb_unchecked(50),
+        DEFAULT_LONG_TERM_FEE_RATE,
+        &UTXOStrategy::LargestFirst,
+    )
+    .unwrap();
+    assert!(bump.fee > bump.original_fee);
+    let Replacement::Psbt(psbt) = bump.replacement else {
+        panic!("a watch-only wallet gets a PSBT");
+    };
+    let replacement = sign(&sim, &multisig, psbt).extract_tx().unwrap();
+    assert_eq!(replacement.input.len(), 2);
+    let replacement_txid = sim.send_raw_transaction(&replacement).unwrap();
+    assert_eq!(sim.mempool(), vec![replacement]);
+
+    // Neither the replaced nor the confirmed transaction can be bumped
+    for txid in [txid, replacement_txid] {
+        if txid == replacement_txid {
+            sim.generate_to_address(1, &mining).unwrap();
+        }
+        let err = bump_fee(
+            &multisig,
+            &txid,
+            FeeRate::from_sat_per_vb_unchecked(100),
+            DEFAULT_LONG_TERM_FEE_RATE,
+            &UTXOStrategy::LargestFirst,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("not in the mempool"), "{}", err);
+    }
+}
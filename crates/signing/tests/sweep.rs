@@ -0,0 +1,210 @@
+use std::{collections::HashSet, slice};
+
+use bitcoin::{
+    absolute::LockTime, transaction::Version, Address, Amount, FeeRate, OutPoint, Sequence,
+    Transaction, TxIn, TxOut,
+};
+use bitcoincore_rpc::json::AddressType;
+
+use satoshi_suite_client::{ChainBackend, Simulator};
+use satoshi_suite_signing::{consolidate, sweep, MAX_STANDARD_TX_WEIGHT};
+use satoshi_suite_wallet::Wallet;
+
+const FEE_RATE: FeeRate = FeeRate::from_sat_per_vb_unchecked(2);
+
+/// A simulator with a mining wallet and an empty wallet `alice`.
+fn setup() -> (Simulator, Wallet<Simulator>, Wallet<Simulator>) {
+    let sim = Simulator::new();
+    let miner = Wallet::open(&sim, "miner").unwrap();
+    let mining = miner.new_address(&AddressType::Bech32).unwrap();
+    sim.generate_to_address(101, &mining).unwrap();
+    let alice = Wallet::open(&sim, "alice").unwrap();
+    (sim, miner, alice)
+}
+
+/// Confirm a coin of each of `amounts` paying `wallet`, all from one
+/// transaction of the miner.
+fn fund(sim: &Simulator, miner: &Wallet<Simulator>, wallet: &Wallet<Simulator>, amounts: &[u64]) {
+    let coin = miner
+        .spendable_unspent()
+        .unwrap()
+        .into_iter()
+        .max_by_key(|coin| coin.amount)
+        .unwrap();
+    let address = wallet.new_address(&AddressType::Bech32).unwrap();
+    let mut output: Vec<TxOut> = amounts
+        .iter()
+        .map(|sats| TxOut {
+            value: Amount::from_sat(*sats),
+            script_pubkey: address.script_pubkey(),
+        })
+        .collect();
+    let paid: Amount = output.iter().map(|output| output.value).sum();
+    output.push(TxOut {
+        value: coin.amount - paid - Amount::from_sat(200_000),
+        script_pubkey: miner
+            .new_address(&AddressType::Bech32)
+            .unwrap()
+            .script_pubkey(),
+    });
+    let tx = Transaction {
+        version: Version::TWO,
+        lock_time: LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: OutPoint::new(coin.txid, coin.vout),
+            sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+            ..Default::default()
+        }],
+        output,
+    };
+    sim.send_raw_transaction(&miner.sign_tx(&tx).unwrap())
+        .unwrap();
+    let mining = miner.new_address(&AddressType::Bech32).unwrap();
+    sim.generate_to_address(1, &mining).unwrap();
+}
+
+fn recipient(sim: &Simulator) -> Address {
+    Wallet::open(sim, "bob")
+        .unwrap()
+        .new_address(&AddressType::Bech32)
+        .unwrap()
+}
+
+#[test]
+fn sweep_takes_the_fee_from_its_single_output() {
+    let (sim, miner, alice) = setup();
+    fund(&sim, &miner, &alice, &[100_000, 250_000, 40_000]);
+    let to = recipient(&sim);
+
+    let swept = sweep(&alice, slice::from_ref(&to), FEE_RATE).unwrap();
+    assert_eq!(swept.tx.input.len(), 3);
+    assert_eq!(swept.tx.output.len(), 1);
+    assert_eq!(swept.tx.output[0].script_pubkey, to.script_pubkey());
+    assert_eq!(
+        swept.tx.output[0].value,
+        Amount::from_sat(390_000) - swept.fee
+    );
+    // Estimated no lighter than the signed transaction
+    assert!(swept.fee >= FEE_RATE.fee_wu(swept.tx.weight()).unwrap());
+
+    sim.send_raw_transaction(&swept.tx).unwrap();
+    assert!(alice.spendable_unspent().unwrap().is_empty());
+}
+
+#[test]
+fn sweep_of_dust_or_nothing_is_refused() {
+    let (sim, miner, alice) = setup();
+    let to = recipient(&sim);
+    let err = sweep(&alice, slice::from_ref(&to), FEE_RATE).unwrap_err();
+    assert!(
+        err.to_string().contains("No coins worth spending"),
+        "{}",
+        err
+    );
+
+    // Each coin pays for itself but not for a share above dust for everyone
+    fund(&sim, &miner, &alice, &[2_000]);
+    let recipients = vec![to; 10];
+    let err = sweep(&alice, &recipients, FEE_RATE).unwrap_err();
+    assert!(
+        err.to_string().contains("too small to pay 10 recipients"),
+        "{}",
+        err
+    );
+
+    // At this rate spending the coin costs more than it holds
+    let err = sweep(
+        &alice,
+        &recipients[..1],
+        FeeRate::from_sat_per_vb_unchecked(50),
+    )
+    .unwrap_err();
+    assert!(
+        err.to_string().contains("No coins worth spending"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn consolidation_leaves_coins_at_or_above_the_threshold() {
+    let (sim, miner, alice) = setup();
+    fund(
+        &sim,
+        &miner,
+        &alice,
+        &[10_000, 20_000, 30_000, 100_000, 500_000],
+    );
+
+    let merged = consolidate(&alice, Amount::from_sat(100_000), FEE_RATE, 100).unwrap();
+    assert_eq!(merged.len(), 1);
+    let tx = &merged[0].tx;
+    assert_eq!(tx.input.len(), 3);
+    assert_eq!(tx.output.len(), 1);
+    assert_eq!(tx.output[0].value, Amount::from_sat(60_000) - merged[0].fee);
+    let spent: HashSet<Amount> = alice
+        .spendable_unspent()
+        .unwrap()
+        .into_iter()
+        .filter(|utxo| {
+            tx.input
+                .iter()
+                .any(|input| input.previous_output == OutPoint::new(utxo.txid, utxo.vout))
+        })
+        .map(|utxo| utxo.amount)
+        .collect();
+    assert_eq!(
+        spent,
+        HashSet::from([10_000, 20_000, 30_000].map(Amount::from_sat))
+    );
+    sim.send_raw_transaction(tx).unwrap();
+}
+
+#[test]
+fn consolidation_without_two_coins_worth_merging_is_refused() {
+    let (sim, miner, alice) = setup();
+    fund(&sim, &miner, &alice, &[10_000, 500_000]);
+    let err = consolidate(&alice, Amount::from_sat(100_000), FEE_RATE, 100).unwrap_err();
+    assert!(err.to_string().contains("Fewer than two coins"), "{}", err);
+
+    // Worth less than spending them at this rate
+    fund(&sim, &miner, &alice, &[1_000, 1_000, 1_000]);
+    let fee_rate = FeeRate::from_sat_per_vb_unchecked(20);
+    let err = consolidate(&alice, Amount::from_sat(5_000), fee_rate, 100).unwrap_err();
+    assert!(err.to_string().contains("Fewer than two coins"), "{}", err);
+}
+
+#[test]
+fn consolidation_splits_at_max_inputs_and_skips_a_lone_coin() {
+    let (sim, miner, alice) = setup();
+    fund(&sim, &miner, &alice, &[10_000; 7]);
+
+    let merged = consolidate(&alice, Amount::from_sat(100_000), FEE_RATE, 3).unwrap();
+    let inputs: Vec<usize> = merged.iter().map(|merge| merge.tx.input.len()).collect();
+    assert_eq!(inputs, [3, 3]);
+    for merge in &merged {
+        sim.send_raw_transaction(&merge.tx).unwrap();
+    }
+}
+
+#[test]
+fn consolidation_splits_at_the_standard_weight() {
+    let (sim, miner, alice) = setup();
+    fund(&sim, &miner, &alice, &[10_000; 1_600]);
+
+    let merged = consolidate(&alice, Amount::from_sat(100_000), FEE_RATE, usize::MAX).unwrap();
+    assert_eq!(merged.len(), 2);
+    assert_eq!(
+        merged
+            .iter()
+            .map(|merge| merge.tx.input.len())
+            .sum::<usize>(),
+        1_600
+    );
+    for merge in &merged {
+        assert!(merge.tx.weight() <= MAX_STANDARD_TX_WEIGHT);
+    }
+    // The first is filled before the second starts
+    assert!(merged[0].tx.input.len() > merged[1].tx.input.len());
+    assert!(merged[0].tx.weight() > MAX_STANDARD_TX_WEIGHT * 9 / 10);
+}
@@ -0,0 +1,82 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use bitcoin::{hashes::Hash, Amount, FeeRate, ScriptBuf, Txid, WPubkeyHash};
+use bitcoincore_rpc::json::ListUnspentResultEntry;
+
+use satoshi_suite_utxo_selection::{
+    candidates, select_coins_bnb, strat_handler, CoinSelectionParams, UTXOStrategy,
+};
+
+const POOL_SIZES: [usize; 3] = [100, 1_000, 5_000];
+
+/// A wallet of P2WPKH outputs between 1,000 sats and 0.1 BTC, spread evenly
+/// over orders of magnitude like a long-lived wallet's.
+fn utxo_pool(size: usize, rng: &mut StdRng) -> Vec<ListUnspentResultEntry> {
+    (0..size)
+        .map(|i| {
+            let sats = 10f64.powf(rng.gen_range(3.0..7.0)) as u64;
+            ListUnspentResultEntry {
+                txid: Txid::from_byte_array(rng.gen()),
+                vout: i as u32,
+                address: None,
+                label: None,
+                redeem_script: None,
+                witness_script: None,
+                script_pub_key: ScriptBuf::new_p2wpkh(&WPubkeyHash::from_byte_array(rng.gen())),
+                amount: Amount::from_sat(sats),
+                confirmations: 6,
+                spendable: true,
+                solvable: true,
+                descriptor: None,
+                safe: true,
+            }
+        })
+        .collect()
+}
+
+fn bench_branch_and_bound(c: &mut Criterion) {
+    let fee_rate = FeeRate::from_sat_per_vb_unchecked(10);
+    let params = CoinSelectionParams {
+        fee_rate,
+        long_term_fee_rate: fee_rate,
+        base_fee: Amount::from_sat(1_000),
+        change_fee: Amount::from_sat(310),
+        change_spend_fee: Amount::from_sat(680),
+        min_change: Amount::from_sat(294),
+    };
+    let target = Amount::from_sat(2_500_000);
+
+    let mut group = c.benchmark_group("branch_and_bound");
+    for size in POOL_SIZES {
+        let utxos = utxo_pool(size, &mut StdRng::seed_from_u64(size as u64));
+
+        // The whole search, including the fallback when no changeless set exists
+        group.bench_with_input(
+            BenchmarkId::new("strat_handler", size),
+            &utxos,
+            |b, utxos| {
+                b.iter(|| {
+                    strat_handler(
+                        black_box(utxos),
+                        target,
+                        &params,
+                        &UTXOStrategy::BranchAndBound,
+                    )
+                })
+            },
+        );
+
+        // The search alone, bounded by its iteration cap however large the pool
+        let candidates = candidates(&utxos, &params);
+        group.bench_with_input(
+            BenchmarkId::new("select_coins_bnb", size),
+            &candidates,
+            |b, candidates| b.iter(|| select_coins_bnb(black_box(candidates), target, &params)),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_branch_and_bound);
+criterion_main!(benches);
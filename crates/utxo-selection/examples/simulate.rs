@@ -0,0 +1,44 @@
+use std::env;
+
+use satoshi_suite_utxo_selection::{
+    simulate, CoinSelector, Scenario, UTXOStrategy, DEFAULT_LONG_TERM_FEE_RATE,
+};
+
+/// Replays a random scenario against every built-in strategy and prints how
+/// each fared. The seed drives the strategies' random choices too, so runs
+/// are reproducible.
+///
+/// cargo run -p satoshi-suite-utxo-selection --example simulate [seed] [events]
+fn main() {
+    let mut args = env::args().skip(1);
+    let seed = args
+        .next()
+        .map_or(0, |seed| seed.parse().expect("seed is a number"));
+    let events = args
+        .next()
+        .map_or(1_000, |events| events.parse().expect("events is a number"));
+
+    let scenario = Scenario::random(seed, events);
+    println!(
+        "{:<20} {:>8} {:>8} {:>14} {:>14} {:>8} {:>10} {:>10}",
+        "strategy", "funded", "failed", "fees", "waste", "change", "max utxos", "end utxos"
+    );
+    for strategy in UTXOStrategy::ALL {
+        let report = simulate(
+            &scenario,
+            &strategy.seeded(seed),
+            DEFAULT_LONG_TERM_FEE_RATE,
+        );
+        println!(
+            "{:<20} {:>8} {:>8} {:>14} {:>14} {:>8} {:>10} {:>10}",
+            strategy.name(),
+            report.withdrawals - report.failed_withdrawals,
+            report.failed_withdrawals,
+            report.fees_paid.to_sat(),
+            report.waste.to_sat(),
+            report.change_outputs,
+            report.max_pool_size(),
+            report.pool_sizes.last().copied().unwrap_or(0),
+        );
+    }
+}
@@ -0,0 +1,91 @@
+use bitcoin::{Amount, SignedAmount};
+
+use crate::{signed, Candidate, CoinSelectionParams};
+
+/// Upper bound on the branches the search visits, as in Bitcoin Core.
+pub const BNB_TOTAL_TRIES: usize = 100_000;
+
+/// Depth-first Branch and Bound after Bitcoin Core's `SelectCoinsBnB`.
+///
+/// Looks for a set of candidates whose effective values add up to between
+/// `target` and `target + cost_of_change`, so the transaction needs no change
+/// output, preferring the set with the least [`waste`](crate::waste). Returns `None` if no
+/// such set is found within [`BNB_TOTAL_TRIES`] steps.
+pub fn select_coins_bnb(
+    candidates: &[Candidate],
+    target: Amount,
+    params: &CoinSelectionParams,
+) -> Option<Vec<Candidate>> {
+    let mut pool = candidates.to_vec();
+    pool.sort_by_key(|c| std::cmp::Reverse(c.effective_value));
+
+    let mut available: Amount = pool.iter().map(|c| c.effective_value).sum();
+    if available < target {
+        return None;
+    }
+
+    // Spending inputs now rather than later only adds waste when fees are
+    // higher than in the long run
+    let fees_high = params.fee_rate > params.long_term_fee_rate;
+    let upper_bound = target + params.cost_of_change();
+    let mut value = Amount::ZERO;
+    let mut timing_cost = SignedAmount::ZERO;
+    // Positions in `pool` of the candidates on the current branch
+    let mut selection: Vec<usize> = Vec::new();
+    let mut best: Option<(Vec<usize>, SignedAmount)> = None;
+
+    let mut index = 0;
+    for _ in 0..BNB_TOTAL_TRIES {
+        let mut backtrack = false;
+        if value + available < target
+            || value > upper_bound
+            || fees_high && best.as_ref().is_some_and(|(_, waste)| timing_cost > *waste)
+        {
+            // Cannot reach the target, already past the window, or only
+            // getting more wasteful than the best solution
+            backtrack = true;
+        } else if value >= target {
+            // Without change the excess goes to the fee
+            let waste = timing_cost + signed(value - target);
+            if best
+                .as_ref()
+                .map_or(true, |(_, best_waste)| waste <= *best_waste)
+            {
+                best = Some((selection.clone(), waste));
+            }
+            backtrack = true;
+        }
+
+        if backtrack {
+            let Some(&last) = selection.last() else {
+                // Every branch has been explored
+                break;
+            };
+            // Put the candidates skipped after the last inclusion back in reach,
+            // then explore the branch that omits it
+            index -= 1;
+            while index > last {
+                available += pool[index].effective_value;
+                index -= 1;
+            }
+            value -= pool[last].effective_value;
+            timing_cost -= pool[last].timing_cost();
+            selection.pop();
+        } else {
+            let candidate = pool[index];
+            available -= candidate.effective_value;
+            // Omitting a candidate and then including an equal one next would
+            // repeat the branch just explored
+            let repeats_omitted = selection.last().is_some_and(|&last| last != index - 1)
+                && pool[index - 1].effective_value == candidate.effective_value;
+            if !repeats_omitted {
+                selection.push(index);
+                value += candidate.effective_value;
+                timing_cost += candidate.timing_cost();
+            }
+        }
+        index += 1;
+    }
+
+    best.map(|(selection, _)| selection.into_iter().map(|i| pool[i]).collect())
+}
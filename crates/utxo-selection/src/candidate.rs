@@ -0,0 +1,84 @@
+use std::collections::HashMap;
+
+use bitcoin::{Amount, SignedAmount, Weight};
+use bitcoincore_rpc::json::ListUnspentResultEntry;
+
+use satoshi_suite_client::utxo_satisfaction_weight;
+
+use crate::{signed, CoinSelectionParams};
+
+/// Weight of an input before its script_sig and witness: outpoint, sequence,
+/// an empty script_sig and an empty witness.
+pub const INPUT_BASE_WEIGHT: Weight = Weight::from_wu(4 * 41 + 1);
+
+/// A UTXO worth spending at the current fee rate, with its value net of the
+/// fee its input costs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Candidate {
+    /// Position of the UTXO in the list it was taken from.
+    pub index: usize,
+    pub effective_value: Amount,
+    /// Fee for the input at the current fee rate.
+    pub fee: Amount,
+    /// Fee for the same input at the long-term fee rate.
+    pub long_term_fee: Amount,
+    /// Position of the first UTXO on the same address, shared by every
+    /// UTXO on it.
+    pub address: usize,
+    /// Position of the first UTXO received under the same label, or the
+    /// first unlabelled one.
+    pub label: usize,
+}
+
+impl Candidate {
+    /// What spending the input now costs over spending it at the long-term
+    /// fee rate.
+    pub fn timing_cost(&self) -> SignedAmount {
+        signed(self.fee) - signed(self.long_term_fee)
+    }
+}
+
+/// Full weight of the input spending `utxo`, if its script can be sized.
+pub fn input_weight(utxo: &ListUnspentResultEntry) -> Option<Weight> {
+    utxo_satisfaction_weight(utxo).map(|satisfaction| INPUT_BASE_WEIGHT + satisfaction)
+}
+
+/// The UTXOs with a positive effective value at the fee rate in `params`.
+/// UTXOs that cost more to spend than they are worth, or whose size is
+/// unknown, are left out.
+pub fn candidates(
+    utxos: &[ListUnspentResultEntry],
+    params: &CoinSelectionParams,
+) -> Vec<Candidate> {
+    let mut addresses = HashMap::new();
+    let mut labels = HashMap::new();
+    utxos
+        .iter()
+        .enumerate()
+        .filter_map(|(index, utxo)| {
+            let address = *addresses.entry(&utxo.script_pub_key).or_insert(index);
+            let label = *labels.entry(utxo_label(utxo)).or_insert(index);
+            let weight = input_weight(utxo)?;
+            // Whole vbytes, so the fees of the parts of a transaction never
+            // add up to less than the fee of the whole
+            let vsize = weight.to_vbytes_ceil();
+            let fee = params.fee_rate.fee_vb(vsize)?;
+            let long_term_fee = params.long_term_fee_rate.fee_vb(vsize)?;
+            let effective_value = utxo.amount.checked_sub(fee).filter(|v| *v > Amount::ZERO)?;
+            Some(Candidate {
+                index,
+                effective_value,
+                fee,
+                long_term_fee,
+                address,
+                label,
+            })
+        })
+        .collect()
+}
+
+/// The label `utxo` was received under, with no label and an empty one
+/// treated alike.
+pub fn utxo_label(utxo: &ListUnspentResultEntry) -> Option<&str> {
+    utxo.label.as_deref().filter(|label| !label.is_empty())
+}
@@ -0,0 +1,123 @@
+use bitcoin::Amount;
+use rand::{seq::SliceRandom, Rng};
+
+use crate::Candidate;
+
+/// Random subsets [`select_coins_knapsack`] tries, as in Bitcoin Core.
+pub const KNAPSACK_ITERATIONS: usize = 1_000;
+
+/// Knapsack solver after Bitcoin Core's `KnapsackSolver`.
+///
+/// Looks for an exact match for `target` first, otherwise approximates the
+/// subset of the smaller candidates that overshoots `target + min_change`
+/// least, and settles for the smallest single candidate larger than that
+/// when it comes closer.
+pub fn select_coins_knapsack<R: Rng + ?Sized>(
+    candidates: &[Candidate],
+    target: Amount,
+    min_change: Amount,
+    rng: &mut R,
+) -> Option<Vec<Candidate>> {
+    let mut pool = candidates.to_vec();
+    pool.shuffle(rng);
+
+    let mut applicable = Vec::new();
+    let mut total_lower = Amount::ZERO;
+    let mut lowest_larger: Option<Candidate> = None;
+    for candidate in pool {
+        if candidate.effective_value == target {
+            return Some(vec![candidate]);
+        } else if candidate.effective_value < target + min_change {
+            total_lower += candidate.effective_value;
+            applicable.push(candidate);
+        } else if lowest_larger.map_or(true, |c| candidate.effective_value < c.effective_value) {
+            lowest_larger = Some(candidate);
+        }
+    }
+
+    if total_lower == target {
+        return Some(applicable);
+    }
+    if total_lower < target {
+        return lowest_larger.map(|c| vec![c]);
+    }
+
+    applicable.sort_by_key(|c| std::cmp::Reverse(c.effective_value));
+    let (mut best, mut best_value) = approximate_best_subset(&applicable, total_lower, target, rng);
+    if best_value != target && total_lower >= target + min_change {
+        (best, best_value) =
+            approximate_best_subset(&applicable, total_lower, target + min_change, rng);
+    }
+
+    // A single larger coin wins if the subset leaves too little for change or
+    // is no closer to the target
+    if let Some(larger) = lowest_larger {
+        if (best_value != target && best_value < target + min_change)
+            || larger.effective_value <= best_value
+        {
+            return Some(vec![larger]);
+        }
+    }
+
+    Some(
+        applicable
+            .into_iter()
+            .zip(best)
+            .filter_map(|(candidate, included)| included.then_some(candidate))
+            .collect(),
+    )
+}
+
+/// Stochastic approximation of the subset of `pool`, which adds up to
+/// `total`, that reaches `target` with the least overshoot. Each pass includes
+/// candidates at random, then fills in the rest in order until the target is
+/// reached.
+fn approximate_best_subset<R: Rng + ?Sized>(
+    pool: &[Candidate],
+    total: Amount,
+    target: Amount,
+    rng: &mut R,
+) -> (Vec<bool>, Amount) {
+    let mut best = vec![true; pool.len()];
+    let mut best_value = total;
+
+    for _ in 0..KNAPSACK_ITERATIONS {
+        if best_value == target {
+            break;
+        }
+
+        let mut included = vec![false; pool.len()];
+        let mut value = Amount::ZERO;
+        let mut reached = false;
+        for pass in 0..2 {
+            if reached {
+                break;
+            }
+            for (i, candidate) in pool.iter().enumerate() {
+                let include = if pass == 0 {
+                    rng.gen_bool(0.5)
+                } else {
+                    !included[i]
+                };
+                if !include {
+                    continue;
+                }
+
+                value += candidate.effective_value;
+                included[i] = true;
+                if value >= target {
+                    reached = true;
+                    if value < best_value {
+                        best_value = value;
+                        best.clone_from(&included);
+                    }
+                    // Try the remaining candidates in place of this one
+                    value -= candidate.effective_value;
+                    included[i] = false;
+                }
+            }
+        }
+    }
+
+    (best, best_value)
+}
@@ -1,29 +1,81 @@
-use std::{collections::VecDeque, error::Error, fmt};
+use std::{error::Error, fmt};
 
-use bitcoin::Amount;
+use bitcoin::{Amount, OutPoint};
 use bitcoincore_rpc::json::ListUnspentResultEntry;
+use rand::{rngs::StdRng, thread_rng, Rng, SeedableRng};
+
+use satoshi_suite_client::ClientError;
+
+mod branch_and_bound;
+pub use branch_and_bound::*;
+
+mod candidate;
+pub use candidate::*;
+
+mod knapsack;
+pub use knapsack::*;
+
+mod privacy;
+pub use privacy::*;
+
+mod protection;
+pub use protection::*;
+
+mod selection;
+pub use selection::*;
+
+mod selector;
+pub use selector::*;
+
+mod simulation;
+pub use simulation::*;
+
+mod single_random_draw;
+pub use single_random_draw::*;
 
 #[derive(Debug)]
 pub enum UtilsError {
+    AssetIndex(ClientError),
     ExternalXpubNotFound,
     InternalXpubNotFound,
     InsufficientUTXOs,
+    InvalidSelection(String, String),
     JsonParsingError(serde_json::Error),
+    UneconomicUTXO(OutPoint),
 }
 
 impl fmt::Display for UtilsError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
+            UtilsError::AssetIndex(e) => write!(f, "Asset index error: {}", e),
             UtilsError::ExternalXpubNotFound => write!(f, "External xpub descriptor not found"),
             UtilsError::InternalXpubNotFound => write!(f, "Internal xpub descriptor not found"),
             UtilsError::InsufficientUTXOs => write!(f, "Insufficient UTXOs to meet target amount"),
+            UtilsError::InvalidSelection(selector, reason) => {
+                write!(
+                    f,
+                    "Coin selector {} made an invalid selection: {}",
+                    selector, reason
+                )
+            }
             UtilsError::JsonParsingError(e) => write!(f, "JSON parsing error: {}", e),
+            UtilsError::UneconomicUTXO(outpoint) => write!(
+                f,
+                "UTXO {} costs more to spend than it holds, or cannot be sized",
+                outpoint
+            ),
         }
     }
 }
 
 impl Error for UtilsError {}
 
+impl From<ClientError> for UtilsError {
+    fn from(err: ClientError) -> Self {
+        UtilsError::AssetIndex(err)
+    }
+}
+
 impl From<serde_json::Error> for UtilsError {
     fn from(err: serde_json::Error) -> Self {
         UtilsError::JsonParsingError(err)
@@ -37,138 +89,281 @@ pub enum UTXOStrategy {
     LargestFirst,
     SmallestFirst,
     SingleUTXO,
+    /// Bitcoin Core's knapsack solver over randomly ordered UTXOs.
+    Knapsack,
+    /// UTXOs in random order until they pay for change.
+    SingleRandomDraw,
+    /// Whichever of the other strategies wastes least.
+    LeastWaste,
+    /// Whole address groups under as few labels as possible.
+    Privacy,
 }
 
-pub fn strat_handler(
-    utxos: &[ListUnspentResultEntry],
-    target_amount: Amount,
-    fee_amount: Amount,
-    utxo_strategy: UTXOStrategy,
-) -> Result<Vec<ListUnspentResultEntry>, UtilsError> {
-    match utxo_strategy {
-        UTXOStrategy::BranchAndBound => {
-            select_utxos_branch_and_bound(utxos, target_amount, fee_amount)
-                .ok_or(UtilsError::InsufficientUTXOs)
-        }
-        UTXOStrategy::Fifo => select_utxos_fifo(utxos, target_amount, fee_amount),
-        UTXOStrategy::LargestFirst => select_utxos_largest_first(utxos, target_amount, fee_amount),
-        UTXOStrategy::SmallestFirst => {
-            select_utxos_smallest_first(utxos, target_amount, fee_amount)
+impl UTXOStrategy {
+    pub const ALL: [UTXOStrategy; 9] = [
+        UTXOStrategy::BranchAndBound,
+        UTXOStrategy::Fifo,
+        UTXOStrategy::LargestFirst,
+        UTXOStrategy::SmallestFirst,
+        UTXOStrategy::SingleUTXO,
+        UTXOStrategy::Knapsack,
+        UTXOStrategy::SingleRandomDraw,
+        UTXOStrategy::LeastWaste,
+        UTXOStrategy::Privacy,
+    ];
+
+    /// The strategies [`UTXOStrategy::LeastWaste`] chooses between.
+    const CONCRETE: [UTXOStrategy; 7] = [
+        UTXOStrategy::BranchAndBound,
+        UTXOStrategy::Fifo,
+        UTXOStrategy::LargestFirst,
+        UTXOStrategy::SmallestFirst,
+        UTXOStrategy::SingleUTXO,
+        UTXOStrategy::Knapsack,
+        UTXOStrategy::SingleRandomDraw,
+    ];
+
+    /// This strategy with its random choices drawn from `seed`, so the same
+    /// UTXOs always give the same selection.
+    pub fn seeded(self, seed: u64) -> SeededStrategy {
+        SeededStrategy {
+            strategy: self,
+            seed,
         }
-        UTXOStrategy::SingleUTXO => select_single_utxo(utxos, target_amount, fee_amount),
     }
-}
 
-fn select_utxos_branch_and_bound(
-    utxos: &[ListUnspentResultEntry],
-    target_amount: Amount,
-    fee_amount: Amount,
-) -> Option<Vec<ListUnspentResultEntry>> {
-    let mut current_best_solution = None;
-    let mut current_best_change = Amount::from_sat(u64::MAX);
-
-    // the queue is a "vector double ended queue" that allows us to add and remove
-    // elements from both ends of the vector
-    let mut queue: VecDeque<(Vec<ListUnspentResultEntry>, Amount)> = VecDeque::new();
-
-    // add the first element to the queue
-    queue.push_back((Vec::new(), Amount::from_sat(0)));
-
-    // This while loop uses a breadth-first search approach to explore all possible combinations of UTXOs.
-    // It continually checks if the current combination is sufficient to cover the target amount plus fees
-    // and updates the best solution found so far. If a combination is not sufficient, it expands the search
-    // by adding more UTXOs to the combination and continues the process until all possibilities have been
-    // explored. This ensures that the algorithm finds an optimal set of UTXOs with minimal leftover change.
-    while let Some((current_selection, current_total)) = queue.pop_front() {
-        if current_total >= target_amount + fee_amount {
-            let change = current_total - target_amount - fee_amount;
-            if change < current_best_change {
-                current_best_change = change;
-                current_best_solution = Some(current_selection.clone());
+    fn select_with<R: Rng + ?Sized>(
+        &self,
+        candidates: &[Candidate],
+        target: Amount,
+        params: &CoinSelectionParams,
+        rng: &mut R,
+    ) -> Option<Vec<Candidate>> {
+        match self {
+            UTXOStrategy::BranchAndBound => {
+                select_utxos_branch_and_bound(candidates, target, params, rng)
             }
-        } else {
-            for (_index, utxo) in utxos.iter().enumerate() {
-                if !current_selection.contains(utxo) {
-                    let mut new_selection = current_selection.clone();
-                    new_selection.push(utxo.clone());
-                    let new_total = current_total + Amount::from_sat(utxo.amount.to_sat());
-                    queue.push_back((new_selection, new_total));
-                }
+            UTXOStrategy::Fifo => select_utxos_fifo(candidates, target, params),
+            UTXOStrategy::LargestFirst => select_utxos_largest_first(candidates, target, params),
+            UTXOStrategy::SmallestFirst => select_utxos_smallest_first(candidates, target, params),
+            UTXOStrategy::SingleUTXO => select_single_utxo(candidates, target, params),
+            UTXOStrategy::Knapsack => select_coins_knapsack(
+                candidates,
+                target,
+                params.change_fee + params.min_change,
+                rng,
+            ),
+            UTXOStrategy::SingleRandomDraw => {
+                select_coins_srd(candidates, change_target(target, params), rng)
             }
+            UTXOStrategy::LeastWaste => UTXOStrategy::CONCRETE
+                .iter()
+                .filter_map(|strategy| strategy.select_with(candidates, target, params, rng))
+                .min_by_key(|selected| waste(selected, target, params)),
+            UTXOStrategy::Privacy => select_coins_private(candidates, target, params),
         }
     }
+}
 
-    current_best_solution
+impl CoinSelector for UTXOStrategy {
+    fn name(&self) -> &str {
+        match self {
+            UTXOStrategy::BranchAndBound => "branch-and-bound",
+            UTXOStrategy::Fifo => "fifo",
+            UTXOStrategy::LargestFirst => "largest-first",
+            UTXOStrategy::SmallestFirst => "smallest-first",
+            UTXOStrategy::SingleUTXO => "single-utxo",
+            UTXOStrategy::Knapsack => "knapsack",
+            UTXOStrategy::SingleRandomDraw => "single-random-draw",
+            UTXOStrategy::LeastWaste => "least-waste",
+            UTXOStrategy::Privacy => "privacy",
+        }
+    }
+
+    fn select(
+        &self,
+        candidates: &[Candidate],
+        target: Amount,
+        params: &CoinSelectionParams,
+    ) -> Option<Vec<Candidate>> {
+        self.select_with(candidates, target, params, &mut thread_rng())
+    }
 }
 
-fn select_utxos_fifo(
+/// A [`UTXOStrategy`] whose random choices, made by Knapsack, Single Random
+/// Draw and the Branch and Bound fallback, come from a fixed seed instead of
+/// fresh entropy.
+#[derive(Clone, Copy, Debug)]
+pub struct SeededStrategy {
+    pub strategy: UTXOStrategy,
+    pub seed: u64,
+}
+
+impl CoinSelector for SeededStrategy {
+    fn name(&self) -> &str {
+        self.strategy.name()
+    }
+
+    fn select(
+        &self,
+        candidates: &[Candidate],
+        target: Amount,
+        params: &CoinSelectionParams,
+    ) -> Option<Vec<Candidate>> {
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        self.strategy
+            .select_with(candidates, target, params, &mut rng)
+    }
+}
+
+/// Select UTXOs with `selector` paying `target_amount` plus the fee they
+/// need at the rates in `params`. UTXOs are valued net of the fee their own
+/// input costs, so ones worth less than that are never selected.
+pub fn strat_handler(
     utxos: &[ListUnspentResultEntry],
     target_amount: Amount,
-    fee_amount: Amount,
-) -> Result<Vec<ListUnspentResultEntry>, UtilsError> {
-    let sorted_utxos = utxos.to_vec();
-    return select_utxos(sorted_utxos, target_amount, fee_amount);
+    params: &CoinSelectionParams,
+    selector: &dyn CoinSelector,
+) -> Result<Selection, UtilsError> {
+    let candidates = candidates(utxos, params);
+    let target = target_amount + params.base_fee;
+
+    let selected = selector
+        .select(&candidates, target, params)
+        .ok_or(UtilsError::InsufficientUTXOs)?;
+
+    // Selectors may come from outside this crate, so check what they return
+    // before building a transaction from it
+    let invalid = |reason: &str| {
+        UtilsError::InvalidSelection(selector.name().to_string(), reason.to_string())
+    };
+    let mut indices: Vec<usize> = selected.iter().map(|c| c.index).collect();
+    indices.sort_unstable();
+    indices.dedup();
+    if indices.len() != selected.len() {
+        return Err(invalid("a UTXO is selected twice"));
+    }
+    if selected.iter().any(|c| !candidates.contains(c)) {
+        return Err(invalid("a UTXO is not among the candidates"));
+    }
+    let value: Amount = selected.iter().map(|c| c.effective_value).sum();
+    if value < target {
+        return Err(invalid("the UTXOs do not cover the target"));
+    }
+
+    Ok(Selection::new(utxos, &selected, target_amount, params))
 }
 
-fn select_utxos_largest_first(
+/// Spend exactly `utxos`, chosen by hand rather than by a selector, to pay
+/// `target_amount` plus the fee they need at the rates in `params`.
+pub fn manual_selection(
     utxos: &[ListUnspentResultEntry],
     target_amount: Amount,
-    fee_amount: Amount,
-) -> Result<Vec<ListUnspentResultEntry>, UtilsError> {
-    // Sort UTXOs by amount in descending order
-    let mut sorted_utxos = utxos.to_vec();
-    sorted_utxos.sort_by(|a, b| b.amount.cmp(&a.amount));
+    params: &CoinSelectionParams,
+) -> Result<Selection, UtilsError> {
+    let selected = candidates(utxos, params);
+    if let Some(utxo) = utxos
+        .iter()
+        .enumerate()
+        .find(|(index, _)| !selected.iter().any(|c| c.index == *index))
+        .map(|(_, utxo)| utxo)
+    {
+        return Err(UtilsError::UneconomicUTXO(OutPoint::new(
+            utxo.txid, utxo.vout,
+        )));
+    }
+    let value: Amount = selected.iter().map(|c| c.effective_value).sum();
+    if value < target_amount + params.base_fee {
+        return Err(UtilsError::InsufficientUTXOs);
+    }
+    Ok(Selection::new(utxos, &selected, target_amount, params))
+}
 
-    return select_utxos(sorted_utxos, target_amount, fee_amount);
+/// Look for a changeless solution with Branch and Bound, falling back to a
+/// single random draw that leaves enough over for a change output.
+fn select_utxos_branch_and_bound<R: Rng + ?Sized>(
+    candidates: &[Candidate],
+    target: Amount,
+    params: &CoinSelectionParams,
+    rng: &mut R,
+) -> Option<Vec<Candidate>> {
+    select_coins_bnb(candidates, target, params)
+        .or_else(|| select_coins_srd(candidates, change_target(target, params), rng))
+}
+
+fn select_utxos_fifo(
+    candidates: &[Candidate],
+    target: Amount,
+    params: &CoinSelectionParams,
+) -> Option<Vec<Candidate>> {
+    select_utxos(candidates.to_vec(), target, params)
+}
+
+fn select_utxos_largest_first(
+    candidates: &[Candidate],
+    target: Amount,
+    params: &CoinSelectionParams,
+) -> Option<Vec<Candidate>> {
+    // Sort UTXOs by effective value in descending order
+    let mut sorted = candidates.to_vec();
+    sorted.sort_by_key(|c| std::cmp::Reverse(c.effective_value));
+
+    select_utxos(sorted, target, params)
 }
 
 fn select_utxos_smallest_first(
-    utxos: &[ListUnspentResultEntry],
-    target_amount: Amount,
-    fee_amount: Amount,
-) -> Result<Vec<ListUnspentResultEntry>, UtilsError> {
-    // Sort UTXOs by amount in descending order
-    let mut sorted_utxos = utxos.to_vec();
-    sorted_utxos.sort_by(|a, b| a.amount.cmp(&b.amount));
+    candidates: &[Candidate],
+    target: Amount,
+    params: &CoinSelectionParams,
+) -> Option<Vec<Candidate>> {
+    // Sort UTXOs by effective value in ascending order
+    let mut sorted = candidates.to_vec();
+    sorted.sort_by_key(|c| c.effective_value);
 
-    return select_utxos(sorted_utxos, target_amount, fee_amount);
+    select_utxos(sorted, target, params)
 }
 
 fn select_single_utxo(
-    utxos: &[ListUnspentResultEntry],
-    target_amount: Amount,
-    fee_amount: Amount,
-) -> Result<Vec<ListUnspentResultEntry>, UtilsError> {
-    let total_needed = target_amount + fee_amount;
-
-    // Find the first UTXO that's large enough to cover both target amount and fee
-    let selected_utxo = utxos
+    candidates: &[Candidate],
+    target: Amount,
+    params: &CoinSelectionParams,
+) -> Option<Vec<Candidate>> {
+    // Prefer the first UTXO that also pays for change, then the first that
+    // covers the target at all
+    let with_change = change_target(target, params);
+    candidates
         .iter()
-        .find(|utxo| Amount::from_sat(utxo.amount.to_sat()) >= total_needed)
-        .cloned();
-
-    match selected_utxo {
-        Some(utxo) => Ok(vec![utxo]),
-        None => Err(UtilsError::InsufficientUTXOs),
-    }
+        .find(|c| c.effective_value >= with_change)
+        .or_else(|| candidates.iter().find(|c| c.effective_value >= target))
+        .map(|c| vec![*c])
 }
 
+/// Take `sorted` in order until it pays for a change output, or failing that
+/// the shortest run that covers `target` without one.
 fn select_utxos(
-    sorted_utxos: Vec<ListUnspentResultEntry>,
-    target_amount: Amount,
-    fee_amount: Amount,
-) -> Result<Vec<ListUnspentResultEntry>, UtilsError> {
-    let mut selected_utxos = Vec::new();
-    let mut total_amount = Amount::from_sat(0);
+    sorted: Vec<Candidate>,
+    target: Amount,
+    params: &CoinSelectionParams,
+) -> Option<Vec<Candidate>> {
+    let with_change = change_target(target, params);
+    let mut changeless = None;
+    let mut total = Amount::ZERO;
 
-    for utxo in sorted_utxos.iter() {
-        selected_utxos.push(utxo.clone());
-        total_amount += Amount::from_sat(utxo.amount.to_sat());
-
-        if total_amount >= target_amount + fee_amount {
-            return Ok(selected_utxos);
+    for (i, candidate) in sorted.iter().enumerate() {
+        total += candidate.effective_value;
+        if total >= with_change {
+            return Some(sorted[..=i].to_vec());
+        }
+        if total >= target && changeless.is_none() {
+            changeless = Some(i);
         }
     }
 
-    Err(UtilsError::InsufficientUTXOs)
+    changeless.map(|i| sorted[..=i].to_vec())
+}
+
+/// What the selected effective values must reach to leave change worth an
+/// output.
+fn change_target(target: Amount, params: &CoinSelectionParams) -> Amount {
+    target + params.change_fee + params.min_change
 }
@@ -0,0 +1,185 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt,
+};
+
+use bitcoin::Amount;
+use bitcoincore_rpc::json::ListUnspentResultEntry;
+
+use crate::{select_coins_bnb, waste, Candidate, CoinSelectionParams};
+
+/// Score lost for each address linked to the first one.
+const ADDRESS_PENALTY: usize = 20;
+/// Score lost for each label mixed in beyond the first.
+const LABEL_PENALTY: usize = 30;
+/// Score lost for each address whose other UTXOs are left for later.
+const PARTIAL_SPEND_PENALTY: usize = 10;
+
+/// How much spending a set of UTXOs together tells an observer.
+///
+/// Spending UTXOs in one transaction links their addresses, and through
+/// them the sources they were received from. Leaving some UTXOs of an
+/// address behind links this transaction to whichever spends them later.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Privacy {
+    /// Distinct addresses the inputs spend from.
+    pub addresses: usize,
+    /// Distinct labels the inputs were received under, unlabelled counting
+    /// as one.
+    pub labels: usize,
+    /// Addresses with UTXOs that are not spent along with the rest.
+    pub partial_spends: usize,
+    /// 100 for inputs from a single address spent in full, less for every
+    /// address, label or partial spend on top.
+    pub score: u8,
+}
+
+impl Privacy {
+    /// The privacy of spending `selected` out of `utxos`.
+    pub fn new(utxos: &[ListUnspentResultEntry], selected: &[Candidate]) -> Self {
+        let mut addresses: BTreeMap<usize, usize> = BTreeMap::new();
+        let mut labels = HashSet::new();
+        for candidate in selected {
+            *addresses.entry(candidate.address).or_default() += 1;
+            labels.insert(candidate.label);
+        }
+
+        let partial_spends = addresses
+            .iter()
+            .filter(|(&address, &spent)| {
+                let script = &utxos[address].script_pub_key;
+                utxos
+                    .iter()
+                    .filter(|utxo| &utxo.script_pub_key == script)
+                    .count()
+                    > spent
+            })
+            .count();
+
+        let penalty = addresses.len().saturating_sub(1) * ADDRESS_PENALTY
+            + labels.len().saturating_sub(1) * LABEL_PENALTY
+            + partial_spends * PARTIAL_SPEND_PENALTY;
+
+        Privacy {
+            addresses: addresses.len(),
+            labels: labels.len(),
+            partial_spends,
+            score: 100 - penalty.min(100) as u8,
+        }
+    }
+}
+
+impl fmt::Display for Privacy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}/100 ({} addresses, {} labels, {} partially spent)",
+            self.score, self.addresses, self.labels, self.partial_spends
+        )
+    }
+}
+
+/// Privacy-aware selection, after Bitcoin Core's `-avoidpartialspends`.
+///
+/// Candidates are grouped by address and label, and every group is spent
+/// in full or not at all. Groups under a single label are preferred, and
+/// among those the fewest groups, then the least waste. Only when no label
+/// covers `target` on its own are labels mixed, the largest labels first so
+/// as few as possible are linked.
+pub fn select_coins_private(
+    candidates: &[Candidate],
+    target: Amount,
+    params: &CoinSelectionParams,
+) -> Option<Vec<Candidate>> {
+    let mut groups: BTreeMap<(usize, usize), Vec<Candidate>> = BTreeMap::new();
+    for candidate in candidates {
+        groups
+            .entry((candidate.label, candidate.address))
+            .or_default()
+            .push(*candidate);
+    }
+    let groups: Vec<Vec<Candidate>> = groups.into_values().collect();
+
+    // Each group as a single candidate, indexed by its position in `groups`,
+    // so whole groups can be selected like coins
+    let pool: Vec<Candidate> = groups
+        .iter()
+        .enumerate()
+        .map(|(index, group)| Candidate {
+            index,
+            effective_value: group.iter().map(|c| c.effective_value).sum(),
+            fee: group.iter().map(|c| c.fee).sum(),
+            long_term_fee: group.iter().map(|c| c.long_term_fee).sum(),
+            address: group[0].address,
+            label: group[0].label,
+        })
+        .collect();
+
+    let mut labels: Vec<usize> = pool.iter().map(|group| group.label).collect();
+    labels.dedup();
+
+    let selected = labels
+        .iter()
+        .filter_map(|&label| {
+            let same_label: Vec<Candidate> = pool
+                .iter()
+                .filter(|group| group.label == label)
+                .copied()
+                .collect();
+            select_groups(&same_label, target, params)
+        })
+        .min_by_key(|selected| (selected.len(), waste(selected, target, params)))
+        .or_else(|| mix_labels(pool, target))?;
+
+    Some(
+        selected
+            .iter()
+            .flat_map(|group| groups[group.index].iter().copied())
+            .collect(),
+    )
+}
+
+/// A changeless combination of `groups` if there is one, otherwise the
+/// largest groups until `target` is covered.
+fn select_groups(
+    groups: &[Candidate],
+    target: Amount,
+    params: &CoinSelectionParams,
+) -> Option<Vec<Candidate>> {
+    if let Some(selected) = select_coins_bnb(groups, target, params) {
+        return Some(selected);
+    }
+
+    let mut sorted = groups.to_vec();
+    sorted.sort_by_key(|group| std::cmp::Reverse(group.effective_value));
+    take_until(sorted, target)
+}
+
+/// Groups from the labels worth most in total first, and within a label the
+/// largest groups first, until `target` is covered.
+fn mix_labels(mut groups: Vec<Candidate>, target: Amount) -> Option<Vec<Candidate>> {
+    let mut label_totals: HashMap<usize, Amount> = HashMap::new();
+    for group in &groups {
+        *label_totals.entry(group.label).or_default() += group.effective_value;
+    }
+    groups.sort_by_key(|group| {
+        (
+            std::cmp::Reverse(label_totals[&group.label]),
+            group.label,
+            std::cmp::Reverse(group.effective_value),
+        )
+    });
+    take_until(groups, target)
+}
+
+/// The shortest prefix of `sorted` that covers `target`.
+fn take_until(sorted: Vec<Candidate>, target: Amount) -> Option<Vec<Candidate>> {
+    let mut total = Amount::ZERO;
+    for (i, group) in sorted.iter().enumerate() {
+        total += group.effective_value;
+        if total >= target {
+            return Some(sorted[..=i].to_vec());
+        }
+    }
+    None
+}
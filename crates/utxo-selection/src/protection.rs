@@ -0,0 +1,194 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    time::Duration,
+};
+
+use serde::Deserialize;
+use tracing::warn;
+use url::Url;
+
+use bitcoin::OutPoint;
+use bitcoincore_rpc::json::ListUnspentResultEntry;
+
+use satoshi_suite_client::ClientError;
+
+use crate::UtilsError;
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Inscriptions and runes an output carries.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct OutputAssets {
+    /// Inscription IDs, e.g. `<txid>i0`.
+    pub inscriptions: Vec<String>,
+    /// Spaced rune names, e.g. `UNCOMMON•GOODS`.
+    pub runes: Vec<String>,
+}
+
+impl OutputAssets {
+    pub fn is_empty(&self) -> bool {
+        self.inscriptions.is_empty() && self.runes.is_empty()
+    }
+}
+
+/// Something that knows which outputs carry inscriptions or runes.
+pub trait AssetIndex {
+    /// The assets on `outpoint`, or `None` if the index has not seen it
+    /// yet and cannot tell.
+    fn output_assets(&self, outpoint: &OutPoint) -> Result<Option<OutputAssets>, UtilsError>;
+}
+
+/// An index kept by the caller. Outputs missing from it carry nothing.
+impl AssetIndex for HashMap<OutPoint, OutputAssets> {
+    fn output_assets(&self, outpoint: &OutPoint) -> Result<Option<OutputAssets>, UtilsError> {
+        Ok(Some(self.get(outpoint).cloned().unwrap_or_default()))
+    }
+}
+
+#[derive(Deserialize)]
+struct OrdOutput {
+    #[serde(default)]
+    indexed: bool,
+    #[serde(default)]
+    inscriptions: Option<Vec<String>>,
+    /// A map from rune name to balance, or a list of `[name, balance]` pairs
+    /// in older servers.
+    #[serde(default)]
+    runes: Option<serde_json::Value>,
+}
+
+/// The JSON API of an `ord` server, e.g. `ord server --http-port 80` run
+/// with `--index-runes` so rune balances are known.
+pub struct OrdServer {
+    agent: ureq::Agent,
+    base_url: Url,
+}
+
+impl OrdServer {
+    /// `base_url` is the server root and must end in `/`.
+    pub fn new(base_url: Url) -> Self {
+        OrdServer {
+            agent: ureq::AgentBuilder::new().timeout(TIMEOUT).build(),
+            base_url,
+        }
+    }
+}
+
+impl AssetIndex for OrdServer {
+    fn output_assets(&self, outpoint: &OutPoint) -> Result<Option<OutputAssets>, UtilsError> {
+        let path = format!("output/{}", outpoint);
+        let url = self
+            .base_url
+            .join(&path)
+            .map_err(|e| ClientError::InvalidConfiguration(e.to_string()))?;
+
+        let output: OrdOutput = match self
+            .agent
+            .request_url("GET", &url)
+            .set("Accept", "application/json")
+            .call()
+        {
+            Ok(response) => response
+                .into_json()
+                .map_err(|e| ClientError::InvalidResponse(e.to_string()))?,
+            Err(ureq::Error::Status(404, _)) => return Ok(None),
+            Err(err) => return Err(ClientError::Http(Box::new(err)).into()),
+        };
+        if !output.indexed {
+            return Ok(None);
+        }
+
+        let runes = match output.runes {
+            Some(serde_json::Value::Object(balances)) => balances.keys().cloned().collect(),
+            Some(serde_json::Value::Array(pairs)) => pairs
+                .iter()
+                .map(|pair| match pair.get(0) {
+                    Some(serde_json::Value::String(name)) => name.clone(),
+                    _ => pair.to_string(),
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        Ok(Some(OutputAssets {
+            inscriptions: output.inscriptions.unwrap_or_default(),
+            runes,
+        }))
+    }
+}
+
+/// Why a UTXO is kept out of coin selection.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Protection {
+    Assets(OutputAssets),
+    /// The index has not caught up with the output, so it may carry either.
+    NotIndexed,
+}
+
+impl fmt::Display for Protection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Protection::Assets(assets) => {
+                let mut held = Vec::new();
+                if !assets.inscriptions.is_empty() {
+                    held.push(format!("inscriptions {}", assets.inscriptions.join(", ")));
+                }
+                if !assets.runes.is_empty() {
+                    held.push(format!("runes {}", assets.runes.join(", ")));
+                }
+                write!(f, "holds {}", held.join(" and "))
+            }
+            Protection::NotIndexed => write!(f, "not indexed yet"),
+        }
+    }
+}
+
+/// Keeps UTXOs carrying inscriptions or runes out of every coin selection,
+/// except for outpoints explicitly allowed.
+pub struct AssetProtection {
+    index: Box<dyn AssetIndex>,
+    allowed: HashSet<OutPoint>,
+}
+
+impl AssetProtection {
+    pub fn new(index: Box<dyn AssetIndex>) -> Self {
+        AssetProtection {
+            index,
+            allowed: HashSet::new(),
+        }
+    }
+
+    /// Let `outpoint` be spent whatever it carries.
+    pub fn allow(&mut self, outpoint: OutPoint) {
+        self.allowed.insert(outpoint);
+    }
+
+    /// Why `outpoint` may not be spent, if it may not.
+    pub fn protection(&self, outpoint: &OutPoint) -> Result<Option<Protection>, UtilsError> {
+        if self.allowed.contains(outpoint) {
+            return Ok(None);
+        }
+        Ok(match self.index.output_assets(outpoint)? {
+            Some(assets) if assets.is_empty() => None,
+            Some(assets) => Some(Protection::Assets(assets)),
+            None => Some(Protection::NotIndexed),
+        })
+    }
+
+    /// The UTXOs in `utxos` that are safe to spend.
+    pub fn spendable(
+        &self,
+        utxos: Vec<ListUnspentResultEntry>,
+    ) -> Result<Vec<ListUnspentResultEntry>, UtilsError> {
+        let mut spendable = Vec::with_capacity(utxos.len());
+        for utxo in utxos {
+            let outpoint = OutPoint::new(utxo.txid, utxo.vout);
+            match self.protection(&outpoint)? {
+                Some(protection) => warn!("Not spending {}: {}", outpoint, protection),
+                None => spendable.push(utxo),
+            }
+        }
+        Ok(spendable)
+    }
+}
@@ -0,0 +1,101 @@
+use bitcoin::{Amount, FeeRate, SignedAmount};
+use bitcoincore_rpc::json::ListUnspentResultEntry;
+
+use crate::{Candidate, Privacy};
+
+/// Long-term fee rate used when none is given, Bitcoin Core's default
+/// `-consolidatefeerate`.
+pub const DEFAULT_LONG_TERM_FEE_RATE: FeeRate = FeeRate::from_sat_per_vb_unchecked(10);
+
+/// What coins cost to select for a particular transaction.
+#[derive(Clone, Copy, Debug)]
+pub struct CoinSelectionParams {
+    /// Fee rate the transaction pays.
+    pub fee_rate: FeeRate,
+    /// Fee rate spending the same coins is expected to cost later on.
+    pub long_term_fee_rate: FeeRate,
+    /// Fee for the transaction without its inputs and change output.
+    pub base_fee: Amount,
+    /// Fee for adding a change output at `fee_rate`.
+    pub change_fee: Amount,
+    /// Fee for spending that change output later at `long_term_fee_rate`.
+    pub change_spend_fee: Amount,
+    /// Smallest change worth an output; anything less goes to the fee.
+    pub min_change: Amount,
+}
+
+impl CoinSelectionParams {
+    /// What creating change costs, now and when it is spent.
+    pub fn cost_of_change(&self) -> Amount {
+        self.change_fee + self.change_spend_fee
+    }
+
+    /// Change left over when the selected effective values exceed the
+    /// target by `excess`, if it is worth an output.
+    pub fn change(&self, excess: Amount) -> Option<Amount> {
+        excess
+            .checked_sub(self.change_fee)
+            .filter(|change| *change >= self.min_change)
+    }
+}
+
+/// Coins paying for a transaction, the fee they leave and the change
+/// output they need, if any.
+#[derive(Debug)]
+pub struct Selection {
+    pub utxos: Vec<ListUnspentResultEntry>,
+    pub fee: Amount,
+    pub change: Option<Amount>,
+    /// Bitcoin Core's waste metric for the selection.
+    pub waste: SignedAmount,
+    /// What the inputs reveal about the wallet when spent together.
+    pub privacy: Privacy,
+}
+
+impl Selection {
+    /// The selection of `selected`, taken from `utxos`, paying `target_amount`.
+    pub fn new(
+        utxos: &[ListUnspentResultEntry],
+        selected: &[Candidate],
+        target_amount: Amount,
+        params: &CoinSelectionParams,
+    ) -> Self {
+        let target = target_amount + params.base_fee;
+        let value: Amount = selected.iter().map(|c| c.effective_value).sum();
+        let change = params.change(value - target);
+        let privacy = Privacy::new(utxos, selected);
+        let utxos: Vec<ListUnspentResultEntry> =
+            selected.iter().map(|c| utxos[c.index].clone()).collect();
+        let total_in: Amount = utxos.iter().map(|utxo| utxo.amount).sum();
+
+        Selection {
+            fee: total_in - target_amount - change.unwrap_or(Amount::ZERO),
+            change,
+            waste: waste(selected, target, params),
+            privacy,
+            utxos,
+        }
+    }
+}
+
+/// Bitcoin Core's waste metric: what spending `selected` now costs over
+/// spending the same inputs at the long-term fee rate, plus either the cost
+/// of change or, without change, the excess given up to the fee. Lower is
+/// better, and it goes negative when fees are below the long-term rate.
+///
+/// `target` includes `params.base_fee`, and `selected` must cover it.
+pub fn waste(selected: &[Candidate], target: Amount, params: &CoinSelectionParams) -> SignedAmount {
+    let timing_cost: SignedAmount = selected.iter().map(Candidate::timing_cost).sum();
+    let value: Amount = selected.iter().map(|c| c.effective_value).sum();
+    let excess = value - target;
+
+    match params.change(excess) {
+        Some(_) => timing_cost + signed(params.cost_of_change()),
+        None => timing_cost + signed(excess),
+    }
+}
+
+pub(crate) fn signed(amount: Amount) -> SignedAmount {
+    // No amount of bitcoin comes close to overflowing an i64 of sats
+    SignedAmount::from_sat(amount.to_sat() as i64)
+}
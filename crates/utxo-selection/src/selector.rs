@@ -0,0 +1,75 @@
+use std::collections::BTreeMap;
+
+use bitcoin::Amount;
+
+use crate::{Candidate, CoinSelectionParams, UTXOStrategy};
+
+/// A way of choosing which coins pay for a transaction.
+///
+/// Selectors see the wallet's UTXOs as [`Candidate`]s, valued net of the fee
+/// their own input costs at `params.fee_rate`, and pick some whose effective
+/// values add up to at least `target`, which already includes
+/// `params.base_fee`. Whether the leftover becomes change is decided
+/// afterwards from `params`.
+pub trait CoinSelector {
+    /// Name the selector is registered under and chosen by.
+    fn name(&self) -> &str;
+
+    /// The candidates to spend, or `None` if this selector finds no set
+    /// covering `target`.
+    fn select(
+        &self,
+        candidates: &[Candidate],
+        target: Amount,
+        params: &CoinSelectionParams,
+    ) -> Option<Vec<Candidate>>;
+}
+
+/// Coin selectors by name.
+pub struct CoinSelectorRegistry {
+    selectors: BTreeMap<String, Box<dyn CoinSelector>>,
+}
+
+impl Default for CoinSelectorRegistry {
+    /// A registry of the built-in [`UTXOStrategy`] selectors.
+    fn default() -> Self {
+        let mut registry = CoinSelectorRegistry::empty();
+        for strategy in UTXOStrategy::ALL {
+            registry.register(Box::new(strategy));
+        }
+        registry
+    }
+}
+
+impl CoinSelectorRegistry {
+    pub fn empty() -> Self {
+        CoinSelectorRegistry {
+            selectors: BTreeMap::new(),
+        }
+    }
+
+    /// A registry of the built-in selectors, each drawing its random
+    /// choices from `seed`.
+    pub fn seeded(seed: u64) -> Self {
+        let mut registry = CoinSelectorRegistry::empty();
+        for strategy in UTXOStrategy::ALL {
+            registry.register(Box::new(strategy.seeded(seed)));
+        }
+        registry
+    }
+
+    /// Add `selector` under its name, replacing any selector registered
+    /// under the same name.
+    pub fn register(&mut self, selector: Box<dyn CoinSelector>) {
+        self.selectors.insert(selector.name().to_string(), selector);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn CoinSelector> {
+        self.selectors.get(name).map(|selector| selector.as_ref())
+    }
+
+    /// Names of the registered selectors, in alphabetical order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.selectors.keys().map(String::as_str)
+    }
+}
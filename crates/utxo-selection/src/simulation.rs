@@ -0,0 +1,186 @@
+use bitcoin::{
+    absolute::LockTime, hashes::Hash, transaction::Version, Amount, FeeRate, ScriptBuf,
+    SignedAmount, Transaction, TxOut, Txid, WPubkeyHash,
+};
+use bitcoincore_rpc::json::ListUnspentResultEntry;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use satoshi_suite_client::{estimate_fee, script_satisfaction_weight};
+
+use crate::{strat_handler, CoinSelectionParams, CoinSelector, INPUT_BASE_WEIGHT};
+
+/// Something that happens to a simulated wallet.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Event {
+    /// A payment to the wallet, adding one UTXO.
+    Deposit(Amount),
+    /// A payment from the wallet, funded by the selector under test.
+    Withdrawal { amount: Amount, fee_rate: FeeRate },
+}
+
+/// A stream of deposits and withdrawals to replay against coin selectors.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Scenario {
+    pub events: Vec<Event>,
+}
+
+impl Scenario {
+    /// `len` events drawn from `seed`: deposits between 1,000 sats and
+    /// 0.1 BTC, spread evenly over orders of magnitude, withdrawals of the
+    /// same sizes about half as often, and fee rates between 1 and
+    /// 100 sat/vB that drift over time like the mempool's.
+    pub fn random(seed: u64, len: usize) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut fee_rate: f64 = rng.gen_range(1.0..20.0);
+        let events = (0..len)
+            .map(|_| {
+                fee_rate = (fee_rate * rng.gen_range(0.8..1.25)).clamp(1.0, 100.0);
+                let amount = Amount::from_sat(10f64.powf(rng.gen_range(3.0..7.0)) as u64);
+                if rng.gen_bool(2.0 / 3.0) {
+                    Event::Deposit(amount)
+                } else {
+                    Event::Withdrawal {
+                        amount,
+                        fee_rate: FeeRate::from_sat_per_vb_unchecked(fee_rate as u64),
+                    }
+                }
+            })
+            .collect();
+        Scenario { events }
+    }
+}
+
+/// How a coin selector fared over a [`Scenario`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SimulationReport {
+    pub selector: String,
+    pub withdrawals: usize,
+    /// Withdrawals the selector could not fund.
+    pub failed_withdrawals: usize,
+    /// Amounts of the withdrawals that were funded, without their fees.
+    pub withdrawn: Amount,
+    pub fees_paid: Amount,
+    /// Waste of every funded withdrawal added up.
+    pub waste: SignedAmount,
+    pub change_outputs: usize,
+    /// UTXOs in the wallet after each event.
+    pub pool_sizes: Vec<usize>,
+    /// What the wallet holds at the end.
+    pub balance: Amount,
+}
+
+impl SimulationReport {
+    /// Largest the UTXO pool grew.
+    pub fn max_pool_size(&self) -> usize {
+        self.pool_sizes.iter().copied().max().unwrap_or(0)
+    }
+}
+
+/// Replay `scenario` against a wallet of P2WPKH outputs that funds every
+/// withdrawal with `selector`, paying change back to a fresh P2WPKH output.
+pub fn simulate(
+    scenario: &Scenario,
+    selector: &dyn CoinSelector,
+    long_term_fee_rate: FeeRate,
+) -> SimulationReport {
+    let mut report = SimulationReport {
+        selector: selector.name().to_string(),
+        withdrawals: 0,
+        failed_withdrawals: 0,
+        withdrawn: Amount::ZERO,
+        fees_paid: Amount::ZERO,
+        waste: SignedAmount::ZERO,
+        change_outputs: 0,
+        pool_sizes: Vec::with_capacity(scenario.events.len()),
+        balance: Amount::ZERO,
+    };
+    let mut utxos: Vec<ListUnspentResultEntry> = Vec::new();
+
+    for (n, event) in scenario.events.iter().enumerate() {
+        match *event {
+            Event::Deposit(amount) => utxos.push(simulated_utxo(n, amount)),
+            Event::Withdrawal { amount, fee_rate } => {
+                report.withdrawals += 1;
+                let params = p2wpkh_params(fee_rate, long_term_fee_rate);
+                match strat_handler(&utxos, amount, &params, selector) {
+                    Ok(selection) => {
+                        utxos.retain(|utxo| {
+                            !selection
+                                .utxos
+                                .iter()
+                                .any(|spent| spent.txid == utxo.txid && spent.vout == utxo.vout)
+                        });
+                        if let Some(change) = selection.change {
+                            utxos.push(simulated_utxo(n, change));
+                            report.change_outputs += 1;
+                        }
+                        report.withdrawn += amount;
+                        report.fees_paid += selection.fee;
+                        report.waste += selection.waste;
+                    }
+                    Err(_) => report.failed_withdrawals += 1,
+                }
+            }
+        }
+        report.pool_sizes.push(utxos.len());
+    }
+
+    report.balance = utxos.iter().map(|utxo| utxo.amount).sum();
+    report
+}
+
+/// Selection parameters for paying one P2WPKH output with change to another.
+pub fn p2wpkh_params(fee_rate: FeeRate, long_term_fee_rate: FeeRate) -> CoinSelectionParams {
+    let output = TxOut {
+        value: Amount::ZERO,
+        script_pubkey: p2wpkh_script(0),
+    };
+    let unsigned = Transaction {
+        version: Version::TWO,
+        lock_time: LockTime::ZERO,
+        input: Vec::new(),
+        output: vec![output.clone()],
+    };
+    let change_spend_weight = INPUT_BASE_WEIGHT
+        + script_satisfaction_weight(&output.script_pubkey).expect("P2WPKH is sized");
+
+    CoinSelectionParams {
+        fee_rate,
+        long_term_fee_rate,
+        base_fee: estimate_fee(&unsigned, &[], fee_rate),
+        change_fee: fee_rate
+            .fee_wu(output.weight())
+            .unwrap_or(Amount::MAX_MONEY),
+        change_spend_fee: long_term_fee_rate
+            .fee_wu(change_spend_weight)
+            .unwrap_or(Amount::MAX_MONEY),
+        min_change: output.script_pubkey.dust_value(),
+    }
+}
+
+/// The output of the `n`th simulated transaction, on an address of its own.
+fn simulated_utxo(n: usize, amount: Amount) -> ListUnspentResultEntry {
+    let mut txid = [0u8; 32];
+    txid[..8].copy_from_slice(&(n as u64).to_le_bytes());
+    ListUnspentResultEntry {
+        txid: Txid::from_byte_array(txid),
+        vout: 0,
+        address: None,
+        label: None,
+        redeem_script: None,
+        witness_script: None,
+        script_pub_key: p2wpkh_script(n as u64 + 1),
+        amount,
+        confirmations: 6,
+        spendable: true,
+        solvable: true,
+        descriptor: None,
+        safe: true,
+    }
+}
+
+fn p2wpkh_script(n: u64) -> ScriptBuf {
+    let mut hash = [0u8; 20];
+    hash[..8].copy_from_slice(&n.to_le_bytes());
+    ScriptBuf::new_p2wpkh(&WPubkeyHash::from_byte_array(hash))
+}
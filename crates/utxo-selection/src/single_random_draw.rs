@@ -0,0 +1,27 @@
+use bitcoin::Amount;
+use rand::{seq::SliceRandom, Rng};
+
+use crate::Candidate;
+
+/// Single Random Draw: candidates in random order until their effective
+/// values reach `target`.
+pub fn select_coins_srd<R: Rng + ?Sized>(
+    candidates: &[Candidate],
+    target: Amount,
+    rng: &mut R,
+) -> Option<Vec<Candidate>> {
+    let mut pool = candidates.to_vec();
+    pool.shuffle(rng);
+
+    let mut selected = Vec::new();
+    let mut value = Amount::ZERO;
+    for candidate in pool {
+        selected.push(candidate);
+        value += candidate.effective_value;
+        if value >= target {
+            return Some(selected);
+        }
+    }
+
+    None
+}
@@ -0,0 +1,259 @@
+use std::collections::HashSet;
+
+use proptest::prelude::*;
+
+use bitcoin::{hashes::Hash, Amount, FeeRate, ScriptBuf, Txid, WPubkeyHash};
+use bitcoincore_rpc::json::ListUnspentResultEntry;
+
+use satoshi_suite_utxo_selection::{
+    candidates, input_weight, p2wpkh_params, select_coins_bnb, simulate, strat_handler, Candidate,
+    CoinSelectionParams, CoinSelector, Event, Scenario, UTXOStrategy, UtilsError,
+    DEFAULT_LONG_TERM_FEE_RATE,
+};
+
+/// Strategies that make the same selection every time for the same UTXOs.
+/// `branch-and-bound` falls back to a random draw when it finds no
+/// changeless set, and `least-waste` runs the random strategies.
+const DETERMINISTIC: [UTXOStrategy; 5] = [
+    UTXOStrategy::Fifo,
+    UTXOStrategy::LargestFirst,
+    UTXOStrategy::SmallestFirst,
+    UTXOStrategy::SingleUTXO,
+    UTXOStrategy::Privacy,
+];
+
+/// P2WPKH UTXOs worth `amounts`, spread over `addresses` addresses and two
+/// labels so the privacy strategy has groups to work with.
+fn utxo_pool(amounts: &[u64], addresses: u8) -> Vec<ListUnspentResultEntry> {
+    amounts
+        .iter()
+        .enumerate()
+        .map(|(i, &sats)| {
+            let address = i as u8 % addresses;
+            ListUnspentResultEntry {
+                txid: Txid::from_byte_array([i as u8; 32]),
+                vout: i as u32,
+                address: None,
+                label: Some(if address % 2 == 0 { "even" } else { "odd" }.to_string()),
+                redeem_script: None,
+                witness_script: None,
+                script_pub_key: ScriptBuf::new_p2wpkh(&WPubkeyHash::from_byte_array([address; 20])),
+                amount: Amount::from_sat(sats),
+                confirmations: 6,
+                spendable: true,
+                solvable: true,
+                descriptor: None,
+                safe: true,
+            }
+        })
+        .collect()
+}
+
+fn params(fee_rate: u64) -> CoinSelectionParams {
+    p2wpkh_params(
+        FeeRate::from_sat_per_vb_unchecked(fee_rate),
+        DEFAULT_LONG_TERM_FEE_RATE,
+    )
+}
+
+prop_compose! {
+    fn wallet()(
+        amounts in prop::collection::vec(1_000u64..10_000_000, 1..40),
+        addresses in 1u8..10,
+        fee_rate in 1u64..100,
+    ) -> (Vec<ListUnspentResultEntry>, CoinSelectionParams) {
+        (utxo_pool(&amounts, addresses), params(fee_rate))
+    }
+}
+
+proptest! {
+    // Knapsack makes a thousand random passes per selection
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn selection_covers_target_and_fee(
+        (utxos, params) in wallet(),
+        target in 1_000u64..20_000_000,
+    ) {
+        let target = Amount::from_sat(target);
+        for strategy in UTXOStrategy::ALL {
+            let Ok(selection) = strat_handler(&utxos, target, &params, &strategy) else {
+                continue;
+            };
+
+            let total_in: Amount = selection.utxos.iter().map(|utxo| utxo.amount).sum();
+            let change = selection.change.unwrap_or(Amount::ZERO);
+            prop_assert_eq!(total_in, target + selection.fee + change, "{}", strategy.name());
+
+            // The fee pays for every input, the outputs and any change output
+            let input_fees: Amount = selection
+                .utxos
+                .iter()
+                .map(|utxo| params.fee_rate.fee_wu(input_weight(utxo).unwrap()).unwrap())
+                .sum();
+            let change_fee = selection.change.map_or(Amount::ZERO, |_| params.change_fee);
+            prop_assert!(
+                selection.fee >= params.base_fee + input_fees + change_fee,
+                "{} pays {} in fees",
+                strategy.name(),
+                selection.fee
+            );
+            if let Some(change) = selection.change {
+                prop_assert!(change >= params.min_change, "{} leaves dust", strategy.name());
+            }
+        }
+    }
+
+    #[test]
+    fn selection_has_no_duplicates(
+        (utxos, params) in wallet(),
+        target in 1_000u64..20_000_000,
+    ) {
+        for strategy in UTXOStrategy::ALL {
+            let Ok(selection) =
+                strat_handler(&utxos, Amount::from_sat(target), &params, &strategy)
+            else {
+                continue;
+            };
+            let outpoints: HashSet<_> =
+                selection.utxos.iter().map(|utxo| (utxo.txid, utxo.vout)).collect();
+            prop_assert_eq!(outpoints.len(), selection.utxos.len(), "{}", strategy.name());
+        }
+    }
+
+    #[test]
+    fn selection_fails_only_when_funds_are_short(
+        (utxos, params) in wallet(),
+        target in 1_000u64..20_000_000,
+    ) {
+        // Largest first spends everything before it gives up
+        let target = Amount::from_sat(target);
+        let available: Amount = candidates(&utxos, &params)
+            .iter()
+            .map(|c| c.effective_value)
+            .sum();
+        let funded = strat_handler(&utxos, target, &params, &UTXOStrategy::LargestFirst).is_ok();
+        prop_assert_eq!(funded, available >= target + params.base_fee);
+    }
+
+    #[test]
+    fn deterministic_strategies_repeat_their_selection(
+        (utxos, params) in wallet(),
+        target in 1_000u64..20_000_000,
+    ) {
+        let target = Amount::from_sat(target);
+        for strategy in DETERMINISTIC {
+            let first = strat_handler(&utxos, target, &params, &strategy).ok();
+            let second = strat_handler(&utxos, target, &params, &strategy).ok();
+            prop_assert_eq!(
+                first.map(|selection| selection.utxos),
+                second.map(|selection| selection.utxos),
+                "{}",
+                strategy.name()
+            );
+        }
+
+        let candidates = candidates(&utxos, &params);
+        let target = target + params.base_fee;
+        prop_assert_eq!(
+            select_coins_bnb(&candidates, target, &params),
+            select_coins_bnb(&candidates, target, &params)
+        );
+    }
+
+    #[test]
+    fn random_strategies_repeat_with_a_fixed_seed(
+        (utxos, params) in wallet(),
+        target in 1_000u64..20_000_000,
+        seed: u64,
+    ) {
+        let target = Amount::from_sat(target);
+        for strategy in UTXOStrategy::ALL {
+            let seeded = strategy.seeded(seed);
+            let first = strat_handler(&utxos, target, &params, &seeded).ok();
+            let second = strat_handler(&utxos, target, &params, &seeded).ok();
+            prop_assert_eq!(
+                first.map(|selection| selection.utxos),
+                second.map(|selection| selection.utxos),
+                "{}",
+                strategy.name()
+            );
+        }
+    }
+}
+
+proptest! {
+    // Every case replays a whole scenario against every strategy
+    #![proptest_config(ProptestConfig::with_cases(4))]
+
+    #[test]
+    fn simulation_accounts_for_every_sat(seed: u64) {
+        let scenario = Scenario::random(seed, 200);
+        let deposits: Amount = scenario
+            .events
+            .iter()
+            .filter_map(|event| match event {
+                Event::Deposit(amount) => Some(*amount),
+                Event::Withdrawal { .. } => None,
+            })
+            .sum();
+
+        for strategy in UTXOStrategy::ALL {
+            let report = simulate(&scenario, &strategy, DEFAULT_LONG_TERM_FEE_RATE);
+            prop_assert_eq!(
+                report.withdrawn + report.fees_paid + report.balance,
+                deposits,
+                "{}",
+                strategy.name()
+            );
+            prop_assert_eq!(report.pool_sizes.len(), scenario.events.len());
+            prop_assert!(report.change_outputs <= report.withdrawals - report.failed_withdrawals);
+        }
+    }
+
+    #[test]
+    fn simulation_is_deterministic_with_fixed_seed(seed: u64) {
+        let scenario = Scenario::random(seed, 100);
+        prop_assert_eq!(&scenario, &Scenario::random(seed, 100));
+        for strategy in DETERMINISTIC {
+            prop_assert_eq!(
+                simulate(&scenario, &strategy, DEFAULT_LONG_TERM_FEE_RATE),
+                simulate(&scenario, &strategy, DEFAULT_LONG_TERM_FEE_RATE)
+            );
+        }
+        // Knapsack, single random draw, branch and bound's fallback and
+        // least waste, which runs them all, only repeat with their seed
+        for strategy in UTXOStrategy::ALL {
+            let seeded = strategy.seeded(seed);
+            prop_assert_eq!(
+                simulate(&scenario, &seeded, DEFAULT_LONG_TERM_FEE_RATE),
+                simulate(&scenario, &seeded, DEFAULT_LONG_TERM_FEE_RATE)
+            );
+        }
+    }
+}
+
+/// Selects the same UTXO twice.
+struct Greedy;
+
+impl CoinSelector for Greedy {
+    fn name(&self) -> &str {
+        "greedy"
+    }
+
+    fn select(
+        &self,
+        candidates: &[Candidate],
+        _target: Amount,
+        _params: &CoinSelectionParams,
+    ) -> Option<Vec<Candidate>> {
+        candidates.first().map(|c| vec![*c, *c])
+    }
+}
+
+#[test]
+fn strat_handler_rejects_invalid_selections() {
+    let utxos = utxo_pool(&[100_000, 200_000], 2);
+    let result = strat_handler(&utxos, Amount::from_sat(50_000), &params(1), &Greedy);
+    assert!(matches!(result, Err(UtilsError::InvalidSelection(name, _)) if name == "greedy"));
+}
@@ -7,38 +7,86 @@ use bitcoin::sighash::{Prevouts, SighashCache};
 use bitcoin::taproot::{LeafVersion, TaprootSpendInfo};
 use bitcoin::transaction::{Sequence, Version};
 use bitcoin::{
-    Amount, OutPoint, ScriptBuf, TapLeafHash, TapSighashType, Transaction, TxIn, TxOut, Witness,
+    Amount, FeeRate, OutPoint, ScriptBuf, TapLeafHash, TapSighashType, Transaction, TxIn, TxOut,
+    Witness,
 };
 use bitcoincore_rpc::json::{AddressType, ListUnspentResultEntry};
 
+use satoshi_suite_client::{
+    estimate_fee, tap_script_satisfaction_weight, utxo_satisfaction_weight, WalletBackend,
+};
+
 use crate::Wallet;
 
-pub fn build_commit_transaction(
-    wallet: &Wallet,
+/// Fee of a commit transaction spending `utxo` to `commit_script` with a
+/// taproot change output at `fee_rate`.
+pub fn estimate_commit_fee(
+    utxo: &ListUnspentResultEntry,
+    commit_script: &ScriptBuf,
+    postage: Amount,
+    fee_rate: FeeRate,
+) -> Result<Amount, Box<dyn Error>> {
+    // Change goes to a Bech32m address, which is the size of the commit output
+    let tx = commit_transaction(utxo, commit_script, postage, Some(commit_script.clone()));
+    let weight = utxo_satisfaction_weight(utxo).ok_or_else(|| {
+        format!(
+            "Cannot estimate the size of input {}:{}",
+            utxo.txid, utxo.vout
+        )
+    })?;
+    Ok(estimate_fee(&tx, &[weight], fee_rate))
+}
+
+pub fn build_commit_transaction<B: WalletBackend>(
+    wallet: &Wallet<B>,
     _secp: &Secp256k1<All>,
     utxo: ListUnspentResultEntry,
     postage: Amount,
-    fee_amount: Amount,
+    fee_rate: FeeRate,
     commit_script: ScriptBuf,
 ) -> Result<(Transaction, u32), Box<dyn Error>> {
-    let total_needed = postage
-        .to_sat()
-        .checked_add(fee_amount.to_sat())
-        .ok_or("Amount overflow")?;
+    let fee_amount = estimate_commit_fee(&utxo, &commit_script, postage, fee_rate)?;
+    let total_needed = postage.checked_add(fee_amount).ok_or("Amount overflow")?;
 
-    if total_needed > utxo.amount.to_sat() {
+    if total_needed > utxo.amount {
         return Err("Insufficient funds for commit transaction".into());
     }
 
-    let change_amount = Amount::from_sat(
-        utxo.amount
-            .to_sat()
-            .checked_sub(total_needed)
-            .ok_or("Amount underflow")?,
+    // Leave change below the dust limit to the fee
+    let change_script = wallet.new_address(&AddressType::Bech32m)?.script_pubkey();
+    let change_amount = utxo.amount - total_needed;
+    let mut tx = commit_transaction(
+        &utxo,
+        &commit_script,
+        postage,
+        (change_amount >= change_script.dust_value()).then_some(change_script),
     );
+    if let Some(change) = tx.output.get_mut(1) {
+        change.value = change_amount;
+    }
+
+    let signed_tx = wallet.sign_tx(&tx)?;
+    Ok((signed_tx, 0))
+}
+
+/// The unsigned commit transaction, with a zero value change output paying
+/// to `change_script` if there is one.
+fn commit_transaction(
+    utxo: &ListUnspentResultEntry,
+    commit_script: &ScriptBuf,
+    postage: Amount,
+    change_script: Option<ScriptBuf>,
+) -> Transaction {
+    let mut output = vec![TxOut {
+        value: postage,
+        script_pubkey: commit_script.clone(),
+    }];
+    output.extend(change_script.map(|script_pubkey| TxOut {
+        value: Amount::ZERO,
+        script_pubkey,
+    }));
 
-    // Create commit transaction
-    let tx = Transaction {
+    Transaction {
         version: Version::TWO,
         lock_time: LockTime::ZERO,
         input: vec![TxIn {
@@ -50,22 +98,13 @@ pub fn build_commit_transaction(
             sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
             witness: Witness::default(),
         }],
-        output: vec![
-            TxOut {
-                value: postage,
-                script_pubkey: commit_script,
-            },
-            TxOut {
-                value: change_amount,
-                script_pubkey: wallet.new_address(&AddressType::Bech32m)?.script_pubkey(),
-            },
-        ],
-    };
-
-    let signed_tx = wallet.sign_tx(&tx)?;
-    Ok((signed_tx, 0))
+        output,
+    }
 }
 
+/// Build and sign the reveal transaction spending the commit output through
+/// `reveal_script`. The fee at `fee_rate` comes out of the first output.
+#[allow(clippy::too_many_arguments)]
 pub fn build_reveal_transaction(
     secp: &Secp256k1<All>,
     key_pair: &UntweakedKeypair,
@@ -74,9 +113,17 @@ pub fn build_reveal_transaction(
     commit_outpoint: OutPoint,
     postage: Amount,
     sequence: Sequence,
+    fee_rate: FeeRate,
     reveal_outputs: Vec<TxOut>,
 ) -> Result<Transaction, Box<dyn Error>> {
-    // TODO: check reveal outputs length
+    if reveal_outputs.is_empty() {
+        return Err("Reveal transaction needs at least one output".into());
+    }
+
+    let control_block = taproot_spend_info
+        .control_block(&(reveal_script.clone(), LeafVersion::TapScript))
+        .ok_or("Reveal script is not in the taproot tree")?;
+
     let mut reveal_tx = Transaction {
         version: Version::TWO,
         lock_time: LockTime::ZERO,
@@ -89,6 +136,20 @@ pub fn build_reveal_transaction(
         output: reveal_outputs,
     };
 
+    let satisfaction_weight = tap_script_satisfaction_weight(reveal_script, &control_block, 1);
+    let fee = estimate_fee(&reveal_tx, &[satisfaction_weight], fee_rate);
+    let first_output = &mut reveal_tx.output[0];
+    first_output.value = first_output
+        .value
+        .checked_sub(fee)
+        .filter(|value| *value >= first_output.script_pubkey.dust_value())
+        .ok_or_else(|| {
+            format!(
+                "Postage of {} cannot pay the reveal fee of {} and leave a spendable output",
+                first_output.value, fee
+            )
+        })?;
+
     // Create the input's previous output
     let prev_tx_out = TxOut {
         value: postage,
@@ -119,12 +180,7 @@ pub fn build_reveal_transaction(
 
     witness.push(signature.as_ref());
     witness.push(reveal_script);
-    witness.push(
-        &taproot_spend_info
-            .control_block(&(reveal_script.clone(), LeafVersion::TapScript))
-            .expect("Failed to create control block")
-            .serialize(),
-    );
+    witness.push(control_block.serialize());
 
     Ok(reveal_tx)
 }
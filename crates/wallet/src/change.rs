@@ -0,0 +1,155 @@
+use tracing::warn;
+
+use bitcoin::secp256k1::rand::{thread_rng, Rng};
+use bitcoin::{Address, Amount, FeeRate, Script, TxOut};
+use bitcoincore_rpc::json::AddressType;
+
+use satoshi_suite_client::WalletBackend;
+
+use crate::{Wallet, WalletError};
+
+/// How change is paid back to the wallet.
+///
+/// Change below the dust limit of its output is never created; it is left
+/// to the fee when the coins are selected.
+#[derive(Clone, Copy, Debug)]
+pub struct ChangePolicy {
+    /// Pay change to the same type of address as the recipient, so the two
+    /// outputs cannot be told apart by type.
+    pub match_recipient_type: bool,
+    /// Put change outputs at random positions rather than last.
+    pub random_position: bool,
+    /// Split change into up to this many outputs of equal value.
+    pub max_outputs: usize,
+}
+
+impl Default for ChangePolicy {
+    /// A single Bech32 change output after the payments.
+    fn default() -> Self {
+        ChangePolicy {
+            match_recipient_type: false,
+            random_position: false,
+            max_outputs: 1,
+        }
+    }
+}
+
+impl ChangePolicy {
+    /// A fresh change address for a payment to `recipient`. When the wallet
+    /// cannot make an address of the recipient's type, e.g. a multisig
+    /// wallet of `wsh` descriptors paying a taproot address, the change goes
+    /// to a Bech32 address instead, or Bech32m for a wallet of taproot
+    /// descriptors only.
+    pub fn change_address<B: WalletBackend>(
+        &self,
+        wallet: &Wallet<B>,
+        recipient: &Script,
+    ) -> Result<Address, WalletError> {
+        let matched = address_type(recipient).filter(|_| self.match_recipient_type);
+        match matched {
+            Some(address_type) if address_type != AddressType::Bech32 => {
+                wallet.new_address(&address_type).or_else(|e| {
+                    warn!(
+                        "Cannot match the recipient's address type for change: {}",
+                        e
+                    );
+                    segwit_address(wallet)
+                })
+            }
+            _ => segwit_address(wallet),
+        }
+    }
+
+    /// Outputs paying `change` back to the wallet, the first to
+    /// `change_address` and any others to fresh addresses of the same type.
+    /// Every output after the first costs its fee at `fee_rate` out of the
+    /// change, and change is only split as far as every part stays above
+    /// the dust limit.
+    pub fn change_outputs<B: WalletBackend>(
+        &self,
+        wallet: &Wallet<B>,
+        change_address: Address,
+        change: Amount,
+        fee_rate: FeeRate,
+    ) -> Result<Vec<TxOut>, WalletError> {
+        let script_pubkey = change_address.script_pubkey();
+        let output_fee = fee_rate
+            .fee_wu(
+                TxOut {
+                    value: change,
+                    script_pubkey: script_pubkey.clone(),
+                }
+                .weight(),
+            )
+            .ok_or(WalletError::FundingFailed("Fee rate too high".to_string()))?;
+        let parts = self.split(change, output_fee, script_pubkey.dust_value());
+
+        let address_type = address_type(&script_pubkey).unwrap_or(AddressType::Bech32);
+        let mut outputs = vec![TxOut {
+            value: parts[0],
+            script_pubkey,
+        }];
+        for value in &parts[1..] {
+            outputs.push(TxOut {
+                value: *value,
+                script_pubkey: wallet.new_address(&address_type)?.script_pubkey(),
+            });
+        }
+        Ok(outputs)
+    }
+
+    /// `change` split into as many of `max_outputs` equal parts as it
+    /// allows, after `output_fee` for every part but the first, with none
+    /// below `min_change`. The first part takes any remainder.
+    pub fn split(&self, change: Amount, output_fee: Amount, min_change: Amount) -> Vec<Amount> {
+        for count in (2..=self.max_outputs as u64).rev() {
+            let Some(left) = change.checked_sub(output_fee * (count - 1)) else {
+                continue;
+            };
+            let part = left / count;
+            if part >= min_change {
+                let mut parts = vec![part; count as usize];
+                parts[0] += left - part * count;
+                return parts;
+            }
+        }
+        vec![change]
+    }
+
+    /// Add `change` to `outputs`, each at a random position or else at the
+    /// end.
+    pub fn place_change(&self, outputs: &mut Vec<TxOut>, change: Vec<TxOut>) {
+        let mut rng = thread_rng();
+        for output in change {
+            let position = match self.random_position {
+                true => rng.gen_range(0..=outputs.len()),
+                false => outputs.len(),
+            };
+            outputs.insert(position, output);
+        }
+    }
+}
+
+/// A fresh Bech32 address, or Bech32m from a wallet without Bech32
+/// descriptors.
+fn segwit_address<B: WalletBackend>(wallet: &Wallet<B>) -> Result<Address, WalletError> {
+    wallet
+        .new_address(&AddressType::Bech32)
+        .or_else(|_| wallet.new_address(&AddressType::Bech32m))
+}
+
+/// The type of wallet address paying to `script_pubkey`, if the wallet can
+/// make one.
+pub fn address_type(script_pubkey: &Script) -> Option<AddressType> {
+    if script_pubkey.is_p2pkh() {
+        Some(AddressType::Legacy)
+    } else if script_pubkey.is_p2sh() {
+        Some(AddressType::P2shSegwit)
+    } else if script_pubkey.is_p2wpkh() || script_pubkey.is_p2wsh() {
+        Some(AddressType::Bech32)
+    } else if script_pubkey.is_p2tr() {
+        Some(AddressType::Bech32m)
+    } else {
+        None
+    }
+}
@@ -0,0 +1,140 @@
+use std::{
+    collections::BTreeSet,
+    env, fs, io,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use bitcoin::OutPoint;
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+
+use satoshi_suite_config::Chain;
+
+use crate::WalletError;
+
+/// Bytes of a wallet name escaped in its file name: all but letters,
+/// digits, `-`, `_` and `.`, so no name reaches outside the directory.
+const FILE_NAME_ESCAPES: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'_').remove(b'.');
+
+/// Outpoints a wallet never spends until they are thawed. Unlike Bitcoin
+/// Core's `lockunspent` locks, which go when the wallet is unloaded, the
+/// set is saved to a file on every change.
+#[derive(Debug, Default)]
+pub struct FrozenCoins {
+    /// Where the set is saved; `None` keeps it in memory only.
+    path: Option<PathBuf>,
+    outpoints: BTreeSet<OutPoint>,
+}
+
+impl FrozenCoins {
+    /// The set saved at `path`, or an empty one to be saved there if the
+    /// file does not exist yet.
+    pub fn load(path: &Path) -> Result<Self, WalletError> {
+        let io_error = |e| WalletError::Io(path.to_path_buf(), e);
+        let outpoints = match fs::read_to_string(path) {
+            Ok(contents) => {
+                let invalid = |e: String| io_error(io::Error::new(io::ErrorKind::InvalidData, e));
+                serde_json::from_str::<Vec<String>>(&contents)
+                    .map_err(|e| invalid(e.to_string()))?
+                    .iter()
+                    .map(|outpoint| {
+                        OutPoint::from_str(outpoint).map_err(|e| invalid(e.to_string()))
+                    })
+                    .collect::<Result<_, _>>()?
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => BTreeSet::new(),
+            Err(e) => return Err(io_error(e)),
+        };
+        Ok(FrozenCoins {
+            path: Some(path.to_path_buf()),
+            outpoints,
+        })
+    }
+
+    /// `$XDG_DATA_HOME/satoshi-suite/frozen/<chain>/<wallet_name>.json`,
+    /// with `~/.local/share` for an unset `XDG_DATA_HOME`. The wallet name
+    /// is percent-encoded, as Bitcoin Core allows `/` in it.
+    pub fn default_path(chain: Chain, wallet_name: &str) -> Option<PathBuf> {
+        let data_home = env::var_os("XDG_DATA_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| {
+                env::var_os("HOME")
+                    .filter(|home| !home.is_empty())
+                    .map(|home| Path::new(&home).join(".local").join("share"))
+            })?;
+        Some(default_path_in(&data_home, chain, wallet_name))
+    }
+
+    pub fn contains(&self, outpoint: &OutPoint) -> bool {
+        self.outpoints.contains(outpoint)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.outpoints.is_empty()
+    }
+
+    pub fn outpoints(&self) -> Vec<OutPoint> {
+        self.outpoints.iter().copied().collect()
+    }
+
+    pub fn freeze(&mut self, outpoints: &[OutPoint]) -> Result<(), WalletError> {
+        self.outpoints.extend(outpoints);
+        self.save()
+    }
+
+    /// Let `outpoints` be spent again. Outpoints that are not frozen are
+    /// ignored.
+    pub fn thaw(&mut self, outpoints: &[OutPoint]) -> Result<(), WalletError> {
+        for outpoint in outpoints {
+            self.outpoints.remove(outpoint);
+        }
+        self.save()
+    }
+
+    fn save(&self) -> Result<(), WalletError> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let io_error = |e| WalletError::Io(path.clone(), e);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(io_error)?;
+        }
+        let outpoints: Vec<String> = self.outpoints.iter().map(OutPoint::to_string).collect();
+        let contents = serde_json::to_string_pretty(&outpoints).expect("strings serialize");
+        fs::write(path, contents).map_err(io_error)
+    }
+}
+
+/// [`FrozenCoins::default_path`] under the data directory `data_home`.
+fn default_path_in(data_home: &Path, chain: Chain, wallet_name: &str) -> PathBuf {
+    data_home
+        .join("satoshi-suite")
+        .join("frozen")
+        .join(chain.to_string())
+        .join(format!(
+            "{}.json",
+            utf8_percent_encode(wallet_name, FILE_NAME_ESCAPES)
+        ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wallet_names_stay_inside_the_chain_directory() {
+        let dir = Path::new("/data/satoshi-suite/frozen/regtest");
+        for (name, file) in [
+            ("default_wallet", "default_wallet.json"),
+            ("../../escape", "..%2F..%2Fescape.json"),
+            ("..", "...json"),
+            ("/etc/passwd", "%2Fetc%2Fpasswd.json"),
+            ("my wallet", "my%20wallet.json"),
+        ] {
+            let path = default_path_in(Path::new("/data"), Chain::Regtest, name);
+            assert_eq!(path, dir.join(file));
+            assert_eq!(path.parent(), Some(dir));
+        }
+    }
+}
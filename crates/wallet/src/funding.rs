@@ -0,0 +1,183 @@
+use std::error::Error;
+
+use tracing::info;
+
+use bitcoin::{
+    absolute::{self, LockTime},
+    relative,
+    transaction::Version,
+    Amount, FeeRate, OutPoint, Script, Sequence, Transaction, TxOut, Weight,
+};
+use bitcoincore_rpc::json::ListUnspentResultEntry;
+
+use satoshi_suite_client::{estimate_fee, script_satisfaction_weight, utxo_satisfaction_weight};
+use satoshi_suite_utxo_selection::{
+    manual_selection, strat_handler, CoinSelectionParams, CoinSelector, Selection,
+    INPUT_BASE_WEIGHT,
+};
+
+/// Choices about the coins one transaction spends, which
+/// [`crate::Wallet::fund`] honours on top of the wallet's own frozen coins
+/// and asset protection.
+#[derive(Clone, Debug, Default)]
+pub struct SpendOptions {
+    /// Outpoints chosen by hand to spend, instead of letting a coin selector
+    /// pick.
+    pub inputs: Vec<OutPoint>,
+    /// Relative timelock every input carries, to spend through an `older()`
+    /// branch. In blocks, only coins confirmed at least this many blocks ago
+    /// are spent. In units of time only confirmed coins are, as when they
+    /// confirmed is not known here; the node refuses the transaction until
+    /// the lock has passed.
+    pub older: Option<relative::LockTime>,
+    /// Lock time of the transaction, to spend through an `after()` branch.
+    pub after: Option<absolute::LockTime>,
+}
+
+impl SpendOptions {
+    /// The sequence of every input, locking them for [`SpendOptions::older`]
+    /// if set and otherwise `default`, made non-final if the transaction
+    /// has a lock time.
+    pub fn sequence(&self, default: Sequence) -> Sequence {
+        match self.older {
+            Some(relative::LockTime::Blocks(height)) => Sequence::from_height(height.value()),
+            Some(relative::LockTime::Time(time)) => {
+                Sequence::from_512_second_intervals(time.value())
+            }
+            None if self.after.is_some() && default.is_final() => Sequence::ENABLE_LOCKTIME_NO_RBF,
+            None => default,
+        }
+    }
+
+    /// The lock time of the transaction, [`SpendOptions::after`] or zero.
+    pub fn lock_time(&self) -> LockTime {
+        self.after.unwrap_or(LockTime::ZERO)
+    }
+
+    /// Whether `utxo` is old enough for [`SpendOptions::older`].
+    pub(crate) fn old_enough(&self, utxo: &ListUnspentResultEntry) -> bool {
+        match self.older {
+            Some(relative::LockTime::Blocks(height)) => {
+                utxo.confirmations >= u32::from(height.value())
+            }
+            Some(relative::LockTime::Time(_)) => utxo.confirmations > 0,
+            None => true,
+        }
+    }
+
+    /// [`SpendOptions::older`] in words, for errors about coins too young.
+    pub(crate) fn older_description(&self) -> String {
+        match self.older {
+            Some(relative::LockTime::Blocks(height)) => format!("{} blocks", height),
+            Some(relative::LockTime::Time(time)) => format!("{} 512-second intervals", time),
+            None => "none".to_string(),
+        }
+    }
+}
+
+/// Select coins with `selector` that cover `outputs` plus the fee for
+/// spending them at `fee_rate`. Change goes to `change_script` unless it
+/// would be dust, in which case it is left to the fee. `long_term_fee_rate`
+/// is what the coins are expected to cost to spend later, which decides
+/// whether spending more of them now is worth it.
+pub fn fund_outputs(
+    utxos: &[ListUnspentResultEntry],
+    outputs: &[TxOut],
+    change_script: &Script,
+    fee_rate: FeeRate,
+    long_term_fee_rate: FeeRate,
+    selector: &dyn CoinSelector,
+) -> Result<Selection, Box<dyn Error>> {
+    let total_out: Amount = outputs.iter().map(|output| output.value).sum();
+    let params = funding_params(utxos, outputs, change_script, fee_rate, long_term_fee_rate)?;
+
+    let selection = strat_handler(utxos, total_out, &params, selector)
+        .map_err(|e| format!("Error selecting UTXOs: {}", e))?;
+    info!(
+        "Selected {} UTXOs with {}, waste: {}, privacy: {}",
+        selection.utxos.len(),
+        selector.name(),
+        selection.waste,
+        selection.privacy
+    );
+    Ok(selection)
+}
+
+/// Pay for `outputs` with exactly `utxos`, chosen by hand, like
+/// [`fund_outputs`] does with the coins a selector picks.
+pub fn fund_outputs_with(
+    utxos: &[ListUnspentResultEntry],
+    outputs: &[TxOut],
+    change_script: &Script,
+    fee_rate: FeeRate,
+    long_term_fee_rate: FeeRate,
+) -> Result<Selection, Box<dyn Error>> {
+    let total_out: Amount = outputs.iter().map(|output| output.value).sum();
+    let params = funding_params(utxos, outputs, change_script, fee_rate, long_term_fee_rate)?;
+
+    let selection = manual_selection(utxos, total_out, &params)
+        .map_err(|e| format!("Cannot fund with the chosen inputs: {}", e))?;
+    info!(
+        "Spending {} chosen UTXOs, waste: {}, privacy: {}",
+        selection.utxos.len(),
+        selection.waste,
+        selection.privacy
+    );
+    Ok(selection)
+}
+
+fn funding_params(
+    utxos: &[ListUnspentResultEntry],
+    outputs: &[TxOut],
+    change_script: &Script,
+    fee_rate: FeeRate,
+    long_term_fee_rate: FeeRate,
+) -> Result<CoinSelectionParams, Box<dyn Error>> {
+    let change_output = TxOut {
+        value: Amount::ZERO,
+        script_pubkey: change_script.to_owned(),
+    };
+    let change_spend_weight = change_satisfaction_weight(utxos, change_script)
+        .map(|weight| INPUT_BASE_WEIGHT + weight)
+        .ok_or("Cannot estimate the size of spending the change output")?;
+
+    Ok(CoinSelectionParams {
+        fee_rate,
+        long_term_fee_rate,
+        base_fee: estimate_fee(&unsigned_tx(outputs), &[], fee_rate),
+        change_fee: fee_rate
+            .fee_vb(change_output.weight().to_vbytes_ceil())
+            .ok_or("Fee rate too high")?,
+        change_spend_fee: long_term_fee_rate
+            .fee_wu(change_spend_weight)
+            .ok_or("Long-term fee rate too high")?,
+        min_change: change_script.dust_value(),
+    })
+}
+
+/// Satisfaction weight of spending `change_script`. A wallet's change
+/// shares the descriptor of its coins, so it is sized like the heaviest of
+/// `utxos` of the same script type, which also covers multisig change that
+/// its type alone does not size.
+pub fn change_satisfaction_weight(
+    utxos: &[ListUnspentResultEntry],
+    change_script: &Script,
+) -> Option<Weight> {
+    let script_type = |script: &Script| (script.witness_version(), script.len());
+    utxos
+        .iter()
+        .filter(|utxo| script_type(&utxo.script_pub_key) == script_type(change_script))
+        .filter_map(utxo_satisfaction_weight)
+        .max()
+        .or_else(|| script_satisfaction_weight(change_script))
+}
+
+/// A transaction paying `outputs` without any inputs yet, for sizing.
+fn unsigned_tx(outputs: &[TxOut]) -> Transaction {
+    Transaction {
+        version: Version::TWO,
+        lock_time: LockTime::ZERO,
+        input: Vec::new(),
+        output: outputs.to_vec(),
+    }
+}
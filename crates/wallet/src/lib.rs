@@ -4,9 +4,24 @@ pub use wallet::*;
 mod multisig_wallet;
 pub use multisig_wallet::*;
 
+mod policy_wallet;
+pub use policy_wallet::*;
+
 mod builder;
 pub use builder::*;
 
+mod change;
+pub use change::*;
+
+mod frozen;
+pub use frozen::*;
+
+mod funding;
+pub use funding::*;
+
+mod payments;
+pub use payments::*;
+
 mod taproot;
 pub use taproot::*;
 
@@ -1,228 +1,518 @@
-use std::{collections::HashMap, error::Error};
+use std::{error::Error, fmt, str::FromStr};
 
-use tracing::info;
+use tracing::{info, warn};
 
+use serde::Serialize;
 use serde_json::json;
 
-use bitcoin::Amount;
-use bitcoincore_rpc::{
-    json::{AddressType, CreateRawTransactionInput, WalletCreateFundedPsbtResult},
-    RpcApi,
+use bitcoin::{
+    bip32::{ChildNumber, DerivationPath, Xpub},
+    transaction::Version,
+    Address, Amount, FeeRate, OutPoint, Psbt, Sequence, Transaction, TxIn, TxOut,
+};
+use bitcoincore_rpc::json::WalletCreateFundedPsbtResult;
+use miniscript::{
+    descriptor::{DescriptorXKey, Wildcard},
+    Descriptor, DescriptorPublicKey,
 };
 
-use satoshi_suite_client::create_rpc_client;
-use satoshi_suite_config::Config;
-use satoshi_suite_utxo_selection::{strat_handler, UTXOStrategy};
+use satoshi_suite_client::WalletBackend;
+use satoshi_suite_utxo_selection::CoinSelector;
+
+use crate::{ChangePolicy, Payment, SpendOptions, Wallet};
+
+/// Most keys `multi` and `sortedmulti` take, the limit of
+/// `OP_CHECKMULTISIG`.
+pub const MAX_MULTI_KEYS: usize = 20;
+
+/// Most keys `multi_a` and `sortedmulti_a` take, keeping every signature
+/// and key within the 1000 item stack limit of tapscript.
+pub const MAX_MULTI_A_KEYS: usize = 999;
+
+/// BIP341's provably unspendable internal key `H`, leaving taproot
+/// multisig coins only the script path.
+pub const NUMS_INTERNAL_KEY: &str =
+    "50929b74c1a04954b78b4b6035e97a5e078a5a0f28ec96d547bfee9ace803ac0";
+
+/// Script a multisig wallet locks its coins to.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MultisigScript {
+    /// `wsh(sortedmulti(...))`, for up to [`MAX_MULTI_KEYS`] keys.
+    #[default]
+    Wsh,
+    /// `tr(...)` with a `sortedmulti_a` leaf of all keys, for up to
+    /// [`MAX_MULTI_A_KEYS`] keys.
+    Tr(TaprootMultisig),
+}
 
-use crate::Wallet;
+/// Spending paths of a taproot multisig wallet besides its leaf needing
+/// `nrequired` of all keys.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct TaprootMultisig {
+    /// Index among the cosigners of the one whose key is the internal key,
+    /// spending alone by the key path. Without one the internal key is
+    /// [`NUMS_INTERNAL_KEY`] and coins only spend by script.
+    pub key_path_signer: Option<usize>,
+    /// Further leaves needing fewer signatures once coins are old enough.
+    pub timelocked: Vec<TimelockedLeaf>,
+}
 
-#[derive(Debug)]
-pub struct MultisigWallet {
-    pub name: String,
+/// A leaf needing `nrequired` of all keys once a coin has been confirmed
+/// for `blocks` blocks, written `NREQUIRED@BLOCKS`. Descriptors only take
+/// `sortedmulti_a` as a whole leaf, so its keys are in `multi_a` in the
+/// order of their account keys.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct TimelockedLeaf {
     pub nrequired: u32,
-    pub signers: Vec<String>,
+    pub blocks: u16,
 }
 
-impl MultisigWallet {
-    pub fn new(
-        wallet_names: &Vec<String>,
-        nrequired: u32,
-        multisig_name: &str,
-        config: &Config,
-    ) -> Result<Self, Box<dyn Error>> {
-        if wallet_names.len() < nrequired as usize {
-            return Err("More required signers than wallets".into());
-        }
+impl FromStr for TimelockedLeaf {
+    type Err = String;
 
-        let mut xpubs: HashMap<String, String> = HashMap::new();
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid timelocked leaf {}: expected NREQUIRED@BLOCKS", s);
+        let (nrequired, blocks) = s.split_once('@').ok_or_else(invalid)?;
+        Ok(TimelockedLeaf {
+            nrequired: nrequired.parse().map_err(|_| invalid())?,
+            blocks: blocks.parse().map_err(|_| invalid())?,
+        })
+    }
+}
 
-        for wallet_name in wallet_names {
-            Wallet::new(wallet_name, config)?;
+impl MultisigScript {
+    pub fn max_keys(&self) -> usize {
+        match self {
+            MultisigScript::Wsh => MAX_MULTI_KEYS,
+            MultisigScript::Tr(_) => MAX_MULTI_A_KEYS,
         }
+    }
 
-        for (i, wallet_name) in wallet_names.iter().enumerate() {
-            let client = create_rpc_client(config, Some(wallet_name))?;
-            let descriptors: serde_json::Value = client.call("listdescriptors", &[])?;
-            let descriptors_array: &Vec<serde_json::Value> = descriptors["descriptors"]
-                .as_array()
-                .ok_or_else(|| format!("Invalid descriptor format for wallet {}", wallet_name))?;
-            xpubs = extract_int_ext_xpubs(xpubs, descriptors_array.clone(), i)?;
+    /// Reasons `nrequired` of `count` keys cannot make this script.
+    fn validate(&self, nrequired: u32, count: usize) -> Result<(), String> {
+        let MultisigScript::Tr(taproot) = self else {
+            return Ok(());
+        };
+        if taproot.key_path_signer.is_some_and(|index| index >= count) {
+            return Err("Key-path signer is not one of the cosigners".to_string());
         }
+        for (i, leaf) in taproot.timelocked.iter().enumerate() {
+            if leaf.nrequired == 0 {
+                return Err("Timelocked leaves need at least one signature".to_string());
+            }
+            if leaf.nrequired >= nrequired {
+                return Err(format!(
+                    "Timelocked leaf {}@{} must need fewer than the {} signatures needed without a timelock",
+                    leaf.nrequired, leaf.blocks, nrequired
+                ));
+            }
+            if leaf.blocks == 0 {
+                return Err("Timelocked leaves need a timelock of at least one block".to_string());
+            }
+            if taproot.timelocked[..i].contains(leaf) {
+                return Err(format!(
+                    "Timelocked leaf {}@{} given twice",
+                    leaf.nrequired, leaf.blocks
+                ));
+            }
+        }
+        Ok(())
+    }
 
-        let num_signers = nrequired.to_string();
-        let external_desc = format!(
-            "wsh(sortedmulti({}, {}, {}, {}))",
-            num_signers,
-            xpubs["external_xpub_1"],
-            xpubs["external_xpub_2"],
-            xpubs["external_xpub_3"]
-        );
-        let internal_desc = format!(
-            "wsh(sortedmulti({}, {}, {}, {}))",
-            num_signers,
-            xpubs["internal_xpub_1"],
-            xpubs["internal_xpub_2"],
-            xpubs["internal_xpub_3"]
-        );
-
-        let client = create_rpc_client(config, None)?;
+    /// The descriptor requiring `nrequired` of `keys`, each already ending
+    /// in its derivation path, with `internal_key` the derived key of a
+    /// taproot key-path signer.
+    fn descriptor(&self, nrequired: u32, keys: &[String], internal_key: Option<String>) -> String {
+        let MultisigScript::Tr(taproot) = self else {
+            return format!("wsh(sortedmulti({},{}))", nrequired, keys.join(","));
+        };
+        let multi_a = |nrequired: u32| format!("multi_a({},{})", nrequired, keys.join(","));
+        let mut leaves = vec![format!("sorted{}", multi_a(nrequired))];
+        leaves.extend(taproot.timelocked.iter().map(|leaf| {
+            format!(
+                "and_v(v:{},older({}))",
+                multi_a(leaf.nrequired),
+                leaf.blocks
+            )
+        }));
+        // The leaf spent most often sits nearest the root, for the shortest
+        // control block
+        let tree = leaves
+            .into_iter()
+            .rev()
+            .reduce(|subtree, leaf| format!("{{{},{}}}", leaf, subtree))
+            .expect("at least one leaf");
+        format!(
+            "tr({},{})",
+            internal_key.as_deref().unwrap_or(NUMS_INTERNAL_KEY),
+            tree
+        )
+    }
+}
 
-        let external_desc_info = client.get_descriptor_info(&external_desc)?;
-        let internal_desc_info = client.get_descriptor_info(&internal_desc)?;
+impl fmt::Display for MultisigScript {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MultisigScript::Wsh => write!(f, "wsh"),
+            MultisigScript::Tr(_) => write!(f, "tr"),
+        }
+    }
+}
 
-        let external_descriptor = external_desc_info.descriptor;
-        let internal_descriptor = internal_desc_info.descriptor;
+/// A key of a multisig wallet, as an account-level extended public key
+/// whose `/0/*` and `/1/*` children receive and take change.
+#[derive(Clone, Debug)]
+pub enum Cosigner {
+    /// A wallet with private keys on the same node, signing with the
+    /// account key of its active `wpkh` descriptors.
+    Wallet(String),
+    /// A key held elsewhere, such as on a hardware wallet.
+    Xpub(DescriptorPublicKey),
+}
 
-        let multisig_ext_desc = json!({
-            "desc": external_descriptor,
-            "active": true,
-            "internal": false,
-            "timestamp": json!("now")
-        });
+impl Cosigner {
+    /// An external key given as `[fingerprint/path]xpub`. The key origin
+    /// lets signers find their key, so a key without one is accepted with
+    /// a warning.
+    pub fn xpub(key: &str) -> Result<Self, Box<dyn Error>> {
+        let invalid = |reason: &str| format!("Invalid cosigner key {}: {}", key, reason);
+        let parsed = DescriptorPublicKey::from_str(key).map_err(|e| invalid(&e.to_string()))?;
+        match &parsed {
+            DescriptorPublicKey::XPub(xkey)
+                if xkey.derivation_path.is_master() && xkey.wildcard == Wildcard::None =>
+            {
+                if xkey.origin.is_none() {
+                    warn!(
+                        "Cosigner key {} has no [fingerprint/path] origin; signers may not recognise it",
+                        key
+                    );
+                }
+                Ok(Cosigner::Xpub(parsed))
+            }
+            DescriptorPublicKey::XPub(_) => {
+                Err(invalid("expected an account key without a derivation path after it").into())
+            }
+            _ => Err(invalid("expected an extended public key").into()),
+        }
+    }
 
-        let multisig_int_desc = json!({
-            "desc": internal_descriptor,
-            "active": true,
-            "internal": true,
-            "timestamp": json!("now")
-        });
+    /// Whether `name_or_key` names this wallet or is this key.
+    pub fn matches(&self, name_or_key: &str) -> bool {
+        match self {
+            Cosigner::Wallet(wallet_name) => wallet_name == name_or_key,
+            Cosigner::Xpub(key) => {
+                DescriptorPublicKey::from_str(name_or_key).is_ok_and(|other| other == *key)
+            }
+        }
+    }
 
-        let multisig_desc = json!([multisig_ext_desc, multisig_int_desc]);
+    /// The account key, looked up on `node` for a wallet.
+    pub(crate) fn account_key<B: WalletBackend>(
+        &self,
+        node: &B,
+    ) -> Result<DescriptorXKey<Xpub>, Box<dyn Error>> {
+        match self {
+            Cosigner::Wallet(wallet_name) => wallet_account_key(node, wallet_name),
+            Cosigner::Xpub(DescriptorPublicKey::XPub(xkey)) => Ok(xkey.clone()),
+            Cosigner::Xpub(key) => Err(format!("Not an extended public key: {}", key).into()),
+        }
+    }
+}
 
-        client.create_wallet(multisig_name, Some(true), Some(true), None, None)?;
+impl fmt::Display for Cosigner {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Cosigner::Wallet(wallet_name) => write!(f, "{}", wallet_name),
+            Cosigner::Xpub(key) => write!(f, "{}", key),
+        }
+    }
+}
 
-        let multisig_desc_vec: Vec<serde_json::Value> = serde_json::from_value(multisig_desc)?;
-        let client2 = create_rpc_client(config, Some(multisig_name))?;
-        client2.call::<serde_json::Value>("importdescriptors", &[json!(multisig_desc_vec)])?;
+#[derive(Debug, Serialize)]
+pub struct MultisigWallet {
+    pub name: String,
+    pub nrequired: u32,
+    pub signers: Vec<String>,
+    pub script: MultisigScript,
+    /// The receiving descriptor, with checksum, for backing up the wallet.
+    pub descriptor: String,
+}
 
-        let wallet = Wallet::new(multisig_name, config)?;
-        let info = wallet.get_wallet_info()?;
-        info!("{:#?}", info);
+impl MultisigWallet {
+    /// Create the watch-only wallet `multisig_name` on `node`, whose coins
+    /// need `nrequired` signatures of `cosigners`.
+    pub fn new<B: WalletBackend>(
+        node: &B,
+        cosigners: &[Cosigner],
+        nrequired: u32,
+        script: MultisigScript,
+        multisig_name: &str,
+    ) -> Result<Self, Box<dyn Error>> {
+        if cosigners.is_empty() {
+            return Err("No cosigners given".into());
+        }
+        if nrequired == 0 {
+            return Err("At least one signature must be required".into());
+        }
+        if nrequired as usize > cosigners.len() {
+            return Err(format!(
+                "{} signatures required but only {} cosigners given",
+                nrequired,
+                cosigners.len()
+            )
+            .into());
+        }
+        if cosigners.len() > script.max_keys() {
+            let hint = match script {
+                MultisigScript::Wsh => "; use taproot for more",
+                MultisigScript::Tr(_) => "",
+            };
+            return Err(format!(
+                "{} multisig takes at most {} keys, not {}{}",
+                script,
+                script.max_keys(),
+                cosigners.len(),
+                hint
+            )
+            .into());
+        }
+        script.validate(nrequired, cosigners.len())?;
+
+        let mut account_keys: Vec<DescriptorXKey<Xpub>> = Vec::new();
+        for cosigner in cosigners {
+            let account_key = cosigner.account_key(node)?;
+            if account_keys.iter().any(|key| key.xkey == account_key.xkey) {
+                return Err(format!("Cosigner key given twice: {}", account_key.xkey).into());
+            }
+            account_keys.push(account_key);
+        }
+        let key_path_key = match &script {
+            MultisigScript::Tr(taproot) => taproot
+                .key_path_signer
+                .map(|index| account_keys[index].clone()),
+            MultisigScript::Wsh => None,
+        };
+        // The same cosigners in any order make the same wallet
+        account_keys.sort_by_key(|key| key.xkey.encode());
+
+        let chain = |branch: u32| {
+            let derived = |key: &DescriptorXKey<Xpub>| {
+                format!("{}/{}/*", DescriptorPublicKey::XPub(key.clone()), branch)
+            };
+            let keys: Vec<String> = account_keys.iter().map(derived).collect();
+            let internal_key = key_path_key.as_ref().map(derived);
+            node.get_descriptor_info(&script.descriptor(nrequired, &keys, internal_key))
+        };
+        let external_descriptor = chain(0)?.descriptor;
+        let internal_descriptor = chain(1)?.descriptor;
+
+        create_watch_only(
+            node,
+            multisig_name,
+            &external_descriptor,
+            &internal_descriptor,
+        )?;
 
         Ok(Self {
             name: multisig_name.to_string(),
             nrequired,
-            signers: wallet_names.clone(),
+            signers: cosigners.iter().map(Cosigner::to_string).collect(),
+            script,
+            descriptor: external_descriptor,
         })
     }
 
-    pub fn create_psbt(
-        wallet_name: &str,
-        recipient: &String,
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_psbt<B: WalletBackend>(
+        wallet: &Wallet<B>,
+        recipient: &str,
         amount: Amount,
-        fee_amount: Amount,
-        utxo_strat: UTXOStrategy,
-        config: &Config,
+        fee_rate: FeeRate,
+        long_term_fee_rate: FeeRate,
+        selector: &dyn CoinSelector,
+        change_policy: &ChangePolicy,
+        options: &SpendOptions,
+    ) -> Result<WalletCreateFundedPsbtResult, Box<dyn Error>> {
+        let payment = Payment {
+            address: Address::from_str(recipient)?.require_network(wallet.network)?,
+            amount,
+        };
+        MultisigWallet::create_batch_psbt(
+            wallet,
+            &[payment],
+            fee_rate,
+            long_term_fee_rate,
+            selector,
+            change_policy,
+            options,
+        )
+    }
+
+    /// A PSBT paying every one of `payments`, with coins selected once for
+    /// all of them. Change matching the recipient's type follows the first
+    /// payment and is placed as `change_policy` says. Inputs carry the
+    /// relative timelock of [`SpendOptions::older`] and the transaction the
+    /// lock time of [`SpendOptions::after`].
+    pub fn create_batch_psbt<B: WalletBackend>(
+        wallet: &Wallet<B>,
+        payments: &[Payment],
+        fee_rate: FeeRate,
+        long_term_fee_rate: FeeRate,
+        selector: &dyn CoinSelector,
+        change_policy: &ChangePolicy,
+        options: &SpendOptions,
     ) -> Result<WalletCreateFundedPsbtResult, Box<dyn Error>> {
-        let wallet: Wallet = Wallet::new(wallet_name, config)?;
         // Ensure the wallet is a multisig wallet
         if wallet.get_wallet_info()?.private_keys_enabled {
             return Err("Wallet is not a multisig wallet".into());
         }
 
+        let first = payments.first().ok_or("No payments given")?;
+        let total: Amount = payments.iter().map(|payment| payment.amount).sum();
+
         let bal = wallet.get_balances()?;
-        if bal.mine.trusted.to_sat() < amount.to_sat() {
+        if bal.mine.trusted.to_sat() < total.to_sat() {
             return Err("Insufficient balance".into());
         }
 
-        let unspent_txs = wallet.list_all_unspent(None)?;
-        if unspent_txs.is_empty() {
-            return Err("No unspent transactions".into());
-        }
-
-        // Based on the strategy, select UTXOs
-        let selected_utxos = strat_handler(&unspent_txs, amount, fee_amount, utxo_strat)
-            .map_err(|e| format!("Error selecting UTXOs: {}", e))?;
-
-        let mut tx_inputs = Vec::new();
-        let mut total_amount = Amount::from_sat(0);
-        for utxo in &selected_utxos {
-            tx_inputs.push(CreateRawTransactionInput {
-                txid: utxo.txid,
-                vout: utxo.vout,
-                sequence: None,
-            });
-            total_amount += utxo.amount;
-        }
-
-        let mut tx_outputs: HashMap<String, Amount> = HashMap::new();
-        tx_outputs.insert(recipient.to_string(), amount);
-
-        // Add change output if there's any remaining amount
-        let change_amount = total_amount - amount - fee_amount;
-        if change_amount.to_sat() > 0 {
-            let change_address = wallet.new_address(&AddressType::Bech32)?;
-            tx_outputs.insert(change_address.to_string(), change_amount);
-        }
-
-        let locktime = None;
-        // TODO: can optionally specify the fee rate here, otherwise it will have the wallet estimate it
-        let options = None;
-        let bip32derivs = None;
-        let client = create_rpc_client(config, Some(wallet_name))?;
-        let psbt = client.wallet_create_funded_psbt(
-            &tx_inputs[..],
-            &tx_outputs,
-            locktime,
+        let mut outputs: Vec<TxOut> = payments.iter().map(Payment::to_txout).collect();
+
+        // Based on the strategy, select UTXOs that also cover their own fee
+        let change_address =
+            change_policy.change_address(wallet, &first.address.script_pubkey())?;
+        let funding = wallet.fund(
+            &outputs,
+            &change_address.script_pubkey(),
+            fee_rate,
+            long_term_fee_rate,
+            selector,
             options,
-            bip32derivs,
         )?;
 
-        Ok(psbt)
+        let sequence = options.sequence(Sequence::ENABLE_RBF_NO_LOCKTIME);
+        let input: Vec<TxIn> = funding
+            .utxos
+            .iter()
+            .map(|utxo| TxIn {
+                previous_output: OutPoint::new(utxo.txid, utxo.vout),
+                sequence,
+                ..Default::default()
+            })
+            .collect();
+
+        // Add change unless it would be dust
+        let mut change_scripts = Vec::new();
+        if let Some(change_amount) = funding.change {
+            let change =
+                change_policy.change_outputs(wallet, change_address, change_amount, fee_rate)?;
+            change_scripts = change
+                .iter()
+                .map(|output| output.script_pubkey.clone())
+                .collect();
+            change_policy.place_change(&mut outputs, change);
+        }
+        let change_position = outputs
+            .iter()
+            .position(|output| change_scripts.contains(&output.script_pubkey))
+            .map_or(-1, |position| position as i32);
+
+        // Splitting change pays for the extra outputs out of it
+        let total_in: Amount = funding.utxos.iter().map(|utxo| utxo.amount).sum();
+        let total_out: Amount = outputs.iter().map(|output| output.value).sum();
+        let fee = total_in - total_out;
+        info!("Fee: {}", fee);
+
+        let tx = Transaction {
+            version: Version::TWO,
+            lock_time: options.lock_time(),
+            input,
+            output: outputs,
+        };
+
+        // The wallet only adds what signers need to know about the inputs
+        // and change; it does not fund the transaction again
+        let unsigned = Psbt::from_unsigned_tx(tx)?;
+        let processed = wallet.process_psbt(&unsigned.to_string())?;
+
+        Ok(WalletCreateFundedPsbtResult {
+            psbt: processed.psbt,
+            fee,
+            change_position,
+        })
     }
 }
 
-pub fn extract_int_ext_xpubs(
-    mut xpubs: HashMap<String, String>,
-    descriptors_array: Vec<serde_json::Value>,
-    i: usize,
-) -> Result<HashMap<String, String>, Box<dyn Error>> {
-    // Find the correct descriptors for external and internal xpubs
-    let external_xpub = descriptors_array
-        .iter()
-        .find(|desc| {
-            desc["desc"]
-                .as_str()
-                .unwrap_or_default()
-                .starts_with("wpkh")
-                && desc["desc"].as_str().unwrap_or_default().contains("/0/*")
-        })
-        .ok_or(format!("External xpub not found for wallet {}", i + 1))?
-        .as_str()
-        .ok_or(format!(
-            "External xpub cannot be parsed to string {}",
-            i + 1
-        ))?
-        .to_string();
-
-    let internal_xpub = descriptors_array
-        .iter()
-        .find(|desc| {
-            desc["desc"]
-                .as_str()
-                .unwrap_or_default()
-                .starts_with("wpkh")
-                && desc["desc"].as_str().unwrap_or_default().contains("/1/*")
-        })
-        .ok_or(format!("Internal xpub not found for wallet {}", i + 1))?
-        .as_str()
-        .ok_or(format!(
-            "Internal xpub cannot be parsed to string {}",
-            i + 1
-        ))?
-        .to_string();
-
-    // formatting notes: https://bitcoincoredocs.com/descriptors.html
-    // split at "]" and take the last part
-    let external_xpub_no_path = external_xpub.split("]").last().unwrap().to_string();
-    let internal_xpub_no_path = internal_xpub.split("]").last().unwrap().to_string();
-
-    // split at ")" and take the first part
-    let external_xpub_no_path = external_xpub_no_path.split(")").next().unwrap().to_string();
-    let internal_xpub_no_path = internal_xpub_no_path.split(")").next().unwrap().to_string();
-
-    xpubs.insert(format!("internal_xpub_{}", i + 1), internal_xpub_no_path);
-    xpubs.insert(format!("external_xpub_{}", i + 1), external_xpub_no_path);
-
-    Ok(xpubs)
+/// Create the watch-only wallet `wallet_name` on `node`, receiving to
+/// `external_descriptor` and taking change to `internal_descriptor`.
+pub(crate) fn create_watch_only<B: WalletBackend>(
+    node: &B,
+    wallet_name: &str,
+    external_descriptor: &str,
+    internal_descriptor: &str,
+) -> Result<(), Box<dyn Error>> {
+    let ext_desc = json!({
+        "desc": external_descriptor,
+        "active": true,
+        "internal": false,
+        "timestamp": json!("now")
+    });
+
+    let int_desc = json!({
+        "desc": internal_descriptor,
+        "active": true,
+        "internal": true,
+        "timestamp": json!("now")
+    });
+
+    let client = node.create_watch_only_wallet(wallet_name)?;
+    client.import_descriptors(&[ext_desc, int_desc])?;
+
+    let wallet = Wallet::open(node, wallet_name)?;
+    let info = wallet.get_wallet_info()?;
+    info!("{:#?}", info);
+    Ok(())
+}
+
+/// Account key of the active external `wpkh` descriptor of the wallet
+/// `wallet_name`, with its key origin.
+fn wallet_account_key<B: WalletBackend>(
+    node: &B,
+    wallet_name: &str,
+) -> Result<DescriptorXKey<Xpub>, Box<dyn Error>> {
+    let wallet = Wallet::open(node, wallet_name)?;
+    let descriptors = wallet.client.list_descriptors()?;
+    let descriptors = descriptors["descriptors"]
+        .as_array()
+        .ok_or_else(|| format!("Invalid descriptor format for wallet {}", wallet_name))?;
+
+    for descriptor in descriptors {
+        if descriptor["internal"].as_bool() == Some(true)
+            || descriptor["active"].as_bool() == Some(false)
+        {
+            continue;
+        }
+        let Some(Ok(Descriptor::Wpkh(wpkh))) = descriptor["desc"]
+            .as_str()
+            .map(Descriptor::<DescriptorPublicKey>::from_str)
+        else {
+            continue;
+        };
+        if let DescriptorPublicKey::XPub(xkey) = wpkh.as_inner() {
+            if xkey.derivation_path == DerivationPath::from(vec![ChildNumber::from(0)])
+                && xkey.wildcard == Wildcard::Unhardened
+            {
+                return Ok(DescriptorXKey {
+                    derivation_path: DerivationPath::master(),
+                    wildcard: Wildcard::None,
+                    ..xkey.clone()
+                });
+            }
+        }
+    }
+    Err(format!(
+        "No active wpkh descriptor with an xpub in wallet {}",
+        wallet_name
+    )
+    .into())
 }
@@ -0,0 +1,154 @@
+use std::{collections::HashSet, error::Error};
+
+use serde_json::Value;
+
+use bitcoin::{amount::Denomination::Bitcoin, Address, Amount, Network, TxOut};
+
+use crate::string_to_address;
+
+/// An amount to pay to an address.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Payment {
+    pub address: Address,
+    pub amount: Amount,
+}
+
+impl Payment {
+    pub fn to_txout(&self) -> TxOut {
+        TxOut {
+            value: self.amount,
+            script_pubkey: self.address.script_pubkey(),
+        }
+    }
+}
+
+/// Parse a list of payments, each checked against `network`.
+///
+/// JSON is either an array of `{"address": ..., "amount": ...}` objects and
+/// BIP21 URIs, or an object of address to amount as Bitcoin Core's
+/// `sendmany` takes. Anything else is read as CSV of one `address,amount`
+/// pair or BIP21 URI per line, with an optional header and `#` comments.
+/// Amounts are in BTC, and no address may be paid twice.
+pub fn parse_payments(text: &str, network: Network) -> Result<Vec<Payment>, Box<dyn Error>> {
+    let text = text.trim();
+    let payments = if text.starts_with('[') || text.starts_with('{') {
+        parse_json_payments(text, network)?
+    } else {
+        parse_csv_payments(text, network)?
+    };
+
+    if payments.is_empty() {
+        return Err("No payments given".into());
+    }
+    let mut addresses = HashSet::new();
+    for payment in &payments {
+        if !addresses.insert(&payment.address) {
+            return Err(format!("Address paid more than once: {}", payment.address).into());
+        }
+    }
+    Ok(payments)
+}
+
+fn parse_json_payments(text: &str, network: Network) -> Result<Vec<Payment>, Box<dyn Error>> {
+    let payment = |address: &str, amount: &Value| -> Result<Payment, Box<dyn Error>> {
+        let amount = match amount {
+            Value::Number(amount) => parse_btc(&amount.to_string())?,
+            Value::String(amount) => parse_btc(amount)?,
+            _ => return Err(format!("Invalid amount for {}: {}", address, amount).into()),
+        };
+        Ok(Payment {
+            address: string_to_address(address, network)?,
+            amount,
+        })
+    };
+
+    match serde_json::from_str(text)? {
+        Value::Object(outputs) => outputs
+            .iter()
+            .map(|(address, amount)| payment(address, amount))
+            .collect(),
+        Value::Array(entries) => entries
+            .iter()
+            .map(|entry| match entry {
+                Value::String(uri) => parse_bip21(uri, network),
+                Value::Object(fields) => {
+                    let address = fields
+                        .get("address")
+                        .and_then(Value::as_str)
+                        .ok_or(format!("Payment without an address: {}", entry))?;
+                    let amount = fields
+                        .get("amount")
+                        .ok_or(format!("Payment without an amount: {}", entry))?;
+                    payment(address, amount)
+                }
+                _ => Err(format!("Invalid payment: {}", entry).into()),
+            })
+            .collect(),
+        _ => Err("Payments must be a JSON array or object".into()),
+    }
+}
+
+fn parse_csv_payments(text: &str, network: Network) -> Result<Vec<Payment>, Box<dyn Error>> {
+    let mut payments = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let at_line = |e: Box<dyn Error>| format!("Line {}: {}", i + 1, e);
+
+        if is_bip21(line) {
+            payments.push(parse_bip21(line, network).map_err(at_line)?);
+            continue;
+        }
+        let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+        let [address, amount] = fields[..] else {
+            return Err(at_line("expected address,amount".into()).into());
+        };
+        // A header names its columns instead of giving an amount
+        if payments.is_empty() && amount.eq_ignore_ascii_case("amount") {
+            continue;
+        }
+        payments.push(Payment {
+            address: string_to_address(address, network).map_err(at_line)?,
+            amount: parse_btc(amount).map_err(at_line)?,
+        });
+    }
+    Ok(payments)
+}
+
+fn is_bip21(s: &str) -> bool {
+    s.get(..8)
+        .is_some_and(|scheme| scheme.eq_ignore_ascii_case("bitcoin:"))
+}
+
+/// A BIP21 `bitcoin:` URI with an amount. Labels and messages are ignored,
+/// and URIs with `req-` parameters this parser does not know are rejected
+/// as BIP21 requires.
+pub fn parse_bip21(uri: &str, network: Network) -> Result<Payment, Box<dyn Error>> {
+    if !is_bip21(uri) {
+        return Err(format!("Not a bitcoin: URI: {}", uri).into());
+    }
+    let (address, query) = uri[8..].split_once('?').unwrap_or((&uri[8..], ""));
+
+    let mut amount = None;
+    for param in query.split('&').filter(|param| !param.is_empty()) {
+        let (key, value) = param.split_once('=').unwrap_or((param, ""));
+        match key {
+            "amount" => amount = Some(parse_btc(value)?),
+            key if key.starts_with("req-") => {
+                return Err(format!("Unsupported required parameter {} in {}", key, uri).into())
+            }
+            _ => {}
+        }
+    }
+
+    Ok(Payment {
+        address: string_to_address(address, network)?,
+        amount: amount.ok_or(format!("No amount in {}", uri))?,
+    })
+}
+
+fn parse_btc(s: &str) -> Result<Amount, Box<dyn Error>> {
+    Amount::from_str_in(s, Bitcoin).map_err(|e| format!("Invalid amount {}: {}", s, e).into())
+}
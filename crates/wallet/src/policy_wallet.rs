@@ -0,0 +1,216 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    error::Error,
+    fmt,
+    str::FromStr,
+};
+
+use serde::Serialize;
+
+use bitcoin::{
+    bip32::{ChildNumber, Xpub},
+    hashes::{hash160, ripemd160, sha256},
+};
+use miniscript::{
+    descriptor::{DescriptorXKey, Wildcard},
+    hash256,
+    policy::Concrete,
+    Descriptor, DescriptorPublicKey, Segwitv0, TranslateErr, TranslatePk, Translator,
+};
+
+use satoshi_suite_client::WalletBackend;
+
+use crate::{create_watch_only, Cosigner, NUMS_INTERNAL_KEY};
+
+/// Script a policy compiles to.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PolicyScript {
+    /// `wsh(...)`, one miniscript for the whole policy.
+    #[default]
+    Wsh,
+    /// `tr(...)`, with the policy's likeliest lone key as the internal key,
+    /// or [`NUMS_INTERNAL_KEY`] without one, and its other branches as
+    /// leaves.
+    Tr,
+}
+
+impl fmt::Display for PolicyScript {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PolicyScript::Wsh => write!(f, "wsh"),
+            PolicyScript::Tr => write!(f, "tr"),
+        }
+    }
+}
+
+/// The signer of a key a policy names, given as `NAME=SIGNER`, where the
+/// signer is a wallet on the node or an `[fingerprint/path]xpub` key.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PolicyKey {
+    pub name: String,
+    pub signer: String,
+}
+
+impl FromStr for PolicyKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('=') {
+            Some((name, signer)) if !name.is_empty() && !signer.is_empty() => Ok(PolicyKey {
+                name: name.to_string(),
+                signer: signer.to_string(),
+            }),
+            _ => Err(format!("expected NAME=WALLET or NAME=XPUB, got {}", s)),
+        }
+    }
+}
+
+impl PolicyKey {
+    fn cosigner(&self) -> Result<Cosigner, Box<dyn Error>> {
+        match DescriptorPublicKey::from_str(&self.signer) {
+            Ok(_) => Cosigner::xpub(&self.signer),
+            Err(_) => Ok(Cosigner::Wallet(self.signer.clone())),
+        }
+    }
+}
+
+/// A watch-only wallet whose coins spend under a miniscript policy, such as
+/// `or(pk(A),and(pk(B),older(1000)))`.
+#[derive(Debug, Serialize)]
+pub struct PolicyWallet {
+    pub name: String,
+    pub policy: String,
+    /// Signers, by the name the policy gives their key.
+    pub keys: BTreeMap<String, String>,
+    pub script: PolicyScript,
+    /// The receiving descriptor, with checksum, for backing up the wallet.
+    pub descriptor: String,
+}
+
+impl PolicyWallet {
+    /// Compile `policy` and create the watch-only wallet `wallet_name` on
+    /// `node` for it. Every key the policy names needs a signer in `keys`,
+    /// whose account key's `/0/*` and `/1/*` children receive and take
+    /// change, as for a [`crate::MultisigWallet`].
+    pub fn new<B: WalletBackend>(
+        node: &B,
+        policy: &str,
+        keys: &[PolicyKey],
+        script: PolicyScript,
+        wallet_name: &str,
+    ) -> Result<Self, Box<dyn Error>> {
+        let parsed = Concrete::<String>::from_str(policy)
+            .map_err(|e| format!("Invalid policy {}: {}", policy, e))?;
+        let named: BTreeSet<&String> = parsed.keys().into_iter().collect();
+
+        let mut account_keys: BTreeMap<String, DescriptorXKey<Xpub>> = BTreeMap::new();
+        let mut signers = BTreeMap::new();
+        for key in keys {
+            if !named.contains(&key.name) {
+                return Err(format!("Key {} is not in the policy", key.name).into());
+            }
+            if account_keys.contains_key(&key.name) {
+                return Err(format!("Key {} given twice", key.name).into());
+            }
+            let cosigner = key.cosigner()?;
+            let account_key = cosigner.account_key(node)?;
+            if account_keys
+                .values()
+                .any(|other| other.xkey == account_key.xkey)
+            {
+                return Err(format!("Cosigner key given twice: {}", account_key.xkey).into());
+            }
+            account_keys.insert(key.name.clone(), account_key);
+            signers.insert(key.name.clone(), cosigner.to_string());
+        }
+        if let Some(name) = named.iter().find(|name| !account_keys.contains_key(**name)) {
+            return Err(format!("No signer given for key {} of the policy", name).into());
+        }
+
+        // Compiled once by name, so both chains get the same script
+        let compiled = match script {
+            PolicyScript::Wsh => parsed
+                .compile::<Segwitv0>()
+                .map_err(|e| e.to_string())
+                .and_then(|ms| Descriptor::new_wsh(ms).map_err(|e| e.to_string())),
+            PolicyScript::Tr => parsed
+                .compile_tr(Some(NUMS_INTERNAL_KEY.to_string()))
+                .map_err(|e| e.to_string()),
+        }
+        .map_err(|e| format!("Cannot compile the policy to {}: {}", script, e))?;
+
+        let chain = |branch: u32| {
+            let mut derived = DerivedKeys {
+                account_keys: &account_keys,
+                branch,
+            };
+            let descriptor = compiled.translate_pk(&mut derived).map_err(|e| match e {
+                TranslateErr::TranslatorErr(e) => e,
+                TranslateErr::OuterError(e) => e.to_string(),
+            })?;
+            Ok::<_, Box<dyn Error>>(node.get_descriptor_info(&descriptor.to_string())?)
+        };
+        let external_descriptor = chain(0)?.descriptor;
+        let internal_descriptor = chain(1)?.descriptor;
+
+        create_watch_only(
+            node,
+            wallet_name,
+            &external_descriptor,
+            &internal_descriptor,
+        )?;
+
+        Ok(Self {
+            name: wallet_name.to_string(),
+            policy: policy.to_string(),
+            keys: signers,
+            script,
+            descriptor: external_descriptor,
+        })
+    }
+}
+
+/// Turns the names of a policy's keys into children of their signers'
+/// account keys on one chain, `/0/*` or `/1/*`.
+struct DerivedKeys<'a> {
+    account_keys: &'a BTreeMap<String, DescriptorXKey<Xpub>>,
+    branch: u32,
+}
+
+impl Translator<String, DescriptorPublicKey, String> for DerivedKeys<'_> {
+    fn pk(&mut self, name: &String) -> Result<DescriptorPublicKey, String> {
+        if name == NUMS_INTERNAL_KEY {
+            return DescriptorPublicKey::from_str(NUMS_INTERNAL_KEY).map_err(|e| e.to_string());
+        }
+        let account_key = self
+            .account_keys
+            .get(name)
+            .ok_or_else(|| format!("No signer given for key {} of the policy", name))?;
+        Ok(DescriptorPublicKey::XPub(DescriptorXKey {
+            derivation_path: vec![ChildNumber::from(self.branch)].into(),
+            wildcard: Wildcard::Unhardened,
+            ..account_key.clone()
+        }))
+    }
+
+    fn sha256(&mut self, hash: &String) -> Result<sha256::Hash, String> {
+        parse_hash(hash)
+    }
+
+    fn hash256(&mut self, hash: &String) -> Result<hash256::Hash, String> {
+        parse_hash(hash)
+    }
+
+    fn ripemd160(&mut self, hash: &String) -> Result<ripemd160::Hash, String> {
+        parse_hash(hash)
+    }
+
+    fn hash160(&mut self, hash: &String) -> Result<hash160::Hash, String> {
+        parse_hash(hash)
+    }
+}
+
+fn parse_hash<H: FromStr>(hash: &str) -> Result<H, String> {
+    H::from_str(hash).map_err(|_| format!("Invalid hash in the policy: {}", hash))
+}
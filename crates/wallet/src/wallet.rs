@@ -1,29 +1,37 @@
-use std::{error::Error, fmt};
-
-use serde::Deserialize;
+use std::{error::Error, fmt, io, path::PathBuf};
 
 use bitcoin::key::UntweakedKeypair;
 use bitcoin::script::Builder as ScriptBuilder;
 use bitcoin::secp256k1::{rand, Secp256k1};
-use bitcoin::{Address, Amount, Network, OutPoint, ScriptBuf, Sequence, Transaction, TxOut, Txid};
+use bitcoin::{
+    transaction::Version, Address, Amount, FeeRate, Network, OutPoint, Script, ScriptBuf, Sequence,
+    Transaction, TxIn, TxOut, Txid,
+};
 use bitcoincore_rpc::json::{
     AddressType, GetAddressInfoResult, GetBalancesResult, GetWalletInfoResult,
     ListUnspentQueryOptions, ListUnspentResultEntry, WalletProcessPsbtResult,
 };
-use bitcoincore_rpc::jsonrpc::serde_json::{json, Value};
-use bitcoincore_rpc::{Client, Error as RpcError, RpcApi};
+use bitcoincore_rpc::Error as RpcError;
 
 use ord::Chain;
 
 use ordinals::{Etching, Runestone};
 
-use satoshi_suite_client::{create_rpc_client, ClientError};
-use satoshi_suite_config::Config;
+use serde_json::json;
+
+use satoshi_suite_client::{
+    resolve_fee_rate, ClientError, ElectrumBackend, FeeTarget, RpcBackend, WalletBackend,
+};
+use satoshi_suite_config::{BackendConfig, Config};
 use satoshi_suite_ordinals::InscriptionData;
-use satoshi_suite_utxo_selection::{strat_handler, UTXOStrategy};
-use tracing::info;
+use satoshi_suite_utxo_selection::{
+    AssetProtection, CoinSelector, OrdServer, Selection, UTXOStrategy, DEFAULT_LONG_TERM_FEE_RATE,
+};
 
-use crate::{build_commit_transaction, build_reveal_transaction, create_taproot_info};
+use crate::{
+    build_commit_transaction, build_reveal_transaction, create_taproot_info, estimate_commit_fee,
+    fund_outputs, fund_outputs_with, FrozenCoins, SpendOptions,
+};
 
 #[derive(Debug)]
 pub enum WalletError {
@@ -31,8 +39,10 @@ pub enum WalletError {
     WalletCreationDisabled(String),
     AddressNetworkMismatch,
     SigningFailed(String),
+    FundingFailed(String),
     RpcError(RpcError),
     AddressNotFound,
+    Io(PathBuf, io::Error),
 }
 
 impl fmt::Display for WalletError {
@@ -44,8 +54,10 @@ impl fmt::Display for WalletError {
             }
             WalletError::AddressNetworkMismatch => write!(f, "Address network mismatch"),
             WalletError::SigningFailed(err) => write!(f, "Signing failed: {}", err),
+            WalletError::FundingFailed(err) => write!(f, "Funding failed: {}", err),
             WalletError::RpcError(err) => write!(f, "RPC error: {}", err),
             WalletError::AddressNotFound => write!(f, "Address not found in transaction details"),
+            WalletError::Io(path, err) => write!(f, "Cannot access {}: {}", path.display(), err),
         }
     }
 }
@@ -55,6 +67,7 @@ impl Error for WalletError {
         match self {
             WalletError::ClientError(err) => Some(err),
             WalletError::RpcError(err) => Some(err),
+            WalletError::Io(_, err) => Some(err),
             _ => None,
         }
     }
@@ -72,11 +85,6 @@ impl From<RpcError> for WalletError {
     }
 }
 
-#[derive(Deserialize)]
-struct SendResult {
-    txid: Txid,
-}
-
 // Commit / reveal transaction data
 #[derive(Debug)]
 pub struct CommitRevealTxPair {
@@ -96,45 +104,80 @@ pub struct InscriptionTransactions {
 pub struct EtchingTransactions {
     pub base: CommitRevealTxPair,
     pub rune_id: ordinals::Rune,
+    /// Receives the postage and any premine.
+    pub recipient: Address,
 }
 
-pub struct Wallet {
-    pub client: Client,
+pub struct Wallet<B: WalletBackend = RpcBackend> {
+    pub client: B,
     pub network: Network,
+    /// Keeps UTXOs holding inscriptions or runes from being spent, when an
+    /// index is available to tell them apart.
+    pub asset_protection: Option<AssetProtection>,
+    /// Outpoints kept out of every coin selection until they are thawed.
+    pub frozen: FrozenCoins,
 }
 
 impl Wallet {
     pub fn new(name: &str, config: &Config) -> Result<Self, WalletError> {
-        let name = name.to_string();
-        let client = create_rpc_client(config, None)?;
+        let node = RpcBackend::new(config, None)?;
+        let mut wallet = Wallet::open(&node, name)?;
+        wallet.asset_protection = config
+            .ord_url
+            .clone()
+            .map(|url| AssetProtection::new(Box::new(OrdServer::new(url))));
+        if let Some(path) = FrozenCoins::default_path(config.bitcoin_rpc.chain(), name) {
+            wallet.frozen = FrozenCoins::load(&path)?;
+        }
+        Ok(wallet)
+    }
+}
 
-        let wallet_list = client.list_wallet_dir()?;
+impl Wallet<ElectrumBackend> {
+    /// Track public `descriptors` through the configured Electrum server,
+    /// without importing them into a Bitcoin Core wallet.
+    pub fn watch_only(
+        name: &str,
+        descriptors: &[String],
+        config: &Config,
+    ) -> Result<Self, WalletError> {
+        let BackendConfig::Electrum { url } = &config.backend else {
+            return Err(ClientError::InvalidConfiguration(
+                "watch-only descriptors need the electrum backend".to_string(),
+            )
+            .into());
+        };
 
-        if wallet_list.contains(&name) {
-            let loaded_wallets = client.list_wallets()?;
+        let server = ElectrumBackend::new(url, config.bitcoin_rpc.network())?;
+        let client = server.create_watch_only_wallet(name)?;
+        let requests: Vec<_> = descriptors
+            .iter()
+            .map(|desc| json!({ "desc": desc, "active": desc.contains('*'), "timestamp": "now" }))
+            .collect();
+        client.import_descriptors(&requests)?;
 
-            if !loaded_wallets.contains(&name) {
-                info!("Loading wallet {}", name);
-                client.load_wallet(&name)?;
-            } else {
-                info!("Wallet {} already loaded", name);
-            }
-        } else {
-            if !config.create_wallets {
-                return Err(WalletError::WalletCreationDisabled(name));
-            }
-            info!("Creating wallet {}", name);
-            client.create_wallet(&name, None, None, None, None)?;
-        }
+        Wallet::open(&server, name)
+    }
+}
+
+impl<B: WalletBackend> Wallet<B> {
+    /// Load or create the named wallet on `node`.
+    pub fn open(node: &B, name: &str) -> Result<Self, WalletError> {
+        let client = node.open_wallet(name).map_err(|err| match err {
+            ClientError::WalletNotFound(name) => WalletError::WalletCreationDisabled(name),
+            err => WalletError::ClientError(err),
+        })?;
 
         Ok(Wallet {
-            client: create_rpc_client(config, Some(&name))?,
-            network: config.bitcoin_rpc.network(),
+            network: client.network(),
+            client,
+            asset_protection: None,
+            frozen: FrozenCoins::default(),
         })
     }
 
     pub fn new_address(&self, address_type: &AddressType) -> Result<Address, WalletError> {
-        let address = self.client.get_new_address(None, Some(*address_type))?;
+        let address = self.client.get_new_address(*address_type)?;
         address
             .require_network(self.network)
             .map_err(|_| WalletError::AddressNetworkMismatch)
@@ -144,40 +187,90 @@ impl Wallet {
         self.client.get_balances().map_err(WalletError::from)
     }
 
-    pub fn send(&self, address: &Address, amount: Amount) -> Result<OutPoint, WalletError> {
-        let output = json!([{
-            address.to_string(): amount.to_btc()
-        }]);
-        let send_result: SendResult = self
-            .client
-            .call("send", &[output, Value::Null, "unset".into(), 1.into()])?;
-        let txid = send_result.txid;
+    pub fn send(
+        &self,
+        address: &Address,
+        amount: Amount,
+        options: &SpendOptions,
+    ) -> Result<OutPoint, WalletError> {
+        // The node's own coin selection knows nothing of inscriptions, runes,
+        // frozen coins and timelocks
+        let txid = match self.asset_protection.is_some()
+            || !self.frozen.is_empty()
+            || !options.inputs.is_empty()
+            || options.older.is_some()
+            || options.after.is_some()
+        {
+            true => self.send_spendable(address, amount, options)?,
+            false => self.client.send(address, amount)?,
+        };
 
-        let transaction_info = self.client.get_transaction(&txid, None)?;
-        let target_vout = transaction_info
-            .details
+        let tx = self.client.get_raw_transaction(&txid)?;
+        let target_vout = tx
+            .output
             .iter()
-            .find_map(|details| {
-                details
-                    .address
-                    .as_ref()
-                    .filter(|&tx_address| tx_address == address)
-                    .map(|_| details.vout)
-            })
+            .position(|output| output.script_pubkey == address.script_pubkey())
             .ok_or(WalletError::AddressNotFound)?;
 
         Ok(OutPoint {
             txid,
-            vout: target_vout,
+            vout: target_vout as u32,
         })
     }
 
+    /// Fund, sign and broadcast a payment with [`Wallet::fund`].
+    fn send_spendable(
+        &self,
+        address: &Address,
+        amount: Amount,
+        options: &SpendOptions,
+    ) -> Result<Txid, WalletError> {
+        let mut output = vec![TxOut {
+            value: amount,
+            script_pubkey: address.script_pubkey(),
+        }];
+        let change_script = self.new_address(&AddressType::Bech32)?.script_pubkey();
+        let fee_rate = resolve_fee_rate(&self.client, FeeTarget::default())?;
+
+        let funding = self
+            .fund(
+                &output,
+                &change_script,
+                fee_rate,
+                DEFAULT_LONG_TERM_FEE_RATE,
+                &UTXOStrategy::LeastWaste,
+                options,
+            )
+            .map_err(|e| WalletError::FundingFailed(e.to_string()))?;
+
+        if let Some(change) = funding.change {
+            output.push(TxOut {
+                value: change,
+                script_pubkey: change_script,
+            });
+        }
+        let tx = Transaction {
+            version: Version::TWO,
+            lock_time: options.lock_time(),
+            input: funding
+                .utxos
+                .iter()
+                .map(|utxo| TxIn {
+                    previous_output: OutPoint::new(utxo.txid, utxo.vout),
+                    sequence: options.sequence(Sequence::ENABLE_RBF_NO_LOCKTIME),
+                    ..Default::default()
+                })
+                .collect(),
+            output,
+        };
+
+        let signed = self.sign_tx(&tx)?;
+        Ok(self.client.send_raw_transaction(&signed)?)
+    }
+
     pub fn sign_tx(&self, tx: &Transaction) -> Result<Transaction, WalletError> {
-        let signed = self
-            .client
-            .sign_raw_transaction_with_wallet(tx, None, None)?;
-        signed
-            .transaction()
+        self.client
+            .sign_raw_transaction(tx)
             .map_err(|e| WalletError::SigningFailed(e.to_string()))
     }
 
@@ -196,13 +289,159 @@ impl Wallet {
         query_options: Option<ListUnspentQueryOptions>,
     ) -> Result<Vec<ListUnspentResultEntry>, WalletError> {
         self.client
-            .list_unspent(Some(1), Some(9999999), None, None, query_options)
+            .list_unspent(query_options)
             .map_err(WalletError::from)
     }
 
+    /// Unspent outputs coin selection may spend: all of them, less frozen
+    /// ones and those [`Wallet::asset_protection`] keeps back.
+    pub fn spendable_unspent(&self) -> Result<Vec<ListUnspentResultEntry>, WalletError> {
+        let mut utxos = self.list_all_unspent(None)?;
+        utxos.retain(|utxo| !self.frozen.contains(&OutPoint::new(utxo.txid, utxo.vout)));
+        match &self.asset_protection {
+            Some(protection) => protection
+                .spendable(utxos)
+                .map_err(|e| WalletError::FundingFailed(e.to_string())),
+            None => Ok(utxos),
+        }
+    }
+
+    /// Keep `outpoints` out of coin selection: frozen for good when
+    /// `freeze`, and otherwise locked by the node until it unloads the
+    /// wallet.
+    pub fn lock_unspent(
+        &mut self,
+        outpoints: &[OutPoint],
+        freeze: bool,
+    ) -> Result<(), WalletError> {
+        if !freeze {
+            return Ok(self.client.lock_unspent(outpoints)?);
+        }
+        for outpoint in outpoints {
+            if self.client.get_tx_out(outpoint, true)?.is_none() {
+                return Err(WalletError::ClientError(ClientError::NotFound(format!(
+                    "unspent output {}",
+                    outpoint
+                ))));
+            }
+        }
+        self.frozen.freeze(outpoints)
+    }
+
+    /// Unlock and thaw `outpoints`, each of which must be locked or frozen.
+    pub fn unlock_unspent(&mut self, outpoints: &[OutPoint]) -> Result<(), WalletError> {
+        let locked = self.list_locked()?;
+        if let Some(outpoint) = outpoints
+            .iter()
+            .find(|o| !locked.contains(o) && !self.frozen.contains(o))
+        {
+            return Err(WalletError::ClientError(ClientError::NotFound(format!(
+                "locked or frozen output {}",
+                outpoint
+            ))));
+        }
+        let unlock: Vec<OutPoint> = outpoints
+            .iter()
+            .filter(|o| locked.contains(o))
+            .copied()
+            .collect();
+        if !unlock.is_empty() {
+            self.client.unlock_unspent(&unlock)?;
+        }
+        self.frozen.thaw(outpoints)
+    }
+
+    /// Outpoints the node has locked, not counting frozen ones.
+    pub fn list_locked(&self) -> Result<Vec<OutPoint>, WalletError> {
+        Ok(self.client.list_lock_unspent()?)
+    }
+
+    /// Coins paying for `outputs`: exactly the [`SpendOptions::inputs`]
+    /// when any are chosen, and otherwise those `selector` picks from
+    /// [`Wallet::spendable_unspent`]. Chosen coins must be unspent, unlocked
+    /// and not frozen, and asset protection still applies to them. Either
+    /// way coins must be old enough for [`SpendOptions::older`].
+    pub fn fund(
+        &self,
+        outputs: &[TxOut],
+        change_script: &Script,
+        fee_rate: FeeRate,
+        long_term_fee_rate: FeeRate,
+        selector: &dyn CoinSelector,
+        options: &SpendOptions,
+    ) -> Result<Selection, Box<dyn Error>> {
+        if options.inputs.is_empty() {
+            let mut utxos = self.spendable_unspent()?;
+            utxos.retain(|utxo| options.old_enough(utxo));
+            if utxos.is_empty() && options.older.is_some() {
+                return Err(format!(
+                    "No coins old enough for a timelock of {}",
+                    options.older_description()
+                )
+                .into());
+            }
+            if utxos.is_empty() {
+                return Err("No unspent transactions".into());
+            }
+            return fund_outputs(
+                &utxos,
+                outputs,
+                change_script,
+                fee_rate,
+                long_term_fee_rate,
+                selector,
+            );
+        }
+
+        let unspent = self.list_all_unspent(None)?;
+        let mut chosen: Vec<ListUnspentResultEntry> = Vec::new();
+        for outpoint in &options.inputs {
+            if chosen
+                .iter()
+                .any(|utxo| utxo.txid == outpoint.txid && utxo.vout == outpoint.vout)
+            {
+                return Err(format!("Input {} is chosen twice", outpoint).into());
+            }
+            if self.frozen.contains(outpoint) {
+                return Err(format!("Input {} is frozen; unlock it to spend it", outpoint).into());
+            }
+            if let Some(protection) = &self.asset_protection {
+                if let Some(reason) = protection.protection(outpoint)? {
+                    return Err(format!("Input {} is protected: {}", outpoint, reason).into());
+                }
+            }
+            let utxo = unspent
+                .iter()
+                .find(|utxo| utxo.txid == outpoint.txid && utxo.vout == outpoint.vout)
+                .ok_or_else(|| {
+                    format!(
+                        "Input {} is not a confirmed, unlocked UTXO of the wallet",
+                        outpoint
+                    )
+                })?;
+            if !options.old_enough(utxo) {
+                return Err(format!(
+                    "Input {} has {} confirmations, too few for a timelock of {}",
+                    outpoint,
+                    utxo.confirmations,
+                    options.older_description()
+                )
+                .into());
+            }
+            chosen.push(utxo.clone());
+        }
+        fund_outputs_with(
+            &chosen,
+            outputs,
+            change_script,
+            fee_rate,
+            long_term_fee_rate,
+        )
+    }
+
     pub fn process_psbt(&self, psbt: &str) -> Result<WalletProcessPsbtResult, WalletError> {
         self.client
-            .wallet_process_psbt(psbt, None, None, None)
+            .wallet_process_psbt(psbt)
             .map_err(WalletError::from)
     }
 
@@ -226,10 +465,9 @@ impl Wallet {
     pub fn inscribe_ordinal(
         &self,
         postage: &u64,
-        commit_fee: Amount,
-        reveal_fee: Amount,
+        fee_rate: FeeRate,
         file_path: &str,
-        config: &Config,
+        miner: &Wallet<B>,
     ) -> Result<InscriptionTransactions, Box<dyn Error>> {
         let secp = Secp256k1::new();
         let key_pair = UntweakedKeypair::new(&secp, &mut rand::thread_rng());
@@ -245,34 +483,30 @@ impl Wallet {
         let postage = Amount::from_sat(*postage);
 
         // Get unspent outputs for funding
-        let utxos = self.list_all_unspent(None)?;
+        let utxos = self.spendable_unspent()?;
         if utxos.is_empty() {
             return Err("No unspent outputs available for inscription".into());
         }
 
         // Select a single UTXO for the commit transaction
-        let selected_utxos = strat_handler(&utxos, postage, commit_fee, UTXOStrategy::SingleUTXO)?;
-
-        if selected_utxos.is_empty() {
-            return Err("No UTXOs selected for inscription".into());
-        }
+        let commit_utxo = select_commit_utxo(&utxos, &commit_script, postage, fee_rate)?;
 
         // Build commit transaction
         let (commit_tx, commit_vout) = build_commit_transaction(
-            &self,
+            self,
             &secp,
-            selected_utxos[0].clone(),
+            commit_utxo.clone(),
             postage,
-            commit_fee,
+            fee_rate,
             commit_script,
         )?;
 
         // Get recipient address for reveal tx
         let recipient_address = self.new_address(&AddressType::Bech32m)?;
 
-        // Change output
+        // Inscription output, which pays the reveal fee out of the postage
         let reveal_outputs = vec![TxOut {
-            value: postage.checked_sub(reveal_fee).unwrap_or(Amount::ZERO),
+            value: postage,
             script_pubkey: recipient_address.script_pubkey(),
         }];
 
@@ -288,6 +522,7 @@ impl Wallet {
             },
             postage,
             Sequence::ENABLE_RBF_NO_LOCKTIME,
+            fee_rate,
             reveal_outputs,
         )?;
 
@@ -295,7 +530,6 @@ impl Wallet {
         let commit_txid = self.client.send_raw_transaction(&commit_tx)?;
 
         // mine 6 blocks to confirm the commit transaction
-        let miner = Wallet::new("miner", &config)?;
         let _ = miner.mine_blocks(&AddressType::Bech32, 6)?;
 
         // Send reveal transaction
@@ -305,20 +539,20 @@ impl Wallet {
             base: CommitRevealTxPair {
                 commit_txid,
                 reveal_txid,
-                total_fees: commit_fee.to_sat() + reveal_fee.to_sat(),
+                total_fees: total_fees(&commit_utxo, &commit_tx, postage, &reveal_tx).to_sat(),
             },
         })
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn etch_rune(
         &self,
         etching: Etching,
         postage: &u64,
-        commit_fee: Amount,
-        reveal_fee: Amount,
+        fee_rate: FeeRate,
         premine_tx_amount: Amount,
         file_path: &str,
-        config: &Config,
+        miner: &Wallet<B>,
     ) -> Result<EtchingTransactions, Box<dyn Error>> {
         let secp = Secp256k1::new();
         let key_pair = UntweakedKeypair::new(&secp, &mut rand::thread_rng());
@@ -344,36 +578,34 @@ impl Wallet {
             create_taproot_info(&secp, &key_pair, reveal_script.clone())?;
 
         // Get unspent outputs for funding
-        let utxos = self.list_all_unspent(None)?;
+        let utxos = self.spendable_unspent()?;
         if utxos.is_empty() {
             return Err("No unspent outputs available for etching".into());
         }
 
         let postage = Amount::from_sat(*postage);
 
-        // Select UTXOs
-        let selected_utxos = strat_handler(&utxos, postage, commit_fee, UTXOStrategy::SingleUTXO)?;
-        if selected_utxos.is_empty() {
-            return Err("No UTXOs selected for etching".into());
-        }
+        // Select a single UTXO for the commit transaction
+        let commit_utxo = select_commit_utxo(&utxos, &commit_script, postage, fee_rate)?;
 
         // Create and sign commit transaction
         let (commit_tx, commit_vout) = build_commit_transaction(
-            &self,
+            self,
             &secp,
-            selected_utxos[0].clone(),
+            commit_utxo.clone(),
             postage,
-            commit_fee,
+            fee_rate,
             commit_script,
         )?;
 
         // Get new addresses for deploy and mint txs
         let recipient_address = self.new_address(&AddressType::Bech32m)?;
-        println!("Recipient address: {}", recipient_address);
 
-        // Create reveal outputs
+        // Create reveal outputs; the first pays the reveal fee
         let mut reveal_outputs = vec![TxOut {
-            value: postage - premine_tx_amount - reveal_fee,
+            value: postage
+                .checked_sub(premine_tx_amount)
+                .ok_or("Postage does not cover the premine output")?,
             script_pubkey: recipient_address.script_pubkey(),
         }];
 
@@ -409,13 +641,13 @@ impl Wallet {
             },
             postage,
             Sequence::from_height(Runestone::COMMIT_CONFIRMATIONS - 1),
+            fee_rate,
             reveal_outputs,
         )?;
 
         // Broadcast transactions
         let commit_txid = self.client.send_raw_transaction(&commit_tx)?;
 
-        let miner = Wallet::new("miner", &config)?;
         let _ = miner.mine_blocks(&AddressType::Bech32, 6)?;
 
         let reveal_txid = self.client.send_raw_transaction(&reveal_tx)?;
@@ -424,9 +656,39 @@ impl Wallet {
             base: CommitRevealTxPair {
                 commit_txid,
                 reveal_txid,
-                total_fees: commit_fee.to_sat() + reveal_fee.to_sat(),
+                total_fees: total_fees(&commit_utxo, &commit_tx, postage, &reveal_tx).to_sat(),
             },
             rune_id: etching.rune.unwrap(),
+            recipient: recipient_address,
         })
     }
 }
+
+/// The first UTXO that can fund a commit transaction for `postage` at
+/// `fee_rate` on its own.
+fn select_commit_utxo(
+    utxos: &[ListUnspentResultEntry],
+    commit_script: &ScriptBuf,
+    postage: Amount,
+    fee_rate: FeeRate,
+) -> Result<ListUnspentResultEntry, Box<dyn Error>> {
+    utxos
+        .iter()
+        .find(|utxo| {
+            estimate_commit_fee(utxo, commit_script, postage, fee_rate)
+                .is_ok_and(|fee| utxo.amount >= postage + fee)
+        })
+        .cloned()
+        .ok_or_else(|| "No single UTXO covers the postage and commit fee".into())
+}
+
+/// Fees paid by a commit and reveal transaction pair.
+fn total_fees(
+    commit_utxo: &ListUnspentResultEntry,
+    commit_tx: &Transaction,
+    postage: Amount,
+    reveal_tx: &Transaction,
+) -> Amount {
+    let spent = |tx: &Transaction| tx.output.iter().map(|output| output.value).sum::<Amount>();
+    (commit_utxo.amount - spent(commit_tx)) + (postage - spent(reveal_tx))
+}
@@ -0,0 +1,181 @@
+use std::str::FromStr;
+
+use bitcoin::{secp256k1::Secp256k1, Address, Amount, FeeRate, Network, Psbt};
+use bitcoincore_rpc::json::AddressType;
+use miniscript::{
+    descriptor::{DescriptorPublicKey, TapTree},
+    Descriptor, Terminal,
+};
+
+use satoshi_suite_client::{ChainBackend, Simulator};
+use satoshi_suite_utxo_selection::{UTXOStrategy, DEFAULT_LONG_TERM_FEE_RATE};
+use satoshi_suite_wallet::{
+    ChangePolicy, Cosigner, MultisigScript, MultisigWallet, Payment, SpendOptions, TaprootMultisig,
+    Wallet,
+};
+
+#[test]
+fn batch_psbt_spends_the_selected_coins_in_order() {
+    let sim = Simulator::new();
+    let miner = Wallet::open(&sim, "miner").unwrap();
+    let mining = miner.new_address(&AddressType::Bech32).unwrap();
+    sim.generate_to_address(101, &mining).unwrap();
+
+    let cosigners = [
+        Cosigner::Wallet("alice".to_string()),
+        Cosigner::Wallet("bob".to_string()),
+    ];
+    for cosigner in ["alice", "bob"] {
+        Wallet::open(&sim, cosigner).unwrap();
+    }
+    MultisigWallet::new(&sim, &cosigners, 2, MultisigScript::Wsh, "multi").unwrap();
+    let multisig = Wallet::open(&sim, "multi").unwrap();
+    for _ in 0..3 {
+        let address = multisig.new_address(&AddressType::Bech32).unwrap();
+        miner
+            .send(
+                &address,
+                Amount::from_sat(200_000),
+                &SpendOptions::default(),
+            )
+            .unwrap();
+    }
+    sim.generate_to_address(1, &mining).unwrap();
+
+    let payments: Vec<Payment> = [50_000, 60_000, 70_000]
+        .into_iter()
+        .map(|sats| Payment {
+            address: miner.new_address(&AddressType::Bech32).unwrap(),
+            amount: Amount::from_sat(sats),
+        })
+        .collect();
+    let funded = MultisigWallet::create_batch_psbt(
+        &multisig,
+        &payments,
+        FeeRate::from_sat_per_vb_unchecked(2),
+        DEFAULT_LONG_TERM_FEE_RATE,
+        &UTXOStrategy::LargestFirst,
+        &ChangePolicy::default(),
+        &SpendOptions::default(),
+    )
+    .unwrap();
+
+    // One coin pays for all three and change follows the payments
+    let psbt = Psbt::from_str(&funded.psbt).unwrap();
+    let tx = &psbt.unsigned_tx;
+    assert_eq!(tx.input.len(), 1);
+    assert_eq!(tx.output.len(), payments.len() + 1);
+    for (output, payment) in tx.output.iter().zip(&payments) {
+        assert_eq!(output.script_pubkey, payment.address.script_pubkey());
+        assert_eq!(output.value, payment.amount);
+    }
+    assert_eq!(funded.change_position, payments.len() as i32);
+    let total_out: Amount = tx.output.iter().map(|output| output.value).sum();
+    assert_eq!(Amount::from_sat(200_000) - total_out, funded.fee);
+    assert!(psbt.inputs[0].witness_utxo.is_some());
+
+    let mut psbt = funded.psbt;
+    for cosigner in ["alice", "bob"] {
+        psbt = Wallet::open(&sim, cosigner)
+            .unwrap()
+            .process_psbt(&psbt)
+            .unwrap()
+            .psbt;
+    }
+    let signed = multisig.process_psbt(&psbt).unwrap();
+    assert!(signed.complete);
+}
+
+#[test]
+fn taproot_multisig_sorts_its_keys_for_every_address() {
+    let sim = Simulator::new();
+    let miner = Wallet::open(&sim, "miner").unwrap();
+    let mining = miner.new_address(&AddressType::Bech32).unwrap();
+    sim.generate_to_address(101, &mining).unwrap();
+
+    let names = ["alice", "bob", "carol"];
+    for cosigner in names {
+        Wallet::open(&sim, cosigner).unwrap();
+    }
+    let cosigners: Vec<Cosigner> = names
+        .iter()
+        .map(|name| Cosigner::Wallet(name.to_string()))
+        .collect();
+    let script = MultisigScript::Tr(TaprootMultisig::default());
+    let created = MultisigWallet::new(&sim, &cosigners, 2, script, "multi").unwrap();
+    assert!(created.descriptor.contains("sortedmulti_a(2,"));
+    let multisig = Wallet::open(&sim, "multi").unwrap();
+
+    // Read as multi_a, the keys keep the order of the account keys
+    let (body, _checksum) = created.descriptor.split_once('#').unwrap();
+    let unsorted =
+        Descriptor::<DescriptorPublicKey>::from_str(&body.replace("sortedmulti_a(", "multi_a("))
+            .unwrap();
+    let secp = Secp256k1::verification_only();
+    let mut reordered = false;
+    for index in 0..8 {
+        let Descriptor::Tr(tr) = unsorted.at_derivation_index(index).unwrap() else {
+            panic!("not a taproot descriptor");
+        };
+        let Some(TapTree::Leaf(leaf)) = tr.tap_tree() else {
+            panic!("not a single leaf");
+        };
+        let Terminal::MultiA(_, keys) = &leaf.node else {
+            panic!("not a multi_a leaf");
+        };
+        let mut keys: Vec<String> = keys
+            .iter()
+            .map(|key| {
+                let key = key.derive_public_key(&secp).unwrap();
+                key.inner.x_only_public_key().0.to_string()
+            })
+            .collect();
+        let written = keys.clone();
+        keys.sort();
+        reordered |= keys != written;
+
+        let expected = Descriptor::<DescriptorPublicKey>::from_str(&format!(
+            "tr({},multi_a(2,{}))",
+            tr.internal_key(),
+            keys.join(",")
+        ))
+        .unwrap()
+        .at_derivation_index(0)
+        .unwrap()
+        .address(Network::Regtest)
+        .unwrap();
+        let address: Address = multisig.new_address(&AddressType::Bech32m).unwrap();
+        assert_eq!(address, expected, "address {}", index);
+    }
+    assert!(reordered, "no address sorted its keys differently");
+
+    let address = multisig.new_address(&AddressType::Bech32m).unwrap();
+    miner
+        .send(
+            &address,
+            Amount::from_sat(200_000),
+            &SpendOptions::default(),
+        )
+        .unwrap();
+    sim.generate_to_address(1, &mining).unwrap();
+    let funded = MultisigWallet::create_psbt(
+        &multisig,
+        &mining.to_string(),
+        Amount::from_sat(100_000),
+        FeeRate::from_sat_per_vb_unchecked(2),
+        DEFAULT_LONG_TERM_FEE_RATE,
+        &UTXOStrategy::LargestFirst,
+        &ChangePolicy::default(),
+        &SpendOptions::default(),
+    )
+    .unwrap();
+    let mut psbt = funded.psbt;
+    for cosigner in ["alice", "carol"] {
+        psbt = Wallet::open(&sim, cosigner)
+            .unwrap()
+            .process_psbt(&psbt)
+            .unwrap()
+            .psbt;
+    }
+    assert!(multisig.process_psbt(&psbt).unwrap().complete);
+}
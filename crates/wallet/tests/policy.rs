@@ -0,0 +1,126 @@
+use std::str::FromStr;
+
+use bitcoin::{absolute, relative, Amount, FeeRate, Psbt, Sequence};
+use bitcoincore_rpc::json::AddressType;
+
+use satoshi_suite_client::{ChainBackend, Simulator};
+use satoshi_suite_utxo_selection::{UTXOStrategy, DEFAULT_LONG_TERM_FEE_RATE};
+use satoshi_suite_wallet::{
+    ChangePolicy, MultisigWallet, Payment, PolicyKey, PolicyScript, PolicyWallet, SpendOptions,
+    Wallet,
+};
+
+const POLICY: &str = "or(pk(A),and(pk(B),older(1000)))";
+
+#[test]
+fn timelocked_policy_compiles_to_fixed_descriptors() {
+    let sim = Simulator::new();
+    // Keys of BIP32 test vectors 1 and 2
+    let keys = [
+        "A=[3442193e/48h/1h/0h/2h]xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet8",
+        "B=[bd16bee5/48h/1h/0h/2h]xpub661MyMwAqRbcFW31YEwpkMuc5THy2PSt5bDMsktWQcFF8syAmRUapSCGu8ED9W6oDMSgv6Zz8idoc4a6mr8BDzTJY47LJhkJ8UB7WEGuduB",
+    ]
+    .map(|key| PolicyKey::from_str(key).unwrap());
+
+    let a = "[3442193e/48'/1'/0'/2']xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet8/0/*";
+    let b = "[bd16bee5/48'/1'/0'/2']xpub661MyMwAqRbcFW31YEwpkMuc5THy2PSt5bDMsktWQcFF8syAmRUapSCGu8ED9W6oDMSgv6Zz8idoc4a6mr8BDzTJY47LJhkJ8UB7WEGuduB/0/*";
+    for (script, expected) in [
+        (
+            PolicyScript::Wsh,
+            format!("wsh(or_d(pk({a}),and_v(v:pk({b}),older(1000))))#nc6s6mn7"),
+        ),
+        // A alone spends by the key path
+        (
+            PolicyScript::Tr,
+            format!("tr({a},and_v(v:pk({b}),older(1000)))#chjacqmc"),
+        ),
+    ] {
+        let name = format!("heir-{}", script);
+        let wallet = PolicyWallet::new(&sim, POLICY, &keys, script, &name).unwrap();
+        assert_eq!(wallet.descriptor, expected);
+    }
+}
+
+#[test]
+fn psbt_carries_the_timelocks_of_its_spend_options() {
+    let sim = Simulator::new();
+    let miner = Wallet::open(&sim, "miner").unwrap();
+    let mining = miner.new_address(&AddressType::Bech32).unwrap();
+    sim.generate_to_address(101, &mining).unwrap();
+
+    for signer in ["alice", "bob"] {
+        Wallet::open(&sim, signer).unwrap();
+    }
+    let keys = ["A=alice", "B=bob"].map(|key| PolicyKey::from_str(key).unwrap());
+    PolicyWallet::new(&sim, POLICY, &keys, PolicyScript::Wsh, "heir").unwrap();
+    let heir = Wallet::open(&sim, "heir").unwrap();
+    let address = heir.new_address(&AddressType::Bech32).unwrap();
+    miner
+        .send(
+            &address,
+            Amount::from_sat(200_000),
+            &SpendOptions::default(),
+        )
+        .unwrap();
+    sim.generate_to_address(1, &mining).unwrap();
+
+    let height = absolute::LockTime::from_height(500).unwrap();
+    let time = relative::Time::from_512_second_intervals(8);
+    for (options, lock_time, sequence) in [
+        (
+            SpendOptions {
+                after: Some(height),
+                ..Default::default()
+            },
+            height,
+            Sequence::ENABLE_RBF_NO_LOCKTIME,
+        ),
+        (
+            SpendOptions {
+                older: Some(time.into()),
+                ..Default::default()
+            },
+            absolute::LockTime::ZERO,
+            Sequence::from_512_second_intervals(8),
+        ),
+    ] {
+        let payment = Payment {
+            address: mining.clone(),
+            amount: Amount::from_sat(50_000),
+        };
+        let funded = MultisigWallet::create_batch_psbt(
+            &heir,
+            &[payment],
+            FeeRate::from_sat_per_vb_unchecked(2),
+            DEFAULT_LONG_TERM_FEE_RATE,
+            &UTXOStrategy::LargestFirst,
+            &ChangePolicy::default(),
+            &options,
+        )
+        .unwrap();
+        let tx = Psbt::from_str(&funded.psbt).unwrap().unsigned_tx;
+        assert_eq!(tx.lock_time, lock_time);
+        assert!(tx.input.iter().all(|input| input.sequence == sequence));
+        // Either way the lock time is enforced
+        assert!(!tx.input[0].sequence.is_final());
+    }
+
+    // Too young for a relative timelock of 10 blocks
+    let err = MultisigWallet::create_batch_psbt(
+        &heir,
+        &[Payment {
+            address: mining,
+            amount: Amount::from_sat(50_000),
+        }],
+        FeeRate::from_sat_per_vb_unchecked(2),
+        DEFAULT_LONG_TERM_FEE_RATE,
+        &UTXOStrategy::LargestFirst,
+        &ChangePolicy::default(),
+        &SpendOptions {
+            older: Some(relative::Height::from(10).into()),
+            ..Default::default()
+        },
+    )
+    .unwrap_err();
+    assert!(err.to_string().contains("10 blocks"), "{}", err);
+}